@@ -0,0 +1,48 @@
+#ifndef TENTRIS_LICENSE_AGGREGATOR_H
+#define TENTRIS_LICENSE_AGGREGATOR_H
+
+/* Generated with cbindgen, do not edit manually: cbindgen --config cbindgen.toml --output include/tentris_license_aggregator.h */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Run the complete aggregation (including thirdparty licenses) for the Cargo.toml at `manifest_path`.
+ *
+ * `config_path` may be NULL, in which case `about.toml` next to the manifest is used if it exists.
+ *
+ * Returns the collected packages as a JSON array, or NULL on error (see [`tla_last_error`]).
+ * The returned string must be released with [`tla_string_free`].
+ *
+ * # Safety
+ * `manifest_path` must be a valid NUL-terminated string, `config_path` must be NULL or a valid NUL-terminated string.
+ */
+char *tla_aggregate(const char *manifest_path,
+                    const char *config_path);
+
+/**
+ * The message of the last error that occurred on the calling thread, or NULL if there was none.
+ *
+ * The returned pointer stays valid until the next call into this library on the same thread and must not be freed.
+ */
+const char *tla_last_error(void);
+
+/**
+ * Release a string returned by this library
+ *
+ * # Safety
+ * `s` must be NULL or a pointer previously returned by this library that has not been freed yet
+ */
+void tla_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TENTRIS_LICENSE_AGGREGATOR_H */
@@ -0,0 +1,106 @@
+//! C API of the tentris-license-aggregator, so the C++ build can run the aggregation in-process.
+//!
+//! All strings passed in and returned are NUL-terminated UTF-8.
+//! Strings returned by this library must be released with [`tla_string_free`].
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+};
+use tentris_license_aggregator::Aggregator;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(msg: String) {
+    let msg = CString::new(msg).unwrap_or_else(|_| c"error message contained a NUL byte".to_owned());
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// # Safety
+/// `s` must be NULL or a valid NUL-terminated string
+unsafe fn to_str<'a>(s: *const c_char, what: &str) -> Result<Option<&'a str>, String> {
+    if s.is_null() {
+        return Ok(None);
+    }
+
+    CStr::from_ptr(s)
+        .to_str()
+        .map(Some)
+        .map_err(|e| format!("{what} is not valid UTF-8: {e}"))
+}
+
+/// Run the complete aggregation (including thirdparty licenses) for the Cargo.toml at `manifest_path`.
+///
+/// `config_path` may be NULL, in which case `about.toml` next to the manifest is used if it exists.
+///
+/// Returns the collected packages as a JSON array, or NULL on error (see [`tla_last_error`]).
+/// The returned string must be released with [`tla_string_free`].
+///
+/// # Safety
+/// `manifest_path` must be a valid NUL-terminated string, `config_path` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tla_aggregate(manifest_path: *const c_char, config_path: *const c_char) -> *mut c_char {
+    let result = catch_unwind(AssertUnwindSafe(|| -> Result<CString, String> {
+        let manifest = to_str(manifest_path, "manifest_path")?.ok_or("manifest_path must not be NULL")?;
+        let config = to_str(config_path, "config_path")?;
+
+        let mut aggregator = Aggregator::new(manifest).with_cpp_thirdparty(true);
+        if let Some(config) = config {
+            aggregator = aggregator.config_path(config);
+        }
+
+        let aggregation = aggregator.run().map_err(|e| error_chain(&e))?;
+        let json = serde_json::to_string(&aggregation.packages).map_err(|e| e.to_string())?;
+
+        CString::new(json).map_err(|e| e.to_string())
+    }));
+
+    match result {
+        Ok(Ok(json)) => json.into_raw(),
+        Ok(Err(msg)) => {
+            set_last_error(msg);
+            ptr::null_mut()
+        },
+        Err(_) => {
+            set_last_error("tla_aggregate panicked".to_owned());
+            ptr::null_mut()
+        },
+    }
+}
+
+/// The message of the last error that occurred on the calling thread, or NULL if there was none.
+///
+/// The returned pointer stays valid until the next call into this library on the same thread and must not be freed.
+#[no_mangle]
+pub extern "C" fn tla_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |msg| msg.as_ptr()))
+}
+
+/// Release a string returned by this library
+///
+/// # Safety
+/// `s` must be NULL or a pointer previously returned by this library that has not been freed yet
+#[no_mangle]
+pub unsafe extern "C" fn tla_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Renders the error together with its chain of causes
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = e.source();
+
+    while let Some(cause) = source {
+        msg.push_str(": ");
+        msg.push_str(&cause.to_string());
+        source = cause.source();
+    }
+
+    msg
+}
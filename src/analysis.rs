@@ -0,0 +1,1187 @@
+//! The license text analysis and SPDX handling that does not depend on cargo.
+//!
+//! This is everything that is needed to augment and minimize already collected [`Package`]s,
+//! so it is also available without the `cargo` feature (e.g. when building for `wasm32-unknown-unknown`).
+
+use crate::{
+    obligations::{classify_license, LicenseCategory},
+    AggregateError, DetectionMethod, Diagnostic, DiagnosticKind, Diagnostics, Expression, LicenseCandidate,
+    LicenseFile, LicenseStore, Package, Severity,
+};
+use askalono::{ScanMode, ScanStrategy, TextData};
+use sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    str::FromStr,
+};
+
+/// Files whose license is detected with a score below this are reported as [`DiagnosticKind::LowConfidence`] by default
+pub const DEFAULT_CONFIDENCE_THRESHOLD: f32 = 0.95;
+
+/// Number of candidate licenses that are reported for ambiguous detections by default
+pub const DEFAULT_CANDIDATES: usize = 3;
+
+/// What happens with a license file whose detected license scores below the threshold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LowConfidenceAction {
+    /// Assign the best match and report a warning
+    #[default]
+    Warn,
+    /// Leave the license of the file unknown and report an error
+    Error,
+    /// Leave the license of the file unknown and report it for manual review (see [`Diagnostics::needs_review`])
+    Review,
+}
+
+/// Options for the detection of licenses in license texts
+#[derive(Clone, Debug)]
+pub struct DetectionOptions {
+    /// Minimum score (between 0 and 1) for a detection to be accepted without further action
+    pub threshold: f32,
+    /// What happens with detections below the threshold
+    pub low_confidence: LowConfidenceAction,
+    /// Number of best matching licenses that are reported for detections below the threshold, 0 disables them
+    pub candidates: usize,
+    /// Split license files that contain several license texts (e.g. MIT and Apache-2.0 in one COPYING)
+    /// into one file per license, see [`split_license_text`]
+    pub split_multi_license: bool,
+}
+
+impl Default for DetectionOptions {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_CONFIDENCE_THRESHOLD,
+            low_confidence: LowConfidenceAction::default(),
+            candidates: DEFAULT_CANDIDATES,
+            split_multi_license: true,
+        }
+    }
+}
+
+/// How SPDX expressions that are not valid according to the specification are treated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpdxParsing {
+    /// Normalize them to valid expressions where possible, see [`normalize_expression`]
+    #[default]
+    Lenient,
+    /// Reject them
+    Strict,
+}
+
+/// Deprecated identifiers (and the license they denote) that are not just missing the `-only` suffix
+const DEPRECATED_IDS: &[(&str, &str)] = &[
+    ("GPL-2.0-with-autoconf-exception", "GPL-2.0-only WITH Autoconf-exception-2.0"),
+    ("GPL-2.0-with-bison-exception", "GPL-2.0-or-later WITH Bison-exception-2.2"),
+    ("GPL-2.0-with-classpath-exception", "GPL-2.0-only WITH Classpath-exception-2.0"),
+    ("GPL-2.0-with-font-exception", "GPL-2.0-only WITH Font-exception-2.0"),
+    ("GPL-2.0-with-GCC-exception", "GPL-2.0-only WITH GCC-exception-2.0"),
+    ("GPL-3.0-with-autoconf-exception", "GPL-3.0-only WITH Autoconf-exception-3.0"),
+    ("GPL-3.0-with-GCC-exception", "GPL-3.0-only WITH GCC-exception-3.1"),
+    ("eCos-2.0", "GPL-2.0-or-later WITH eCos-exception-2.0"),
+    ("wxWindows", "LGPL-2.0-or-later WITH WxWindows-exception-3.1"),
+    ("StandardML-NJ", "SMLNJ"),
+    ("Nunit", "zlib-acknowledgement"),
+];
+
+/// Turn a nonstandard SPDX expression into a valid one:
+/// - `/` is replaced with `OR` and lower case operators are upper cased (`MIT/Apache-2.0`, `mit or apache-2.0`),
+/// - identifiers are matched case-insensitively and common misspellings are corrected (`apache2`, `BSD`),
+/// - `+` on GNU licenses becomes `-or-later` and deprecated identifiers are replaced
+///   (`GPL-2.0` becomes `GPL-2.0-only`, `GPL-2.0-with-classpath-exception` becomes
+///   `GPL-2.0-only WITH Classpath-exception-2.0`).
+pub fn normalize_expression(expression: &str) -> Result<Expression, spdx::ParseError> {
+    let cased = map_terms(expression, |term| {
+        if ["and", "or", "with"].iter().any(|op| op.eq_ignore_ascii_case(term)) {
+            return Some(term.to_ascii_uppercase());
+        }
+
+        let (base, plus) = term.strip_suffix('+').map_or((term, ""), |base| (base, "+"));
+        if spdx::license_id(base).is_some() || spdx::exception_id(base).is_some() {
+            return None;
+        }
+
+        spdx::identifiers::LICENSES
+            .iter()
+            .map(|l| l.0)
+            .chain(spdx::identifiers::EXCEPTIONS.iter().map(|e| e.0))
+            .find(|id| id.eq_ignore_ascii_case(base))
+            .map(|id| format!("{id}{plus}"))
+    });
+
+    let canonical = spdx::Expression::canonicalize(&cased)?.unwrap_or(cased);
+
+    let current = map_terms(&canonical, |term| {
+        if let Some((_, replacement)) = DEPRECATED_IDS.iter().find(|(id, _)| *id == term) {
+            // parentheses keep a replacement with an exception intact inside of larger expressions
+            let nested = replacement.contains(' ') && canonical.trim() != term;
+            return Some(if nested { format!("({replacement})") } else { (*replacement).to_owned() });
+        }
+
+        let id = spdx::license_id(term)?;
+        let only = format!("{term}-only");
+        (id.is_deprecated() && id.is_gnu() && spdx::license_id(&only).is_some()).then_some(only)
+    });
+
+    Expression::parse(&current)
+}
+
+/// The canonical form of an expression: operands of nested operators of the same kind are flattened,
+/// sorted and deduplicated, and every nested operation is parenthesized (after sorting, so nested operations sort by
+/// their first operand), e.g. `MIT OR Apache-2.0 OR MIT` becomes `Apache-2.0 OR MIT` and `Zlib AND MIT OR Apache-2.0`
+/// becomes `Apache-2.0 OR (MIT AND Zlib)`.
+///
+/// Expressions with the same canonical form are equivalent, so comparing outputs is not obstructed by cosmetic differences.
+pub fn canonical_expression(expression: &Expression) -> Expression {
+    enum Node {
+        Req(String),
+        Op(spdx::expression::Operator, Vec<Node>),
+    }
+
+    fn render(node: Node) -> String {
+        let (op, children) = match node {
+            Node::Req(req) => return req,
+            Node::Op(op, children) => (op, children),
+        };
+
+        let mut operands: Vec<_> = children.into_iter().map(render).collect();
+        operands.sort();
+        operands.dedup();
+
+        if let [operand] = operands.as_slice() {
+            return operand.clone();
+        }
+
+        for operand in &mut operands {
+            if operand.contains(" AND ") || operand.contains(" OR ") {
+                *operand = format!("({operand})");
+            }
+        }
+
+        let separator = match op {
+            spdx::expression::Operator::And => " AND ",
+            spdx::expression::Operator::Or => " OR ",
+        };
+        operands.join(separator)
+    }
+
+    let mut stack = vec![];
+
+    for node in expression.iter() {
+        match node {
+            spdx::expression::ExprNode::Req(req) => stack.push(Node::Req(req.req.to_string())),
+            spdx::expression::ExprNode::Op(op) => {
+                let (Some(rhs), Some(lhs)) = (stack.pop(), stack.pop()) else {
+                    return expression.clone();
+                };
+
+                let mut children = vec![];
+                for child in [lhs, rhs] {
+                    match child {
+                        Node::Op(child_op, grandchildren) if child_op == *op => children.extend(grandchildren),
+                        child => children.push(child),
+                    }
+                }
+                stack.push(Node::Op(*op, children));
+            },
+        }
+    }
+
+    match stack.pop() {
+        Some(root) if stack.is_empty() => Expression::parse(&render(root)).unwrap_or_else(|_| expression.clone()),
+        _ => expression.clone(),
+    }
+}
+
+/// Replace the terms (identifiers and operators) of an expression for which `f` returns a replacement
+fn map_terms<F: Fn(&str) -> Option<String>>(expression: &str, f: F) -> String {
+    let mut out = String::with_capacity(expression.len());
+    let mut term = String::new();
+
+    let flush = |term: &mut String, out: &mut String| {
+        if !term.is_empty() {
+            out.push_str(&f(term).unwrap_or_else(|| term.clone()));
+            term.clear();
+        }
+    };
+
+    for c in expression.chars() {
+        if c.is_whitespace() || matches!(c, '(' | ')' | '/') {
+            flush(&mut term, &mut out);
+            out.push(c);
+        } else {
+            term.push(c);
+        }
+    }
+
+    flush(&mut term, &mut out);
+    out
+}
+
+/// The result of analyzing a license text with the license store
+#[derive(Clone, Debug)]
+pub struct Classification {
+    /// Name of the best matching license in the store
+    pub name: String,
+    /// Similarity between the text and the matched license, between 0 and 1
+    pub score: f32,
+    /// The matched license as SPDX expression, if the name is a valid one
+    pub spdx: Option<Expression>,
+}
+
+/// Determine the license of a text with the license store
+pub fn classify_text(license_store: &LicenseStore, text: &str) -> Classification {
+    let analysis = license_store.analyze(&text.into());
+
+    Classification {
+        name: analysis.name.to_owned(),
+        score: analysis.score,
+        spdx: Expression::from_str(analysis.name).ok(),
+    }
+}
+
+/// The `n` licenses of the store that match the text best, ordered by descending score.
+///
+/// This compares the text with every license of the store, so it is considerably slower than [`classify_text`].
+pub fn license_candidates(license_store: &LicenseStore, text: &str, n: usize) -> Vec<LicenseCandidate> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let text = TextData::from(text);
+    let mut candidates: Vec<_> = license_store
+        .licenses()
+        .filter_map(|name| {
+            let original = license_store.get_original(name)?;
+            Some(LicenseCandidate { name: name.clone(), score: original.match_score(&text) })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    candidates.truncate(n);
+    candidates
+}
+
+/// Split a text that contains several license texts into one text per license.
+///
+/// Each part covers the lines of one license with at least the given score; lines between the licenses
+/// (e.g. copyright notices) belong to the following part and trailing lines to the last one, so no text is lost.
+/// Returns an empty list if the text does not contain at least two licenses.
+pub fn split_license_text(license_store: &LicenseStore, text: &str, threshold: f32) -> Vec<(Classification, String)> {
+    // the elimination mode of askalono only finds the best matching license in concatenated texts
+    let scan = ScanStrategy::new(license_store)
+        .mode(ScanMode::TopDown)
+        .confidence_threshold(threshold);
+
+    let mut contained = match scan.scan(&text.into()) {
+        Ok(result) => result.containing,
+        Err(e) => {
+            tracing::debug!("unable to scan license text for contained licenses: {e}");
+            return Vec::new();
+        },
+    };
+
+    if contained.len() < 2 {
+        return Vec::new();
+    }
+
+    contained.sort_by_key(|c| c.line_range.0);
+
+    // the line ranges refer to the text split at '\n', the normalization of askalono keeps the number of lines
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut start = 0;
+
+    contained
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let end = if i + 1 == contained.len() { lines.len() } else { c.line_range.1.clamp(start, lines.len()) };
+            let part = lines[start..end].join("\n");
+            start = end;
+
+            let classification = Classification {
+                name: c.license.name.to_owned(),
+                score: c.score,
+                spdx: Expression::from_str(c.license.name).ok(),
+            };
+
+            (classification, part)
+        })
+        .collect()
+}
+
+/// Results of the license store analysis, keyed by the SHA-256 of the license text, so byte-identical texts (e.g. the
+/// same MIT license shipped by dozens of packages) are only analyzed once. The results can be kept across runs with
+/// the [`crate::LicenseCache`] of the [`crate::GatherOptions`], they are only valid for the license store they were
+/// computed with (see [`store_fingerprint`]).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AnalysisMemo {
+    results: HashMap<String, (String, f32)>,
+    /// The candidates with the number of candidates they were computed for
+    candidates: HashMap<String, (usize, Vec<LicenseCandidate>)>,
+}
+
+impl AnalysisMemo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Like [`classify_text`], but reuses the result for texts that were already analyzed
+    pub fn classify(&mut self, license_store: &LicenseStore, text: &str) -> Classification {
+        let (name, score) = self.analyze(license_store, text);
+        Classification { name: name.to_owned(), score, spdx: Expression::from_str(name).ok() }
+    }
+
+    /// Like [`license_candidates`], but reuses the result for texts that were already analyzed for at least `n`
+    /// candidates
+    pub fn candidates(&mut self, license_store: &LicenseStore, text: &str, n: usize) -> Vec<LicenseCandidate> {
+        let entry = self.candidates.entry(Self::key(text)).or_default();
+        if entry.0 < n || entry.1.is_empty() {
+            *entry = (n, license_candidates(license_store, text, n));
+        }
+
+        entry.1.iter().take(n).cloned().collect()
+    }
+
+    /// Number of distinct texts analyzed so far
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    fn key(text: &str) -> String {
+        format!("{:x}", Sha256::digest(text.as_bytes()))
+    }
+
+    fn analyze(&mut self, license_store: &LicenseStore, text: &str) -> (&str, f32) {
+        let (name, score) = self.results.entry(Self::key(text)).or_insert_with(|| {
+            let analysis = license_store.analyze(&text.into());
+            (analysis.name.to_owned(), analysis.score)
+        });
+
+        (name.as_str(), *score)
+    }
+}
+
+/// The hex encoded SHA-256 of the licenses (and their aliases) of the store and the version of this crate, which
+/// identifies the store an [`AnalysisMemo`] was computed with. The texts are not part of the fingerprint, since the
+/// store only keeps their n-grams: a changed text of a custom license has to get a new name.
+pub fn store_fingerprint(license_store: &LicenseStore) -> String {
+    let mut names: Vec<_> = license_store.licenses().collect();
+    names.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    for name in names {
+        hasher.update([0]);
+        hasher.update(name);
+        for alias in license_store.aliases(name).into_iter().flatten() {
+            hasher.update([1]);
+            hasher.update(alias);
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Detect the SPDX of all license files of the package that do not have one yet
+pub fn detect_file_licenses(
+    pkg: &mut Package,
+    license_store: &LicenseStore,
+    options: &DetectionOptions,
+    memo: &mut AnalysisMemo,
+    diagnostics: &mut Diagnostics,
+) {
+    if options.split_multi_license {
+        split_license_files(pkg, license_store, options, memo, diagnostics);
+    }
+
+    for l in &mut pkg.license_files {
+        if l.spdx.is_some() || !l.kind.is_license() {
+            continue;
+        }
+
+        let (name, score) = memo.analyze(license_store, &l.text);
+        let name = name.to_owned();
+        l.detection_score = Some(score);
+        l.detection_method = Some(DetectionMethod::FileAnalysis);
+
+        if score < options.threshold {
+            l.candidates = memo.candidates(license_store, &l.text, options.candidates);
+
+            let mut message = format!(
+                "Low confidence of {} for {} on license file SPDX detection for {} of '{} {}'",
+                score, name, l.name, pkg.package_name, pkg.package_version
+            );
+
+            if !l.candidates.is_empty() {
+                let candidates: Vec<_> = l.candidates.iter().map(|c| format!("{} ({})", c.name, c.score)).collect();
+                message.push_str(&format!(", candidates: {}", candidates.join(", ")));
+            }
+
+            let diagnostic = match options.low_confidence {
+                LowConfidenceAction::Warn => Diagnostic::warning(DiagnosticKind::LowConfidence, message),
+                LowConfidenceAction::Error => Diagnostic::new(Severity::Error, DiagnosticKind::LowConfidence, message),
+                LowConfidenceAction::Review => Diagnostic::warning(DiagnosticKind::NeedsReview, message),
+            };
+
+            diagnostics.push(
+                diagnostic
+                    .with_package(&pkg.package_name, &pkg.package_version)
+                    .with_file(&l.name),
+            );
+
+            if options.low_confidence != LowConfidenceAction::Warn {
+                continue;
+            }
+        }
+
+        match Expression::from_str(&name) {
+            Ok(file_spdx) => {
+                if pkg
+                    .license_spdx
+                    .as_ref()
+                    .is_some_and(|pkg_spdx| !spdx_any_in_common(pkg_spdx, &file_spdx))
+                {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            DiagnosticKind::FileLicenseMismatch,
+                            format!(
+                                "License detection of file {} detected as {} for '{} {}' is probably wrong: package license and file license have nothing in common",
+                                l.name, file_spdx, pkg.package_name, pkg.package_version
+                            ),
+                        )
+                        .with_package(&pkg.package_name, &pkg.package_version)
+                        .with_file(&l.name),
+                    );
+                }
+
+                l.spdx = Some(file_spdx)
+            },
+            Err(e) => diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::InvalidDetectedLicense,
+                    format!("License analysis yielded invalid license: {e}"),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version)
+                .with_file(&l.name),
+            ),
+        }
+    }
+}
+
+/// Replace the license files without SPDX that do not match any single license confidently,
+/// but contain several licenses, by one file per contained license
+fn split_license_files(
+    pkg: &mut Package,
+    license_store: &LicenseStore,
+    options: &DetectionOptions,
+    memo: &mut AnalysisMemo,
+    diagnostics: &mut Diagnostics,
+) {
+    let files = std::mem::take(&mut pkg.license_files);
+
+    for l in files {
+        let parts = if l.spdx.is_none() && l.kind.is_license() && memo.analyze(license_store, &l.text).1 < options.threshold {
+            split_license_text(license_store, &l.text, options.threshold)
+        } else {
+            Vec::new()
+        };
+
+        if parts.is_empty() {
+            pkg.license_files.push(l);
+            continue;
+        }
+
+        let names: Vec<_> = parts.iter().map(|(c, _)| c.name.as_str()).collect();
+        diagnostics.push(
+            Diagnostic::new(
+                Severity::Info,
+                DiagnosticKind::SplitLicenseFile,
+                format!(
+                    "License file {} of '{} {}' contains several licenses, split into {}",
+                    l.name,
+                    pkg.package_name,
+                    pkg.package_version,
+                    names.join(", ")
+                ),
+            )
+            .with_package(&pkg.package_name, &pkg.package_version)
+            .with_file(&l.name),
+        );
+
+        for (classification, text) in parts {
+            // the parts are detected again (with the surrounding lines) by detect_file_licenses
+            let name = format!("{} ({})", l.name, classification.name);
+            pkg.license_files.push(LicenseFile::new(name, None, text));
+        }
+    }
+}
+
+/// Add the canonical SPDX texts of the licenses (and exceptions) in the package SPDX as license files,
+/// if the package has no license files (notices aside). The added files are marked as [`LicenseFile::synthesized`].
+///
+/// Licenses that are not on the SPDX list (`LicenseRef-`) have no canonical text and are skipped.
+#[cfg(feature = "canonical-texts")]
+pub fn synthesize_license_files(pkg: &mut Package, diagnostics: &mut Diagnostics) {
+    if pkg.license_files.iter().any(|l| l.kind.is_license()) {
+        return;
+    }
+
+    let Some(spdx) = &pkg.license_spdx else {
+        return;
+    };
+
+    let mut files: Vec<LicenseFile> = vec![];
+    for req in spdx.requirements() {
+        let spdx::LicenseItem::Spdx { id, .. } = &req.req.license else {
+            continue;
+        };
+
+        let mut text = id.text().to_owned();
+        let mut name = id.name.to_owned();
+        if let Some(exception) = req.req.exception {
+            text.push_str("\n\n");
+            text.push_str(exception.text());
+            name = format!("{name} WITH {}", exception.name);
+        }
+
+        if files.iter().any(|f| f.name == name) {
+            continue;
+        }
+
+        let mut file = LicenseFile::new(name, Expression::from_str(&req.req.to_string()).ok(), text);
+        file.detection_method = Some(DetectionMethod::DeclaredMetadata);
+        file.synthesized = true;
+        files.push(file);
+    }
+
+    if !files.is_empty() {
+        let names: Vec<_> = files.iter().map(|f| f.name.as_str()).collect();
+        diagnostics.push(
+            Diagnostic::new(
+                Severity::Info,
+                DiagnosticKind::SynthesizedLicenseText,
+                format!(
+                    "Using the canonical SPDX texts of {} for '{} {}', which ships no license files",
+                    names.join(", "),
+                    pkg.package_name,
+                    pkg.package_version
+                ),
+            )
+            .with_package(&pkg.package_name, &pkg.package_version),
+        );
+    }
+
+    pkg.license_files.extend(files);
+}
+
+/// Classify a package without a license whose license files only claim a copyright (see
+/// [`crate::reserves_all_rights`]) as [`crate::obligations::ALL_RIGHTS_RESERVED_REF`], which is reported as a
+/// [`DiagnosticKind::Unlicensed`] error. This has to happen before the license detection, which would otherwise
+/// assign the license whose text is the least different with a low confidence.
+///
+/// Licenses that were declared or configured for any of the files are kept.
+pub fn detect_unlicensed(pkg: &mut Package, diagnostics: &mut Diagnostics) {
+    if pkg.license_spdx.is_some() {
+        return;
+    }
+
+    let mut files = pkg.license_files.iter().filter(|l| l.kind.is_license()).peekable();
+    let unlicensed = files.peek().is_some()
+        && files.all(|l| {
+            let detected = l.spdx.is_none() || l.detection_method == Some(DetectionMethod::FileAnalysis);
+            detected && crate::search::reserves_all_rights(&l.text)
+        });
+    if !unlicensed {
+        return;
+    }
+
+    let Ok(license) = Expression::from_str(crate::obligations::ALL_RIGHTS_RESERVED_REF) else {
+        return;
+    };
+
+    let mut names = vec![];
+    for l in pkg.license_files.iter_mut().filter(|l| l.kind.is_license()) {
+        l.spdx = Some(license.clone());
+        l.detection_method = Some(DetectionMethod::FileAnalysis);
+        l.candidates.clear();
+        names.push(l.name.clone());
+    }
+
+    diagnostics.push(
+        Diagnostic::new(
+            Severity::Error,
+            DiagnosticKind::Unlicensed,
+            format!(
+                "'{} {}' grants no license in {}, all rights are reserved by its authors",
+                pkg.package_name,
+                pkg.package_version,
+                names.join(", ")
+            ),
+        )
+        .with_package(&pkg.package_name, &pkg.package_version),
+    );
+    pkg.license_spdx = Some(license);
+}
+
+/// Report if the number of licenses in the package SPDX differs from the number of licenses found in its files,
+/// unless every requirement has a license file and every license file belongs to a requirement (see
+/// [`pair_license_files`]). Otherwise the report names the requirements without and the files beyond them.
+pub fn check_license_count(pkg: &Package, diagnostics: &mut Diagnostics) {
+    let licenses_in_top_level_expr = licenses_in_expr_opt(pkg.license_spdx.as_ref());
+    let licenses_in_files: usize = pkg
+        .license_files
+        .iter()
+        .map(|file| licenses_in_expr_opt(file.spdx.as_ref()))
+        .sum();
+
+    if licenses_in_top_level_expr == licenses_in_files {
+        return;
+    }
+
+    let (requirements, pairing) = license_file_pairing(pkg);
+    let missing: Vec<_> = requirements
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !pairing.iter().flatten().any(|paired| paired.contains(i)))
+        .map(|(_, r)| r.to_string())
+        .collect();
+    let unpaired: Vec<_> = pkg
+        .license_files
+        .iter()
+        .zip(&pairing)
+        .filter(|(l, paired)| l.kind.is_license() && paired.is_none())
+        .map(|(l, _)| l.name.as_str())
+        .collect();
+
+    if pkg.license_spdx.is_some() && missing.is_empty() && unpaired.is_empty() {
+        return;
+    }
+
+    let mut details = String::new();
+    if !missing.is_empty() {
+        details.push_str(&format!(", no license file for {}", missing.join(", ")));
+    }
+    if !unpaired.is_empty() {
+        details.push_str(&format!(", no requirement for {}", unpaired.join(", ")));
+    }
+
+    diagnostics.push(
+        Diagnostic::warning(
+            DiagnosticKind::LicenseCountMismatch,
+            format!(
+                "Mismatch between license SPDX and number of licenses found in files for crate '{} {}'. SPDX specifies {licenses_in_top_level_expr} but found {licenses_in_files} in files{details}",
+                pkg.package_name, pkg.package_version
+            ),
+        )
+        .with_package(&pkg.package_name, &pkg.package_version),
+    );
+}
+
+/// Assign every license file to the requirement of the package license it satisfies (see
+/// [`LicenseFile::requirement`]), first by the detected license of the file and then by the naming convention of
+/// the file (e.g. `LICENSE-MIT` or `LICENSE-APACHE`). Returns the requirements without a license file.
+pub fn pair_license_files(pkg: &mut Package) -> Vec<String> {
+    let (requirements, pairing) = license_file_pairing(pkg);
+
+    for (l, paired) in pkg.license_files.iter_mut().zip(&pairing) {
+        l.requirement = paired.as_ref().and_then(|paired| paired.first()).map(|&i| requirements[i].to_string());
+    }
+
+    requirements
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !pairing.iter().flatten().any(|paired| paired.contains(i)))
+        .map(|(_, r)| r.to_string())
+        .collect()
+}
+
+/// The distinct requirements of the package license and the indices of the requirements every license file belongs
+/// to, `None` for notices and files that belong to no requirement
+fn license_file_pairing(pkg: &Package) -> (Vec<spdx::LicenseReq>, Vec<Option<Vec<usize>>>) {
+    let mut requirements: Vec<spdx::LicenseReq> = vec![];
+    for r in pkg.license_spdx.iter().flat_map(|l| l.requirements()) {
+        if !requirements.contains(&r.req) {
+            requirements.push(r.req.clone());
+        }
+    }
+
+    let mut pairing: Vec<Option<Vec<usize>>> = pkg
+        .license_files
+        .iter()
+        .map(|l| {
+            let file_spdx = l.spdx.as_ref().filter(|_| l.kind.is_license())?;
+            let covered: Vec<usize> = (0..requirements.len())
+                .filter(|&i| file_spdx.requirements().any(|f| requirement_covers(&requirements[i], &f.req)))
+                .collect();
+            (!covered.is_empty()).then_some(covered)
+        })
+        .collect();
+
+    // files whose content matches nothing are paired by their name, preferring requirements without a file
+    for (l, paired) in pkg.license_files.iter().zip(pairing.iter_mut()) {
+        if paired.is_some() || !l.kind.is_license() {
+            continue;
+        }
+
+        let tokens: Vec<String> =
+            l.name.split(|c: char| !c.is_ascii_alphanumeric()).map(|t| t.to_ascii_uppercase()).collect();
+        let named: Vec<usize> = (0..requirements.len())
+            .filter(|&i| tokens.iter().any(|t| *t == license_file_token(&requirements[i])))
+            .collect();
+
+        *paired = named.first().map(|&i| vec![i]);
+    }
+
+    (requirements, pairing)
+}
+
+/// The token by which license files are conventionally named after their license, e.g. `APACHE` for `Apache-2.0`
+fn license_file_token(req: &spdx::LicenseReq) -> String {
+    let name = match &req.license {
+        spdx::LicenseItem::Spdx { id, .. } => id.name.to_owned(),
+        spdx::LicenseItem::Other { lic_ref, .. } => lic_ref.clone(),
+    };
+
+    name.split(['-', '.']).next().unwrap_or_default().to_ascii_uppercase()
+}
+
+/// Minimize the license requirements of the package with the accepted licenses, ordered by preference.
+/// License files that are no longer required are removed.
+///
+/// A file is kept if its license is one of the requirements satisfied by the minimization, where `-or-later` (`+`)
+/// and exceptions are taken into account: e.g. a `GPL-2.0-only` text is kept for `GPL-2.0-or-later` when `GPL-3.0` is
+/// accepted, and an `Apache-2.0` text is kept for `Apache-2.0 WITH LLVM-exception`.
+pub fn minimize_package(p: &mut Package, accepted: &[spdx::Licensee]) -> Result<(), AggregateError> {
+    minimize_package_files(p, accepted).map(|_| ())
+}
+
+/// Like [`minimize_package`], but returns the removed license files
+fn minimize_package_files(p: &mut Package, accepted: &[spdx::Licensee]) -> Result<Vec<LicenseFile>, AggregateError> {
+    let Some(lspdx) = &p.license_spdx else {
+        return Ok(vec![]);
+    };
+
+    let minimized = lspdx
+        .minimized_requirements(accepted)
+        .map_err(|source| AggregateError::Minimization {
+            package: format!("{} {}", p.package_name, p.package_version),
+            expression: lspdx.to_string(),
+            source,
+        })?;
+
+    // the minimized requirements are the accepted licensees, so map them back to the requirements of the package
+    let kept: Vec<_> = lspdx
+        .requirements()
+        .filter(|r| {
+            minimized
+                .iter()
+                .any(|m| spdx::Licensee::new(m.license.clone(), m.exception).satisfies(&r.req))
+        })
+        .map(|r| &r.req)
+        .collect();
+
+    // retain the file if any of its SPDX components
+    // appear in the minimized version
+    let (retained, removed) = std::mem::take(&mut p.license_files).into_iter().partition(|license_file| {
+        license_file.spdx.as_ref().is_none_or(|file_spdx| {
+            file_spdx
+                .requirements()
+                .any(|file_req| kept.iter().any(|req| requirement_covers(req, &file_req.req)))
+        })
+    });
+
+    p.license_files = retained;
+    Ok(removed)
+}
+
+/// A package whose license requirements could not be minimized, its license files are left untouched
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinimizationFailure {
+    pub package_name: String,
+    pub package_version: String,
+    /// The license expression of the package
+    pub expression: String,
+    /// Why the minimization failed, e.g. because none of the licenses is accepted
+    pub reason: String,
+    /// The licenses that would have to be accepted additionally for the minimization to succeed,
+    /// see [`suggest_accepted`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggested_accepted: Vec<String>,
+}
+
+/// A license file that was removed by the minimization, since its license is not required
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RemovedLicenseFile {
+    pub package_name: String,
+    pub package_version: String,
+    /// Name of the license file
+    pub name: String,
+    /// License of the file
+    pub spdx: Option<Expression>,
+    /// SHA-256 of the text of the file
+    pub text_sha256: String,
+}
+
+/// The outcome of [`minimize_packages`]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MinimizationReport {
+    pub failures: Vec<MinimizationFailure>,
+    /// The license files that were dropped, so the reduction can be verified
+    #[serde(default, rename = "removed_by_minimization")]
+    pub removed: Vec<RemovedLicenseFile>,
+}
+
+impl MinimizationReport {
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Report every failure as [`DiagnosticKind::MinimizationFailed`]
+    /// and every removed file as [`DiagnosticKind::RemovedByMinimization`]
+    pub fn report_to(&self, diagnostics: &mut Diagnostics) {
+        for removed in &self.removed {
+            let license = removed.spdx.as_ref().map_or_else(|| "unknown".to_owned(), ToString::to_string);
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Info,
+                    DiagnosticKind::RemovedByMinimization,
+                    format!(
+                        "Removed license file {} ({license}) of '{} {}', its license is not required",
+                        removed.name, removed.package_name, removed.package_version
+                    ),
+                )
+                .with_package(&removed.package_name, &removed.package_version)
+                .with_file(&removed.name),
+            );
+        }
+
+        for failure in &self.failures {
+            let mut message = format!(
+                "Unable to minimize requirements of '{} {}' ({}): {}, keeping all license files",
+                failure.package_name, failure.package_version, failure.expression, failure.reason
+            );
+            if !failure.suggested_accepted.is_empty() {
+                message.push_str(&format!(", accepting {} would suffice", failure.suggested_accepted.join(" and ")));
+            }
+
+            diagnostics.push(
+                Diagnostic::warning(DiagnosticKind::MinimizationFailed, message)
+                    .with_package(&failure.package_name, &failure.package_version),
+            );
+        }
+
+        for (license, packages) in self.suggested_accepted() {
+            diagnostics.push(Diagnostic::new(
+                Severity::Info,
+                DiagnosticKind::AcceptedSuggestion,
+                format!("Accepting {license} is needed to minimize the requirements of {}", packages.join(", ")),
+            ));
+        }
+    }
+
+    /// The licenses suggested for the failed packages and the packages (`<name> <version>`) they would fix
+    pub fn suggested_accepted(&self) -> BTreeMap<String, Vec<String>> {
+        let mut suggestions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for failure in &self.failures {
+            for license in &failure.suggested_accepted {
+                suggestions
+                    .entry(license.clone())
+                    .or_default()
+                    .push(format!("{} {}", failure.package_name, failure.package_version));
+            }
+        }
+
+        suggestions
+    }
+}
+
+/// The licenses of the expression that would have to be accepted in addition to `accepted`, so the requirements
+/// can be minimized. Prefers the licenses that come first in the expression, empty if nothing would help.
+///
+/// The suggestions are in the form of `accepted`, i.e. without `-only`/`-or-later` (e.g. `GPL-2.0`).
+pub fn suggest_accepted(expression: &Expression, accepted: &[spdx::Licensee]) -> Vec<String> {
+    let candidates: Vec<_> = expression
+        .requirements()
+        .map(|r| {
+            // `-or-later` only applies to the license holder, a licensee is always a specific version
+            let license = match &r.req.license {
+                spdx::LicenseItem::Spdx { id, .. } => spdx::LicenseItem::Spdx { id: *id, or_later: false },
+                other => other.clone(),
+            };
+            spdx::Licensee::new(license, r.req.exception)
+        })
+        .filter(|candidate| !accepted.contains(candidate))
+        .collect();
+
+    let all: Vec<_> = accepted.iter().chain(&candidates).cloned().collect();
+    let Ok(minimized) = expression.minimized_requirements(&all) else {
+        return Vec::new();
+    };
+
+    let mut suggestions: Vec<_> = minimized
+        .into_iter()
+        .filter(|req| !accepted.iter().any(|a| a.satisfies(req)))
+        .map(|req| req.to_string())
+        .collect();
+    suggestions.dedup();
+    suggestions
+}
+
+/// How the licenses that remain after minimization are chosen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MinimizationStrategy {
+    /// Prefer the accepted licenses in the order in which they are accepted
+    #[default]
+    Accepted,
+    /// Prefer the accepted licenses in the order of [`MinimizationOptions::priority`],
+    /// the remaining accepted licenses follow in the order in which they are accepted
+    Priority,
+    /// Do not minimize at all, all license files are kept
+    KeepAll,
+}
+
+/// Options for the minimization of the license requirements, see [`minimize_packages_with`]
+#[derive(Clone, Debug, Default)]
+pub struct MinimizationOptions {
+    pub strategy: MinimizationStrategy,
+    /// The preferred licenses for [`MinimizationStrategy::Priority`], licenses that are not accepted are ignored
+    pub priority: Vec<spdx::Licensee>,
+    /// The license that is chosen for a package (by name) if its expression allows it, regardless of the strategy
+    /// (except for [`MinimizationStrategy::KeepAll`])
+    pub pinned: HashMap<String, spdx::Licensee>,
+    /// Whether the licenses of the public domain category count as accepted (after all accepted licenses), see
+    /// [`crate::policy::Policy::accept_public_domain`]
+    pub accept_public_domain: bool,
+}
+
+impl MinimizationOptions {
+    /// The licenses for the minimization of the package, in the order of preference
+    fn preference(&self, p: &Package, accepted: &[spdx::Licensee]) -> Vec<spdx::Licensee> {
+        let mut preference: Vec<_> = self.pinned.get(&p.package_name).cloned().into_iter().collect();
+
+        if self.strategy == MinimizationStrategy::Priority {
+            preference.extend(self.priority.iter().filter(|l| accepted.contains(l)).cloned());
+        }
+
+        preference.extend(accepted.iter().cloned());
+
+        if self.accept_public_domain {
+            let public_domain = p
+                .license_spdx
+                .iter()
+                .flat_map(|l| l.requirements())
+                .filter(|r| classify_license(&r.req) == LicenseCategory::PublicDomain)
+                .map(|r| match &r.req.license {
+                    spdx::LicenseItem::Spdx { id, .. } => spdx::LicenseItem::Spdx { id: *id, or_later: false },
+                    other => other.clone(),
+                })
+                .map(|license| spdx::Licensee::new(license, None));
+            for licensee in public_domain {
+                if !preference.contains(&licensee) {
+                    preference.push(licensee);
+                }
+            }
+        }
+
+        preference
+    }
+}
+
+/// Minimize the license requirements of all packages (see [`minimize_package`]).
+///
+/// Failing packages are left as they are and collected in the report, so they do not prevent the minimization of
+/// the others.
+pub fn minimize_packages(packages: &mut [Package], accepted: &[spdx::Licensee]) -> MinimizationReport {
+    minimize_packages_with(packages, accepted, &MinimizationOptions::default())
+}
+
+/// Like [`minimize_packages`], but the remaining licenses are chosen according to the options
+pub fn minimize_packages_with(
+    packages: &mut [Package],
+    accepted: &[spdx::Licensee],
+    options: &MinimizationOptions,
+) -> MinimizationReport {
+    let mut report = MinimizationReport::default();
+    if options.strategy == MinimizationStrategy::KeepAll {
+        return report;
+    }
+
+    for p in packages {
+        let preference = options.preference(p, accepted);
+        match minimize_package_files(p, &preference) {
+            Ok(removed) => report.removed.extend(removed.into_iter().map(|l| RemovedLicenseFile {
+                package_name: p.package_name.clone(),
+                package_version: p.package_version.clone(),
+                text_sha256: l.text_sha256.unwrap_or_else(|| format!("{:x}", Sha256::digest(l.text.as_bytes()))),
+                name: l.name,
+                spdx: l.spdx,
+            })),
+            Err(e) => {
+                let reason = std::error::Error::source(&e).map_or_else(|| e.to_string(), |source| source.to_string());
+                report.failures.push(MinimizationFailure {
+                    package_name: p.package_name.clone(),
+                    package_version: p.package_version.clone(),
+                    expression: p.license_spdx.as_ref().map(ToString::to_string).unwrap_or_default(),
+                    reason,
+                    suggested_accepted: p
+                        .license_spdx
+                        .as_ref()
+                        .map(|l| suggest_accepted(l, &preference))
+                        .unwrap_or_default(),
+                });
+            },
+        }
+    }
+
+    report
+}
+
+/// Whether a license file with the license `file_req` belongs to the requirement `req` of a package,
+/// i.e. it is the same license regardless of `-only`/`-or-later` and the file has no or the same exception
+/// (the text of an exception usually ships separately from the license text)
+pub fn requirement_covers(req: &spdx::LicenseReq, file_req: &spdx::LicenseReq) -> bool {
+    if file_req.exception.is_some() && file_req.exception != req.exception {
+        return false;
+    }
+
+    match (&req.license, &file_req.license) {
+        (spdx::LicenseItem::Spdx { id, .. }, spdx::LicenseItem::Spdx { id: file_id, .. }) => {
+            base_license_name(id.name) == base_license_name(file_id.name)
+        },
+        (req_license, file_license) => req_license == file_license,
+    }
+}
+
+/// The name of a license without the `-only`/`-or-later`/`+` suffix
+fn base_license_name(name: &str) -> &str {
+    let name = name.strip_suffix('+').unwrap_or(name);
+    name.strip_suffix("-only")
+        .or_else(|| name.strip_suffix("-or-later"))
+        .unwrap_or(name)
+}
+
+/// Whether both expressions contain the same licenses, regardless of their order and combination
+pub fn same_licenses(expr1: &Expression, expr2: &Expression) -> bool {
+    let licenses = |expr: &Expression| expr.requirements().map(|r| r.req.to_string()).collect::<BTreeSet<_>>();
+    licenses(expr1) == licenses(expr2)
+}
+
+fn spdx_any_in_common(expr1: &Expression, expr2: &Expression) -> bool {
+    expr1
+        .requirements()
+        .any(|req1| expr2.requirements().any(|req2| req1 == req2))
+}
+
+fn licenses_in_expr(expr: &spdx::Expression) -> usize {
+    expr.requirements().count()
+}
+
+fn licenses_in_expr_opt(expr: Option<&Expression>) -> usize {
+    expr.map(|expr| licenses_in_expr(&expr.0)).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // askalono drops lines with copyright statements, so the texts are split into lines like the real ones
+    const MIT: &str = "Permission is hereby granted, free of charge, to any person obtaining a copy\n\
+        of this software and associated documentation files (the \"Software\"), to deal\n\
+        in the Software without restriction, including without limitation the rights\n\
+        to use, copy, modify, merge, publish, distribute, sublicense, and/or sell\n\
+        copies of the Software, and to permit persons to whom the Software is\n\
+        furnished to do so, subject to the following conditions:\n";
+    const ISC: &str = "Permission to use, copy, modify, and/or distribute this software for any\n\
+        purpose with or without fee is hereby granted, provided that the above\n\
+        notice and this permission notice appear in all copies.\n";
+    const ZERO_BSD: &str = "Permission to use, copy, modify, and/or distribute this software for any\n\
+        purpose with or without fee is hereby granted.\n";
+
+    fn store() -> LicenseStore {
+        let mut store = LicenseStore::new();
+        for (name, text) in [("MIT", MIT), ("ISC", ISC), ("0BSD", ZERO_BSD)] {
+            store.add_license(name.to_owned(), TextData::new(text));
+        }
+        store
+    }
+
+    #[test]
+    fn memo_reuses_analyses() {
+        let store = store();
+        let mut memo = AnalysisMemo::new();
+        assert_eq!(memo.classify(&store, MIT).name, "MIT");
+        assert_eq!(memo.classify(&store, MIT).name, "MIT");
+        assert_eq!(memo.classify(&store, ISC).name, "ISC");
+        assert_eq!(memo.len(), 2);
+    }
+
+    #[test]
+    fn memo_candidates_grow() {
+        let store = store();
+        let mut memo = AnalysisMemo::new();
+        assert_eq!(memo.candidates(&store, ISC, 1).len(), 1);
+        assert_eq!(memo.candidates(&store, ISC, 3).len(), 3);
+        assert_eq!(memo.candidates(&store, ISC, 2).len(), 2);
+        assert_eq!(memo.candidates(&store, ISC, 3), license_candidates(&store, ISC, 3));
+    }
+
+    #[test]
+    fn memo_round_trip() {
+        let store = store();
+        let mut memo = AnalysisMemo::new();
+        memo.classify(&store, MIT);
+
+        let mut loaded: AnalysisMemo = serde_json::from_str(&serde_json::to_string(&memo).unwrap()).unwrap();
+        // an empty store would classify nothing, so the result has to come from the memo
+        assert_eq!(loaded.classify(&LicenseStore::new(), MIT).name, "MIT");
+    }
+
+    #[test]
+    fn fingerprint_depends_on_the_licenses() {
+        let mut other = store();
+        assert_eq!(store_fingerprint(&store()), store_fingerprint(&other));
+        other.add_license("LicenseRef-custom".to_owned(), TextData::new("custom"));
+        assert_ne!(store_fingerprint(&store()), store_fingerprint(&other));
+    }
+
+    #[test]
+    fn canonical_form() {
+        let canonical = |s: &str| canonical_expression(&Expression::parse(s).unwrap()).to_string();
+        assert_eq!(canonical("MIT OR Apache-2.0 OR MIT"), "Apache-2.0 OR MIT");
+        assert_eq!(canonical("Zlib AND MIT OR Apache-2.0"), "Apache-2.0 OR (MIT AND Zlib)");
+        assert_eq!(canonical("(MIT OR Apache-2.0) AND (Apache-2.0 OR MIT)"), "Apache-2.0 OR MIT");
+        assert_eq!(canonical("Unicode-3.0 AND (MIT OR Apache-2.0)"), "(Apache-2.0 OR MIT) AND Unicode-3.0");
+        let with = "GPL-2.0-or-later WITH Classpath-exception-2.0";
+        assert_eq!(canonical(with), with);
+    }
+
+    #[test]
+    fn normalized_expressions() {
+        let normalize = |s: &str| normalize_expression(s).map(|e| e.to_string()).ok();
+        assert_eq!(normalize("MIT/Apache-2.0").as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(normalize("mit or apache-2.0").as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(normalize("GPL-2.0").as_deref(), Some("GPL-2.0-only"));
+        assert_eq!(normalize("LGPL-2.1+").as_deref(), Some("LGPL-2.1-or-later"));
+        let classpath = normalize("GPL-2.0-with-classpath-exception");
+        assert_eq!(classpath.as_deref(), Some("GPL-2.0-only WITH Classpath-exception-2.0"));
+        let nested = normalize("MIT OR GPL-2.0-with-classpath-exception");
+        assert_eq!(nested.as_deref(), Some("MIT OR (GPL-2.0-only WITH Classpath-exception-2.0)"));
+        assert_eq!(normalize("Apache-2.0 WITH LLVM-exception").as_deref(), Some("Apache-2.0 WITH LLVM-exception"));
+        assert_eq!(normalize("apache2").as_deref(), Some("Apache-2.0"));
+        assert_eq!(normalize("MIT AND"), None);
+
+        assert_eq!(normalize("GPL-3.0+").as_deref(), Some("GPL-3.0-or-later"));
+        assert_eq!(normalize("AGPL-3.0").as_deref(), Some("AGPL-3.0-only"));
+        assert_eq!(normalize("bsd-3-clause and isc").as_deref(), Some("BSD-3-Clause AND ISC"));
+        assert_eq!(normalize("GPL-2.0-only OR MIT").as_deref(), Some("GPL-2.0-only OR MIT"), "current ids are kept");
+        assert_eq!(normalize("MIT OR (LGPL-2.1 AND Zlib)").as_deref(), Some("MIT OR (LGPL-2.1-only AND Zlib)"));
+    }
+}
+
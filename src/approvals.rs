@@ -0,0 +1,337 @@
+//! Legal sign-off of the licenses of the packages, recorded in a sidecar file (`license-approvals.toml`) next to the
+//! project, see [`Approvals`]
+
+use crate::{analysis::canonical_expression, AggregateError, Package, Utf8Path};
+use krates::semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+
+/// The approved package/license combinations of a project.
+///
+/// # Example
+/// ```toml
+/// [[approvals]]
+/// package = "ring"
+/// version = "^0.17"
+/// license = "ISC AND MIT AND OpenSSL"
+/// license-hash = "5d3c..."
+/// reviewer = "legal@example.org"
+/// date = "2026-10-14"
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Approvals {
+    #[serde(default)]
+    pub approvals: Vec<Approval>,
+}
+
+/// The approval of the license of a package
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Approval {
+    /// Name of the package
+    pub package: String,
+    /// The approved versions, a semver requirement (e.g. `^0.17`) or the exact version of packages
+    /// whose version is not semver (e.g. thirdparty packages)
+    pub version: String,
+    /// The license of the package at the time of the approval, for the reader of the file
+    pub license: Option<String>,
+    /// The [`license_hash`] of the package at the time of the approval
+    pub license_hash: String,
+    pub reviewer: String,
+    /// The date of the approval, e.g. `2026-10-14`
+    pub date: String,
+}
+
+impl Approval {
+    /// Whether the approval applies to the given version of the package with the given name
+    pub fn matches(&self, name: &str, version: &str) -> bool {
+        if self.package != name {
+            return false;
+        }
+
+        match (VersionReq::parse(&self.version), Version::parse(version)) {
+            (Ok(req), Ok(version)) => req.matches(&version),
+            _ => self.version == version,
+        }
+    }
+}
+
+/// Why a package needs an approval
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PendingReason {
+    /// No approval covers the version of the package
+    NotApproved,
+    /// The license (or a license text) of the package changed since the approval
+    LicenseChanged,
+}
+
+/// A package whose license has to be approved
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub package_name: String,
+    pub package_version: String,
+    pub license: Option<String>,
+    pub license_hash: String,
+    pub reason: PendingReason,
+}
+
+/// The result of [`Approvals::review`]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ApprovalReport {
+    pub pending: Vec<PendingApproval>,
+}
+
+impl ApprovalReport {
+    pub fn is_approved(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Fail with [`AggregateError::NotApproved`] if there are pending approvals
+    pub fn into_result(self) -> Result<Self, AggregateError> {
+        if self.is_approved() {
+            Ok(self)
+        } else {
+            Err(AggregateError::NotApproved(self))
+        }
+    }
+}
+
+impl Display for PendingApproval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let license = self.license.as_deref().unwrap_or("unknown license");
+        match self.reason {
+            PendingReason::NotApproved => {
+                write!(f, "'{} {}' ({license}) is not approved", self.package_name, self.package_version)
+            },
+            PendingReason::LicenseChanged => write!(
+                f,
+                "the license of '{} {}' ({license}) changed since its approval",
+                self.package_name, self.package_version
+            ),
+        }
+    }
+}
+
+impl Display for ApprovalReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for pending in &self.pending {
+            writeln!(f, "- {pending}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The hex encoded SHA-256 of the license of a package and the texts of its license files.
+///
+/// The hash neither depends on the order of the license files nor on the canonicalization, truncation (see
+/// [`crate::truncate_texts`]) or deduplication (see [`crate::DedupedPackages`]) of the packages.
+pub fn license_hash(package: &Package) -> String {
+    let mut text_hashes: Vec<_> = package
+        .license_files
+        .iter()
+        .map(|l| match (&l.text_ref, &l.text_sha256) {
+            (Some(key), _) => key.clone(),
+            (None, Some(sha256)) if l.text_truncated => sha256.clone(),
+            _ => format!("{:x}", Sha256::digest(l.text.as_bytes())),
+        })
+        .collect();
+    text_hashes.sort();
+
+    let mut hasher = Sha256::new();
+    if let Some(license) = &package.license_spdx {
+        hasher.update(canonical_expression(license).to_string());
+    }
+    for hash in text_hashes {
+        hasher.update(b"\n");
+        hasher.update(hash);
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+impl Approvals {
+    /// The approval covering the given package, if any
+    pub fn find(&self, package: &Package) -> Option<&Approval> {
+        self.approvals
+            .iter()
+            .rev()
+            .find(|a| a.matches(&package.package_name, &package.package_version))
+    }
+
+    /// The packages whose license has not been approved, either because no approval covers their version or because
+    /// their license changed since it was approved
+    pub fn review(&self, packages: &[Package]) -> ApprovalReport {
+        let pending = packages
+            .iter()
+            .filter_map(|p| {
+                let hash = license_hash(p);
+                let reason = match self.find(p) {
+                    None => PendingReason::NotApproved,
+                    Some(approval) if approval.license_hash != hash => PendingReason::LicenseChanged,
+                    Some(_) => return None,
+                };
+
+                Some(PendingApproval {
+                    package_name: p.package_name.clone(),
+                    package_version: p.package_version.clone(),
+                    license: p.license_spdx.as_ref().map(ToString::to_string),
+                    license_hash: hash,
+                    reason,
+                })
+            })
+            .collect();
+
+        ApprovalReport { pending }
+    }
+
+    /// Approve the current license of the given version of the package, replacing previous approvals of the same
+    /// version
+    pub fn approve<R: Into<String>, D: Into<String>>(&mut self, package: &Package, reviewer: R, date: D) {
+        let version = match Version::parse(&package.package_version) {
+            Ok(_) => format!("={}", package.package_version),
+            Err(_) => package.package_version.clone(),
+        };
+
+        self.approvals.retain(|a| a.package != package.package_name || a.version != version);
+        self.approvals.push(Approval {
+            package: package.package_name.clone(),
+            version,
+            license: package.license_spdx.as_ref().map(ToString::to_string),
+            license_hash: license_hash(package),
+            reviewer: reviewer.into(),
+            date: date.into(),
+        });
+    }
+
+    /// Write the approvals to the given path, e.g. after [`Approvals::approve`]
+    pub fn save<P: AsRef<Utf8Path>>(&self, path: P) -> Result<(), AggregateError> {
+        let path = path.as_ref();
+        let content = toml::to_string(self)
+            .map_err(|e| AggregateError::Write { path: path.to_owned(), source: std::io::Error::other(e) })?;
+
+        std::fs::write(path, content).map_err(|source| AggregateError::Write { path: path.to_owned(), source })
+    }
+}
+
+/// Load the approvals from the given path, nothing is approved if the file does not exist (yet)
+pub fn load_approvals<P: AsRef<Utf8Path>>(path: P) -> Result<Approvals, AggregateError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(Approvals::default());
+    }
+
+    let content = std::fs::read_to_string(path)
+        .map_err(|source| AggregateError::ConfigRead { path: path.to_owned(), source })?;
+
+    toml::from_str(&content).map_err(|source| AggregateError::ConfigParse { path: path.to_owned(), source })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str, license: &str, texts: &[&str]) -> Package {
+        let files: Vec<_> =
+            texts.iter().map(|text| serde_json::json!({"name": "LICENSE", "spdx": null, "text": text})).collect();
+        serde_json::from_value(serde_json::json!({
+            "package_name": name,
+            "package_version": version,
+            "package_url": null,
+            "license_spdx": license,
+            "license_files": files,
+        }))
+        .unwrap()
+    }
+
+    fn approval(package: &str, version: &str) -> Approval {
+        Approval {
+            package: package.to_owned(),
+            version: version.to_owned(),
+            license: None,
+            license_hash: String::new(),
+            reviewer: "legal@example.org".to_owned(),
+            date: "2026-10-14".to_owned(),
+        }
+    }
+
+    #[test]
+    fn matching_versions() {
+        let ring = approval("ring", "^0.17");
+        assert!(ring.matches("ring", "0.17.8"));
+        assert!(!ring.matches("ring", "0.16.20"));
+        assert!(!ring.matches("ring", "0.18.0-alpha"));
+        assert!(!ring.matches("other", "0.17.8"));
+
+        // compared exactly with versions that are not semver, a requirement for the others
+        let zlib = approval("zlib", "1.3");
+        assert!(zlib.matches("zlib", "1.3"));
+        assert!(zlib.matches("zlib", "1.3.1"));
+        assert!(!zlib.matches("zlib", "1.2"));
+        let openssl = approval("openssl", "3.0.2a");
+        assert!(openssl.matches("openssl", "3.0.2a"));
+        assert!(!openssl.matches("openssl", "3.0.2b"));
+    }
+
+    #[test]
+    fn hashes() {
+        let hash = license_hash(&package("a", "1.0.0", "MIT OR Apache-2.0", &["mit", "apache"]));
+        assert_eq!(hash, license_hash(&package("a", "1.0.0", "Apache-2.0 OR MIT", &["apache", "mit"])));
+        assert_eq!(hash, license_hash(&package("b", "2.0.0", "MIT OR Apache-2.0", &["mit", "apache"])));
+        assert_ne!(hash, license_hash(&package("a", "1.0.0", "MIT", &["mit", "apache"])));
+        assert_ne!(hash, license_hash(&package("a", "1.0.0", "MIT OR Apache-2.0", &["mit", "apache 2"])));
+        assert_ne!(hash, license_hash(&package("a", "1.0.0", "MIT OR Apache-2.0", &["mit"])));
+    }
+
+    #[test]
+    fn review() {
+        let ring = package("ring", "0.17.8", "ISC AND MIT", &["isc", "mit"]);
+        let mut approvals = Approvals::default();
+        approvals.approve(&ring, "legal@example.org", "2026-10-14");
+        assert_eq!(approvals.approvals[0].version, "=0.17.8");
+        assert_eq!(approvals.approvals[0].license.as_deref(), Some("ISC AND MIT"));
+
+        let relicensed = package("ring", "0.17.8", "ISC AND MIT AND OpenSSL", &["isc", "mit"]);
+        let packages = [ring.clone(), relicensed, package("ring", "0.17.9", "ISC AND MIT", &["isc", "mit"])];
+        let report = approvals.review(&packages);
+        let pending: Vec<_> = report.pending.iter().map(|p| (p.package_version.as_str(), p.reason)).collect();
+        assert_eq!(pending, [("0.17.8", PendingReason::LicenseChanged), ("0.17.9", PendingReason::NotApproved)]);
+        assert_eq!(report.pending[0].license_hash, license_hash(&packages[1]));
+        assert_eq!(
+            report.to_string(),
+            "- the license of 'ring 0.17.8' (ISC AND MIT AND OpenSSL) changed since its approval\n\
+             - 'ring 0.17.9' (ISC AND MIT) is not approved\n"
+        );
+        assert!(matches!(report.into_result(), Err(AggregateError::NotApproved(_))));
+
+        // a requirement covers the new version, the last matching approval wins
+        approvals.approvals.push(Approval { license_hash: license_hash(&ring), ..approval("ring", "^0.17") });
+        assert!(approvals.review(&packages[2..]).is_approved());
+        assert!(approvals.review(&[ring]).is_approved());
+    }
+
+    #[test]
+    fn approve_replaces_the_same_version() {
+        let mut approvals = Approvals { approvals: vec![approval("zlib", "^1"), approval("other", "=1.3.1")] };
+        approvals.approve(&package("zlib", "1.3.1", "Zlib", &["zlib"]), "a@example.org", "2026-01-01");
+        approvals.approve(&package("zlib", "1.3.1", "Zlib", &["zlib 2"]), "b@example.org", "2026-02-01");
+        approvals.approve(&package("zlib", "1.3-p1", "Zlib", &["zlib"]), "b@example.org", "2026-02-01");
+
+        let entries: Vec<_> =
+            approvals.approvals.iter().map(|a| (a.package.as_str(), a.version.as_str(), a.reviewer.as_str())).collect();
+        assert_eq!(
+            entries,
+            [
+                ("zlib", "^1", "legal@example.org"),
+                ("other", "=1.3.1", "legal@example.org"),
+                ("zlib", "=1.3.1", "b@example.org"),
+                ("zlib", "1.3-p1", "b@example.org"),
+            ]
+        );
+        let latest = package("zlib", "1.3.1", "Zlib", &["zlib 2"]);
+        assert_eq!(approvals.find(&latest).unwrap().license_hash, license_hash(&latest));
+    }
+}
@@ -0,0 +1,141 @@
+//! In-toto statements, checksums and signatures of the generated attribution files, so they can be verified later,
+//! see [`Statement`] and [`write_checksum`]
+
+use crate::{AggregateError, Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, process::Command};
+
+/// The type of in-toto statements
+pub const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+/// The type of the predicate of the statements written by this crate
+pub const ATTRIBUTION_PREDICATE_TYPE: &str = "https://github.com/dice-group/tentris-license-aggregator/attribution/v1";
+
+/// An in-toto statement (v1) that the subjects were generated by this crate from the inputs, e.g. to be signed with
+/// `cosign attest-blob` in a release pipeline
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Statement {
+    /// Always [`STATEMENT_TYPE`]
+    #[serde(rename = "_type")]
+    pub statement_type: String,
+    /// The generated files
+    pub subject: Vec<ResourceDescriptor>,
+    /// Always [`ATTRIBUTION_PREDICATE_TYPE`]
+    pub predicate_type: String,
+    pub predicate: AttributionPredicate,
+}
+
+/// A file with its digests
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResourceDescriptor {
+    pub name: String,
+    /// The hex encoded digests by algorithm, i.e. `sha256`
+    pub digest: BTreeMap<String, String>,
+}
+
+/// How the subjects of a [`Statement`] were generated
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AttributionPredicate {
+    pub tool: Tool,
+    /// The files the subjects were generated from, e.g. the manifest and the `Cargo.lock`
+    pub inputs: Vec<ResourceDescriptor>,
+    /// The configuration of the run, if it was loaded from a file
+    pub config: Option<ResourceDescriptor>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tool {
+    pub name: String,
+    pub version: String,
+}
+
+impl ResourceDescriptor {
+    /// Hash the file, named as given
+    pub fn from_file<P: AsRef<Utf8Path>>(path: P) -> Result<Self, AggregateError> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).map_err(|source| AggregateError::Attestation { path: path.to_owned(), source })?;
+
+        Ok(Self {
+            name: path.to_string(),
+            digest: BTreeMap::from([("sha256".to_owned(), format!("{:x}", Sha256::digest(bytes)))]),
+        })
+    }
+}
+
+impl Statement {
+    /// The statement about the generated files, which are named by their file name. Inputs that do not exist
+    /// (e.g. a missing `Cargo.lock`) are skipped
+    pub fn new<P: AsRef<Utf8Path>>(
+        subjects: &[P],
+        inputs: &[P],
+        config: Option<&Utf8Path>,
+    ) -> Result<Self, AggregateError> {
+        let inputs = inputs.iter().filter(|input| input.as_ref().exists());
+
+        Ok(Self {
+            statement_type: STATEMENT_TYPE.to_owned(),
+            subject: subjects.iter().map(subject).collect::<Result<_, _>>()?,
+            predicate_type: ATTRIBUTION_PREDICATE_TYPE.to_owned(),
+            predicate: AttributionPredicate {
+                tool: Tool { name: env!("CARGO_PKG_NAME").to_owned(), version: env!("CARGO_PKG_VERSION").to_owned() },
+                inputs: inputs.map(ResourceDescriptor::from_file).collect::<Result<_, _>>()?,
+                config: config.map(ResourceDescriptor::from_file).transpose()?,
+            },
+        })
+    }
+
+    /// Write the statement as JSON to the given file
+    pub fn write_to<P: AsRef<Utf8Path>>(&self, path: P) -> Result<(), AggregateError> {
+        let path = path.as_ref();
+        let write_err = |source| AggregateError::Write { path: path.to_owned(), source };
+
+        let json = serde_json::to_string_pretty(self).map_err(|e| write_err(e.into()))?;
+        std::fs::write(path, json).map_err(write_err)
+    }
+}
+
+/// Write the SHA-256 of the file next to it (e.g. `packages.json.sha256`) in the format of `sha256sum`, so it can be
+/// verified with `sha256sum -c`. Returns the path of the checksum file.
+pub fn write_checksum<P: AsRef<Utf8Path>>(path: P) -> Result<Utf8PathBuf, AggregateError> {
+    let path = path.as_ref();
+    let descriptor = subject(path)?;
+    let checksum_path = Utf8PathBuf::from(format!("{path}.sha256"));
+    let line = format!("{}  {}\n", descriptor.digest["sha256"], descriptor.name);
+    match std::fs::write(&checksum_path, line) {
+        Ok(()) => Ok(checksum_path),
+        Err(source) => Err(AggregateError::Write { path: checksum_path, source }),
+    }
+}
+
+/// Sign the file with the `minisign` executable and the given secret key, which must not be protected by a password.
+/// The detached signature is written next to the file (e.g. `packages.json.minisig`), its path is returned.
+pub fn sign_with_minisign<P: AsRef<Utf8Path>, K: AsRef<Utf8Path>>(
+    path: P,
+    secret_key: K,
+) -> Result<Utf8PathBuf, AggregateError> {
+    let path = path.as_ref();
+    let signature_path = Utf8PathBuf::from(format!("{path}.minisig"));
+    let failed = |reason: String| AggregateError::Signature { path: path.to_owned(), reason };
+
+    let output = Command::new("minisign")
+        .args(["-S", "-s", secret_key.as_ref().as_str(), "-m", path.as_str(), "-x", signature_path.as_str()])
+        .output()
+        .map_err(|e| failed(format!("unable to run minisign: {e}")))?;
+    if !output.status.success() {
+        return Err(failed(String::from_utf8_lossy(&output.stderr).trim().to_owned()));
+    }
+
+    Ok(signature_path)
+}
+
+/// Subjects are named by their file name, the directory they are generated into is irrelevant for their verification
+fn subject<P: AsRef<Utf8Path>>(path: P) -> Result<ResourceDescriptor, AggregateError> {
+    let path = path.as_ref();
+    let mut descriptor = ResourceDescriptor::from_file(path)?;
+    if let Some(file_name) = path.file_name() {
+        descriptor.name = file_name.to_owned();
+    }
+    Ok(descriptor)
+}
@@ -0,0 +1,114 @@
+//! Records of the automated decisions of a run, so it can be reconstructed later how an attribution file was produced,
+//! see [`AuditRecord`]
+
+use crate::{
+    analysis::RemovedLicenseFile, policy::Date, AggregateError, Aggregation, DetectionMethod, Diagnostic,
+    DiagnosticKind, Utf8Path, Utf8PathBuf,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, io::Write};
+
+/// How the license of a single file was determined
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditDetection {
+    pub package_name: String,
+    pub package_version: String,
+    pub file: String,
+    pub spdx: Option<String>,
+    pub detection_method: Option<DetectionMethod>,
+    pub detection_score: Option<f32>,
+    /// Where the text was fetched from, if it was not part of the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+}
+
+/// The record of a single run, appended as one line of JSON to the audit log (see [`AuditRecord::append_to`])
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// The day of the run (UTC)
+    pub date: Date,
+    /// Seconds since the unix epoch at the end of the run
+    pub timestamp: u64,
+    /// The version of this library that produced the output
+    pub tool_version: String,
+    /// The root of the workspace whose licenses were aggregated
+    pub workspace: Utf8PathBuf,
+    /// The detected (or otherwise determined) license of every file
+    pub detections: Vec<AuditDetection>,
+    /// The license files that were removed by the minimization
+    pub removed: Vec<RemovedLicenseFile>,
+    /// The remote sources that provided license information, e.g. `clearlydefined.io` or the URL of a fetched file
+    pub network_sources: BTreeSet<String>,
+    /// All diagnostics of the run, which include the overrides, clarifications and resolved licenses
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl AuditRecord {
+    /// Record the decisions of the given aggregation of the given workspace
+    pub fn new<P: Into<Utf8PathBuf>>(workspace: P, aggregation: &Aggregation) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        let detections = aggregation
+            .packages
+            .iter()
+            .flat_map(|p| {
+                p.license_files.iter().map(|l| AuditDetection {
+                    package_name: p.package_name.clone(),
+                    package_version: p.package_version.clone(),
+                    file: l.name.clone(),
+                    spdx: l.spdx.as_ref().map(ToString::to_string),
+                    detection_method: l.detection_method,
+                    detection_score: l.detection_score,
+                    source_url: l.source_url.clone(),
+                })
+            })
+            .collect();
+
+        let mut network_sources: BTreeSet<String> = aggregation
+            .packages
+            .iter()
+            .flat_map(|p| p.license_files.iter())
+            .filter_map(|l| l.source_url.clone())
+            .collect();
+
+        network_sources.extend(aggregation.diagnostics.iter().filter_map(|d| {
+            match d.kind {
+                DiagnosticKind::ClearlyDefinedLicense | DiagnosticKind::ClearlyDefinedMismatch => Some("clearlydefined.io"),
+                DiagnosticKind::CratesIoLicense
+                | DiagnosticKind::CratesIoLicenseMismatch
+                | DiagnosticKind::CratesIoLookupFailed => Some("crates.io"),
+                _ => None,
+            }
+            .map(ToOwned::to_owned)
+        }));
+
+        Self {
+            date: Date::today(),
+            timestamp,
+            tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+            workspace: workspace.into(),
+            detections,
+            removed: aggregation.minimization.removed.clone(),
+            network_sources,
+            diagnostics: aggregation.diagnostics.iter().cloned().collect(),
+        }
+    }
+
+    /// Append the record as one line of JSON to the given file, which is created if it does not exist
+    pub fn append_to<P: AsRef<Utf8Path>>(&self, path: P) -> Result<(), AggregateError> {
+        let path = path.as_ref();
+        let write_err = |source| AggregateError::Write { path: path.to_owned(), source };
+
+        let mut line = serde_json::to_string(self).map_err(|e| write_err(e.into()))?;
+        line.push('\n');
+
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(write_err)
+    }
+}
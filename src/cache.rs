@@ -0,0 +1,126 @@
+use crate::{
+    analysis::{store_fingerprint, AnalysisMemo},
+    LicenseStore, Package, Utf8Path, Utf8PathBuf,
+};
+use std::time::{Duration, SystemTime};
+
+/// On-disk cache of the packages gathered for crates from crates.io, keyed by crate name and version.
+///
+/// If every crate from crates.io in the dependency graph has a fresh entry, gathering runs without any remote lookups
+/// (only the sources of the remaining crates are scanned) and the cached packages are used instead.
+/// Otherwise all crates are gathered as usual and the entries are refreshed.
+/// [`crate::KrateHook`]s are not invoked for crates that are taken from the cache.
+///
+/// The cache also keeps the analyses of the license texts of thirdparty packages (see
+/// [`crate::analysis::AnalysisMemo`]), so unchanged texts are not analyzed again in later runs.
+///
+/// # Example
+/// `GatherOptions::new().cache(LicenseCache::user_default().unwrap().ttl(Duration::from_secs(24 * 60 * 60)))`
+#[derive(Clone, Debug)]
+pub struct LicenseCache {
+    dir: Utf8PathBuf,
+    ttl: Duration,
+}
+
+impl LicenseCache {
+    /// Cache in the given directory with a time to live of 7 days
+    pub fn new<P: Into<Utf8PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into(), ttl: Duration::from_secs(7 * 24 * 60 * 60) }
+    }
+
+    /// Cache in `$XDG_CACHE_HOME/tentris-license-aggregator` (or `~/.cache/tentris-license-aggregator`),
+    /// `None` if neither variable is set
+    pub fn user_default() -> Option<Self> {
+        let base = match std::env::var("XDG_CACHE_HOME") {
+            Ok(dir) if !dir.is_empty() => Utf8PathBuf::from(dir),
+            _ => Utf8PathBuf::from(std::env::var("HOME").ok()?).join(".cache"),
+        };
+
+        Some(Self::new(base.join(env!("CARGO_PKG_NAME"))))
+    }
+
+    /// Entries older than this are not used
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    /// Cache with the same time to live in a subdirectory, for entries that are not gathered packages
+    #[cfg(feature = "network")]
+    pub(crate) fn subcache(&self, name: &str) -> Self {
+        Self { dir: self.dir.join(name), ttl: self.ttl }
+    }
+
+    /// The fresh cache entry of the crate, if any
+    pub(crate) fn get(&self, name: &str, version: &str) -> Option<Package> {
+        let path = self.path(name, version);
+
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if SystemTime::now().duration_since(modified).unwrap_or_default() > self.ttl {
+            return None;
+        }
+
+        let content = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(package) => Some(package),
+            Err(e) => {
+                tracing::warn!("ignoring invalid cache entry {path}: {e}");
+                None
+            },
+        }
+    }
+
+    /// Store the package, failures are only logged since the cache is an optimization
+    pub(crate) fn put(&self, package: &Package) {
+        let path = self.path(&package.package_name, &package.package_version);
+
+        let result = std::fs::create_dir_all(&self.dir).and_then(|_| {
+            let content = serde_json::to_string(package)?;
+            std::fs::write(&path, content)
+        });
+
+        if let Err(e) = result {
+            tracing::warn!("unable to write cache entry {path}: {e}");
+        }
+    }
+
+    /// The license text analyses of previous runs with the license store (see [`AnalysisMemo`]), empty if there are
+    /// none. The analyses do not expire, they only depend on the texts and the store.
+    pub(crate) fn analyses(&self, license_store: &LicenseStore) -> AnalysisMemo {
+        let path = self.analyses_path(license_store);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return AnalysisMemo::new();
+        };
+
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring invalid cache entry {path}: {e}");
+            AnalysisMemo::new()
+        })
+    }
+
+    /// Store the license text analyses, failures are only logged since the cache is an optimization
+    pub(crate) fn put_analyses(&self, license_store: &LicenseStore, memo: &AnalysisMemo) {
+        let path = self.analyses_path(license_store);
+
+        let result = std::fs::create_dir_all(&self.dir).and_then(|_| {
+            let content = serde_json::to_string(memo)?;
+            std::fs::write(&path, content)
+        });
+
+        if let Err(e) = result {
+            tracing::warn!("unable to write cache entry {path}: {e}");
+        }
+    }
+
+    fn analyses_path(&self, license_store: &LicenseStore) -> Utf8PathBuf {
+        self.dir.join(format!("analyses-{}.json", store_fingerprint(license_store)))
+    }
+
+    fn path(&self, name: &str, version: &str) -> Utf8PathBuf {
+        self.dir.join(format!("{name}-{version}.json"))
+    }
+}
@@ -0,0 +1,2697 @@
+//! Collection of the licenses of rust crates (and the thirdparty licenses they declare) via cargo-about
+
+use crate::{
+    analysis::{self, AnalysisMemo, DetectionOptions, MinimizationOptions, MinimizationReport},
+    changes::{detect_license_changes, LicenseChange},
+    compatibility::{check_compatibility, CompatibilityReport},
+    env::EnvOverrides,
+    options::check_cancelled,
+    plan::{Plan, PlannedKrate, PlannedThirdpartyFile},
+    policy::{check_policy, PolicyReport},
+    search,
+    add_custom_licenses, check_spdx_headers, find_license_files, find_notice_files, load_aggregator_config,
+    merge_versions, sign_with_minisign, shared_license_store, write_checksum, write_problems, AggregatorConfig,
+    AggregateError, CargoSource, DetectionMethod, Diagnostic, DiagnosticKind, Diagnostics, DuplicateVersions,
+    Expression, FileKind, Fork, GatherOptions, GitReference, LicenseCache, LicenseFile, LicenseOverride, LicenseSource,
+    LicenseStore, LicenseStoreProvider, Linkage, NativeCode, Origin, Package, RunContext, Severity, Statement,
+    ThirdpartySource, UnknownLicenseAction, Utf8Path, Utf8PathBuf, PROBLEMS_FILE,
+};
+#[cfg(feature = "network")]
+use crate::{Advisory, ClearlyDefined, Osv, RepositoryFetcher};
+use cargo_about::{
+    licenses::{
+        config::{Clarification, ClarificationFile, Config},
+        Gatherer, KrateLicense, LicenseFileKind, LicenseInfo,
+    },
+    validate_sha256, Krate, Krates,
+};
+use globset::GlobSet;
+use krates::{DepKind, Scope};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    io::BufReader,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+/// Create a license store from an internal cache
+pub fn license_store_from_cache() -> Result<Arc<LicenseStore>, AggregateError> {
+    let store = cargo_about::licenses::store_from_cache().map_err(|e| AggregateError::LicenseStore(e.into()))?;
+    Ok(Arc::new(store))
+}
+
+/// Load the cargo-about part of a configuration (typically called `about.toml`) from the given path,
+/// see [`load_aggregator_config`] for the complete configuration
+pub fn load_config<P: AsRef<Utf8Path>>(path: P) -> Result<Config, AggregateError> {
+    Ok(load_aggregator_config(path)?.about)
+}
+
+/// Retrieve all rust packages and their licenses based on the Cargo.toml at the given path
+pub fn get_all_licenses<P: AsRef<Utf8Path>>(
+    cargo_toml: P,
+    options: &GatherOptions,
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Package>, AggregateError> {
+    if options.generates_lockfile(cargo_toml.as_ref()) {
+        diagnostics.push(generated_lockfile(cargo_toml.as_ref()));
+    }
+    let krates = get_all_krates(cargo_toml, options, config)?;
+    collect_krate_licenses(&krates, license_store, config, options, diagnostics)
+}
+
+/// Like [`get_all_licenses`], but yields the packages one by one as they are converted.
+///
+/// Gathering (scanning of the crate sources and remote lookups) still happens up front,
+/// but license headers are only read from disk and converted into [`Package`]s when the iterator is advanced,
+/// so callers that process and drop each package do not hold the complete result in memory at once.
+/// The diagnostics are collected in the iterator, see [`PackageIter::diagnostics`].
+pub fn get_all_licenses_iter<P: AsRef<Utf8Path>>(
+    cargo_toml: P,
+    options: &GatherOptions,
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+) -> Result<PackageIter, AggregateError> {
+    let mut diagnostics = Diagnostics::new();
+    if options.generates_lockfile(cargo_toml.as_ref()) {
+        diagnostics.push(generated_lockfile(cargo_toml.as_ref()));
+    }
+    let krates = get_all_krates(cargo_toml, options, config)?;
+    let gathered = gather_krates(&krates, license_store, config, options, |_| true)?;
+    #[cfg_attr(not(all(feature = "network", feature = "tokio")), allow(unused_mut))]
+    let mut converter = Converter::new(options)?;
+    #[cfg(all(feature = "network", feature = "tokio"))]
+    converter.prefetch(&gathered, options)?;
+
+    Ok(PackageIter {
+        gathered: gathered.into_iter(),
+        diagnostics,
+        cancelled: options.cancelled.clone(),
+        converter,
+    })
+}
+
+/// Resolve the dependency graph of the Cargo.toml at the given path.
+///
+/// A missing Cargo.lock is written with [`GatherOptions::allow_lockfile_generation`], which [`get_all_licenses`] and
+/// [`RunContext`] report as [`DiagnosticKind::GeneratedLockfile`].
+pub fn get_all_krates<P: AsRef<Utf8Path>>(
+    cargo_toml: P,
+    options: &GatherOptions,
+    config: &Config,
+) -> Result<Krates, AggregateError> {
+    let cargo_toml = cargo_toml.as_ref();
+    options.check_cancelled()?;
+
+    let mut mdc = krates::Cmd::new();
+    mdc.manifest_path(cargo_toml);
+    mdc.lock_opts(options.lock_options(cargo_toml));
+
+    if options.no_default_features {
+        mdc.no_default_features();
+    }
+
+    if options.all_features {
+        mdc.all_features();
+    }
+
+    mdc.features(options.features.iter().cloned());
+
+    let mut builder = krates::Builder::new();
+
+    if config.ignore_build_dependencies {
+        builder.ignore_kind(DepKind::Build, Scope::All);
+    }
+
+    if config.ignore_dev_dependencies || !options.include_dev_dependencies {
+        builder.ignore_kind(DepKind::Dev, Scope::All);
+    }
+
+    if config.ignore_transitive_dependencies {
+        builder.ignore_kind(DepKind::Normal, Scope::NonWorkspace);
+        builder.ignore_kind(DepKind::Dev, Scope::NonWorkspace);
+        builder.ignore_kind(DepKind::Build, Scope::NonWorkspace);
+    }
+
+    let targets = if options.targets.is_empty() { &config.targets } else { &options.targets };
+    builder.include_targets(targets.iter().map(|triple| (triple.as_str(), vec![])));
+
+    let krates = builder
+        .build(mdc, |filtered: krates::cm::Package| {
+            tracing::debug!("filtered {} {}", filtered.name, filtered.version)
+        })
+        .map_err(|e| AggregateError::ManifestResolution { manifest: cargo_toml.to_owned(), source: e.into() })?;
+
+    options.check_cancelled()?;
+    Ok(krates)
+}
+
+/// The report that resolving the manifest writes its missing Cargo.lock, see [`GatherOptions::generates_lockfile`]
+pub(crate) fn generated_lockfile(cargo_toml: &Utf8Path) -> Diagnostic {
+    Diagnostic::warning(
+        DiagnosticKind::GeneratedLockfile,
+        format!("{cargo_toml} has no Cargo.lock, the dependencies were resolved to write one"),
+    )
+}
+
+/// The metadata key under which crates declare their thirdparty license file by default,
+/// i.e. `[package.metadata.tentris] thirdparty-file-name = "..."`
+pub const DEFAULT_THIRDPARTY_METADATA_KEY: &str = "tentris";
+
+/// Collect the packages from all thirdparty files declared in the crates' metadata
+/// (`[package.metadata.<metadata_key>] thirdparty-file-name = "..."`) and augment their licenses
+/// (see [`augment_licenses_with`], with the detection options of `options`).
+/// The paths are relative to the manifest of the declaring crate.
+///
+/// The thirdparty files are expected to contain a JSON array of [`Package`]s, as generated e.g. from conan.
+/// Packages without an origin whose `source_archive` is a git submodule (e.g. a vendored checkout next to the
+/// thirdparty file) get the remote, commit and tag or branch of the submodule as origin.
+/// See [`stream_thirdparty_licenses`] to process huge files without holding all packages in memory.
+pub fn collect_thirdparty_licenses(
+    krates: &Krates,
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+    options: &GatherOptions,
+    metadata_key: &str,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Package>, AggregateError> {
+    let mut packages = Vec::new();
+    stream_thirdparty_licenses(krates, license_store, config, options, metadata_key, diagnostics, |pkg| {
+        packages.push(pkg)
+    })?;
+    Ok(packages)
+}
+
+/// Like [`collect_thirdparty_licenses`], but pass the augmented packages to `f` one by one while parsing, so only a
+/// single package is held in memory at a time, e.g. to write them out directly
+pub fn stream_thirdparty_licenses<F: FnMut(Package)>(
+    krates: &Krates,
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+    options: &GatherOptions,
+    metadata_key: &str,
+    diagnostics: &mut Diagnostics,
+    mut f: F,
+) -> Result<(), AggregateError> {
+    let mut memo = match &options.cache {
+        Some(cache) => cache.analyses(&license_store),
+        None => AnalysisMemo::new(),
+    };
+
+    for krate in krates.krates() {
+        let Some(file_name) = krate
+            .metadata
+            .get(metadata_key)
+            .and_then(|m| m.get("thirdparty-file-name"))
+            .and_then(|f| f.as_str())
+        else {
+            continue;
+        };
+
+        let path = krate.manifest_path.parent().unwrap().join(file_name);
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(source) => return Err(AggregateError::LicenseRead { path, source }),
+        };
+
+        let parsed = crate::stream_packages_with(BufReader::new(file), options.spdx_parsing, |mut pkg| {
+            if let Some(archive) = pkg.source_archive.as_mut().filter(|a| !a.contains("://")) {
+                *archive = path.parent().unwrap().join(&*archive).to_string();
+            }
+            if pkg.origin.is_none() {
+                let checkout = pkg.source_archive.as_deref().map(Utf8Path::new).filter(|dir| dir.is_dir());
+                pkg.origin = checkout.and_then(crate::scancode::submodule_origin);
+            }
+            augment_package(&mut pkg, &license_store, config, &options.detection, &mut memo, diagnostics);
+            if pkg.purl.is_none() {
+                pkg.purl = Some(crate::generic_purl(&pkg.package_name, &pkg.package_version));
+            }
+            f(pkg);
+        });
+
+        if let Err(source) = parsed {
+            return Err(AggregateError::ThirdpartyParse { path, source });
+        }
+    }
+
+    if let Some(cache) = options.cache.as_ref().filter(|_| !memo.is_empty()) {
+        cache.put_analyses(&license_store, &memo);
+    }
+
+    Ok(())
+}
+
+/// The result of [`Aggregator::run`]
+pub struct Aggregation {
+    /// All collected packages
+    pub packages: Vec<Package>,
+    /// The anomalies found during the run
+    pub diagnostics: Diagnostics,
+    /// The packages whose requirements could not be minimized, empty if minimization is disabled
+    pub minimization: MinimizationReport,
+    /// The evaluation of the license policy, if enabled with [`Aggregator::check_policy`]
+    pub policy: Option<PolicyReport>,
+    /// The compatibility of the dependencies with the project license, if configured
+    /// (see [`GatherOptions::project_licenses`])
+    pub compatibility: Option<CompatibilityReport>,
+    /// The dependencies whose license changed since the previous run, empty without one (see [`GatherOptions::previous`])
+    pub license_changes: Vec<LicenseChange>,
+    /// The advisories affecting the packages, if enabled with [`Aggregator::check_advisories`]
+    #[cfg(feature = "network")]
+    pub advisories: Vec<Advisory>,
+    /// The crates only needed for building, if separated with [`BuildTimeAction::Separate`]
+    pub build_tools: Vec<Package>,
+}
+
+/// What an [`Aggregator`] does with the crates that are only needed for building (see
+/// [`crate::graph::build_time_krates`]), i.e. proc-macros and build dependencies that are not distributed with the
+/// binary
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuildTimeAction {
+    /// Treat them like all other crates
+    #[default]
+    Keep,
+    /// Keep them, but with [`Linkage::ToolOnly`], so they have no obligations
+    Mark,
+    /// Like [`BuildTimeAction::Mark`], but move them into [`Aggregation::build_tools`]
+    Separate,
+    /// Leave them out of the result
+    Omit,
+}
+
+/// A named set of features, one variant of the build (e.g. `default`, `alpine-build` or `cuda`), see
+/// [`Aggregator::run_feature_sets`]
+///
+/// # Example
+/// ```toml
+/// name = "cuda"
+/// features = ["cuda", "tensor-cores"]
+/// no-default-features = true
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FeatureSet {
+    pub name: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub no_default_features: bool,
+}
+
+impl FeatureSet {
+    pub fn new<S: Into<String>>(name: S, features: Vec<String>) -> Self {
+        Self { name: name.into(), features, no_default_features: false }
+    }
+
+    pub fn no_default_features(mut self, no_default_features: bool) -> Self {
+        self.no_default_features = no_default_features;
+        self
+    }
+
+    fn apply(&self, options: GatherOptions) -> GatherOptions {
+        options.features(self.features.clone()).no_default_features(self.no_default_features)
+    }
+}
+
+/// The aggregations of several feature sets, see [`Aggregator::run_feature_sets`]
+pub struct FeatureSetAggregation {
+    /// The name of every feature set with its aggregation, in the order of the feature sets
+    pub feature_sets: Vec<(String, Aggregation)>,
+}
+
+impl FeatureSetAggregation {
+    /// The packages of all feature sets, each only once, annotated with the names of the feature sets that pull them
+    /// in (see [`Package::feature_sets`])
+    pub fn union(&self) -> Vec<Package> {
+        union_packages(&self.feature_sets, |p| &mut p.feature_sets)
+    }
+
+    /// The diagnostics of all feature sets, diagnostics reported by several feature sets only once
+    pub fn diagnostics(&self) -> Diagnostics {
+        union_diagnostics(&self.feature_sets)
+    }
+}
+
+/// The aggregations of several target triples, see [`Aggregator::run_targets`]
+pub struct TargetAggregation {
+    /// Every target triple with its aggregation, in the order of the targets
+    pub targets: Vec<(String, Aggregation)>,
+}
+
+impl TargetAggregation {
+    /// The packages of all targets, each only once, annotated with the target triples that pull them in
+    /// (see [`Package::platforms`])
+    pub fn union(&self) -> Vec<Package> {
+        union_packages(&self.targets, |p| &mut p.platforms)
+    }
+
+    /// The diagnostics of all targets, diagnostics reported for several targets only once
+    pub fn diagnostics(&self) -> Diagnostics {
+        union_diagnostics(&self.targets)
+    }
+}
+
+fn union_packages(sections: &[(String, Aggregation)], annotation: fn(&mut Package) -> &mut Vec<String>) -> Vec<Package> {
+    let mut packages: Vec<Package> = vec![];
+    let mut index: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for (name, aggregation) in sections {
+        for p in &aggregation.packages {
+            let i = *index.entry((&p.package_name, &p.package_version)).or_insert_with(|| {
+                let mut package = p.clone();
+                annotation(&mut package).clear();
+                packages.push(package);
+                packages.len() - 1
+            });
+
+            annotation(&mut packages[i]).push(name.clone());
+        }
+    }
+
+    crate::canonicalize(&mut packages);
+    packages
+}
+
+fn union_diagnostics(sections: &[(String, Aggregation)]) -> Diagnostics {
+    let mut diagnostics = Diagnostics::default();
+    let mut seen = HashSet::new();
+    for (_, aggregation) in sections {
+        for d in aggregation.diagnostics.iter() {
+            if seen.insert((d.kind, &d.package_name, &d.package_version, &d.file, &d.message)) {
+                diagnostics.push(d.clone());
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// The complete aggregation pipeline: collection of rust licenses, collection of
+/// thirdparty (e.g. C++) licenses declared by the crates and minimization of the license requirements.
+///
+/// # Example
+/// `Aggregator::new("Cargo.toml").features(vec!["cuda".to_owned()]).with_cpp_thirdparty(true).run()`
+pub struct Aggregator {
+    manifest: Utf8PathBuf,
+    options: GatherOptions,
+    config_path: Option<Utf8PathBuf>,
+    config: Option<AggregatorConfig>,
+    license_store: Option<Arc<dyn LicenseStoreProvider>>,
+    cpp_thirdparty: bool,
+    thirdparty_metadata_key: String,
+    sources: Vec<Box<dyn LicenseSource>>,
+    minimize: bool,
+    canonicalize: bool,
+    normalize_texts: bool,
+    max_text_bytes: Option<usize>,
+    check_policy: bool,
+    max_unknown: Option<usize>,
+    approvals: Option<Utf8PathBuf>,
+    audit_log: Option<Utf8PathBuf>,
+    output: Option<Utf8PathBuf>,
+    problems: Option<Utf8PathBuf>,
+    attestation: Option<Utf8PathBuf>,
+    checksums: bool,
+    notice_attribution: Option<Utf8PathBuf>,
+    minisign_key: Option<Utf8PathBuf>,
+    unreviewed_dir: Option<Utf8PathBuf>,
+    source_offer_dir: Option<Utf8PathBuf>,
+    lint_config: bool,
+    build_time: BuildTimeAction,
+    duplicate_versions: DuplicateVersions,
+    #[cfg(feature = "network")]
+    repository_fetcher: Option<RepositoryFetcher>,
+    #[cfg(feature = "network")]
+    clearlydefined: Option<ClearlyDefined>,
+    #[cfg(feature = "network")]
+    osv: Option<Osv>,
+    #[cfg(feature = "canonical-texts")]
+    synthesize_missing_texts: bool,
+}
+
+impl Aggregator {
+    /// Create an aggregator for the Cargo.toml at the given path.
+    /// By default no features are enabled, thirdparty licenses are not collected,
+    /// the license requirements are minimized and the packages are canonicalized.
+    pub fn new<P: Into<Utf8PathBuf>>(manifest: P) -> Self {
+        Self {
+            manifest: manifest.into(),
+            options: GatherOptions::default(),
+            config_path: None,
+            config: None,
+            license_store: None,
+            cpp_thirdparty: false,
+            thirdparty_metadata_key: DEFAULT_THIRDPARTY_METADATA_KEY.to_owned(),
+            sources: Vec::new(),
+            minimize: true,
+            canonicalize: true,
+            normalize_texts: false,
+            max_text_bytes: None,
+            check_policy: false,
+            max_unknown: None,
+            approvals: None,
+            audit_log: None,
+            output: None,
+            problems: None,
+            attestation: None,
+            checksums: false,
+            notice_attribution: None,
+            minisign_key: None,
+            unreviewed_dir: None,
+            source_offer_dir: None,
+            lint_config: false,
+            build_time: BuildTimeAction::Keep,
+            duplicate_versions: DuplicateVersions::Keep,
+            #[cfg(feature = "network")]
+            repository_fetcher: None,
+            #[cfg(feature = "network")]
+            clearlydefined: None,
+            #[cfg(feature = "network")]
+            osv: None,
+            #[cfg(feature = "canonical-texts")]
+            synthesize_missing_texts: false,
+        }
+    }
+
+    /// The cargo features to enable when resolving the dependency graph
+    pub fn features(mut self, features: Vec<String>) -> Self {
+        self.options = self.options.features(features);
+        self
+    }
+
+    /// The options for resolving the dependency graph and gathering the rust licenses, replaces previously set features
+    pub fn gather_options(mut self, options: GatherOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Load the configuration from the given path.
+    /// If neither this nor [`Aggregator::config`] is set, `about.toml` next to the manifest is used if it exists.
+    pub fn config_path<P: Into<Utf8PathBuf>>(mut self, path: P) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// Use an already loaded configuration, takes precedence over [`Aggregator::config_path`]
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config.into());
+        self
+    }
+
+    /// Like [`Aggregator::config`], but including the sections specific to this crate
+    pub fn aggregator_config(mut self, config: AggregatorConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Use the given license store instead of the one from the internal cache
+    pub fn license_store(mut self, license_store: Arc<LicenseStore>) -> Self {
+        self.license_store = Some(Arc::new(license_store));
+        self
+    }
+
+    /// Take the license store from the given provider instead of the one from the internal cache.
+    /// Without a provider the store from the internal cache is loaded once per process (see [`shared_license_store`]).
+    pub fn license_store_provider<P: LicenseStoreProvider + 'static>(mut self, provider: P) -> Self {
+        self.license_store = Some(Arc::new(provider));
+        self
+    }
+
+    /// Also collect the thirdparty licenses declared by crates (see [`collect_thirdparty_licenses`])
+    pub fn with_cpp_thirdparty(mut self, cpp_thirdparty: bool) -> Self {
+        self.cpp_thirdparty = cpp_thirdparty;
+        self
+    }
+
+    /// The metadata key under which crates declare their thirdparty license file,
+    /// defaults to [`DEFAULT_THIRDPARTY_METADATA_KEY`]. The `[thirdparty]` section of the configuration takes
+    /// precedence (see [`crate::ThirdpartyConfig`])
+    pub fn thirdparty_metadata_key<S: Into<String>>(mut self, metadata_key: S) -> Self {
+        self.thirdparty_metadata_key = metadata_key.into();
+        self
+    }
+
+    /// Register an additional collector, which runs after the builtin cargo and thirdparty collectors
+    pub fn source<S: LicenseSource + 'static>(mut self, source: S) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Whether to minimize the license requirements of all packages (see [`minimize_requirements`])
+    pub fn minimize(mut self, minimize: bool) -> Self {
+        self.minimize = minimize;
+        self
+    }
+
+    /// Whether to bring the packages into a deterministic order and form (see [`crate::canonicalize`]), enabled by default
+    pub fn canonicalize(mut self, canonicalize: bool) -> Self {
+        self.canonicalize = canonicalize;
+        self
+    }
+
+    /// Whether to normalize the license texts (see [`crate::normalize_texts`]) before they are hashed and compared,
+    /// so identical licenses with cosmetic differences (line endings, trailing whitespace, line wrapping) deduplicate
+    pub fn normalize_texts(mut self, normalize_texts: bool) -> Self {
+        self.normalize_texts = normalize_texts;
+        self
+    }
+
+    /// Shorten the license texts to at most this many bytes, `0` omits them (see [`crate::truncate_texts`])
+    pub fn max_text_bytes(mut self, max_text_bytes: Option<usize>) -> Self {
+        self.max_text_bytes = max_text_bytes;
+        self
+    }
+
+    /// Check mode: evaluate the packages against the license policy of the configuration (see
+    /// [`crate::policy::check_policy`]) after the minimization and fail with [`AggregateError::PolicyViolation`]
+    /// if any package violates it
+    pub fn check_policy(mut self, check_policy: bool) -> Self {
+        self.check_policy = check_policy;
+        self
+    }
+
+    /// Check mode: fail with [`AggregateError::TooManyUnknownLicenses`] if more than `max_unknown` packages have an
+    /// unknown license (after resolving them, see [`crate::UnknownLicenseAction`]), `Some(0)` allows none
+    pub fn max_unknown(mut self, max_unknown: Option<usize>) -> Self {
+        self.max_unknown = max_unknown;
+        self
+    }
+
+    /// Check mode: fail with [`AggregateError::NotApproved`] if the license of a package is not approved in the
+    /// given approvals file (typically `license-approvals.toml`, see [`crate::approvals::Approvals`])
+    pub fn check_approvals<P: Into<Utf8PathBuf>>(mut self, path: P) -> Self {
+        self.approvals = Some(path.into());
+        self
+    }
+
+    /// Verify that the given attribution (e.g. the committed, rendered attribution file) reproduces the `NOTICE` files
+    /// of all Apache-2.0 packages (see [`crate::obligations::verify_notices`]). Missing notices are reported as
+    /// [`DiagnosticKind::NoticeNotReproduced`], in check mode ([`Aggregator::check_policy`]) the run fails with
+    /// [`AggregateError::NoticesNotReproduced`]
+    pub fn verify_notices<P: Into<Utf8PathBuf>>(mut self, attribution: P) -> Self {
+        self.notice_attribution = Some(attribution.into());
+        self
+    }
+
+    /// Write the packages of every successful run as JSON to the given file, the `TLA_OUTPUT` environment variable
+    /// takes precedence
+    pub fn output<P: Into<Utf8PathBuf>>(mut self, path: P) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// Write the warnings and errors of every run with suggested remediations as JSON to the given file
+    /// (see [`crate::write_problems`]), defaults to [`crate::PROBLEMS_FILE`] next to the [`Aggregator::output`].
+    /// Runs that fail a check (e.g. [`Aggregator::check_policy`]) write it too, including the violations.
+    pub fn problems<P: Into<Utf8PathBuf>>(mut self, path: P) -> Self {
+        self.problems = Some(path.into());
+        self
+    }
+
+    /// Write an in-toto statement about the [`Aggregator::output`] and the problems report of every successful run to
+    /// the given file (see [`crate::Statement`]), with the manifest, the `Cargo.lock` and the configuration file as
+    /// inputs. Nothing is attested without an output
+    pub fn attestation<P: Into<Utf8PathBuf>>(mut self, path: P) -> Self {
+        self.attestation = Some(path.into());
+        self
+    }
+
+    /// Write a `.sha256` file next to every file generated by a run (see [`crate::write_checksum`]): the output, the
+    /// problems report, the attestation and the manifests of the source offer and the unreviewed texts
+    pub fn checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Sign every file generated by a run with the given minisign secret key (see [`crate::sign_with_minisign`]),
+    /// requires the `minisign` executable
+    pub fn minisign_key<P: Into<Utf8PathBuf>>(mut self, secret_key: P) -> Self {
+        self.minisign_key = Some(secret_key.into());
+        self
+    }
+
+    /// Append a record of the decisions of every run (see [`crate::AuditRecord`]) to the given file, including the runs
+    /// that fail a check
+    pub fn audit_log<P: Into<Utf8PathBuf>>(mut self, path: P) -> Self {
+        self.audit_log = Some(path.into());
+        self
+    }
+
+    /// Export the license texts that could not be classified confidently into the given directory, as a queue for their
+    /// manual review (see [`crate::export_unreviewed`])
+    pub fn export_unreviewed<P: Into<Utf8PathBuf>>(mut self, dir: P) -> Self {
+        self.unreviewed_dir = Some(dir.into());
+        self
+    }
+
+    /// Collect the source archives of the copyleft packages into the given directory (e.g. `source-offer/`), see
+    /// [`crate::export_source_offer`]
+    pub fn export_source_offer<P: Into<Utf8PathBuf>>(mut self, dir: P) -> Self {
+        self.source_offer_dir = Some(dir.into());
+        self
+    }
+
+    /// What happens with proc-macros and other crates that are only needed for building, see [`BuildTimeAction`]
+    pub fn build_time_crates(mut self, build_time: BuildTimeAction) -> Self {
+        self.build_time = build_time;
+        self
+    }
+
+    /// What happens with packages that are included in several versions, see [`DuplicateVersions`]
+    pub fn duplicate_versions(mut self, duplicate_versions: DuplicateVersions) -> Self {
+        self.duplicate_versions = duplicate_versions;
+        self
+    }
+
+    /// Report the entries of the configuration that matched nothing in the run as
+    /// [`DiagnosticKind::UnusedConfigEntry`] (see [`crate::lint_config`])
+    pub fn lint_config(mut self, lint_config: bool) -> Self {
+        self.lint_config = lint_config;
+        self
+    }
+
+    /// Fetch the license files of packages that ship none from their GitHub or GitLab repository,
+    /// see [`RepositoryFetcher::fetch_missing`]
+    #[cfg(feature = "network")]
+    pub fn fetch_from_repository(mut self, fetcher: RepositoryFetcher) -> Self {
+        self.repository_fetcher = Some(fetcher);
+        self
+    }
+
+    /// Send the remote lookups concurrently on the runtime, see [`crate::nonblocking::run_aggregator`]
+    #[cfg(all(feature = "network", feature = "tokio"))]
+    pub(crate) fn concurrent(mut self, concurrent: crate::nonblocking::Concurrent) -> Self {
+        self.options = self.options.concurrent(concurrent);
+        self
+    }
+
+    /// Merge the curated license and copyright information of clearlydefined.io into the packages,
+    /// see [`ClearlyDefined::enrich`]
+    #[cfg(feature = "network")]
+    pub fn enrich_with_clearlydefined(mut self, clearlydefined: ClearlyDefined) -> Self {
+        self.clearlydefined = Some(clearlydefined);
+        self
+    }
+
+    /// Cross-reference the packages with the advisories of osv.dev (including the RustSec advisory database) and report
+    /// vulnerable, unsound and unmaintained crates, see [`Osv::check`]
+    #[cfg(feature = "network")]
+    pub fn check_advisories(mut self, osv: Osv) -> Self {
+        self.osv = Some(osv);
+        self
+    }
+
+    /// Substitute the canonical SPDX texts for packages without any license file
+    /// (see [`analysis::synthesize_license_files`]), disabled by default
+    #[cfg(feature = "canonical-texts")]
+    pub fn synthesize_missing_texts(mut self, synthesize_missing_texts: bool) -> Self {
+        self.synthesize_missing_texts = synthesize_missing_texts;
+        self
+    }
+
+    /// Run the whole pipeline and return all collected packages
+    pub fn run(mut self) -> Result<Aggregation, AggregateError> {
+        let ctx = self.context()?;
+        self.run_with(&ctx)
+    }
+
+    /// Dry run: resolve the dependency graph and report the crates, the declared thirdparty files and the remote
+    /// lookups a run would perform (see [`Plan`]), without reading license texts or hitting the network, e.g. to
+    /// validate configuration changes quickly. The graph is resolved offline, so the dependencies have to be available
+    /// locally (e.g. from a previous build).
+    pub fn plan(mut self) -> Result<Plan, AggregateError> {
+        let offline = EnvOverrides::from_env()?.offline.unwrap_or(self.options.offline);
+        self.options = self.options.clone().offline(true);
+        let ctx = self.context()?;
+        let (krates, config, options) = (ctx.krates(), ctx.config(), ctx.options());
+
+        let ignore = options.ignore_set()?;
+        let selected = |krate: &Krate| !ignore.is_match(&krate.name);
+        let reused = reused_packages(options, krates, &selected);
+        let build_time: HashSet<_> =
+            crate::graph::build_time_krates(krates).into_iter().map(|k| (&k.name, &k.version)).collect();
+
+        let mut plan = Plan { custom_sources: self.sources.len(), ..Plan::default() };
+        for krate in krates.krates().filter(|k| selected(k)) {
+            let version = krate.version.to_string();
+            let key = (krate.name.clone(), version.clone());
+            plan.krates.push(PlannedKrate {
+                name: krate.name.clone(),
+                origin: origin(krate, &[]),
+                build_time: build_time.contains(&(&krate.name, &krate.version)),
+                reused: reused.as_ref().is_some_and(|reused| reused.contains_key(&key)),
+                version,
+            });
+
+            let file_name = krate
+                .metadata
+                .get(&self.thirdparty_metadata_key)
+                .and_then(|m| m.get("thirdparty-file-name"))
+                .and_then(|f| f.as_str());
+            if let Some(file_name) = file_name.filter(|_| self.cpp_thirdparty) {
+                let path = krate.manifest_path.parent().unwrap().join(file_name);
+                plan.thirdparty_files.push(PlannedThirdpartyFile {
+                    krate: krate.name.clone(),
+                    exists: path.is_file(),
+                    path,
+                });
+            }
+        }
+
+        #[cfg(feature = "network")]
+        {
+            plan.fetches = self.planned_fetches(krates, config, options, offline, reused.is_some(), &selected);
+        }
+        #[cfg(not(feature = "network"))]
+        let _ = (config, offline);
+
+        Ok(plan)
+    }
+
+    /// The remote lookups of a run, see [`Aggregator::plan`]
+    #[cfg(feature = "network")]
+    fn planned_fetches(
+        &self,
+        krates: &Krates,
+        config: &Config,
+        options: &GatherOptions,
+        offline: bool,
+        all_reused: bool,
+        selected: &impl Fn(&Krate) -> bool,
+    ) -> Vec<crate::PlannedFetch> {
+        let names = |filter: &dyn Fn(&Krate) -> bool| -> Vec<String> {
+            krates.krates().filter(|k| selected(k) && filter(k)).map(|k| format!("{} {}", k.name, k.version)).collect()
+        };
+        let fetch = |service: &str, purpose: &str, packages: Vec<String>| crate::PlannedFetch {
+            service: service.to_owned(),
+            purpose: purpose.to_owned(),
+            packages,
+        };
+
+        let mut fetches = vec![];
+        // cargo-about gets no client if all crates are reused
+        if !all_reused {
+            if !config.no_clearly_defined {
+                let packages = names(&is_from_crates_io);
+                fetches.push(fetch("clearlydefined.io", "harvested licenses (cargo-about)", packages));
+            }
+            let clarified = names(&|k| select_clarification(&k.name, config).is_some_and(|c| !c.git.is_empty()));
+            if !clarified.is_empty() {
+                fetches.push(fetch("crate repositories", "files of git clarifications (cargo-about)", clarified));
+            }
+        }
+
+        if options.crates_io_fallback && !offline {
+            let registry = |k: &Krate| {
+                k.source.as_ref().is_some_and(|source| {
+                    source.is_crates_io() || options.registries.iter().any(|r| r.has_index(&source.repr))
+                })
+            };
+            let incomplete = |k: &Krate| k.license.is_none() || (k.repository.is_none() && k.homepage.is_none());
+            let packages = names(&|k| registry(k) && incomplete(k));
+            if !packages.is_empty() {
+                fetches.push(fetch("registries", "metadata of crates without license or url", packages));
+            }
+        }
+
+        if self.repository_fetcher.is_some() {
+            fetches.push(fetch("GitHub and GitLab", "license files of packages without any", Vec::new()));
+        }
+        if self.clearlydefined.is_some() {
+            fetches.push(fetch("clearlydefined.io", "licenses of packages without license files", Vec::new()));
+        }
+        if let Some(osv) = &self.osv {
+            fetches.push(fetch("osv.dev", "advisories", names(&|_| true)));
+            if osv.yanked {
+                fetches.push(fetch("crates.io", "yanked versions", names(&is_from_crates_io)));
+            }
+        }
+
+        if self.source_offer_dir.is_some() && !offline {
+            let caches = crate::offer::registry_caches();
+            let cached = |k: &Krate| {
+                let file_name = format!("{}-{}.crate", k.name, k.version);
+                caches.iter().any(|cache| cache.join(&file_name).is_file())
+            };
+            let packages = names(&|k| is_from_crates_io(k) && !cached(k));
+            if !packages.is_empty() {
+                fetches.push(fetch("static.crates.io", "source archives that are not cached", packages));
+            }
+        }
+
+        fetches
+    }
+
+    /// Feature matrix: run the whole pipeline once for every feature set, instead of the features of the
+    /// [`GatherOptions`]. The configuration and the license store are only loaded once.
+    /// The result contains a section per feature set, and their union with per-package annotations
+    /// (see [`FeatureSetAggregation::union`]).
+    pub fn run_feature_sets(mut self, feature_sets: &[FeatureSet]) -> Result<FeatureSetAggregation, AggregateError> {
+        let Some((first, rest)) = feature_sets.split_first() else {
+            return Ok(FeatureSetAggregation { feature_sets: Vec::new() });
+        };
+
+        self.options = first.apply(self.options);
+        let mut ctx = self.context()?;
+        let mut results = vec![(first.name.clone(), self.run_with(&ctx)?)];
+
+        for set in rest {
+            ctx.resolve(set.apply(ctx.options().clone()))?;
+            results.push((set.name.clone(), self.run_with(&ctx)?));
+        }
+
+        Ok(FeatureSetAggregation { feature_sets: results })
+    }
+
+    /// Run the whole pipeline once for every target triple (e.g. `x86_64-unknown-linux-musl`), instead of the
+    /// targets of the [`GatherOptions`] and the configuration. The configuration and the license store are only loaded
+    /// once. The result contains a section per target, and their union with per-package platform annotations
+    /// (see [`TargetAggregation::union`]).
+    pub fn run_targets<S: AsRef<str>>(mut self, targets: &[S]) -> Result<TargetAggregation, AggregateError> {
+        let Some((first, rest)) = targets.split_first() else {
+            return Ok(TargetAggregation { targets: Vec::new() });
+        };
+
+        self.options = self.options.targets(vec![first.as_ref().to_owned()]);
+        let mut ctx = self.context()?;
+        let mut results = vec![(first.as_ref().to_owned(), self.run_with(&ctx)?)];
+
+        for target in rest {
+            ctx.resolve(ctx.options().clone().targets(vec![target.as_ref().to_owned()]))?;
+            results.push((target.as_ref().to_owned(), self.run_with(&ctx)?));
+        }
+
+        Ok(TargetAggregation { targets: results })
+    }
+
+    /// Load the configuration and the license store and resolve the dependency graph, without collecting anything yet.
+    /// The context can be shared by several runs, see [`Aggregator::run_with`].
+    ///
+    /// The `TLA_*` environment variables replace the accepted licenses ([`crate::ENV_ACCEPTED`]), the confidence
+    /// thresholds ([`crate::ENV_CONFIDENCE_THRESHOLD`] and [`crate::ENV_RUST_CONFIDENCE_THRESHOLD`]), the offline mode
+    /// ([`crate::ENV_OFFLINE`]) and the output ([`crate::ENV_OUTPUT`]) of the configuration and the aggregator.
+    pub fn context(&mut self) -> Result<RunContext, AggregateError> {
+        let env = EnvOverrides::from_env()?;
+        let mut config = match (self.config.take(), &self.config_path) {
+            (Some(config), _) => config,
+            (None, Some(path)) => load_aggregator_config(path)?,
+            (None, None) => {
+                let default_path = self.manifest.with_file_name("about.toml");
+                if default_path.exists() {
+                    load_aggregator_config(default_path)?
+                } else {
+                    AggregatorConfig::default()
+                }
+            },
+        };
+
+        let license_store = if config.custom_licenses.is_empty() {
+            match &self.license_store {
+                Some(provider) => provider.license_store()?,
+                None => shared_license_store()?,
+            }
+        } else {
+            // the custom licenses are added to a private copy, shared stores stay untouched
+            let mut store = match &self.license_store {
+                Some(provider) => {
+                    let shared = provider.license_store()?;
+                    copy_license_store(&shared)?
+                },
+                None => cargo_about::licenses::store_from_cache().map_err(|e| AggregateError::LicenseStore(e.into()))?,
+            };
+
+            add_custom_licenses(&mut store, &config.custom_licenses);
+            Arc::new(store)
+        };
+
+        let mut options = config.unknown_license.apply(config.detection.apply(self.options.clone()));
+        options = config.minimization.apply(options)?;
+        for license_override in &config.overrides {
+            options = options.license_override(license_override.clone());
+        }
+        for attribution in &config.attributions {
+            options = options.attribution(attribution.clone());
+        }
+        for (package, linkage) in &config.linkage {
+            options = options.linkage(package.clone(), *linkage);
+        }
+        if !config.ignore_license_files.is_empty() {
+            let mut patterns = options.ignore_license_files.clone();
+            patterns.extend(config.ignore_license_files);
+            options = options.ignore_license_files(patterns);
+        }
+        #[cfg(feature = "network")]
+        for registry in config.registries.values() {
+            options = options.registry(registry.clone());
+        }
+        #[cfg(feature = "network")]
+        {
+            options = config.http.apply(options);
+        }
+        if !config.policy.is_empty() {
+            options = options.policy(config.policy);
+        }
+        options.minimization.accept_public_domain |= options.policy.accept_public_domain;
+        options = config.project.apply(options)?;
+        if let Some(metadata_key) = config.thirdparty.metadata_key {
+            self.thirdparty_metadata_key = metadata_key;
+        }
+        if let Some(spdx_parsing) = config.spdx_parsing {
+            options = options.spdx_parsing(spdx_parsing);
+        }
+
+        if let Some(accepted) = env.accepted {
+            config.about.accepted = accepted;
+        }
+        if let Some(threshold) = env.confidence_threshold {
+            options.detection.threshold = threshold;
+        }
+        if let Some(threshold) = env.rust_confidence_threshold {
+            options = options.confidence_threshold(threshold);
+        }
+        if let Some(offline) = env.offline {
+            options = options.offline(offline);
+        }
+        if let Some(output) = env.output {
+            self.output = Some(output);
+        }
+
+        RunContext::new(&self.manifest, options, config.about, license_store)
+    }
+
+    /// Run the pipeline on an already resolved context.
+    /// The manifest, configuration, license store and gather options of the context are used instead of the ones of the aggregator.
+    pub fn run_with(&self, ctx: &RunContext) -> Result<Aggregation, AggregateError> {
+        let options = ctx.options();
+        let collect_ctx = ctx.collect_context();
+
+        let cargo = CargoSource;
+        let thirdparty = ThirdpartySource { metadata_key: self.thirdparty_metadata_key.clone() };
+
+        let mut sources: Vec<&dyn LicenseSource> = vec![&cargo];
+        if self.cpp_thirdparty {
+            sources.push(&thirdparty);
+        }
+        sources.extend(self.sources.iter().map(|source| source.as_ref()));
+
+        let mut packages = Vec::new();
+        let mut diagnostics = Diagnostics::new();
+        if ctx.generated_lockfile() {
+            diagnostics.push(generated_lockfile(ctx.manifest()));
+        }
+        for source in sources {
+            options.check_cancelled()?;
+            packages.extend(source.collect(&collect_ctx, &mut diagnostics)?);
+        }
+
+        #[cfg(feature = "network")]
+        if let Some(fetcher) = &self.repository_fetcher {
+            options.check_cancelled()?;
+            fetch_missing(fetcher, options, &mut packages, &mut diagnostics)?;
+        }
+
+        #[cfg(feature = "network")]
+        if let Some(clearlydefined) = &self.clearlydefined {
+            options.check_cancelled()?;
+            enrich(clearlydefined, options, &mut packages, &mut diagnostics)?;
+        }
+
+        #[cfg(feature = "network")]
+        let advisories = match &self.osv {
+            Some(osv) => {
+                options.check_cancelled()?;
+                check_advisories(osv, options, &packages, &mut diagnostics)?
+            },
+            None => Vec::new(),
+        };
+
+        options.check_cancelled()?;
+        #[cfg(feature = "canonical-texts")]
+        if self.synthesize_missing_texts {
+            for p in &mut packages {
+                analysis::synthesize_license_files(p, &mut diagnostics);
+            }
+        }
+
+        if self.build_time != BuildTimeAction::Keep {
+            let build_time: HashSet<_> = crate::graph::build_time_krates(ctx.krates())
+                .into_iter()
+                .map(|k| (k.name.as_str(), k.version.to_string()))
+                .collect();
+
+            for p in &mut packages {
+                if build_time.contains(&(p.package_name.as_str(), p.package_version.clone())) {
+                    p.linkage = Some(Linkage::ToolOnly);
+                }
+            }
+        }
+
+        for p in &mut packages {
+            if let Some(linkage) = options.linkage.get(&p.package_name) {
+                p.linkage = Some(*linkage);
+            }
+
+            for attribution in options.attributions.iter().filter(|a| a.matches(&p.package_name, &p.package_version)) {
+                let mut file = LicenseFile::new(attribution.file_name().to_owned(), None, attribution.text.clone());
+                file.kind = FileKind::Notice;
+                p.license_files.push(file);
+            }
+        }
+
+        let license_changes = detect_license_changes(&options.previous.packages(), &packages);
+        for change in &license_changes {
+            change.report_to(&mut diagnostics);
+        }
+
+        let mut minimization = MinimizationReport::default();
+        if self.minimize {
+            minimization = minimize_requirements_with(&mut packages, ctx.config(), &options.minimization);
+            minimization.report_to(&mut diagnostics);
+        }
+
+        let mut compatibility = None;
+        if !options.project_licenses.is_empty() {
+            let mut report = check_compatibility(&packages, &options.project_licenses, &ctx.config().accepted);
+            crate::graph::annotate_dependency_paths(ctx.krates(), &mut report);
+
+            for conflict in &report.conflicts {
+                let mut message = format!(
+                    "License {} ({}) of '{} {}' is incompatible with the {} project license",
+                    conflict.license,
+                    conflict.category,
+                    conflict.package_name,
+                    conflict.package_version,
+                    report.project_category
+                );
+                if !conflict.path.is_empty() {
+                    message.push_str(&format!(", required via {}", conflict.path.join(" -> ")));
+                }
+
+                diagnostics.push(
+                    Diagnostic::warning(DiagnosticKind::IncompatibleLicense, message)
+                        .with_package(&conflict.package_name, &conflict.package_version),
+                );
+            }
+
+            compatibility = Some(report);
+        }
+
+        if self.lint_config {
+            for lint in crate::lint_config(ctx, &packages) {
+                diagnostics.push(Diagnostic::warning(
+                    DiagnosticKind::UnusedConfigEntry,
+                    format!("The configuration entry {lint}"),
+                ));
+            }
+        }
+
+        // the checks only fail once the problems and the audit record are written, so they include the violations
+        let mut failure = None;
+        let policy = if self.check_policy {
+            let report = check_policy(&packages, &options.policy, &ctx.config().accepted)?;
+            for violation in &report.violations {
+                diagnostics.push(
+                    Diagnostic::new(Severity::Error, DiagnosticKind::PolicyViolation, violation.message.clone())
+                        .with_package(&violation.package_name, &violation.package_version),
+                );
+            }
+            for exception in &report.expiring_exceptions {
+                let expires = exception.expires.map(|e| e.to_string()).unwrap_or_default();
+                diagnostics.push(
+                    Diagnostic::warning(
+                        DiagnosticKind::ExpiringPolicyException,
+                        format!(
+                            "The policy exception for '{} {}' ({}) expires on {expires}",
+                            exception.package_name, exception.package_version, exception.justification
+                        ),
+                    )
+                    .with_package(&exception.package_name, &exception.package_version),
+                );
+            }
+
+            if !report.is_ok() {
+                failure = Some(AggregateError::PolicyViolation(report.clone()));
+            }
+            Some(report)
+        } else {
+            None
+        };
+
+        if let Some(path) = &self.approvals {
+            let report = crate::approvals::load_approvals(path)?.review(&packages);
+            for pending in &report.pending {
+                diagnostics.push(
+                    Diagnostic::new(Severity::Error, DiagnosticKind::NotApproved, pending.to_string())
+                        .with_package(&pending.package_name, &pending.package_version),
+                );
+            }
+            if let Err(e) = report.into_result() {
+                failure.get_or_insert(e);
+            }
+        }
+
+        if let Some(max) = self.max_unknown {
+            if let Err(e) = check_unknown_licenses(&packages, max) {
+                diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticKind::UnknownLicense, e.to_string()));
+                failure.get_or_insert(e);
+            }
+        }
+
+        // every version has an archive of its own, so they are collected before merging the versions
+        if let Some(dir) = &self.source_offer_dir {
+            crate::export_source_offer(&packages, dir, options, &mut diagnostics)?;
+        }
+
+        if self.normalize_texts {
+            crate::normalize_texts(&mut packages);
+        }
+
+        packages = merge_versions(packages, self.duplicate_versions, &mut diagnostics);
+        if self.canonicalize {
+            crate::canonicalize(&mut packages);
+        }
+
+        crate::hash_texts(&mut packages);
+        crate::link_licenses(&mut packages);
+        // the texts are exported before they are shortened
+        if let Some(dir) = &self.unreviewed_dir {
+            crate::export_unreviewed(&packages, dir, options.confidence_threshold)?;
+        }
+
+        if let Some(path) = &self.notice_attribution {
+            let attribution = std::fs::read_to_string(path)
+                .map_err(|source| AggregateError::LicenseRead { path: path.clone(), source })?;
+            let report = crate::obligations::verify_notices(&packages, &attribution);
+            for missing in &report.missing {
+                diagnostics.push(
+                    Diagnostic::new(Severity::Error, DiagnosticKind::NoticeNotReproduced, missing.to_string())
+                        .with_package(&missing.package_name, &missing.package_version)
+                        .with_file(&missing.file),
+                );
+            }
+            if self.check_policy {
+                if let Err(e) = report.into_result() {
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+
+        if let Some(max_text_bytes) = self.max_text_bytes {
+            crate::truncate_texts(&mut packages, max_text_bytes);
+        }
+
+        let mut build_tools = Vec::new();
+        if matches!(self.build_time, BuildTimeAction::Separate | BuildTimeAction::Omit) {
+            let (tools, shipped) = packages.into_iter().partition(|p| p.linkage == Some(Linkage::ToolOnly));
+            packages = shipped;
+            if self.build_time == BuildTimeAction::Separate {
+                build_tools = tools;
+            }
+        }
+
+        let aggregation = Aggregation {
+            packages,
+            diagnostics,
+            minimization,
+            policy,
+            compatibility,
+            license_changes,
+            #[cfg(feature = "network")]
+            advisories,
+            build_tools,
+        };
+        if let Some(path) = &self.audit_log {
+            crate::AuditRecord::new(ctx.krates().workspace_root(), &aggregation).append_to(path)?;
+        }
+        let problems = self.problems.clone().or_else(|| Some(self.output.as_ref()?.with_file_name(PROBLEMS_FILE)));
+        if let Some(path) = &problems {
+            write_problems(&aggregation.diagnostics, path)?;
+        }
+        if let Some(failure) = failure {
+            return Err(failure);
+        }
+
+        if let Some(path) = &self.output {
+            let write_err = |source| AggregateError::Write { path: path.clone(), source };
+            let json = serde_json::to_string_pretty(&aggregation.packages).map_err(|e| write_err(e.into()))?;
+            std::fs::write(path, json).map_err(write_err)?;
+        }
+        if let (Some(path), Some(output)) = (&self.attestation, &self.output) {
+            let root = ctx.krates().workspace_root();
+            let config = self.config_path.clone().or_else(|| {
+                Some(self.manifest.with_file_name("about.toml")).filter(|default_path| default_path.exists())
+            });
+            let subjects: Vec<_> = std::iter::once(output.clone()).chain(problems.clone()).collect();
+            let inputs = [root.join("Cargo.toml"), root.join("Cargo.lock")];
+            Statement::new(&subjects, &inputs, config.as_deref())?.write_to(path)?;
+        }
+
+        if self.checksums || self.minisign_key.is_some() {
+            let generated = [
+                self.output.clone(),
+                problems,
+                self.attestation.clone().filter(|_| self.output.is_some()),
+                self.source_offer_dir.as_ref().map(|dir| dir.join(crate::SOURCE_OFFER_MANIFEST)),
+                self.unreviewed_dir.as_ref().map(|dir| dir.join(crate::UNREVIEWED_INDEX)),
+            ];
+            for path in generated.iter().flatten() {
+                if self.checksums {
+                    write_checksum(path)?;
+                }
+                if let Some(secret_key) = &self.minisign_key {
+                    sign_with_minisign(path, secret_key)?;
+                }
+            }
+        }
+
+        Ok(aggregation)
+    }
+}
+
+/// Fail with [`AggregateError::TooManyUnknownLicenses`] if more than `max` packages have an unknown license
+fn check_unknown_licenses(packages: &[Package], max: usize) -> Result<(), AggregateError> {
+    let count = packages.iter().filter(|p| p.license_spdx.is_none()).count();
+    if count > max {
+        return Err(AggregateError::TooManyUnknownLicenses { count, max });
+    }
+
+    Ok(())
+}
+
+/// [`RepositoryFetcher::fetch_missing`], concurrently on the runtime of [`crate::nonblocking`] if there is one
+#[cfg(feature = "network")]
+#[cfg_attr(not(feature = "tokio"), allow(unused_variables))]
+fn fetch_missing(
+    fetcher: &RepositoryFetcher,
+    options: &GatherOptions,
+    packages: &mut [Package],
+    diagnostics: &mut Diagnostics,
+) -> Result<(), AggregateError> {
+    #[cfg(feature = "tokio")]
+    if let Some(concurrent) = &options.concurrent {
+        return concurrent.block_on(fetcher.fetch_missing_async(packages, diagnostics, concurrent.options()));
+    }
+
+    fetcher.fetch_missing(packages, diagnostics)
+}
+
+/// [`ClearlyDefined::enrich`], concurrently on the runtime of [`crate::nonblocking`] if there is one
+#[cfg(feature = "network")]
+#[cfg_attr(not(feature = "tokio"), allow(unused_variables))]
+fn enrich(
+    clearlydefined: &ClearlyDefined,
+    options: &GatherOptions,
+    packages: &mut [Package],
+    diagnostics: &mut Diagnostics,
+) -> Result<(), AggregateError> {
+    #[cfg(feature = "tokio")]
+    if let Some(concurrent) = &options.concurrent {
+        return concurrent.block_on(clearlydefined.enrich_async(packages, diagnostics, concurrent.options()));
+    }
+
+    clearlydefined.enrich(packages, diagnostics)
+}
+
+/// [`Osv::check`], concurrently on the runtime of [`crate::nonblocking`] if there is one
+#[cfg(feature = "network")]
+#[cfg_attr(not(feature = "tokio"), allow(unused_variables))]
+fn check_advisories(
+    osv: &Osv,
+    options: &GatherOptions,
+    packages: &[Package],
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Advisory>, AggregateError> {
+    #[cfg(feature = "tokio")]
+    if let Some(concurrent) = &options.concurrent {
+        return concurrent.block_on(osv.check_async(packages, diagnostics, concurrent.options()));
+    }
+
+    osv.check(packages, diagnostics)
+}
+
+fn copy_license_store(license_store: &LicenseStore) -> Result<LicenseStore, AggregateError> {
+    let mut buf = Vec::new();
+    license_store
+        .to_cache(&mut buf)
+        .map_err(|e| AggregateError::LicenseStore(e.into()))?;
+
+    LicenseStore::from_cache(buf.as_slice()).map_err(|e| AggregateError::LicenseStore(e.into()))
+}
+
+/// If the SPDX identifier of individual licenses in the packages are unknown
+/// use the license store to analyze the license contents to determine their SPDX.
+///
+/// This is typically used when the list of packages is generated by a third party tool
+/// that has no knowledge of the SPDX identifiers of the individual licenses (e.g. conan).
+pub fn augment_licenses(
+    licenses: &mut [Package],
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), AggregateError> {
+    augment_licenses_with(licenses, license_store, config, &DetectionOptions::default(), diagnostics)
+}
+
+/// Like [`augment_licenses`], but with the given options for the detection of licenses in the texts
+pub fn augment_licenses_with(
+    licenses: &mut [Package],
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+    detection: &DetectionOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), AggregateError> {
+    let mut memo = AnalysisMemo::new();
+
+    for pkg in licenses {
+        augment_package(pkg, &license_store, config, detection, &mut memo, diagnostics);
+    }
+
+    Ok(())
+}
+
+/// Like [`augment_licenses_with`], but with the detection options of the gather options and the analyses of previous
+/// runs in their [`LicenseCache`], which is updated with the new ones
+pub(crate) fn augment_licenses_cached(
+    licenses: &mut [Package],
+    license_store: &LicenseStore,
+    config: &Config,
+    options: &GatherOptions,
+    diagnostics: &mut Diagnostics,
+) {
+    let mut memo = match &options.cache {
+        Some(cache) => cache.analyses(license_store),
+        None => AnalysisMemo::new(),
+    };
+
+    for pkg in licenses {
+        augment_package(pkg, license_store, config, &options.detection, &mut memo, diagnostics);
+    }
+
+    if let Some(cache) = options.cache.as_ref().filter(|_| !memo.is_empty()) {
+        cache.put_analyses(license_store, &memo);
+    }
+}
+
+fn augment_package(
+    pkg: &mut Package,
+    license_store: &LicenseStore,
+    config: &Config,
+    detection: &DetectionOptions,
+    memo: &mut AnalysisMemo,
+    diagnostics: &mut Diagnostics,
+) {
+    let clarify = select_clarification(&pkg.package_name, config);
+
+    if let Some(clarify) = clarify {
+        if !clarify.git.is_empty() {
+            diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::UnsupportedGitClarification,
+                    format!(
+                        "Unsupported git clarification for '{} {}', use files clarification instead",
+                        pkg.package_name, pkg.package_version
+                    ),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version),
+            );
+        }
+
+        pkg.license_spdx = Some(clarify.license.clone().into());
+    } else if pkg.license_spdx.is_none() {
+        diagnostics.push(
+            Diagnostic::warning(
+                DiagnosticKind::MissingPackageLicense,
+                format!("No combined license SPDX available for '{} {}'", pkg.package_name, pkg.package_version),
+            )
+            .with_package(&pkg.package_name, &pkg.package_version),
+        );
+    }
+
+    for l in &mut pkg.license_files {
+        if l.spdx.is_some() && l.detection_method.is_none() {
+            l.detection_method = Some(DetectionMethod::DeclaredMetadata);
+        }
+
+        if let Some(clarify) = select_file_license_clarification(clarify, &l.name) {
+            l.spdx = clarify.license.clone().map(Into::into);
+            l.detection_score = None;
+            l.detection_method = Some(DetectionMethod::ManualOverride);
+
+            if let Err(e) = validate_sha256(&l.text, &clarify.checksum) {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        DiagnosticKind::ClarificationChecksumMismatch,
+                        format!(
+                            "Unable to validate clarification for {} of '{} {}': {}",
+                            l.name, pkg.package_name, pkg.package_version, e
+                        ),
+                    )
+                    .with_package(&pkg.package_name, &pkg.package_version)
+                    .with_file(&l.name),
+                );
+            }
+        }
+    }
+
+    analysis::detect_unlicensed(pkg, diagnostics);
+    analysis::detect_file_licenses(pkg, license_store, detection, memo, diagnostics);
+    analysis::pair_license_files(pkg);
+    analysis::check_license_count(pkg, diagnostics);
+}
+
+fn select_clarification<'cfg>(package_name: &str, config: &'cfg Config) -> Option<&'cfg Clarification> {
+    config.crates.get(package_name)?.clarify.as_ref()
+}
+
+fn select_file_license_clarification<'c>(
+    clarification: Option<&'c Clarification>,
+    license_name: &str,
+) -> Option<&'c ClarificationFile> {
+    clarification?.files.iter().find(|f| f.path == license_name)
+}
+
+/// Minimize the license requirements for the packages, based on preferences in the configuration.
+/// SPDX identifiers in the configuration are ordered based on preference, starting with the most preferred.
+///
+/// Packages that cannot be minimized (e.g. because none of their licenses is accepted) keep all their license files
+/// and are listed in the returned report, see [`analysis::minimize_packages`].
+///
+/// # Example
+/// `MIT OR Apache-2.0` may be minimized to just `MIT`
+pub fn minimize_requirements(packages: &mut [Package], config: &Config) -> MinimizationReport {
+    analysis::minimize_packages(packages, &config.accepted)
+}
+
+/// Like [`minimize_requirements`], but the remaining licenses are chosen according to the options
+/// (e.g. a priority order or licenses pinned for individual packages), see [`analysis::minimize_packages_with`]
+pub fn minimize_requirements_with(
+    packages: &mut [Package],
+    config: &Config,
+    options: &MinimizationOptions,
+) -> MinimizationReport {
+    analysis::minimize_packages_with(packages, &config.accepted, options)
+}
+
+/// Retrieve the licenses of all crates in an already resolved dependency graph (see [`get_all_krates`])
+pub fn collect_krate_licenses(
+    krates: &Krates,
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+    options: &GatherOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Package>, AggregateError> {
+    collect_krate_licenses_filtered(krates, license_store, config, options, diagnostics, |_| true)
+}
+
+/// Like [`collect_krate_licenses`], but only crates for which `filter` returns true are converted into packages
+/// (e.g. only crates reachable from a specific binary, see [`crate::graph::reachable_krates`])
+pub fn collect_krate_licenses_filtered<F: Fn(&Krate) -> bool>(
+    krates: &Krates,
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+    options: &GatherOptions,
+    diagnostics: &mut Diagnostics,
+    filter: F,
+) -> Result<Vec<Package>, AggregateError> {
+    let mut packages = Vec::new();
+    let mut converter = Converter::new(options)?;
+    let gathered = gather_krates(krates, license_store, config, options, filter)?;
+    #[cfg(all(feature = "network", feature = "tokio"))]
+    converter.prefetch(&gathered, options)?;
+
+    for gathered in gathered {
+        options.check_cancelled()?;
+        packages.extend(converter.convert(gathered, diagnostics)?);
+    }
+
+    Ok(packages)
+}
+
+fn gather_krates<F: Fn(&Krate) -> bool>(
+    krates: &Krates,
+    license_store: Arc<LicenseStore>,
+    config: &Config,
+    options: &GatherOptions,
+    filter: F,
+) -> Result<Vec<Gathered>, AggregateError> {
+    let ignore = options.ignore_set()?;
+    let ignored_files = options.ignore_license_files_set()?;
+    let selected = |krate: &Krate| !ignore.is_match(&krate.name) && filter(krate);
+
+    let mut reused = reused_packages(options, krates, &selected);
+    let vendor_dirs = vendor_dirs(krates.workspace_root());
+    let patches = patched_krates(krates.workspace_root());
+    let checksums = lockfile_checksums(krates.workspace_root());
+    let g = Gatherer::with_store(license_store.clone()).with_confidence_threshold(options.confidence_threshold);
+
+    // without a client cargo-about performs no remote lookups at all
+    #[cfg(feature = "network")]
+    let client = if reused.is_some() { None } else { Some(options.http.build_client()?) };
+    #[cfg(not(feature = "network"))]
+    let client = None;
+
+    options.check_cancelled()?;
+    let gathered = g.gather(krates, config, client);
+    options.check_cancelled()?;
+
+    Ok(gathered
+        .into_iter()
+        .filter(|kl| selected(kl.krate))
+        .filter_map(|mut kl| {
+            let reused = reused
+                .as_mut()
+                .and_then(|reused| reused.remove(&(kl.krate.name.clone(), kl.krate.version.to_string())));
+
+            // the origin of reused packages may differ, e.g. in a vendored build
+            let origin = origin(kl.krate, &vendor_dirs);
+            let fork = fork(kl.krate, origin.as_ref(), &patches);
+            let checksum = kl.krate.source.as_ref().and_then(|source| {
+                checksums.get(&(kl.krate.name.clone(), kl.krate.version.to_string(), source.repr.clone())).cloned()
+            });
+            let manifest_dir = kl.krate.manifest_path.parent().unwrap().to_owned();
+            if origin != Some(Origin::Vendored) && !vendor_dirs.is_empty() {
+                // the files of the vendored sources belong to the vendored crates, not to the crate containing them
+                kl.license_files.retain(|l| !vendor_dirs.iter().any(|dir| manifest_dir.join(&l.path).starts_with(dir)));
+            }
+            kl.license_files.retain(|l| {
+                let path = manifest_dir.join(&l.path);
+                !ignored_files.is_match(path.strip_prefix(&manifest_dir).unwrap_or(&path))
+            });
+
+            match reused {
+                Some(package) => Some(Gathered::Reused(Package {
+                    authors: kl.krate.authors.clone(),
+                    description: description(kl.krate),
+                    registry_url: registry_url(kl.krate),
+                    origin,
+                    fork,
+                    checksum,
+                    ..package
+                })),
+                None => options.run_hooks(&mut kl).then(|| {
+                    let clarified = select_clarification(&kl.krate.name, config).is_some();
+                    let license_file = declared_license_file(kl.krate, &license_store, options.confidence_threshold)
+                        .filter(|_| !clarified);
+                    if let Some((path, _)) = &license_file {
+                        kl.license_files.retain(|l| manifest_dir.join(&l.path) != *path);
+                    }
+
+                    let mut gathered = GatheredKrate::from(kl);
+                    gathered.clarified = clarified;
+                    gathered.origin = origin;
+                    gathered.fork = fork;
+                    gathered.checksum = checksum;
+                    gathered.license_file = license_file.map(|(_, file)| Box::new(file));
+                    Gathered::Krate(gathered)
+                }),
+            }
+        })
+        .collect())
+}
+
+/// The packages of all selected crates from crates.io, taken from the previous run or the cache.
+/// `None` if any of them is unknown, so everything has to be gathered.
+///
+/// Crates from crates.io are immutable, so a package with the same name and version is still up-to-date.
+fn reused_packages(
+    options: &GatherOptions,
+    krates: &Krates,
+    selected: &impl Fn(&Krate) -> bool,
+) -> Option<HashMap<(String, String), Package>> {
+    if options.previous.is_empty() && options.cache.is_none() {
+        return None;
+    }
+
+    krates
+        .krates()
+        .filter(|krate| is_from_crates_io(krate) && selected(krate))
+        .map(|krate| {
+            let version = krate.version.to_string();
+            let package = options
+                .previous
+                .get(&krate.name, &version)
+                .or_else(|| options.cache.as_ref()?.get(&krate.name, &version))?;
+
+            Some(((krate.name.clone(), version), package))
+        })
+        .collect()
+}
+
+/// The file declared with `license-file` by a crate without a `license`, analyzed with the license store.
+/// Returns the absolute path of the file as well, so the file gathered for the same path can be replaced.
+fn declared_license_file(
+    krate: &Krate,
+    license_store: &LicenseStore,
+    threshold: f32,
+) -> Option<(Utf8PathBuf, LicenseFile)> {
+    if krate.license.is_some() {
+        return None;
+    }
+
+    let path = krate.license_file()?;
+    let (text, encoding) = search::read_text(&path)
+        .inspect_err(|e| tracing::debug!("unable to read the license-file {path} of '{krate}': {e}"))
+        .ok()?;
+
+    let manifest_dir = krate.manifest_path.parent().unwrap();
+    let name = path.strip_prefix(manifest_dir).unwrap_or(&path).to_string();
+    let classification = analysis::classify_text(license_store, &text);
+    let spdx = classification.spdx.filter(|_| classification.score >= threshold);
+
+    let mut file = LicenseFile::new(name, spdx, text);
+    file.detection_score = Some(classification.score);
+    file.detection_method = Some(DetectionMethod::FileAnalysis);
+    file.encoding = encoding.map(ToOwned::to_owned);
+    Some((path, file))
+}
+
+fn is_from_crates_io(krate: &Krate) -> bool {
+    krate.source.as_ref().is_some_and(|source| source.is_crates_io())
+}
+
+/// A crate that was either gathered in this run or taken from a previous run or the [`LicenseCache`]
+enum Gathered {
+    Krate(GatheredKrate),
+    Reused(Package),
+}
+
+/// State for converting [`Gathered`] crates into [`Package`]s
+struct Converter {
+    cache: Option<LicenseCache>,
+    scan_spdx_headers: bool,
+    unknown_license: UnknownLicenseAction,
+    unknown_license_overrides: HashMap<String, Expression>,
+    license_overrides: Vec<LicenseOverride>,
+    ignored_files: GlobSet,
+    #[cfg(feature = "network")]
+    crates_io: Option<crate::crates_io::CratesIo>,
+}
+
+impl Converter {
+    fn new(options: &GatherOptions) -> Result<Self, AggregateError> {
+        Ok(Self {
+            cache: options.cache.clone(),
+            scan_spdx_headers: options.scan_spdx_headers,
+            unknown_license: options.unknown_license,
+            unknown_license_overrides: options.unknown_license_overrides.clone(),
+            license_overrides: options.license_overrides.clone(),
+            ignored_files: options.ignore_license_files_set()?,
+            #[cfg(feature = "network")]
+            crates_io: (options.crates_io_fallback && !options.offline)
+                .then(|| crate::crates_io::CratesIo::new(options.http.clone(), options.registries.clone())),
+        })
+    }
+
+    /// Look up the crates that may lack metadata in their registry concurrently, on the runtime of
+    /// [`crate::nonblocking`] if there is one, see [`crate::crates_io::CratesIo::prefetch`]
+    #[cfg(all(feature = "network", feature = "tokio"))]
+    fn prefetch(&mut self, gathered: &[Gathered], options: &GatherOptions) -> Result<(), AggregateError> {
+        let (Some(crates_io), Some(concurrent)) = (self.crates_io.as_mut(), &options.concurrent) else {
+            return Ok(());
+        };
+
+        let crates = gathered
+            .iter()
+            .filter_map(|g| match g {
+                Gathered::Krate(krate) => Some(krate),
+                Gathered::Reused(_) => None,
+            })
+            .filter(|krate| krate.url.is_none() || !matches!(krate.lic_info, LicenseInfo::Expr(_)))
+            .filter(|krate| !self.license_overrides.iter().any(|o| o.matches(&krate.name, &krate.version)))
+            .filter_map(|krate| match &krate.origin {
+                None => Some((None, krate.name.clone())),
+                Some(Origin::Registry { url }) => Some((Some(url.clone()), krate.name.clone())),
+                Some(_) => None,
+            })
+            .collect();
+
+        concurrent.block_on(crates_io.prefetch(crates, concurrent.options()))
+    }
+
+    /// Convert into a [`Package`], completing the metadata of freshly gathered crates from crates.io
+    /// and storing them in the cache. Returns `None` for ignored crates.
+    ///
+    /// Crates with a [`LicenseOverride`] get the license of the override, neither crates.io nor the cache is consulted
+    /// for them. Unknown licenses are resolved as configured (see [`UnknownLicenseAction`]),
+    /// which fails for [`UnknownLicenseAction::Error`].
+    fn convert(&mut self, gathered: Gathered, diagnostics: &mut Diagnostics) -> Result<Option<Package>, AggregateError> {
+        let mut package = match gathered {
+            Gathered::Reused(mut package) => {
+                if let Some(license_override) = self.find_override(&package) {
+                    apply_override(&mut package, license_override, diagnostics);
+                }
+
+                package
+            },
+            Gathered::Krate(gathered) => {
+                let from_crates_io = gathered.from_crates_io;
+                let manifest_dir = gathered.manifest_dir.clone();
+                let license_override =
+                    self.license_overrides.iter().find(|o| o.matches(&gathered.name, &gathered.version));
+
+                if let Some(license_override) = license_override {
+                    let mut package = gathered.into_overridden_package(license_override, diagnostics);
+                    apply_override(&mut package, license_override, diagnostics);
+                    package.license_files.retain(|l| !self.ignored_files.is_match(&l.name));
+                    report_native_code(&package, diagnostics);
+                    package.package_url = package.package_url.or_else(|| package.registry_url.clone());
+                    package.purl = Some(package_purl(&package));
+                    return Ok(Some(package));
+                }
+
+                #[cfg_attr(not(feature = "network"), allow(unused_mut))]
+                let Some(mut package) = gathered.into_package(diagnostics) else {
+                    return Ok(None);
+                };
+
+                if self.scan_spdx_headers {
+                    check_spdx_headers(&package, &manifest_dir, diagnostics);
+                }
+
+                #[cfg(feature = "network")]
+                if let Some(crates_io) = self.crates_io.as_mut() {
+                    crates_io.complete(&mut package, diagnostics);
+                }
+
+                if let Some(cache) = self.cache.as_ref().filter(|_| from_crates_io) {
+                    cache.put(&package);
+                }
+
+                package
+            },
+        };
+
+        // files found by searching the package are only known by their name relative to it
+        package.license_files.retain(|l| !self.ignored_files.is_match(&l.name));
+
+        self.override_unknown_license(&mut package, diagnostics);
+        analysis::detect_unlicensed(&mut package, diagnostics);
+        self.resolve_unknown_license(&mut package, diagnostics)?;
+
+        package.package_url = package.package_url.or_else(|| package.registry_url.clone());
+        report_native_code(&package, diagnostics);
+        package.purl = Some(package_purl(&package));
+        Ok(Some(package))
+    }
+
+    fn find_override(&self, pkg: &Package) -> Option<&LicenseOverride> {
+        self.license_overrides.iter().find(|o| o.matches(&pkg.package_name, &pkg.package_version))
+    }
+
+    fn override_unknown_license(&self, pkg: &mut Package, diagnostics: &mut Diagnostics) {
+        if pkg.license_spdx.is_some() {
+            return;
+        }
+
+        if let Some(license) = self.unknown_license_overrides.get(&pkg.package_name) {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Info,
+                    DiagnosticKind::UnknownLicenseResolved,
+                    format!(
+                        "Using configured license {license} for '{} {}'",
+                        pkg.package_name, pkg.package_version
+                    ),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version),
+            );
+            pkg.license_spdx = Some(license.clone());
+        }
+    }
+
+    fn resolve_unknown_license(&self, pkg: &mut Package, diagnostics: &mut Diagnostics) -> Result<(), AggregateError> {
+        if pkg.license_spdx.is_some() {
+            return Ok(());
+        }
+
+        match self.unknown_license {
+            UnknownLicenseAction::Warn => {},
+            UnknownLicenseAction::Detect => {
+                let mut licenses: Vec<_> = pkg
+                    .license_files
+                    .iter()
+                    .filter(|l| l.kind.is_license())
+                    .filter_map(|l| l.spdx.as_ref())
+                    .map(|spdx| format!("({spdx})"))
+                    .collect();
+                licenses.sort();
+                licenses.dedup();
+
+                let detected = Expression::parse(&licenses.join(" AND ")).ok();
+                if let Some(license) = detected.map(|l| analysis::canonical_expression(&l)) {
+                    diagnostics.push(
+                        Diagnostic::new(
+                            Severity::Info,
+                            DiagnosticKind::UnknownLicenseResolved,
+                            format!(
+                                "Using license {license} detected in the license files of '{} {}'",
+                                pkg.package_name, pkg.package_version
+                            ),
+                        )
+                        .with_package(&pkg.package_name, &pkg.package_version),
+                    );
+                    pkg.license_spdx = Some(license);
+                }
+            },
+            UnknownLicenseAction::Review => diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::NeedsReview,
+                    format!(
+                        "The license of '{} {}' is unknown and has to be determined manually",
+                        pkg.package_name, pkg.package_version
+                    ),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version),
+            ),
+            UnknownLicenseAction::Error => {
+                return Err(AggregateError::UnknownLicense {
+                    package: format!("{} {}", pkg.package_name, pkg.package_version),
+                })
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// A gathered [`KrateLicense`] detached from the lifetime of the [`Krates`] graph
+struct GatheredKrate {
+    name: String,
+    version: String,
+    manifest_dir: Utf8PathBuf,
+    url: Option<String>,
+    from_crates_io: bool,
+    /// Whether the configuration contains a clarification for the crate
+    clarified: bool,
+    lic_info: LicenseInfo,
+    license_files: Vec<cargo_about::licenses::LicenseFile>,
+    /// The file declared with `license-file` in the manifest, see [`declared_license_file`]
+    license_file: Option<Box<LicenseFile>>,
+    native_code: Option<NativeCode>,
+    origin: Option<Origin>,
+    fork: Option<Fork>,
+    checksum: Option<String>,
+    authors: Vec<String>,
+    description: Option<String>,
+    registry_url: Option<String>,
+}
+
+impl From<KrateLicense<'_>> for GatheredKrate {
+    fn from(KrateLicense { krate, lic_info, license_files }: KrateLicense<'_>) -> Self {
+        Self {
+            name: krate.name.clone(),
+            version: krate.version.to_string(),
+            manifest_dir: krate.manifest_path.parent().unwrap().to_owned(),
+            url: krate_url(krate),
+            from_crates_io: is_from_crates_io(krate),
+            clarified: false,
+            lic_info,
+            license_files,
+            license_file: None,
+            native_code: native_code(krate),
+            origin: origin(krate, &[]),
+            fork: None,
+            checksum: None,
+            authors: krate.authors.clone(),
+            description: description(krate),
+            registry_url: registry_url(krate),
+        }
+    }
+}
+
+impl GatheredKrate {
+    /// Convert into a [`Package`] with the license of the override instead of the one of the metadata,
+    /// the license files of the crate are only read if the override has no text of its own
+    fn into_overridden_package(self, license_override: &LicenseOverride, diagnostics: &mut Diagnostics) -> Package {
+        let Self {
+            name,
+            version,
+            manifest_dir,
+            url,
+            license_files,
+            license_file,
+            native_code,
+            origin,
+            fork,
+            checksum,
+            authors,
+            description,
+            registry_url,
+            ..
+        } = self;
+
+        if license_override.text.is_some() {
+            return Package {
+                package_name: name,
+                package_version: version,
+                package_url: url,
+                authors,
+                description,
+                registry_url,
+                purl: None,
+                checksum,
+                source_archive: None,
+                license_spdx: None,
+                license_files: find_notice_files(&manifest_dir),
+                copyrights: Vec::new(),
+                native_code,
+                linkage: None,
+                origin,
+                fork,
+                versions: Vec::new(),
+                feature_sets: Vec::new(),
+                platforms: Vec::new(),
+            };
+        }
+
+        let lic_info = LicenseInfo::Expr(license_override.license.0.clone());
+        let license_file = license_file.map(|l| *l);
+        let mut package =
+            package_from_parts(name, version, &manifest_dir, license_file, &lic_info, &license_files, diagnostics)
+                .expect("only ignored crates are skipped");
+        package.package_url = url;
+        package.native_code = native_code;
+        package.origin = origin;
+        package.fork = fork;
+        package.checksum = checksum;
+        package.authors = authors;
+        package.description = description;
+        package.registry_url = registry_url;
+        link_repository_files(&mut package, &manifest_dir);
+        package
+    }
+
+    /// Convert into a [`Package`], reading license headers from disk.
+    /// Returns `None` for ignored crates.
+    fn into_package(self, diagnostics: &mut Diagnostics) -> Option<Package> {
+        let Self {
+            name,
+            version,
+            manifest_dir,
+            url,
+            clarified,
+            mut lic_info,
+            license_files,
+            license_file,
+            native_code,
+            origin,
+            fork,
+            checksum,
+            authors,
+            description,
+            registry_url,
+            ..
+        } = self;
+
+        // the declared file is the license of a crate without a license expression
+        let declared = license_file.as_ref().and_then(|l| Some((l, l.spdx.as_ref()?)));
+        if let (LicenseInfo::Unknown, Some((file, spdx))) = (&lic_info, declared) {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Info,
+                    DiagnosticKind::UnknownLicenseResolved,
+                    format!("Using license {spdx} detected in the license-file {} of '{name} {version}'", file.name),
+                )
+                .with_package(&name, &version)
+                .with_file(&file.name),
+            );
+            lic_info = LicenseInfo::Expr(spdx.0.clone());
+        }
+
+        let license_file = license_file.map(|l| *l);
+        let mut package =
+            package_from_parts(name, version, &manifest_dir, license_file, &lic_info, &license_files, diagnostics)?;
+        package.package_url = url;
+        package.native_code = native_code;
+        package.origin = origin;
+        package.fork = fork;
+        package.checksum = checksum;
+        package.authors = authors;
+        package.description = description;
+        package.registry_url = registry_url;
+        link_repository_files(&mut package, &manifest_dir);
+
+        if clarified {
+            for l in &mut package.license_files {
+                l.detection_method = Some(DetectionMethod::ManualOverride);
+            }
+        }
+
+        Some(package)
+    }
+}
+
+fn report_native_code(pkg: &Package, diagnostics: &mut Diagnostics) {
+    let Some(native_code) = &pkg.native_code else {
+        return;
+    };
+
+    let links = native_code.links.as_ref().map_or_else(String::new, |links| format!(" ('{links}')"));
+    diagnostics.push(
+        Diagnostic::new(
+            Severity::Info,
+            DiagnosticKind::NativeCode,
+            format!(
+                "'{} {}' links a native library{links}, the licenses of its bundled sources have to be reviewed",
+                pkg.package_name, pkg.package_version
+            ),
+        )
+        .with_package(&pkg.package_name, &pkg.package_version),
+    );
+}
+
+/// Replace the license of the package (and its license files, if the override has a text) by the override
+fn apply_override(pkg: &mut Package, license_override: &LicenseOverride, diagnostics: &mut Diagnostics) {
+    diagnostics.push(
+        Diagnostic::new(
+            Severity::Info,
+            DiagnosticKind::LicenseOverridden,
+            format!(
+                "Using license {} of the overrides for '{} {}'",
+                license_override.license, pkg.package_name, pkg.package_version
+            ),
+        )
+        .with_package(&pkg.package_name, &pkg.package_version),
+    );
+
+    pkg.license_spdx = Some(license_override.license.clone());
+
+    if let Some(text) = &license_override.text {
+        let mut file = LicenseFile::new(
+            license_override.file_name().to_owned(),
+            Some(license_override.license.clone()),
+            text.clone(),
+        );
+        file.detection_method = Some(DetectionMethod::ManualOverride);
+
+        pkg.license_files.retain(|l| !l.kind.is_license());
+        pkg.license_files.insert(0, file);
+    }
+}
+
+impl Package {
+    /// Convert a crate gathered by cargo-about (e.g. within a [`crate::KrateHook`] or a custom pipeline) into a package,
+    /// reading license headers relative to the manifest of the crate.
+    /// Returns `None` for ignored crates (e.g. private crates with `publish = false`).
+    pub fn from_krate_license(krate_license: &KrateLicense<'_>, diagnostics: &mut Diagnostics) -> Option<Package> {
+        let krate = krate_license.krate;
+
+        let mut package = package_from_parts(
+            krate.name.clone(),
+            krate.version.to_string(),
+            krate.manifest_path.parent().unwrap(),
+            None,
+            &krate_license.lic_info,
+            &krate_license.license_files,
+            diagnostics,
+        )?;
+
+        package.package_url = krate_url(krate);
+        package.native_code = native_code(krate);
+        package.origin = origin(krate, &[]);
+        package.fork = fork(krate, package.origin.as_ref(), &HashMap::new());
+        package.authors = krate.authors.clone();
+        package.description = description(krate);
+        package.registry_url = registry_url(krate);
+        package.purl = Some(package_purl(&package));
+        link_repository_files(&mut package, krate.manifest_path.parent().unwrap());
+        Some(package)
+    }
+}
+
+/// The native code of crates that declare `links` or follow the `-sys` naming convention
+fn native_code(krate: &Krate) -> Option<NativeCode> {
+    let sys_crate = krate.name.ends_with("-sys");
+
+    (krate.links.is_some() || sys_crate).then(|| NativeCode { links: krate.links.clone(), sys_crate })
+}
+
+/// The checksums of the `.crate` archives recorded in the Cargo.lock of the workspace, by name, version and source
+fn lockfile_checksums(workspace_root: &Utf8Path) -> HashMap<(String, String, String), String> {
+    #[derive(Deserialize)]
+    struct Lockfile {
+        #[serde(default, rename = "package")]
+        packages: Vec<LockedPackage>,
+    }
+
+    #[derive(Deserialize)]
+    struct LockedPackage {
+        name: String,
+        version: String,
+        source: Option<String>,
+        checksum: Option<String>,
+    }
+
+    let lockfile = std::fs::read_to_string(workspace_root.join("Cargo.lock"))
+        .ok()
+        .and_then(|content| toml::from_str::<Lockfile>(&content).ok());
+
+    lockfile
+        .into_iter()
+        .flat_map(|lockfile| lockfile.packages)
+        .filter_map(|p| Some(((p.name, p.version, p.source?), p.checksum?)))
+        .collect()
+}
+
+/// The directories of the sources replaced in the `.cargo/config.toml` of the workspace, i.e. the output of
+/// `cargo vendor`
+fn vendor_dirs(workspace_root: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let config = ["config.toml", "config"]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(workspace_root.join(".cargo").join(name)).ok());
+    let Some(config) = config.and_then(|c| c.parse::<toml::Table>().ok()) else {
+        return Vec::new();
+    };
+
+    let Some(sources) = config.get("source").and_then(|s| s.as_table()) else {
+        return Vec::new();
+    };
+
+    sources
+        .values()
+        .filter_map(|source| source.get("directory")?.as_str())
+        .map(|dir| workspace_root.join(dir))
+        .collect()
+}
+
+/// The origin of crates that are not from crates.io, see [`Origin`].
+/// Crates within one of the `vendor_dirs` are [`Origin::Vendored`], whatever their source.
+fn origin(krate: &Krate, vendor_dirs: &[Utf8PathBuf]) -> Option<Origin> {
+    if vendor_dirs.iter().any(|dir| krate.manifest_path.starts_with(dir)) {
+        return Some(Origin::Vendored);
+    }
+
+    let Some(source) = &krate.source else {
+        return Some(Origin::Path);
+    };
+
+    if source.is_crates_io() {
+        return None;
+    }
+
+    Some(source_origin(&source.repr))
+}
+
+/// The origin of a crate from the source (`repr` of its [`krates::cm::Source`])
+fn source_origin(repr: &str) -> Origin {
+    // e.g. `git+https://github.com/org/repo?branch=main#<commit>` or `registry+https://...`
+    if let Some(git) = repr.strip_prefix("git+") {
+        let (url, commit) = git.split_once('#').map_or((git, None), |(url, commit)| (url, Some(commit.to_owned())));
+        let (url, query) = url.split_once('?').map_or((url, None), |(url, query)| (url, Some(query)));
+        let reference = query.into_iter().flat_map(|q| q.split('&')).find_map(|pair| match pair.split_once('=')? {
+            ("branch", branch) => Some(GitReference::Branch(branch.to_owned())),
+            ("tag", tag) => Some(GitReference::Tag(tag.to_owned())),
+            ("rev", rev) => Some(GitReference::Rev(rev.to_owned())),
+            _ => None,
+        });
+        return Origin::Git { url: url.to_owned(), commit, reference };
+    }
+
+    Origin::Registry { url: repr.strip_prefix("registry+").unwrap_or(repr).to_owned() }
+}
+
+/// The crates (by package name) replaced in a `[patch]` section of the workspace manifest or of its
+/// `.cargo/config.toml`, with the name of the crate they replace
+fn patched_krates(workspace_root: &Utf8Path) -> HashMap<String, String> {
+    let manifests = [
+        workspace_root.join("Cargo.toml"),
+        workspace_root.join(".cargo").join("config.toml"),
+        workspace_root.join(".cargo").join("config"),
+    ];
+
+    let mut patches = HashMap::new();
+    for manifest in manifests {
+        let Some(table) = std::fs::read_to_string(manifest).ok().and_then(|c| c.parse::<toml::Table>().ok()) else {
+            continue;
+        };
+        let Some(sources) = table.get("patch").and_then(|p| p.as_table()) else {
+            continue;
+        };
+
+        // e.g. `[patch.crates-io] foo = { git = "https://github.com/org/foo", package = "foo-fork" }`
+        for patch in sources.values().filter_map(|s| s.as_table()) {
+            for (upstream, dependency) in patch {
+                let package = dependency.get("package").and_then(|p| p.as_str()).unwrap_or(upstream);
+                patches.insert(package.to_owned(), upstream.clone());
+            }
+        }
+    }
+
+    patches
+}
+
+/// The fork of a crate that is patched (see [`patched_krates`]) or a git dependency on a repository other than the
+/// one declared by its manifest
+fn fork(krate: &Krate, origin: Option<&Origin>, patches: &HashMap<String, String>) -> Option<Fork> {
+    let url = match origin {
+        Some(Origin::Git { url, .. }) => Some(url.clone()),
+        _ => None,
+    };
+
+    if let Some(upstream) = patches.get(&krate.name).filter(|_| !is_from_crates_io(krate)) {
+        return Some(Fork { upstream: upstream.clone(), upstream_url: krate.repository.clone(), url, patched: true });
+    }
+
+    // the repository of the manifest is usually left pointing to the upstream crate when forking it
+    let normalize = |url: &str| url.trim_end_matches('/').trim_end_matches(".git").to_lowercase();
+    let (url, repository) = (url?, krate.repository.as_ref()?);
+    (normalize(&url) != normalize(repository)).then(|| Fork {
+        upstream: krate.name.clone(),
+        upstream_url: Some(repository.clone()),
+        url: Some(url),
+        patched: false,
+    })
+}
+
+/// The description of the manifest on a single line
+fn description(krate: &Krate) -> Option<String> {
+    let description = krate.description.as_ref()?.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!description.is_empty()).then_some(description)
+}
+
+/// The page of the crate on crates.io, alternative registries have no known web interface
+fn registry_url(krate: &Krate) -> Option<String> {
+    is_from_crates_io(krate).then(|| format!("https://crates.io/crates/{}/{}", krate.name, krate.version))
+}
+
+/// Link the license files of the crate to its repository at the revision it was packaged from, i.e. the commit of a
+/// git dependency or the one `cargo package` recorded in `.cargo_vcs_info.json`.
+/// Only files of the crate directory are linked, on GitHub, GitLab and Codeberg.
+fn link_repository_files(pkg: &mut Package, manifest_dir: &Utf8Path) {
+    let Some((repository, revision, path_in_vcs)) = vcs_revision(pkg, manifest_dir) else {
+        return;
+    };
+
+    for l in &mut pkg.license_files {
+        if l.repository_url.is_some() || l.synthesized || !manifest_dir.join(&l.name).is_file() {
+            continue;
+        }
+
+        let path = if path_in_vcs.is_empty() { l.name.clone() } else { format!("{path_in_vcs}/{}", l.name) };
+        l.repository_url = repository_file_url(&repository, &revision, &path);
+    }
+}
+
+/// The repository, commit and directory within the repository of the crate, if they are known
+fn vcs_revision(pkg: &Package, manifest_dir: &Utf8Path) -> Option<(String, String, String)> {
+    #[derive(Deserialize)]
+    struct VcsInfo {
+        git: VcsGit,
+        #[serde(default)]
+        path_in_vcs: String,
+    }
+
+    #[derive(Deserialize)]
+    struct VcsGit {
+        sha1: String,
+    }
+
+    if let Some(Origin::Git { url, commit: Some(commit), .. }) = &pkg.origin {
+        // the checkouts of git dependencies are clones of the repository
+        let checkout = manifest_dir.ancestors().find(|dir| dir.join(".git").exists())?;
+        let path_in_vcs = manifest_dir.strip_prefix(checkout).ok()?.as_str().replace('\\', "/");
+        return Some((url.clone(), commit.clone(), path_in_vcs));
+    }
+
+    let content = std::fs::read(manifest_dir.join(".cargo_vcs_info.json")).ok()?;
+    let info: VcsInfo = serde_json::from_slice(&content).ok()?;
+    Some((pkg.package_url.clone()?, info.git.sha1, info.path_in_vcs))
+}
+
+/// The web page of the file at the revision in the repository, for repositories on GitHub, GitLab and Codeberg
+fn repository_file_url(repository: &str, revision: &str, path: &str) -> Option<String> {
+    // e.g. `https://github.com/org/repo.git`, `ssh://git@gitlab.com/org/repo` or `https://github.com/org/repo/tree/x`
+    let address = repository.split_once("://").map_or(repository, |(_, address)| address);
+    let address = address.split_once('@').map_or(address, |(_, address)| address);
+    let (host, repo_path) = address.split_once('/')?;
+    let repo_path = repo_path.split("/-/").next()?.trim_end_matches('/');
+    let repo_path = repo_path.strip_suffix(".git").unwrap_or(repo_path);
+
+    let mut segments = repo_path.split('/').filter(|s| !s.is_empty());
+    let (owner, repo) = (segments.next()?, segments.next()?);
+    match host.to_ascii_lowercase().as_str() {
+        "github.com" | "www.github.com" => Some(format!("https://github.com/{owner}/{repo}/blob/{revision}/{path}")),
+        "gitlab.com" | "www.gitlab.com" => Some(format!("https://gitlab.com/{repo_path}/-/blob/{revision}/{path}")),
+        "codeberg.org" => Some(format!("https://codeberg.org/{owner}/{repo}/src/commit/{revision}/{path}")),
+        _ => None,
+    }
+}
+
+fn package_purl(pkg: &Package) -> String {
+    crate::cargo_purl(&pkg.package_name, &pkg.package_version, pkg.origin.as_ref())
+}
+
+fn krate_url(krate: &Krate) -> Option<String> {
+    krate
+        .repository
+        .as_ref()
+        .or(krate.homepage.as_ref())
+        .map(ToOwned::to_owned)
+}
+
+fn package_from_parts(
+    krate_name: String,
+    krate_version: String,
+    manifest_dir: &Utf8Path,
+    license_file: Option<LicenseFile>,
+    lic_info: &LicenseInfo,
+    license_files: &[cargo_about::licenses::LicenseFile],
+    diagnostics: &mut Diagnostics,
+) -> Option<Package> {
+    let krate = format!("{krate_name} {krate_version}");
+
+    let license = match lic_info {
+        LicenseInfo::Expr(expr) => Some(expr.clone().into()),
+        LicenseInfo::Unknown => {
+            diagnostics.push(
+                Diagnostic::warning(DiagnosticKind::UnknownLicense, format!("crate '{krate}' has unknown license"))
+                    .with_package(&krate_name, &krate_version),
+            );
+            None
+        },
+        LicenseInfo::Ignore => {
+            // private/proprietary dependency (with publish = false in Cargo.toml)
+            return None;
+        },
+    };
+
+    let mut lfiles: Vec<_> = license_file.into_iter().collect();
+    for l in license_files {
+        let name = l.path.file_name().unwrap().to_owned();
+
+        match &l.kind {
+            LicenseFileKind::Text(text) | LicenseFileKind::AddendumText(text, _) => {
+                lfiles.push(detected_license_file(l, name, text.clone()))
+            },
+            LicenseFileKind::Header => {
+                let license_path = if l.path.is_absolute() { l.path.clone() } else { manifest_dir.join(&l.path) };
+
+                let name = license_path.file_name().unwrap().to_owned();
+                match search::read_text(&license_path) {
+                    Ok((text, encoding)) => {
+                        let mut file = detected_license_file(l, name, text);
+                        file.encoding = encoding.map(ToOwned::to_owned);
+                        lfiles.push(file);
+                    },
+                    Err(e) => diagnostics.push(
+                        Diagnostic::warning(
+                            DiagnosticKind::UnreadableLicenseFile,
+                            format!("Unable to read license file {license_path}: {e:#}"),
+                        )
+                        .with_package(&krate_name, &krate_version)
+                        .with_file(name),
+                    ),
+                }
+            },
+        }
+    }
+
+    if lfiles.is_empty() {
+        lfiles = find_license_files(manifest_dir);
+
+        let diagnostic = if lfiles.is_empty() {
+            Diagnostic::warning(DiagnosticKind::NoLicenseFiles, format!("Unable to find any license files for {krate}"))
+        } else {
+            let names: Vec<_> = lfiles.iter().map(|l| l.name.as_str()).collect();
+            Diagnostic::warning(
+                DiagnosticKind::HeuristicLicenseFiles,
+                format!(
+                    "No license files were gathered for {krate}, using {} found by searching {manifest_dir}",
+                    names.join(", ")
+                ),
+            )
+        };
+
+        diagnostics.push(diagnostic.with_package(&krate_name, &krate_version));
+
+        for l in lfiles.iter().filter(|l| l.detection_method == Some(DetectionMethod::ReadmeSection)) {
+            let license = l.spdx.as_ref().map_or_else(|| "no SPDX identifier".to_owned(), ToString::to_string);
+            diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::ReadmeLicense,
+                    format!("Only {} states the license of {krate} ({license}), it has to be reviewed", l.name),
+                )
+                .with_package(&krate_name, &krate_version)
+                .with_file(&l.name),
+            );
+        }
+    }
+
+    for notice in find_notice_files(manifest_dir) {
+        if !lfiles.iter().any(|l| l.name == notice.name) {
+            lfiles.push(notice);
+        }
+    }
+
+    for l in &lfiles {
+        if let Some(encoding) = &l.encoding {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Info,
+                    DiagnosticKind::ConvertedLicenseFile,
+                    format!("License file {} of {krate} is not UTF-8, its text was converted from {encoding}", l.name),
+                )
+                .with_package(&krate_name, &krate_version)
+                .with_file(&l.name),
+            );
+        }
+    }
+
+    let mut pkg = Package {
+        package_name: krate_name,
+        package_version: krate_version,
+        package_url: None,
+        authors: Vec::new(),
+        description: None,
+        registry_url: None,
+        purl: None,
+        checksum: None,
+        source_archive: None,
+        license_spdx: license,
+        license_files: lfiles,
+        copyrights: Vec::new(),
+        native_code: None,
+        linkage: None,
+        origin: None,
+        fork: None,
+        versions: Vec::new(),
+        feature_sets: Vec::new(),
+        platforms: Vec::new(),
+    };
+
+    analysis::pair_license_files(&mut pkg);
+    analysis::check_license_count(&pkg, diagnostics);
+
+    Some(pkg)
+}
+
+/// Files of the local crate sources are scanned by cargo-about with absolute paths,
+/// while the files of clearlydefined.io (and clarifications) are relative to the crate root
+fn detected_license_file(l: &cargo_about::licenses::LicenseFile, name: String, text: String) -> LicenseFile {
+    let mut file = LicenseFile::new(name, Some(l.license_expr.clone().into()), text);
+    file.detection_score = Some(l.confidence);
+    file.detection_method =
+        Some(if l.path.is_absolute() { DetectionMethod::FileAnalysis } else { DetectionMethod::RemoteFetch });
+    file
+}
+
+/// Iterator over the packages of a dependency graph, see [`get_all_licenses_iter`]
+pub struct PackageIter {
+    gathered: std::vec::IntoIter<Gathered>,
+    diagnostics: Diagnostics,
+    cancelled: Option<Arc<AtomicBool>>,
+    converter: Converter,
+}
+
+impl PackageIter {
+    /// The diagnostics emitted for the packages yielded so far
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    pub fn into_diagnostics(self) -> Diagnostics {
+        self.diagnostics
+    }
+}
+
+impl Iterator for PackageIter {
+    type Item = Result<Package, AggregateError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.gathered.len() > 0 {
+            if let Err(e) = check_cancelled(self.cancelled.as_deref()) {
+                // yield the cancellation once and end the iteration
+                self.gathered = Vec::new().into_iter();
+                return Some(Err(e));
+            }
+        }
+
+        for gathered in self.gathered.by_ref() {
+            match self.converter.convert(gathered, &mut self.diagnostics) {
+                Ok(Some(package)) => return Some(Ok(package)),
+                Ok(None) => {},
+                Err(e) => {
+                    self.gathered = Vec::new().into_iter();
+                    return Some(Err(e));
+                },
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_origins() {
+        let git = |url: &str, commit: Option<&str>, reference| Origin::Git {
+            url: url.to_owned(),
+            commit: commit.map(ToOwned::to_owned),
+            reference,
+        };
+        let repo = "https://github.com/org/repo";
+
+        let branch = Some(GitReference::Branch("main".to_owned()));
+        assert_eq!(source_origin(&format!("git+{repo}?branch=main#0123abc")), git(repo, Some("0123abc"), branch));
+        let tag = Some(GitReference::Tag("v1.0".to_owned()));
+        assert_eq!(source_origin(&format!("git+{repo}?tag=v1.0#0123abc")), git(repo, Some("0123abc"), tag));
+        let rev = Some(GitReference::Rev("0123".to_owned()));
+        assert_eq!(source_origin(&format!("git+{repo}?rev=0123#0123abc")), git(repo, Some("0123abc"), rev));
+        assert_eq!(source_origin(&format!("git+{repo}#0123abc")), git(repo, Some("0123abc"), None));
+        // unknown query parameters are skipped
+        let branch = Some(GitReference::Branch("dev".to_owned()));
+        assert_eq!(source_origin(&format!("git+{repo}?depth=1&branch=dev")), git(repo, None, branch));
+        assert_eq!(source_origin(&format!("git+{repo}")), git(repo, None, None));
+
+        // the output pins the revision with the commit and the reference it was resolved from
+        let tag = Some(GitReference::Tag("v1.0".to_owned()));
+        let json = serde_json::to_value(git(repo, Some("0123abc"), tag)).unwrap();
+        let expected = serde_json::json!({"git": {"url": repo, "commit": "0123abc", "reference": {"tag": "v1.0"}}});
+        assert_eq!(json, expected);
+        let json = serde_json::to_value(git(repo, None, None)).unwrap();
+        assert_eq!(json, serde_json::json!({"git": {"url": repo}}));
+    }
+
+    #[test]
+    fn failed_checks_write_the_problems() {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let dir = dir.join(format!("tla-failed-checks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let manifest = "[package]\nname = \"denied\"\nversion = \"0.1.0\"\nlicense = \"GPL-3.0-only\"\n";
+        std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        let lockfile = "version = 4\n\n[[package]]\nname = \"denied\"\nversion = \"0.1.0\"\n";
+        std::fs::write(dir.join("Cargo.lock"), lockfile).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        let config = "accepted = [\"GPL-3.0\"]\n\n[policy]\ndenied-licenses = [\"GPL-3.0\"]\n";
+        std::fs::write(dir.join("about.toml"), config).unwrap();
+
+        let result = Aggregator::new(dir.join("Cargo.toml"))
+            .gather_options(GatherOptions::new().offline(true))
+            .check_policy(true)
+            .output(dir.join("licenses.json"))
+            .audit_log(dir.join("audit.jsonl"))
+            .run();
+        assert!(matches!(result, Err(AggregateError::PolicyViolation(_))));
+
+        let problems = std::fs::read_to_string(dir.join(PROBLEMS_FILE)).unwrap();
+        assert!(problems.contains("policy-violation"), "{problems}");
+        assert!(std::fs::read_to_string(dir.join("audit.jsonl")).unwrap().contains("policy-violation"));
+        assert!(!dir.join("licenses.json").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn generated_lockfile_is_reported() {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let dir = dir.join(format!("tla-generated-lockfile-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let manifest = "[package]\nname = \"fresh\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n";
+        std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+
+        let aggregator = |options: GatherOptions| {
+            Aggregator::new(dir.join("Cargo.toml"))
+                .aggregator_config(toml::from_str("accepted = ['MIT']").unwrap())
+                .gather_options(options.offline(true))
+        };
+        let locked = aggregator(GatherOptions::new()).context();
+        assert!(matches!(locked, Err(AggregateError::ManifestResolution { .. })), "locked without a Cargo.lock");
+
+        let mut generating = aggregator(GatherOptions::new().allow_lockfile_generation(true));
+        let ctx = generating.context().unwrap();
+        assert!(ctx.generated_lockfile());
+        assert!(dir.join("Cargo.lock").is_file());
+        let aggregation = generating.run_with(&ctx).unwrap();
+        let generated: Vec<_> =
+            aggregation.diagnostics.iter().filter(|d| d.kind == DiagnosticKind::GeneratedLockfile).collect();
+        assert_eq!(generated.len(), 1);
+        assert_eq!(generated[0].severity, Severity::Warning);
+
+        // the written Cargo.lock is used by later runs
+        let ctx = aggregator(GatherOptions::new().allow_lockfile_generation(true)).context().unwrap();
+        assert!(!ctx.generated_lockfile());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_license_threshold() {
+        let package = |license: Option<&str>| -> Package {
+            let json = serde_json::json!({"package_name": "a", "package_version": "1.0.0", "package_url": null,
+                "license_spdx": license, "license_files": []});
+            serde_json::from_value(json).unwrap()
+        };
+        let packages = [package(None), package(Some("MIT")), package(None)];
+
+        assert!(check_unknown_licenses(&packages, 2).is_ok());
+        let error = check_unknown_licenses(&packages, 1).unwrap_err();
+        assert!(matches!(error, AggregateError::TooManyUnknownLicenses { count: 2, max: 1 }));
+        assert_eq!(error.class(), crate::ErrorClass::Policy);
+        assert_eq!(error.class().exit_code(), 1);
+    }
+
+    #[test]
+    fn registry_origins() {
+        let url = "https://my-registry.example.com/index";
+        assert_eq!(source_origin(&format!("registry+{url}")), Origin::Registry { url: url.to_owned() });
+        let sparse = "sparse+https://my-registry.example.com/index/";
+        assert_eq!(source_origin(sparse), Origin::Registry { url: sparse.to_owned() });
+
+        // the origins are matched against the configured registries to fetch their licenses remotely
+        #[cfg(feature = "network")]
+        {
+            let registry = crate::Registry::new("sparse+https://my-registry.example.com/index");
+            assert!(registry.has_index(sparse));
+            assert!(!registry.has_index(url));
+            let registry = crate::Registry::new(url);
+            assert!(registry.has_index(&format!("registry+{url}/")));
+            assert!(!registry.has_index("registry+https://other.example.com/index"));
+        }
+    }
+
+    #[test]
+    fn repository_file_urls() {
+        let url = |repository| repository_file_url(repository, "0123abc", "LICENSE");
+        let github = Some("https://github.com/org/repo/blob/0123abc/LICENSE".to_owned());
+        assert_eq!(url("https://github.com/org/repo"), github);
+        assert_eq!(url("https://github.com/org/repo.git"), github);
+        assert_eq!(url("ssh://git@github.com/org/repo.git"), github);
+        assert_eq!(url("https://GitHub.com/org/repo/tree/main/crates/x"), github);
+
+        let gitlab = Some("https://gitlab.com/group/sub/repo/-/blob/0123abc/LICENSE".to_owned());
+        assert_eq!(url("ssh://git@gitlab.com/group/sub/repo.git"), gitlab);
+        assert_eq!(url("https://gitlab.com/group/sub/repo/-/tree/main"), gitlab);
+
+        let codeberg = Some("https://codeberg.org/org/repo/src/commit/0123abc/LICENSE".to_owned());
+        assert_eq!(url("https://codeberg.org/org/repo"), codeberg);
+
+        assert_eq!(url("https://git.example.com/org/repo"), None);
+        assert_eq!(url("https://github.com/org"), None);
+    }
+}
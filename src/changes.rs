@@ -0,0 +1,162 @@
+//! Detection of dependencies that were relicensed between two runs, see [`detect_license_changes`]
+
+use crate::{analysis::canonical_expression, Diagnostic, DiagnosticKind, Diagnostics, Package, Severity};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+/// A package whose license differs from the license of a previous version
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LicenseChange {
+    pub package_name: String,
+    /// The version of the previous run the license is compared with
+    pub previous_version: String,
+    pub package_version: String,
+    pub previous_license: Option<String>,
+    pub license: Option<String>,
+}
+
+impl Display for LicenseChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The license of '{}' changed from {} ({}) to {} ({})",
+            self.package_name,
+            self.previous_license.as_deref().unwrap_or("unknown"),
+            self.previous_version,
+            self.license.as_deref().unwrap_or("unknown"),
+            self.package_version
+        )
+    }
+}
+
+impl LicenseChange {
+    /// Report the change as a [`DiagnosticKind::LicenseChanged`] error
+    pub fn report_to(&self, diagnostics: &mut Diagnostics) {
+        diagnostics.push(
+            Diagnostic::new(Severity::Error, DiagnosticKind::LicenseChanged, self.to_string())
+                .with_package(&self.package_name, &self.package_version),
+        );
+    }
+}
+
+/// Compare the licenses of the packages with the ones of a previous run (e.g. the last committed output).
+///
+/// A package is compared with the highest previous version of the same name, unless the version itself was part of
+/// the previous run, so only version bumps are reported. The expressions are compared in their canonical form
+/// (see [`canonical_expression`]), so `Apache-2.0 OR MIT` and `MIT OR Apache-2.0` are the same license.
+pub fn detect_license_changes(previous: &[Package], packages: &[Package]) -> Vec<LicenseChange> {
+    let mut by_name: HashMap<&str, Vec<&Package>> = HashMap::new();
+    for p in previous {
+        by_name.entry(&p.package_name).or_default().push(p);
+    }
+
+    let license = |p: &Package| p.license_spdx.as_ref().map(|l| canonical_expression(l).to_string());
+
+    let mut changes = vec![];
+    for p in packages {
+        let Some(versions) = by_name.get(p.package_name.as_str()) else {
+            continue;
+        };
+
+        if versions.iter().any(|prev| prev.package_version == p.package_version) {
+            continue;
+        }
+
+        let Some(prev) = versions.iter().max_by(|a, b| compare_versions(&a.package_version, &b.package_version)) else {
+            continue;
+        };
+
+        let (previous_license, current_license) = (license(prev), license(p));
+        if previous_license != current_license {
+            changes.push(LicenseChange {
+                package_name: p.package_name.clone(),
+                previous_version: prev.package_version.clone(),
+                package_version: p.package_version.clone(),
+                previous_license,
+                license: current_license,
+            });
+        }
+    }
+
+    changes
+}
+
+/// Compares the versions by their semver precedence (so pre-releases come before the release), versions of thirdparty
+/// packages need not be semver though, their dot separated components are compared numerically where possible
+pub(crate) fn compare_versions(a: &str, b: &str) -> Ordering {
+    if let (Ok(a), Ok(b)) = (semver::Version::parse(a), semver::Version::parse(b)) {
+        return a.cmp(&b);
+    }
+
+    let components = |v: &'_ str| {
+        v.split(['.', '-', '+'])
+            .map(|c| c.parse::<u64>().map_err(|_| c.to_owned()))
+            .collect::<Vec<_>>()
+    };
+
+    components(a).cmp(&components(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(version: &str, license: Option<&str>) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "package_name": "a",
+            "package_version": version,
+            "package_url": null,
+            "license_spdx": license,
+            "license_files": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(compare_versions("1.0.0-alpha", "1.0.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0-alpha.2", "1.0.0-alpha.10"), Ordering::Less);
+        assert_eq!(compare_versions("1.10.0", "1.9.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2.13", "1.2.13"), Ordering::Equal);
+        // not semver
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("2024.1", "1.0.0"), Ordering::Greater);
+    }
+
+    #[test]
+    fn changes() {
+        let previous = [package("1.0.0", Some("MIT")), package("1.1.0-rc.1", Some("GPL-3.0"))];
+
+        let changes = detect_license_changes(&previous, &[package("1.2.0", Some("Apache-2.0 OR MIT"))]);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].previous_version, "1.1.0-rc.1");
+        assert_eq!(changes[0].previous_license.as_deref(), Some("GPL-3.0"));
+        assert_eq!(changes[0].license.as_deref(), Some("Apache-2.0 OR MIT"));
+        assert_eq!(
+            changes[0].to_string(),
+            "The license of 'a' changed from GPL-3.0 (1.1.0-rc.1) to Apache-2.0 OR MIT (1.2.0)"
+        );
+
+        // compared with the release, not the pre-release
+        let previous = [package("1.0.0-alpha", Some("GPL-3.0")), package("1.0.0", Some("MIT OR Apache-2.0"))];
+        assert!(detect_license_changes(&previous, &[package("1.1.0", Some("Apache-2.0 OR MIT"))]).is_empty());
+
+        // versions of the previous run and unknown packages are not compared
+        assert!(detect_license_changes(&previous, &[package("1.0.0-alpha", Some("MIT"))]).is_empty());
+        let other = Package { package_name: "b".into(), ..package("2.0.0", Some("MIT")) };
+        assert!(detect_license_changes(&previous, &[other]).is_empty());
+
+        let changes = detect_license_changes(&previous, &[package("1.1.0", None)]);
+        assert_eq!(changes[0].license, None);
+
+        let mut diagnostics = Diagnostics::new();
+        changes[0].report_to(&mut diagnostics);
+        let reported = diagnostics.iter().next().unwrap();
+        assert_eq!((reported.severity, reported.kind), (Severity::Error, DiagnosticKind::LicenseChanged));
+        assert_eq!(reported.package_version.as_deref(), Some("1.1.0"));
+    }
+}
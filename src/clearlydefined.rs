@@ -0,0 +1,204 @@
+#[cfg(feature = "tokio")]
+use crate::nonblocking::{join_ordered, AsyncHttp, AsyncOptions};
+use crate::{analysis, AggregateError, Diagnostic, DiagnosticKind, Diagnostics, Expression, HttpOptions, Package, Severity};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+};
+
+/// Number of coordinates per request, the API accepts batches of definitions
+const BATCH_SIZE: usize = 100;
+
+/// Enriches packages with the curated license and copyright information of clearlydefined.io.
+///
+/// Packages are looked up as crates from crates.io (`crate/cratesio/-/<name>/<version>`),
+/// so thirdparty packages usually have no definition and are left as they are.
+/// For each package with a definition
+/// - the declared license is used if the package has no license yet,
+/// - a declared license that differs from the license of the package is reported as
+///   [`DiagnosticKind::ClearlyDefinedMismatch`],
+/// - the attributed parties are added to [`Package::copyrights`].
+///
+/// # Example
+/// `ClearlyDefined::new().enrich(&mut packages, &mut diagnostics)?`
+#[derive(Clone, Debug, Default)]
+pub struct ClearlyDefined {
+    http: HttpOptions,
+    url: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Definition {
+    licensed: Licensed,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Licensed {
+    declared: Option<String>,
+    facets: Facets,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Facets {
+    core: Facet,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Facet {
+    attribution: Attribution,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Attribution {
+    parties: Vec<String>,
+}
+
+impl ClearlyDefined {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn http(mut self, http: HttpOptions) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Base url of the API, defaults to `https://api.clearlydefined.io`
+    pub fn url<S: Into<String>>(mut self, url: S) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Look up all packages and merge their definitions, see [`ClearlyDefined`]
+    pub fn enrich(&self, packages: &mut [Package], diagnostics: &mut Diagnostics) -> Result<(), AggregateError> {
+        if packages.is_empty() {
+            return Ok(());
+        }
+
+        let client = self.http.build_client()?;
+        let url = self.definitions_url();
+
+        for chunk in packages.chunks_mut(BATCH_SIZE) {
+            let (coordinates, body) = batch(chunk)?;
+            let request = client.post(&url).header("Content-Type", "application/json").body(body);
+            let response = self.http.send(request)?.error_for_status()?.text()?;
+            merge_batch(chunk, &coordinates, &response, diagnostics)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like [`ClearlyDefined::enrich`], with the batches sent concurrently
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn enrich_async(
+        &self,
+        packages: &mut [Package],
+        diagnostics: &mut Diagnostics,
+        options: &AsyncOptions,
+    ) -> Result<(), AggregateError> {
+        if packages.is_empty() {
+            return Ok(());
+        }
+
+        let http = AsyncHttp::new(&self.http, options)?;
+        let url = self.definitions_url();
+
+        let mut batches = vec![];
+        let mut requests = vec![];
+        for chunk in packages.chunks(BATCH_SIZE) {
+            let (coordinates, body) = batch(chunk)?;
+            let request = http.client().post(&url).header("Content-Type", "application/json").body(body);
+            let http = http.clone();
+            requests.push(async move { http.text(request).await });
+            batches.push(coordinates);
+        }
+
+        let responses = join_ordered(requests).await?;
+        for ((chunk, coordinates), response) in packages.chunks_mut(BATCH_SIZE).zip(batches).zip(responses) {
+            merge_batch(chunk, &coordinates, &response?, diagnostics)?;
+        }
+
+        Ok(())
+    }
+
+    fn definitions_url(&self) -> String {
+        format!("{}/definitions", self.url.as_deref().unwrap_or("https://api.clearlydefined.io"))
+    }
+}
+
+/// The coordinates of the packages and the body of the request for their definitions
+fn batch(chunk: &[Package]) -> Result<(Vec<String>, String), AggregateError> {
+    let coordinates: Vec<_> = chunk.iter().map(coordinate).collect();
+    let body = serde_json::to_string(&coordinates).map_err(AggregateError::other)?;
+    Ok((coordinates, body))
+}
+
+fn merge_batch(
+    chunk: &mut [Package],
+    coordinates: &[String],
+    response: &str,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), AggregateError> {
+    let mut definitions: HashMap<String, Definition> = serde_json::from_str(response).map_err(AggregateError::other)?;
+    for (pkg, coordinate) in chunk.iter_mut().zip(coordinates) {
+        if let Some(definition) = definitions.remove(coordinate) {
+            merge(pkg, definition, diagnostics);
+        }
+    }
+
+    Ok(())
+}
+
+fn coordinate(pkg: &Package) -> String {
+    format!("crate/cratesio/-/{}/{}", pkg.package_name, pkg.package_version)
+}
+
+fn merge(pkg: &mut Package, definition: Definition, diagnostics: &mut Diagnostics) {
+    let declared = definition
+        .licensed
+        .declared
+        .filter(|d| d != "NOASSERTION" && d != "NONE" && d != "OTHER")
+        .and_then(|d| Expression::from_str(&d).ok());
+
+    if let Some(declared) = declared {
+        match &pkg.license_spdx {
+            None => {
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Info,
+                        DiagnosticKind::ClearlyDefinedLicense,
+                        format!(
+                            "Using license {declared} of clearlydefined.io for '{} {}'",
+                            pkg.package_name, pkg.package_version
+                        ),
+                    )
+                    .with_package(&pkg.package_name, &pkg.package_version),
+                );
+                pkg.license_spdx = Some(declared);
+            },
+            Some(local) if !analysis::same_licenses(local, &declared) => diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::ClearlyDefinedMismatch,
+                    format!(
+                        "License {local} of '{} {}' differs from the license {declared} declared by clearlydefined.io",
+                        pkg.package_name, pkg.package_version
+                    ),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version),
+            ),
+            Some(_) => {},
+        }
+    }
+
+    for party in definition.licensed.facets.core.attribution.parties {
+        if !pkg.copyrights.contains(&party) {
+            pkg.copyrights.push(party);
+        }
+    }
+}
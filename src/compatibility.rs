@@ -0,0 +1,176 @@
+//! Compatibility of the licenses of the dependencies with the license of the project itself,
+//! see [`check_compatibility`]
+
+use crate::{
+    obligations::{classify_license, license_categories, LicenseCategory},
+    Linkage, Package,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// A package whose license is incompatible with the distribution of the project
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LicenseConflict {
+    pub package_name: String,
+    pub package_version: String,
+    /// The license of the package (after minimization with the accepted licenses, if possible)
+    pub license: String,
+    pub category: LicenseCategory,
+    /// The chain of dependencies from a workspace member to the package, if known
+    /// (see [`crate::graph::dependency_path`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<String>,
+}
+
+/// The result of [`check_compatibility`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompatibilityReport {
+    /// The category of the project license, which determines the compatible licenses
+    pub project_category: LicenseCategory,
+    pub conflicts: Vec<LicenseConflict>,
+}
+
+impl CompatibilityReport {
+    pub fn is_compatible(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+impl Display for CompatibilityReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for conflict in &self.conflicts {
+            write!(
+                f,
+                "- '{} {}' is licensed under {} ({}), which is incompatible with the {} project license",
+                conflict.package_name, conflict.package_version, conflict.license, conflict.category, self.project_category
+            )?;
+
+            if !conflict.path.is_empty() {
+                write!(f, ", required via {}", conflict.path.join(" -> "))?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a dependency of the given category may be distributed as part of a project of the given category.
+///
+/// This is the coarse model of the usual distribution of binaries: public domain, permissive and weak copyleft
+/// dependencies (whose source has to be offered separately) are compatible with every project, strong and network
+/// copyleft dependencies only with copyleft projects. Proprietary dependencies have to be reviewed individually and are
+/// not reported, dependencies without any license are compatible with no project.
+pub fn is_compatible(project: LicenseCategory, dependency: LicenseCategory) -> bool {
+    match dependency {
+        LicenseCategory::PublicDomain
+        | LicenseCategory::Permissive
+        | LicenseCategory::WeakCopyleft
+        | LicenseCategory::Proprietary => true,
+        LicenseCategory::StrongCopyleft | LicenseCategory::NetworkCopyleft => {
+            matches!(project, LicenseCategory::StrongCopyleft | LicenseCategory::NetworkCopyleft)
+        },
+        LicenseCategory::ProprietaryUnlicensed => false,
+    }
+}
+
+/// Evaluate the license of every package against the licenses of the project (e.g. `LicenseRef-tentris-commercial`),
+/// the category of the project is the most restrictive of its licenses.
+///
+/// The license of a package is minimized with the accepted licenses first, so only the chosen licenses count
+/// (`MIT OR GPL-3.0-only` is compatible with a proprietary project if MIT is accepted), and each of them has to be
+/// compatible. The conflict is reported with the most restrictive incompatible category.
+/// Packages that are licensed under one of the project licenses (i.e. the crates of the project) and packages that
+/// are not shipped ([`Linkage::ToolOnly`]) are skipped.
+pub fn check_compatibility(
+    packages: &[Package],
+    project_licenses: &[spdx::Licensee],
+    accepted: &[spdx::Licensee],
+) -> CompatibilityReport {
+    let project_category = project_licenses
+        .iter()
+        .map(|l| classify_license(&l.clone().into_req()))
+        .max()
+        .unwrap_or(LicenseCategory::Permissive);
+
+    let mut conflicts = vec![];
+    for p in packages {
+        let Some(license) = p.license_spdx.as_ref().filter(|_| p.linkage != Some(Linkage::ToolOnly)) else {
+            continue;
+        };
+
+        if license.evaluate(|req| project_licenses.iter().any(|l| l.satisfies(req))) {
+            continue;
+        }
+
+        let (license, categories) = match license.minimized_requirements(accepted) {
+            Ok(minimized) if !accepted.is_empty() => {
+                let names: Vec<_> = minimized.iter().map(ToString::to_string).collect();
+                (names.join(" AND "), minimized.iter().map(classify_license).collect())
+            },
+            _ => (license.to_string(), license_categories(&license.0)),
+        };
+
+        // every required license has to be compatible, a proprietary one does not cover a copyleft one
+        if let Some(&category) = categories.iter().rev().find(|&&c| !is_compatible(project_category, c)) {
+            conflicts.push(LicenseConflict {
+                package_name: p.package_name.clone(),
+                package_version: p.package_version.clone(),
+                license,
+                category,
+                path: vec![],
+            });
+        }
+    }
+
+    CompatibilityReport { project_category, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, license: &str, linkage: Option<&str>) -> Package {
+        let json = serde_json::json!({"package_name": name, "package_version": "1.0.0", "package_url": null,
+            "license_spdx": license, "linkage": linkage, "license_files": []});
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn licensees(licenses: &[&str]) -> Vec<spdx::Licensee> {
+        licenses.iter().map(|l| spdx::Licensee::parse(l).unwrap()).collect()
+    }
+
+    fn conflicts(packages: &[Package], accepted: &[&str]) -> Vec<(String, LicenseCategory)> {
+        let report = check_compatibility(packages, &licensees(&["LicenseRef-tentris-commercial"]), &licensees(accepted));
+        assert_eq!(report.project_category, LicenseCategory::Proprietary);
+        report.conflicts.into_iter().map(|c| (c.package_name, c.category)).collect()
+    }
+
+    #[test]
+    fn minimized_with_the_accepted_licenses() {
+        let packages = [package("either", "MIT OR GPL-3.0-only", None)];
+        assert_eq!(conflicts(&packages, &["MIT", "GPL-3.0"]), []);
+        assert_eq!(conflicts(&packages, &["GPL-3.0"]), [("either".to_owned(), LicenseCategory::StrongCopyleft)]);
+    }
+
+    #[test]
+    fn every_part_of_and_expressions() {
+        let packages = [package("both", "GPL-3.0-only AND LicenseRef-vendor", None)];
+        assert_eq!(conflicts(&packages, &[]), [("both".to_owned(), LicenseCategory::StrongCopyleft)]);
+        assert_eq!(
+            conflicts(&packages, &["GPL-3.0", "LicenseRef-vendor"]),
+            [("both".to_owned(), LicenseCategory::StrongCopyleft)]
+        );
+    }
+
+    #[test]
+    fn skipped_packages() {
+        let packages = [
+            package("tool", "GPL-3.0-only", Some("tool-only")),
+            package("own", "LicenseRef-tentris-commercial", None),
+            package("shipped", "GPL-3.0-only", Some("static")),
+        ];
+        assert_eq!(conflicts(&packages, &[]), [("shipped".to_owned(), LicenseCategory::StrongCopyleft)]);
+    }
+}
@@ -0,0 +1,693 @@
+use crate::{
+    analysis::{DetectionOptions, LowConfidenceAction, MinimizationStrategy, SpdxParsing},
+    policy::Policy,
+    load_deny_config, load_ort_curations, AggregateError, Config, Expression, GatherOptions, LicenseStore, Linkage,
+    UnknownLicenseAction, Utf8Path, Utf8PathBuf,
+};
+use askalono::TextData;
+use krates::semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::collections::HashMap;
+#[cfg(feature = "network")]
+use std::time::Duration;
+
+/// The configuration of the aggregator: a cargo-about configuration (`about.toml`)
+/// extended by the sections that are specific to this crate.
+///
+/// # Example
+/// ```toml
+/// include = ["../common-about.toml"]
+/// accepted = ["MIT", "Apache-2.0", "LicenseRef-tentris-commercial"]
+/// spdx-parsing = "strict"
+/// deny-config = "deny.toml"
+/// ort-curations = ["curations.yml"]
+///
+/// [[custom-licenses]]
+/// id = "LicenseRef-tentris-commercial"
+/// name = "TENTRIS Commercial License"
+/// text-file = "licenses/tentris-commercial.txt"
+///
+/// [detection]
+/// threshold = 0.9
+/// low-confidence = "review"
+///
+/// [unknown-license]
+/// action = "detect"
+/// licenses = { "some-crate" = "MIT OR Apache-2.0" }
+///
+/// [minimization]
+/// strategy = "priority"
+/// priority = ["Apache-2.0", "MIT"]
+/// pinned = { "ring" = "ISC" }
+///
+/// [policy]
+/// denied-licenses = ["AGPL-3.0"]
+///
+/// [project]
+/// licenses = ["LicenseRef-tentris-commercial"]
+///
+/// [thirdparty]
+/// metadata-key = "my-org"
+///
+/// [linkage]
+/// "libmimalloc-sys" = "dynamic"
+/// "bindgen" = "tool-only"
+///
+/// [[overrides]]
+/// name = "some-crate"
+/// version = "<0.3"
+/// license = "MIT"
+/// license-file = "licenses/some-crate-LICENSE"
+///
+/// [[attributions]]
+/// name = "openssl"
+/// text-file = "licenses/openssl-advertising.txt"
+///
+/// [http]
+/// proxy = "http://proxy.corp:3128"
+/// timeout-secs = 10
+/// retries = 3
+/// backoff-ms = 1000
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AggregatorConfig {
+    /// The configurations this one is layered on, they are merged when loading the configuration (see
+    /// [`load_aggregator_config`])
+    #[serde(default)]
+    pub include: Vec<Utf8PathBuf>,
+    /// The cargo-about part of the configuration
+    #[serde(flatten)]
+    pub about: Config,
+    /// Organization-specific licenses that are detected in license texts in addition to the SPDX licenses
+    #[serde(default)]
+    pub custom_licenses: Vec<CustomLicense>,
+    /// Thresholds for the detection of licenses
+    #[serde(default)]
+    pub detection: DetectionConfig,
+    /// Whether nonstandard SPDX expressions in thirdparty files are normalized or rejected
+    pub spdx_parsing: Option<SpdxParsing>,
+    /// A cargo-deny configuration (relative to the configuration file) whose `[licenses]` section is merged into this
+    /// configuration when loading it, see [`crate::DenyLicenses::apply`]
+    pub deny_config: Option<Utf8PathBuf>,
+    /// The handling of crates whose license is unknown
+    #[serde(default)]
+    pub unknown_license: UnknownLicenseConfig,
+    /// How the licenses that remain after minimization are chosen
+    #[serde(default)]
+    pub minimization: MinimizationConfig,
+    /// The license policy, see [`crate::policy::Policy`]
+    #[serde(default)]
+    pub policy: Policy,
+    /// The license of the project itself
+    #[serde(default)]
+    pub project: ProjectConfig,
+    /// The discovery of the thirdparty files declared by crates
+    #[serde(default)]
+    pub thirdparty: ThirdpartyConfig,
+    /// Licenses of crates that replace the ones of their (wrong) metadata
+    #[serde(default)]
+    pub overrides: Vec<LicenseOverride>,
+    /// Curation files of the OSS Review Toolkit (relative to the configuration file), whose concluded licenses are
+    /// added to the overrides, see [`crate::load_ort_curations`]
+    #[serde(default)]
+    pub ort_curations: Vec<Utf8PathBuf>,
+    /// Notices that are added to packages in addition to their own ones
+    #[serde(default)]
+    pub attributions: Vec<Attribution>,
+    /// How packages (by name) are linked into the shipped artifact, see [`GatherOptions::linkage`]
+    #[serde(default)]
+    pub linkage: HashMap<String, Linkage>,
+    /// Glob patterns of license files that are not collected, see [`GatherOptions::ignore_license_files`]
+    #[serde(default)]
+    pub ignore_license_files: Vec<String>,
+    /// Alternative registries (by name) whose crates are looked up like the ones of crates.io,
+    /// see [`GatherOptions::registry`]
+    #[cfg(feature = "network")]
+    #[serde(default)]
+    pub registries: HashMap<String, crate::Registry>,
+    /// The HTTP client of the remote lookups, see [`crate::HttpOptions`]
+    #[cfg(feature = "network")]
+    #[serde(default)]
+    pub http: HttpConfig,
+}
+
+/// The `[detection]` section of the configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DetectionConfig {
+    /// Minimum score for licenses detected in the texts of thirdparty packages
+    pub threshold: Option<f32>,
+    /// What happens with thirdparty license texts below the threshold
+    pub low_confidence: Option<LowConfidenceAction>,
+    /// Number of candidate licenses reported for thirdparty license texts below the threshold
+    pub candidates: Option<usize>,
+    /// Whether thirdparty license files containing several licenses are split into one file per license
+    pub split_multi_license: Option<bool>,
+    /// Minimum score for files in the sources of rust crates to be considered license files,
+    /// see [`GatherOptions::confidence_threshold`]
+    pub rust_threshold: Option<f32>,
+}
+
+impl DetectionConfig {
+    /// Apply the configured values to the options, values that are not configured are left as they are
+    pub fn apply(&self, mut options: GatherOptions) -> GatherOptions {
+        if let Some(rust_threshold) = self.rust_threshold {
+            options = options.confidence_threshold(rust_threshold);
+        }
+
+        let detection = DetectionOptions {
+            threshold: self.threshold.unwrap_or(options.detection.threshold),
+            low_confidence: self.low_confidence.unwrap_or(options.detection.low_confidence),
+            candidates: self.candidates.unwrap_or(options.detection.candidates),
+            split_multi_license: self.split_multi_license.unwrap_or(options.detection.split_multi_license),
+        };
+
+        options.detection(detection)
+    }
+}
+
+/// The `[unknown-license]` section of the configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct UnknownLicenseConfig {
+    /// What happens with crates whose license is unknown and not configured in `licenses`
+    pub action: Option<UnknownLicenseAction>,
+    /// The licenses of crates (by name) whose license cargo-about is unable to determine
+    #[serde(default)]
+    pub licenses: HashMap<String, Expression>,
+}
+
+impl UnknownLicenseConfig {
+    /// Apply the configured values to the options, configured licenses are added to the ones of the options
+    pub fn apply(&self, mut options: GatherOptions) -> GatherOptions {
+        if let Some(action) = self.action {
+            options = options.unknown_license(action);
+        }
+
+        for (krate, license) in &self.licenses {
+            options = options.unknown_license_override(krate.clone(), license.clone());
+        }
+
+        options
+    }
+}
+
+/// The `[minimization]` section of the configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MinimizationConfig {
+    pub strategy: Option<MinimizationStrategy>,
+    /// The preferred licenses for the `priority` strategy
+    pub priority: Option<Vec<String>>,
+    /// The license that is chosen for a crate (by name) whenever its expression allows it
+    #[serde(default)]
+    pub pinned: HashMap<String, String>,
+}
+
+impl MinimizationConfig {
+    /// Apply the configured values to the options, values that are not configured are left as they are.
+    /// Fails if a license is not a valid SPDX identifier.
+    pub fn apply(&self, options: GatherOptions) -> Result<GatherOptions, AggregateError> {
+        let parse = |license: &String| {
+            spdx::Licensee::parse(license)
+                .map_err(|source| AggregateError::SpdxParse { expression: license.clone(), source })
+        };
+
+        let mut minimization = options.minimization.clone();
+        if let Some(strategy) = self.strategy {
+            minimization.strategy = strategy;
+        }
+
+        if let Some(priority) = &self.priority {
+            minimization.priority = priority.iter().map(parse).collect::<Result<_, _>>()?;
+        }
+
+        for (krate, license) in &self.pinned {
+            minimization.pinned.insert(krate.clone(), parse(license)?);
+        }
+
+        Ok(options.minimization(minimization))
+    }
+}
+
+/// The `[project]` section of the configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// The licenses under which the project is distributed, the dependencies are checked for compatibility with them
+    pub licenses: Option<Vec<String>>,
+}
+
+impl ProjectConfig {
+    /// Apply the configured values to the options, fails if a license is not a valid SPDX identifier
+    pub fn apply(&self, options: GatherOptions) -> Result<GatherOptions, AggregateError> {
+        let Some(licenses) = &self.licenses else {
+            return Ok(options);
+        };
+
+        let licenses = licenses
+            .iter()
+            .map(|l| {
+                spdx::Licensee::parse(l).map_err(|source| AggregateError::SpdxParse { expression: l.clone(), source })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(options.project_licenses(licenses))
+    }
+}
+
+/// The `[thirdparty]` section of the configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ThirdpartyConfig {
+    /// The metadata key under which crates declare their thirdparty license file
+    /// (`[package.metadata.<metadata-key>] thirdparty-file-name = "..."`), replaces the one of
+    /// [`crate::Aggregator::thirdparty_metadata_key`]
+    pub metadata_key: Option<String>,
+}
+
+/// The `[http]` section of the configuration
+#[cfg(feature = "network")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Proxy for all requests, see [`crate::HttpOptions::proxy`]
+    pub proxy: Option<String>,
+    /// Timeout of a complete request in seconds
+    pub timeout_secs: Option<u64>,
+    /// Timeout for establishing a connection in seconds
+    pub connect_timeout_secs: Option<u64>,
+    pub user_agent: Option<String>,
+    /// Number of times a request is retried after a transient failure, see [`crate::HttpOptions::retries`]
+    pub retries: Option<u32>,
+    /// Delay before the first retry in milliseconds, doubled with every further retry
+    pub backoff_ms: Option<u64>,
+}
+
+#[cfg(feature = "network")]
+impl HttpConfig {
+    /// Apply the configured values to the options, values that are not configured are left as they are
+    pub fn apply(&self, mut options: GatherOptions) -> GatherOptions {
+        let mut http = options.http.clone();
+        if let Some(proxy) = &self.proxy {
+            http = http.proxy(proxy.clone());
+        }
+        if let Some(timeout) = self.timeout_secs {
+            http = http.timeout(Duration::from_secs(timeout));
+        }
+        if let Some(connect_timeout) = self.connect_timeout_secs {
+            http = http.connect_timeout(Duration::from_secs(connect_timeout));
+        }
+        if let Some(user_agent) = &self.user_agent {
+            http = http.user_agent(user_agent.clone());
+        }
+        if let Some(retries) = self.retries {
+            http = http.retries(retries);
+        }
+        if let Some(backoff) = self.backoff_ms {
+            http = http.backoff(Duration::from_millis(backoff));
+        }
+
+        options.http = http;
+        options
+    }
+}
+
+impl From<Config> for AggregatorConfig {
+    fn from(about: Config) -> Self {
+        Self { about, ..Default::default() }
+    }
+}
+
+/// A license that is not on the SPDX license list, e.g. the EULA of an organization
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CustomLicense {
+    /// The identifier in SPDX expressions, has to start with `LicenseRef-`
+    pub id: String,
+    /// Human readable name of the license
+    pub name: String,
+    /// The reference text of the license
+    #[serde(default)]
+    pub text: String,
+    /// File containing the reference text, relative to the configuration file. Replaces `text` when loading the configuration.
+    pub text_file: Option<Utf8PathBuf>,
+}
+
+/// The license of a crate that replaces the license of its metadata, for crates whose metadata is simply wrong
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LicenseOverride {
+    /// Name of the crate
+    pub name: String,
+    /// The versions of the crate the override applies to, all versions if not given
+    pub version: Option<VersionReq>,
+    /// The license of the crate
+    pub license: Expression,
+    /// The text of the license, replaces the license files of the crate if given
+    pub text: Option<String>,
+    /// File containing the text of the license, relative to the configuration file.
+    /// Replaces `text` when loading the configuration.
+    pub license_file: Option<Utf8PathBuf>,
+}
+
+impl LicenseOverride {
+    /// Whether the override applies to the given version of the crate with the given name
+    pub fn matches(&self, name: &str, version: &str) -> bool {
+        self.name == name && version_matches(self.version.as_ref(), version)
+    }
+
+    /// The name of the license file of the override
+    pub(crate) fn file_name(&self) -> &str {
+        self.license_file.as_ref().and_then(|f| f.file_name()).unwrap_or("LICENSE")
+    }
+}
+
+/// An additional notice of a package, e.g. the advertising clause wording required by old OpenSSL versions or a
+/// "modified by ..." statement of a patched fork. It is added as a [`crate::FileKind::Notice`] file, so it is part of
+/// every output like the notices of the package itself.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Attribution {
+    /// Name of the package
+    pub name: String,
+    /// The versions of the package the attribution applies to, all versions if not given
+    pub version: Option<VersionReq>,
+    /// The text of the notice
+    #[serde(default)]
+    pub text: String,
+    /// File containing the text of the notice, relative to the configuration file.
+    /// Replaces `text` when loading the configuration.
+    pub text_file: Option<Utf8PathBuf>,
+}
+
+impl Attribution {
+    /// Whether the attribution applies to the given version of the package with the given name
+    pub fn matches(&self, name: &str, version: &str) -> bool {
+        self.name == name && version_matches(self.version.as_ref(), version)
+    }
+
+    /// The name of the notice file of the attribution
+    pub(crate) fn file_name(&self) -> &str {
+        self.text_file.as_ref().and_then(|f| f.file_name()).unwrap_or("ATTRIBUTION")
+    }
+}
+
+fn version_matches(req: Option<&VersionReq>, version: &str) -> bool {
+    req.is_none_or(|req| Version::parse(version).is_ok_and(|version| req.matches(&version)))
+}
+
+/// Load an aggregator configuration (typically called `about.toml`) from the given path.
+///
+/// The files listed by `include` (relative to the configuration file) are the base the configuration is layered on,
+/// so several repositories can share e.g. their accepted licenses and overrides: tables are merged, arrays are
+/// appended to the ones of the included files and all other values replace them. Included files may include files
+/// themselves, the paths within them stay relative to the file they are part of.
+pub fn load_aggregator_config<P: AsRef<Utf8Path>>(path: P) -> Result<AggregatorConfig, AggregateError> {
+    let path = path.as_ref();
+    let mut stack = vec![path.canonicalize_utf8().unwrap_or_else(|_| path.to_owned())];
+    let table = read_config_table(path, &mut stack)?;
+
+    let mut config: AggregatorConfig = toml::Value::Table(table)
+        .try_into()
+        .map_err(|source| AggregateError::ConfigParse { path: path.to_owned(), source })?;
+
+    let base = path.parent().unwrap_or(Utf8Path::new(""));
+    for license in &mut config.custom_licenses {
+        if !license.id.starts_with("LicenseRef-") {
+            return Err(AggregateError::CustomLicense {
+                id: license.id.clone(),
+                reason: "the identifier has to start with 'LicenseRef-'".to_owned(),
+            });
+        }
+
+        if let Some(text_file) = &license.text_file {
+            let text_path = base.join(text_file);
+            license.text = std::fs::read_to_string(&text_path)
+                .map_err(|source| AggregateError::LicenseRead { path: text_path, source })?;
+        }
+
+        if license.text.trim().is_empty() {
+            return Err(AggregateError::CustomLicense {
+                id: license.id.clone(),
+                reason: "neither 'text' nor 'text-file' is given".to_owned(),
+            });
+        }
+    }
+
+    for license_override in &mut config.overrides {
+        if let Some(license_file) = &license_override.license_file {
+            let text_path = base.join(license_file);
+            license_override.text = Some(
+                std::fs::read_to_string(&text_path)
+                    .map_err(|source| AggregateError::LicenseRead { path: text_path, source })?,
+            );
+        }
+    }
+
+    for curations in config.ort_curations.clone() {
+        config.overrides.extend(load_ort_curations(base.join(curations))?);
+    }
+
+    for attribution in &mut config.attributions {
+        if let Some(text_file) = &attribution.text_file {
+            let text_path = base.join(text_file);
+            attribution.text = std::fs::read_to_string(&text_path)
+                .map_err(|source| AggregateError::LicenseRead { path: text_path, source })?;
+        }
+    }
+
+    if let Some(deny_config) = &config.deny_config {
+        load_deny_config(base.join(deny_config))?.apply(&mut config)?;
+    }
+
+    Ok(config)
+}
+
+/// The configuration file as TOML, merged into the files it includes, see [`load_aggregator_config`].
+/// `stack` contains the files that are being included, to detect cycles.
+fn read_config_table(path: &Utf8Path, stack: &mut Vec<Utf8PathBuf>) -> Result<toml::Table, AggregateError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|source| AggregateError::ConfigRead { path: path.to_owned(), source })?;
+    let mut table: toml::Table =
+        toml::from_str(&content).map_err(|source| AggregateError::ConfigParse { path: path.to_owned(), source })?;
+
+    let include_err = |reason: &str| AggregateError::ConfigInclude { path: path.to_owned(), reason: reason.to_owned() };
+    let includes = match table.remove("include") {
+        None => Vec::new(),
+        Some(toml::Value::Array(includes)) => includes
+            .into_iter()
+            .map(|include| include.as_str().map(ToOwned::to_owned))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| include_err("'include' has to be an array of paths"))?,
+        Some(_) => return Err(include_err("'include' has to be an array of paths")),
+    };
+
+    let base = path.parent().unwrap_or(Utf8Path::new(""));
+    let mut merged = toml::Table::new();
+    for include in includes {
+        let include_path = base.join(&include);
+        let canonical = include_path.canonicalize_utf8().unwrap_or_else(|_| include_path.clone());
+        if stack.contains(&canonical) {
+            return Err(include_err(&format!("including {include} is circular")));
+        }
+
+        stack.push(canonical);
+        let mut included = read_config_table(&include_path, stack)?;
+        stack.pop();
+
+        rebase_config_paths(&mut included, Utf8Path::new(&include).parent().unwrap_or(Utf8Path::new("")));
+        merge_config_tables(&mut merged, included);
+    }
+
+    merge_config_tables(&mut merged, table);
+    Ok(merged)
+}
+
+/// Make the file paths of an included configuration relative to the including one, `dir` is the directory of the
+/// included file relative to the including one
+fn rebase_config_paths(table: &mut toml::Table, dir: &Utf8Path) {
+    let rebase = |value: &mut toml::Value| {
+        if let toml::Value::String(path) = value {
+            *path = dir.join(&*path).into_string();
+        }
+    };
+
+    if let Some(deny_config) = table.get_mut("deny-config") {
+        rebase(deny_config);
+    }
+    for curations in table.get_mut("ort-curations").and_then(|c| c.as_array_mut()).into_iter().flatten() {
+        rebase(curations);
+    }
+
+    let paths = [("custom-licenses", "text-file"), ("overrides", "license-file"), ("attributions", "text-file")];
+    for (section, key) in paths {
+        let entries = table.get_mut(section).and_then(|s| s.as_array_mut()).into_iter().flatten();
+        for path in entries.filter_map(|entry| entry.get_mut(key)) {
+            rebase(path);
+        }
+    }
+}
+
+/// Layer `table` on top of `base`, see [`load_aggregator_config`]
+fn merge_config_tables(base: &mut toml::Table, table: toml::Table) {
+    for (key, value) in table {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(table)) => merge_config_tables(base, table),
+            (Some(toml::Value::Array(base)), toml::Value::Array(array)) => base.extend(array),
+            (_, value) => {
+                base.insert(key, value);
+            },
+        }
+    }
+}
+
+/// Add the custom licenses to the store, so they are detected in license texts like any SPDX license
+pub fn add_custom_licenses(license_store: &mut LicenseStore, custom_licenses: &[CustomLicense]) {
+    for license in custom_licenses {
+        license_store.add_license(license.id.clone(), TextData::new(&license.text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory with the given files
+    fn files(name: &str, files: &[(&str, &str)]) -> Utf8PathBuf {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let dir = dir.join(format!("tla-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn documented_example() {
+        let source = include_str!("config.rs");
+        let example = source.split("/// ```toml\n").nth(1).unwrap().split("/// ```\n").next().unwrap();
+        let example: Vec<_> = example.lines().map(|l| l.trim_start_matches("///").trim_start()).collect();
+
+        let config: AggregatorConfig = toml::from_str(&example.join("\n")).unwrap();
+        assert_eq!(config.custom_licenses.len(), 1);
+        assert_eq!(config.deny_config.as_deref(), Some(Utf8Path::new("deny.toml")));
+    }
+
+    #[test]
+    fn custom_licenses() {
+        let eula = "TENTRIS END USER LICENSE AGREEMENT\n\nThe software may only be used by licensees of the \
+            TENTRIS database. Redistribution of the software, in source or binary form, is prohibited.\n";
+        let custom = "[[custom-licenses]]\nid = 'LicenseRef-tentris-commercial'\nname = 'TENTRIS Commercial'\n";
+        let dir = files(
+            "custom-licenses",
+            &[
+                ("about.toml", &format!("accepted = []\n{custom}text-file = 'licenses/eula.txt'")),
+                ("licenses/eula.txt", eula),
+                ("unprefixed.toml", "accepted = []\n[[custom-licenses]]\nid = 'tentris'\nname = 'x'\ntext = 'x'"),
+                ("empty.toml", &format!("accepted = []\n{custom}text = ' '")),
+            ],
+        );
+        let config = load_aggregator_config(dir.join("about.toml")).unwrap();
+        let reason = |name: &str| match load_aggregator_config(dir.join(name)) {
+            Err(AggregateError::CustomLicense { reason, .. }) => reason,
+            _ => panic!("invalid custom license of {name} accepted"),
+        };
+        assert_eq!(reason("unprefixed.toml"), "the identifier has to start with 'LicenseRef-'");
+        assert_eq!(reason("empty.toml"), "neither 'text' nor 'text-file' is given");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.custom_licenses[0].text, eula);
+        let mut store = LicenseStore::new();
+        add_custom_licenses(&mut store, &config.custom_licenses);
+        let classification = crate::analysis::classify_text(&store, &eula.replace('\n', " "));
+        assert_eq!(classification.name, "LicenseRef-tentris-commercial");
+        assert_eq!(classification.spdx.map(|e| e.to_string()).as_deref(), Some("LicenseRef-tentris-commercial"));
+    }
+
+    #[test]
+    fn merged_tables() {
+        let base = "accepted = ['MIT']\nsort = true\n[detection]\nthreshold = 0.9";
+        let mut base: toml::Table = toml::from_str(base).unwrap();
+        let table = toml::from_str("accepted = ['ISC']\nsort = false\n[detection]\ncandidates = 3").unwrap();
+        merge_config_tables(&mut base, table);
+
+        let expected = "accepted = ['MIT', 'ISC']\nsort = false\n[detection]\nthreshold = 0.9\ncandidates = 3";
+        assert_eq!(base, toml::from_str::<toml::Table>(expected).unwrap());
+    }
+
+    #[test]
+    fn includes() {
+        let dir = files(
+            "includes",
+            &[
+                ("about.toml", "include = ['shared/base.toml']\naccepted = ['Apache-2.0']"),
+                ("shared/base.toml", "include = ['licenses.toml']\nort-curations = ['curations.yml']"),
+                ("shared/licenses.toml", "accepted = ['MIT']\ndeny-config = 'deny.toml'"),
+            ],
+        );
+        let table = read_config_table(&dir.join("about.toml"), &mut vec![]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let expected = "accepted = ['MIT', 'Apache-2.0']\ndeny-config = 'shared/deny.toml'\n\
+            ort-curations = ['shared/curations.yml']";
+        assert_eq!(table.unwrap(), toml::from_str::<toml::Table>(expected).unwrap());
+    }
+
+    #[test]
+    fn circular_includes() {
+        let dir = files("circular", &[("a.toml", "include = ['b.toml']"), ("b.toml", "include = ['a.toml']")]);
+        let loaded = load_aggregator_config(dir.join("a.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let Err(AggregateError::ConfigInclude { path, reason }) = loaded else {
+            panic!("circular include accepted");
+        };
+        assert_eq!((path.file_name(), reason.as_str()), (Some("b.toml"), "including a.toml is circular"));
+    }
+
+    #[test]
+    fn invalid_includes() {
+        let dir = files(
+            "invalid-includes",
+            &[
+                ("scalar.toml", "include = 'base.toml'"),
+                ("number.toml", "include = [1]"),
+                ("missing.toml", "include = ['missing/base.toml']"),
+                // the same base twice is no cycle, its entries are layered twice
+                ("diamond.toml", "include = ['base.toml', 'base.toml']
+accepted = ['ISC']"),
+                ("base.toml", "accepted = ['MIT']"),
+            ],
+        );
+        let reason = |name: &str| match read_config_table(&dir.join(name), &mut vec![]) {
+            Err(AggregateError::ConfigInclude { reason, .. }) => reason,
+            _ => panic!("invalid include of {name} accepted"),
+        };
+        assert_eq!(reason("scalar.toml"), "'include' has to be an array of paths");
+        assert_eq!(reason("number.toml"), "'include' has to be an array of paths");
+
+        let missing = read_config_table(&dir.join("missing.toml"), &mut vec![]);
+        assert!(matches!(missing, Err(AggregateError::ConfigRead { path, .. }) if path.ends_with("missing/base.toml")));
+
+        let diamond = read_config_table(&dir.join("diamond.toml"), &mut vec![]).unwrap();
+        assert_eq!(diamond, toml::from_str::<toml::Table>("accepted = ['MIT', 'MIT', 'ISC']").unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn http_section() {
+        let toml = "accepted = []\n[http]\nretries = 5\nbackoff-ms = 250\ntimeout-secs = 3";
+        let config: AggregatorConfig = toml::from_str(toml).unwrap();
+        let options = config.http.apply(GatherOptions::default());
+        assert_eq!(options.http.retries, Some(5));
+        assert_eq!(options.http.backoff, Some(Duration::from_millis(250)));
+        assert_eq!(options.http.timeout, Some(Duration::from_secs(3)));
+        assert_eq!(options.http.proxy, None);
+    }
+}
@@ -0,0 +1,430 @@
+use crate::{
+    analysis, error::error_chain, AggregateError, Diagnostic, DiagnosticKind, Diagnostics, Expression, HttpOptions,
+    Origin, Package, Registry, Severity,
+};
+#[cfg(feature = "tokio")]
+use crate::nonblocking::{join_ordered, AsyncHttp, AsyncOptions};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+#[cfg(feature = "tokio")]
+use std::{collections::BTreeSet, sync::Arc};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// The crawler policy of crates.io allows at most one request per second
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lookup of the metadata of crates from crates.io (or their alternative registry) for crates whose manifest lacks it,
+/// see [`crate::GatherOptions::crates_io_fallback`]
+pub(crate) struct CratesIo {
+    http: HttpOptions,
+    registries: Vec<Registry>,
+    /// The API and token of every alternative registry (by index) that was looked up so far
+    apis: HashMap<String, Result<RegistryApi, String>>,
+    /// The responses of the crates (by the url of their API and name) looked up so far or ahead, see
+    /// [`CratesIo::prefetch`]
+    responses: HashMap<(String, String), Result<CrateResponse, String>>,
+    client: Option<Client>,
+    last_request: Option<Instant>,
+}
+
+#[derive(Clone)]
+struct RegistryApi {
+    /// e.g. `crates.io` or the host of the API, for the diagnostics
+    name: String,
+    url: String,
+    token: Option<String>,
+}
+
+impl RegistryApi {
+    fn crates_io() -> Self {
+        Self { name: "crates.io".to_owned(), url: "https://crates.io".to_owned(), token: None }
+    }
+
+    fn new(url: String, token: Option<String>) -> Self {
+        let url = url.trim_end_matches('/').to_owned();
+        let name = reqwest::Url::parse(&url)
+            .ok()
+            .and_then(|u| u.host_str().map(ToOwned::to_owned))
+            .unwrap_or_else(|| url.clone());
+        Self { name, url, token }
+    }
+}
+
+/// Where the API of an alternative registry is declared
+enum ApiLocation {
+    Configured(String),
+    /// The url of the `config.json` of a sparse index
+    IndexConfig(String),
+}
+
+/// The `config.json` of a sparse index
+#[derive(Deserialize)]
+struct IndexConfig {
+    api: Option<String>,
+}
+
+impl IndexConfig {
+    fn api(self) -> Result<String, String> {
+        self.api.ok_or_else(|| "the index declares no API".to_owned())
+    }
+}
+
+#[derive(Clone, Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateInfo,
+    #[serde(default)]
+    versions: Vec<VersionInfo>,
+}
+
+#[derive(Clone, Deserialize)]
+struct CrateInfo {
+    repository: Option<String>,
+    homepage: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+struct VersionInfo {
+    num: String,
+    license: Option<String>,
+    #[serde(default)]
+    yanked: bool,
+}
+
+impl CratesIo {
+    pub(crate) fn new(http: HttpOptions, registries: Vec<Registry>) -> Self {
+        Self {
+            http,
+            registries,
+            apis: HashMap::new(),
+            responses: HashMap::new(),
+            client: None,
+            last_request: None,
+        }
+    }
+
+    /// Fill in the url and license of the package from crates.io (or the alternative registry of the crate),
+    /// if either is missing, and report a license that differs from the one of the registry.
+    /// Git and path dependencies are skipped.
+    pub(crate) fn complete(&mut self, pkg: &mut Package, diagnostics: &mut Diagnostics) {
+        if pkg.package_url.is_some() && pkg.license_spdx.is_some() {
+            return;
+        }
+
+        let lookup_failed = |registry: &str, message: String, diagnostics: &mut Diagnostics| {
+            diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::CratesIoLookupFailed,
+                    format!("Unable to look up '{} {}' on {registry}: {message}", pkg.package_name, pkg.package_version),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version),
+            );
+        };
+
+        let api = match &pkg.origin {
+            None => RegistryApi::crates_io(),
+            Some(Origin::Registry { url }) => match self.registry_api(url) {
+                Ok(api) => api,
+                Err(message) => return lookup_failed(url, message, diagnostics),
+            },
+            Some(_) => return,
+        };
+
+        let response = match self.lookup(&api, &pkg.package_name) {
+            Ok(response) => response,
+            Err(message) => return lookup_failed(&api.name, message, diagnostics),
+        };
+
+        if pkg.package_url.is_none() {
+            pkg.package_url = response.krate.repository.or(response.krate.homepage);
+        }
+
+        let license = response
+            .versions
+            .into_iter()
+            .find(|v| v.num == pkg.package_version)
+            .and_then(|v| v.license)
+            .and_then(|l| Expression::from_str(&l).ok());
+
+        match (&pkg.license_spdx, license) {
+            (None, Some(license)) => {
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Info,
+                        DiagnosticKind::CratesIoLicense,
+                        format!(
+                            "Using license {license} of {} for '{} {}'",
+                            api.name, pkg.package_name, pkg.package_version
+                        ),
+                    )
+                    .with_package(&pkg.package_name, &pkg.package_version),
+                );
+                pkg.license_spdx = Some(license);
+            },
+            (Some(local), Some(license)) if !analysis::same_licenses(local, &license) => diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::CratesIoLicenseMismatch,
+                    format!(
+                        "License {local} of '{} {}' differs from the license {license} on {}",
+                        pkg.package_name, pkg.package_version, api.name
+                    ),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version),
+            ),
+            _ => {},
+        }
+    }
+
+    /// Report the versions of the crates of crates.io (the packages with a [`Package::registry_url`]) that were
+    /// yanked as [`DiagnosticKind::Yanked`], the crates are looked up unless they were prefetched
+    pub(crate) fn report_yanked(&mut self, packages: &[Package], diagnostics: &mut Diagnostics) {
+        let api = RegistryApi::crates_io();
+        for pkg in packages.iter().filter(|p| p.registry_url.is_some()) {
+            let response = match self.lookup(&api, &pkg.package_name) {
+                Ok(response) => response,
+                Err(message) => {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            DiagnosticKind::CratesIoLookupFailed,
+                            format!(
+                                "Unable to look up whether '{} {}' is yanked: {message}",
+                                pkg.package_name, pkg.package_version
+                            ),
+                        )
+                        .with_package(&pkg.package_name, &pkg.package_version),
+                    );
+                    continue;
+                },
+            };
+
+            if response.versions.iter().any(|v| v.num == pkg.package_version && v.yanked) {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        DiagnosticKind::Yanked,
+                        format!("'{} {}' was yanked from crates.io", pkg.package_name, pkg.package_version),
+                    )
+                    .with_package(&pkg.package_name, &pkg.package_version),
+                );
+            }
+        }
+    }
+
+    /// Look up the crates (by the index of their alternative registry, `None` for crates.io) concurrently ahead of
+    /// [`CratesIo::complete`], which then uses the responses. The lookups of crates.io are still spaced by the
+    /// interval of its crawler policy, but run alongside the ones of the alternative registries.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn prefetch(
+        &mut self,
+        crates: BTreeSet<(Option<String>, String)>,
+        options: &AsyncOptions,
+    ) -> Result<(), AggregateError> {
+        if crates.is_empty() {
+            return Ok(());
+        }
+
+        let http = AsyncHttp::new(&self.http, options)?;
+
+        let indices: BTreeSet<_> = crates
+            .iter()
+            .filter_map(|(index, _)| index.clone())
+            .filter(|index| !self.apis.contains_key(index))
+            .collect();
+        let resolutions: Vec<_> = indices
+            .into_iter()
+            .map(|index| {
+                let location = self.api_location(&index);
+                let http = http.clone();
+                async move {
+                    let api = match location {
+                        Err(message) => Err(message),
+                        Ok((ApiLocation::Configured(url), token)) => Ok(RegistryApi::new(url, token)),
+                        Ok((ApiLocation::IndexConfig(url), token)) => {
+                            let config = get_async::<IndexConfig>(&http, url, token.clone()).await;
+                            config.and_then(IndexConfig::api).map(|url| RegistryApi::new(url, token))
+                        },
+                    };
+                    (index, api)
+                }
+            })
+            .collect();
+        self.apis.extend(join_ordered(resolutions).await?);
+
+        let pacing = Arc::new(tokio::sync::Mutex::new(self.last_request));
+        let mut keys = vec![];
+        let mut lookups = vec![];
+        for (index, name) in crates {
+            let api = match &index {
+                None => RegistryApi::crates_io(),
+                Some(index) => match self.apis.get(index) {
+                    Some(Ok(api)) => api.clone(),
+                    // reported by `complete`
+                    _ => continue,
+                },
+            };
+
+            let pacing = index.is_none().then(|| pacing.clone());
+            let http = http.clone();
+            keys.push((api.url.clone(), name.clone()));
+            lookups.push(async move {
+                if let Some(pacing) = pacing {
+                    let mut last_request = pacing.lock().await;
+                    if let Some(elapsed) = last_request.map(|last| last.elapsed()) {
+                        if elapsed < REQUEST_INTERVAL {
+                            tokio::time::sleep(REQUEST_INTERVAL - elapsed).await;
+                        }
+                    }
+                    *last_request = Some(Instant::now());
+                }
+
+                get_async::<CrateResponse>(&http, format!("{}/api/v1/crates/{name}", api.url), api.token).await
+            });
+        }
+
+        self.responses.extend(keys.into_iter().zip(join_ordered(lookups).await?));
+        self.last_request = *pacing.lock().await;
+        Ok(())
+    }
+
+    /// The API of the alternative registry with the given index, either configured or from the `config.json` of
+    /// a sparse index
+    fn registry_api(&mut self, index: &str) -> Result<RegistryApi, String> {
+        if let Some(api) = self.apis.get(index) {
+            return api.clone();
+        }
+
+        let api = match self.api_location(index) {
+            Err(message) => Err(message),
+            Ok((ApiLocation::Configured(url), token)) => Ok(RegistryApi::new(url, token)),
+            Ok((ApiLocation::IndexConfig(url), token)) => self
+                .get::<IndexConfig>(&url, token.as_deref())
+                .map_err(|e| error_chain(&e))
+                .and_then(IndexConfig::api)
+                .map(|url| RegistryApi::new(url, token)),
+        };
+
+        self.apis.insert(index.to_owned(), api.clone());
+        api
+    }
+
+    /// Where the API of the alternative registry with the given index is declared, and its token
+    fn api_location(&self, index: &str) -> Result<(ApiLocation, Option<String>), String> {
+        let registry = self
+            .registries
+            .iter()
+            .find(|r| r.has_index(index))
+            .ok_or_else(|| "the registry is not configured".to_owned())?;
+        let token = registry.token_env.as_ref().and_then(|var| std::env::var(var).ok());
+
+        let location = match (&registry.api, index.strip_prefix("sparse+")) {
+            (Some(api), _) => ApiLocation::Configured(api.clone()),
+            (None, Some(sparse)) => ApiLocation::IndexConfig(format!("{}/config.json", sparse.trim_end_matches('/'))),
+            (None, None) => return Err("the registry has no configured API".to_owned()),
+        };
+
+        Ok((location, token))
+    }
+
+    fn lookup(&mut self, api: &RegistryApi, name: &str) -> Result<CrateResponse, String> {
+        if let Some(response) = self.responses.get(&(api.url.clone(), name.to_owned())) {
+            return response.clone();
+        }
+
+        if let Some(elapsed) = self.last_request.map(|last| last.elapsed()) {
+            if elapsed < REQUEST_INTERVAL {
+                std::thread::sleep(REQUEST_INTERVAL - elapsed);
+            }
+        }
+
+        self.last_request = Some(Instant::now());
+        let response =
+            self.get(&format!("{}/api/v1/crates/{name}", api.url), api.token.as_deref()).map_err(|e| error_chain(&e));
+        self.responses.insert((api.url.clone(), name.to_owned()), response.clone());
+        response
+    }
+
+    fn get<T: for<'de> Deserialize<'de>>(&mut self, url: &str, token: Option<&str>) -> Result<T, AggregateError> {
+        let client = match &self.client {
+            Some(client) => client,
+            None => self.client.insert(self.http.build_client()?),
+        };
+
+        let mut request = client.get(url);
+        if let Some(token) = token {
+            request = request.header("Authorization", token);
+        }
+
+        let body = self.http.send(request)?.error_for_status()?.text()?;
+        serde_json::from_str(&body).map_err(AggregateError::other)
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn get_async<T: for<'de> Deserialize<'de>>(
+    http: &AsyncHttp,
+    url: String,
+    token: Option<String>,
+) -> Result<T, String> {
+    let mut request = http.client().get(url);
+    if let Some(token) = token {
+        request = request.header("Authorization", token);
+    }
+
+    let body = http.text(request).await.map_err(|e| error_chain(&e))?;
+    serde_json::from_str(&body).map_err(|e| error_chain(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yanked_versions() {
+        // abridged from `https://crates.io/api/v1/crates/openssl-src`
+        let response = r#"{
+            "crate": {"id": "openssl-src", "repository": "https://github.com/alexcrichton/openssl-src-rs"},
+            "versions": [
+                {"num": "300.0.0", "license": "MIT/Apache-2.0", "yanked": true},
+                {"num": "111.0.0", "license": "MIT/Apache-2.0", "yanked": false}
+            ]
+        }"#;
+        let mut crates_io = CratesIo::new(HttpOptions::new(), Vec::new());
+        let api = RegistryApi::crates_io();
+        crates_io
+            .responses
+            .insert((api.url.clone(), "openssl-src".to_owned()), Ok(serde_json::from_str(response).unwrap()));
+        crates_io.responses.insert((api.url, "missing".to_owned()), Err("404 Not Found".to_owned()));
+
+        let package = |name: &str, version: &str, registry_url: bool| {
+            let mut p: Package = serde_json::from_value(serde_json::json!({
+                "package_name": name,
+                "package_version": version,
+                "package_url": null,
+                "license_spdx": "MIT",
+                "license_files": [],
+            }))
+            .unwrap();
+            p.registry_url = registry_url.then(|| format!("https://crates.io/crates/{name}/{version}"));
+            p
+        };
+        let packages = [
+            package("openssl-src", "300.0.0", true),
+            package("openssl-src", "111.0.0", true),
+            package("missing", "1.0.0", true),
+            // not a crate of crates.io, not looked up
+            package("thirdparty", "1.0.0", false),
+        ];
+
+        let mut diagnostics = Diagnostics::new();
+        crates_io.report_yanked(&packages, &mut diagnostics);
+        let reported: Vec<_> = diagnostics.iter().map(|d| (d.kind, d.package_name.as_deref().unwrap())).collect();
+        assert_eq!(
+            reported,
+            [(DiagnosticKind::Yanked, "openssl-src"), (DiagnosticKind::CratesIoLookupFailed, "missing")]
+        );
+        assert_eq!(diagnostics.iter().next().unwrap().message, "'openssl-src 300.0.0' was yanked from crates.io");
+    }
+}
@@ -0,0 +1,293 @@
+use crate::{
+    analysis::canonical_expression, changes::compare_versions, Diagnostic, DiagnosticKind, Diagnostics, Package,
+    Severity,
+};
+use serde::{ser::SerializeSeq, Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+
+/// Packages whose license texts are stored only once in a table, keyed by the hex encoded SHA-256 of the text.
+///
+/// The license files reference their text via `text_ref` and have an empty `text`, which is left out when serializing.
+/// Most crates ship one of a few standard license texts, so this shrinks the output of big graphs considerably.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DedupedPackages {
+    #[serde(serialize_with = "serialize_referencing")]
+    pub packages: Vec<Package>,
+    pub texts: BTreeMap<String, String>,
+}
+
+impl DedupedPackages {
+    /// Move all license texts of the packages into the text table
+    pub fn new(mut packages: Vec<Package>) -> Self {
+        let mut texts = BTreeMap::new();
+
+        for l in packages.iter_mut().flat_map(|p| p.license_files.iter_mut()) {
+            if l.text_ref.is_some() {
+                continue;
+            }
+
+            let text = std::mem::take(&mut l.text);
+            let key = format!("{:x}", Sha256::digest(text.as_bytes()));
+            texts.entry(key.clone()).or_insert(text);
+            l.text_ref = Some(key);
+        }
+
+        Self { packages, texts }
+    }
+
+    /// Put the texts back into the license files, references to unknown texts are left as they are
+    pub fn into_packages(self) -> Vec<Package> {
+        let Self { mut packages, texts } = self;
+
+        for l in packages.iter_mut().flat_map(|p| p.license_files.iter_mut()) {
+            if let Some(text) = l.text_ref.as_ref().and_then(|key| texts.get(key)) {
+                l.text = text.clone();
+                l.text_ref = None;
+            }
+        }
+
+        packages
+    }
+}
+
+/// Serialize the packages without the empty `text` of the license files referencing the text table
+fn serialize_referencing<S: Serializer>(packages: &[Package], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(packages.len()))?;
+    for p in packages {
+        let mut package = serde_json::to_value(p).map_err(serde::ser::Error::custom)?;
+        let files = package.get_mut("license_files").and_then(|f| f.as_array_mut()).into_iter().flatten();
+        for file in files.filter_map(|f| f.as_object_mut()).filter(|f| f.contains_key("text_ref")) {
+            if file.get("text").and_then(|t| t.as_str()).is_some_and(str::is_empty) {
+                file.remove("text");
+            }
+        }
+        seq.serialize_element(&package)?;
+    }
+    seq.end()
+}
+
+impl From<Vec<Package>> for DedupedPackages {
+    fn from(packages: Vec<Package>) -> Self {
+        Self::new(packages)
+    }
+}
+
+/// Packages grouped by their license, the shape of notice documents: the keys are the canonical license expressions
+/// (see [`canonical_expression`]), packages whose license is unknown are grouped under [`GroupedPackages::UNKNOWN`]
+///
+/// # Example
+/// `{ "Apache-2.0 OR MIT": [<serde>, <rand>], "BSD-3-Clause": [<subtle>] }`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct GroupedPackages {
+    pub licenses: BTreeMap<String, Vec<Package>>,
+}
+
+impl GroupedPackages {
+    /// The key of the packages without a known license
+    pub const UNKNOWN: &'static str = "NOASSERTION";
+
+    pub fn new(packages: Vec<Package>) -> Self {
+        let mut licenses: BTreeMap<String, Vec<Package>> = BTreeMap::new();
+        for p in packages {
+            let license = match &p.license_spdx {
+                Some(license) => canonical_expression(license).to_string(),
+                None => Self::UNKNOWN.to_owned(),
+            };
+            licenses.entry(license).or_default().push(p);
+        }
+
+        Self { licenses }
+    }
+
+    /// The flat list of the packages, ordered by license
+    pub fn into_packages(self) -> Vec<Package> {
+        self.licenses.into_values().flatten().collect()
+    }
+}
+
+impl From<Vec<Package>> for GroupedPackages {
+    fn from(packages: Vec<Package>) -> Self {
+        Self::new(packages)
+    }
+}
+
+/// What happens with packages that are included in several versions, e.g. `syn 1.x` and `syn 2.x`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateVersions {
+    /// Keep one entry per version
+    #[default]
+    Keep,
+    /// Keep one entry per version, but list all versions in [`Package::versions`] of every entry
+    Annotate,
+    /// Merge the versions into one entry of the highest version, if their licenses are the same, and list them in
+    /// [`Package::versions`]. Versions with different licenses are annotated instead.
+    Collapse,
+}
+
+/// Annotate or merge the packages that are included in several versions (see [`DuplicateVersions`]),
+/// every such package is reported as [`DiagnosticKind::DuplicateVersions`]
+pub fn merge_versions(packages: Vec<Package>, mode: DuplicateVersions, diagnostics: &mut Diagnostics) -> Vec<Package> {
+    if mode == DuplicateVersions::Keep {
+        return packages;
+    }
+
+    let mut by_name: BTreeMap<String, Vec<Package>> = BTreeMap::new();
+    for p in packages {
+        by_name.entry(p.package_name.clone()).or_default().push(p);
+    }
+
+    let mut merged = vec![];
+    for (name, mut versions) in by_name {
+        if versions.len() < 2 {
+            merged.extend(versions);
+            continue;
+        }
+
+        versions.sort_by(|a, b| compare_versions(&a.package_version, &b.package_version));
+        let version_list: Vec<String> = versions.iter().map(|p| p.package_version.clone()).collect();
+
+        let license = |p: &Package| p.license_spdx.as_ref().map(|l| canonical_expression(l).to_string());
+        let same_license = versions.iter().all(|p| license(p) == license(&versions[0]));
+        let collapse = mode == DuplicateVersions::Collapse && same_license;
+
+        let message = if collapse {
+            format!("'{name}' is included in the versions {}, which were merged", version_list.join(", "))
+        } else if same_license {
+            format!("'{name}' is included in the versions {}", version_list.join(", "))
+        } else {
+            format!("'{name}' is included in the versions {} under different licenses", version_list.join(", "))
+        };
+        let highest = &version_list[version_list.len() - 1];
+        diagnostics.push(
+            Diagnostic::new(Severity::Info, DiagnosticKind::DuplicateVersions, message).with_package(&name, highest),
+        );
+
+        if collapse {
+            merged.push(collapse_versions(versions, version_list));
+        } else {
+            merged.extend(versions.into_iter().map(|p| Package { versions: version_list.clone(), ..p }));
+        }
+    }
+
+    merged
+}
+
+/// Merge the versions (in ascending order) into the highest one, keeping every distinct license file and copyright
+fn collapse_versions(mut versions: Vec<Package>, version_list: Vec<String>) -> Package {
+    let mut package = versions.pop().expect("at least two versions");
+
+    let mut files: HashSet<(String, String)> =
+        package.license_files.iter().map(|l| (l.name.clone(), l.text.clone())).collect();
+    for p in versions.into_iter().rev() {
+        for l in p.license_files {
+            if files.insert((l.name.clone(), l.text.clone())) {
+                package.license_files.push(l);
+            }
+        }
+
+        for copyright in p.copyrights {
+            if !package.copyrights.contains(&copyright) {
+                package.copyrights.push(copyright);
+            }
+        }
+    }
+
+    package.versions = version_list;
+    package
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texts_serialized_unless_referenced() {
+        let json = r#"[{"package_name": "a", "package_version": "1.0.0", "package_url": null, "license_spdx": null,
+            "license_files": [{"name": "LICENSE", "spdx": null, "text": "license"},
+                {"name": "NOTICE", "spdx": null}]}]"#;
+        let packages: Vec<Package> = serde_json::from_str(json).unwrap();
+        let files = serde_json::to_value(&packages).unwrap()[0]["license_files"].clone();
+        assert_eq!(files[1]["text"], "", "empty texts are kept when not referencing the text table");
+
+        let deduped = serde_json::to_value(DedupedPackages::new(packages)).unwrap();
+        let files = deduped["packages"][0]["license_files"].as_array().unwrap();
+        assert!(files.iter().all(|f| f.get("text").is_none() && f.get("text_ref").is_some()));
+
+        let deduped: DedupedPackages = serde_json::from_value(deduped).unwrap();
+        let texts: Vec<_> = deduped.into_packages()[0].license_files.iter().map(|f| f.text.clone()).collect();
+        assert_eq!(texts, ["license", ""]);
+    }
+
+    fn package(version: &str, license: &str, file: &str, copyright: &str) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "package_name": "syn",
+            "package_version": version,
+            "package_url": null,
+            "license_spdx": license,
+            "license_files": [{"name": "LICENSE", "spdx": null, "text": file}],
+            "copyrights": [copyright],
+        }))
+        .unwrap()
+    }
+
+    fn versions() -> Vec<Package> {
+        vec![
+            package("2.0.0", "MIT OR Apache-2.0", "new", "David Tolnay"),
+            package("1.0.109", "Apache-2.0 OR MIT", "old", "David Tolnay"),
+            package("2.0.0-rc.1", "MIT OR Apache-2.0", "new", "dtolnay"),
+        ]
+    }
+
+    #[test]
+    fn keep_versions() {
+        let mut diagnostics = Diagnostics::new();
+        let merged = merge_versions(versions(), DuplicateVersions::Keep, &mut diagnostics);
+        assert_eq!(merged.len(), 3);
+        assert!(merged.iter().all(|p| p.versions.is_empty()));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn annotate_versions() {
+        let mut diagnostics = Diagnostics::new();
+        let merged = merge_versions(versions(), DuplicateVersions::Annotate, &mut diagnostics);
+        let ordered: Vec<_> = merged.iter().map(|p| p.package_version.as_str()).collect();
+        assert_eq!(ordered, ["1.0.109", "2.0.0-rc.1", "2.0.0"]);
+        assert!(merged.iter().all(|p| p.versions == ordered));
+
+        let reported: Vec<_> = diagnostics.iter().collect();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].kind, DiagnosticKind::DuplicateVersions);
+        assert_eq!(reported[0].package_version.as_deref(), Some("2.0.0"));
+        assert_eq!(reported[0].message, "'syn' is included in the versions 1.0.109, 2.0.0-rc.1, 2.0.0");
+    }
+
+    #[test]
+    fn collapse_same_licenses() {
+        let mut diagnostics = Diagnostics::new();
+        let mut packages = versions();
+        packages.push(Package { package_name: "quote".into(), ..package("1.0.0", "MIT", "quote", "") });
+        let merged = merge_versions(packages, DuplicateVersions::Collapse, &mut diagnostics);
+        assert_eq!(merged.len(), 2);
+
+        let syn = merged.iter().find(|p| p.package_name == "syn").unwrap();
+        assert_eq!(syn.package_version, "2.0.0");
+        assert_eq!(syn.versions, ["1.0.109", "2.0.0-rc.1", "2.0.0"]);
+        let texts: Vec<_> = syn.license_files.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, ["new", "old"], "every distinct license file is kept");
+        assert_eq!(syn.copyrights, ["David Tolnay", "dtolnay"]);
+        assert!(diagnostics.iter().next().unwrap().message.ends_with("which were merged"));
+
+        // annotated instead if the licenses differ
+        let mut diagnostics = Diagnostics::new();
+        let mut packages = versions();
+        packages.push(package("0.15.44", "MIT", "older", ""));
+        let merged = merge_versions(packages, DuplicateVersions::Collapse, &mut diagnostics);
+        assert_eq!(merged.len(), 4);
+        assert!(merged.iter().all(|p| p.versions.len() == 4));
+        assert!(diagnostics.iter().next().unwrap().message.ends_with("under different licenses"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Import of the license configuration of cargo-deny, so both tools share one allow list
+
+use crate::{policy::PolicyException, AggregateError, AggregatorConfig, Utf8Path};
+use cargo_about::licenses::config::KrateConfig;
+use serde::Deserialize;
+
+/// The `[licenses]` section of a cargo-deny configuration (`deny.toml`), other sections are ignored
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DenyLicenses {
+    /// The allowed licenses, which become the accepted licenses
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// The denied licenses (of older cargo-deny versions), which become denied licenses of the policy
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Licenses that are only allowed for individual crates
+    #[serde(default)]
+    pub exceptions: Vec<DenyException>,
+    /// The minimum confidence of the detection of license files
+    pub confidence_threshold: Option<f32>,
+}
+
+/// An entry of `exceptions` of the `[licenses]` section of cargo-deny
+#[derive(Clone, Debug, Deserialize)]
+pub struct DenyException {
+    /// Name of the crate, `name` in older cargo-deny versions
+    #[serde(rename = "crate", alias = "name")]
+    pub krate: String,
+    /// The licenses that are allowed for the crate
+    pub allow: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct DenyToml {
+    #[serde(default)]
+    licenses: DenyLicenses,
+}
+
+/// Load the `[licenses]` section of a cargo-deny configuration
+pub fn load_deny_config<P: AsRef<Utf8Path>>(path: P) -> Result<DenyLicenses, AggregateError> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .map_err(|source| AggregateError::ConfigRead { path: path.to_owned(), source })?;
+
+    let deny: DenyToml =
+        toml::from_str(&content).map_err(|source| AggregateError::ConfigParse { path: path.to_owned(), source })?;
+    Ok(deny.licenses)
+}
+
+impl DenyLicenses {
+    /// Merge the licenses into the configuration:
+    /// - `allow` is appended to `accepted` (licenses of the configuration keep their priority),
+    /// - `deny` is appended to the denied licenses of the policy,
+    /// - `exceptions` become accepted licenses of the crates and exceptions of the policy,
+    /// - `confidence-threshold` is used for license files of crates, unless the configuration has its own.
+    ///
+    /// Fails if a license is not a valid SPDX identifier.
+    pub fn apply(&self, config: &mut AggregatorConfig) -> Result<(), AggregateError> {
+        for license in parse_licensees(&self.allow)? {
+            if !config.about.accepted.contains(&license) {
+                config.about.accepted.push(license);
+            }
+        }
+
+        // validates the identifiers, the policy parses them again on evaluation
+        parse_licensees(&self.deny)?;
+        config.policy.denied_licenses.extend(self.deny.iter().cloned());
+
+        for exception in &self.exceptions {
+            let krate = config
+                .about
+                .crates
+                .entry(exception.krate.clone())
+                .or_insert_with(|| KrateConfig { accepted: Vec::new(), clarify: None });
+            krate.accepted.extend(parse_licensees(&exception.allow)?);
+
+            config.policy.exceptions.push(PolicyException {
+                package: exception.krate.clone(),
+                licenses: exception.allow.clone(),
+                justification: "exception of the cargo-deny configuration".to_owned(),
+                expires: None,
+            });
+        }
+
+        if config.detection.rust_threshold.is_none() {
+            config.detection.rust_threshold = self.confidence_threshold;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_licensees(licenses: &[String]) -> Result<Vec<spdx::Licensee>, AggregateError> {
+    licenses
+        .iter()
+        .map(|l| {
+            spdx::Licensee::parse(l).map_err(|source| AggregateError::SpdxParse { expression: l.clone(), source })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deny(toml: &str) -> DenyLicenses {
+        toml::from_str::<DenyToml>(toml).unwrap().licenses
+    }
+
+    fn licensees(licenses: &[spdx::Licensee]) -> Vec<String> {
+        licenses.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn apply() {
+        let licenses = deny(
+            r#"
+            [advisories]
+            yanked = "deny"
+
+            [licenses]
+            allow = ["MIT", "Apache-2.0", "ISC"]
+            deny = ["GPL-3.0"]
+            confidence-threshold = 0.93
+            exceptions = [
+                { crate = "ring", allow = ["OpenSSL"] },
+                { name = "unicode-ident", allow = ["Unicode-DFS-2016"] },
+            ]
+            "#,
+        );
+
+        let mut config: AggregatorConfig = toml::from_str(
+            r#"
+            accepted = ["Apache-2.0", "BSD-3-Clause"]
+
+            [detection]
+            rust-threshold = 0.8
+
+            [ring]
+            accepted = ["ISC"]
+            "#,
+        )
+        .unwrap();
+        licenses.apply(&mut config).unwrap();
+
+        assert_eq!(licensees(&config.about.accepted), ["Apache-2.0", "BSD-3-Clause", "MIT", "ISC"]);
+        assert_eq!(config.policy.denied_licenses, ["GPL-3.0"]);
+        assert_eq!(licensees(&config.about.crates["ring"].accepted), ["ISC", "OpenSSL"]);
+        assert_eq!(licensees(&config.about.crates["unicode-ident"].accepted), ["Unicode-DFS-2016"]);
+
+        let exceptions: Vec<_> =
+            config.policy.exceptions.iter().map(|e| (e.package.as_str(), e.licenses.clone())).collect();
+        assert_eq!(
+            exceptions,
+            [("ring", vec!["OpenSSL".to_owned()]), ("unicode-ident", vec!["Unicode-DFS-2016".to_owned()])]
+        );
+        assert_eq!(config.detection.rust_threshold, Some(0.8), "the threshold of the configuration has priority");
+
+        let mut config = AggregatorConfig::default();
+        licenses.apply(&mut config).unwrap();
+        assert_eq!(config.detection.rust_threshold, Some(0.93));
+    }
+
+    #[test]
+    fn invalid_licenses() {
+        for toml in ["[licenses]\nallow = [\"MIT\", \"not a license\"]", "[licenses]\ndeny = [\"GPL-3.0 OR\"]"] {
+            let result = deny(toml).apply(&mut AggregatorConfig::default());
+            assert!(matches!(result, Err(AggregateError::SpdxParse { .. })), "{toml}");
+        }
+
+        let exception = "[licenses]\nexceptions = [{ crate = \"ring\", allow = [\"Open SSL\"] }]";
+        assert!(deny(exception).apply(&mut AggregatorConfig::default()).is_err());
+    }
+
+    #[test]
+    fn load() {
+        let dir = crate::Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let path = dir.join(format!("tla-deny-{}.toml", std::process::id()));
+        std::fs::write(&path, "[bans]\nmultiple-versions = \"warn\"\n").unwrap();
+        let licenses = load_deny_config(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(licenses.allow.is_empty() && licenses.exceptions.is_empty());
+
+        assert!(matches!(load_deny_config(&path), Err(AggregateError::ConfigRead { .. })));
+    }
+}
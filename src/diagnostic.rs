@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The class of anomaly a [`Diagnostic`] reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiagnosticKind {
+    /// The crate's license could not be determined
+    UnknownLicense,
+    /// The unknown license of a crate was replaced by a configured or detected one
+    UnknownLicenseResolved,
+    /// The package has copyright notices but grants no license, see [`crate::analysis::detect_unlicensed`]
+    Unlicensed,
+    /// The license of the crate was replaced by an override of the configuration
+    LicenseOverridden,
+    /// The license of a dependency changed with a version bump since the previous run
+    LicenseChanged,
+    /// No combined SPDX expression is available for the package
+    MissingPackageLicense,
+    /// No license files could be found for the package
+    NoLicenseFiles,
+    /// A license file could not be read
+    UnreadableLicenseFile,
+    /// A license file was not UTF-8 and its text was converted, see [`crate::LicenseFile::encoding`]
+    ConvertedLicenseFile,
+    /// The number of licenses in the package SPDX expression differs from the number found in the license files
+    LicenseCountMismatch,
+    /// A git clarification was configured for a package that is not a crate
+    UnsupportedGitClarification,
+    /// The checksum of a clarified license file does not match
+    ClarificationChecksumMismatch,
+    /// The license store detected the license of a file only with low confidence
+    LowConfidence,
+    /// The license store yielded an identifier that is not a valid SPDX expression
+    InvalidDetectedLicense,
+    /// The detected license of a file has nothing in common with the package license
+    FileLicenseMismatch,
+    /// The license of a file could not be detected confidently and has to be determined manually
+    NeedsReview,
+    /// A license file containing several license texts was split into one file per license
+    SplitLicenseFile,
+    /// The gatherer found no license files, but searching the package directory did
+    HeuristicLicenseFiles,
+    /// The only statement of the license was found in the license section of a README
+    ReadmeLicense,
+    /// The canonical SPDX text was substituted for a missing license file
+    SynthesizedLicenseText,
+    /// Missing license files were fetched from the repository of the package
+    FetchedLicenseFiles,
+    /// Missing license files could not be fetched from the repository of the package
+    RepositoryFetchFailed,
+    /// The license of the package was taken from clearlydefined.io
+    ClearlyDefinedLicense,
+    /// The license declared by clearlydefined.io differs from the license of the package
+    ClearlyDefinedMismatch,
+    /// The license of the crate was taken from crates.io, since its manifest has none
+    CratesIoLicense,
+    /// The license of the crate differs from the license on crates.io
+    CratesIoLicenseMismatch,
+    /// The metadata of the crate could not be looked up on crates.io
+    CratesIoLookupFailed,
+    /// The license requirements of a package could not be minimized, so all of its license files are kept
+    MinimizationFailed,
+    /// Accepting a license is needed to minimize the requirements of packages that failed minimization
+    AcceptedSuggestion,
+    /// A license file was removed by the minimization, since its license is not required
+    RemovedByMinimization,
+    /// The package violates the license policy, see [`crate::policy::check_policy`]
+    PolicyViolation,
+    /// The license of the package is not approved, see [`crate::approvals::Approvals::review`]
+    NotApproved,
+    /// An exception of the license policy that a package depends on expires soon
+    ExpiringPolicyException,
+    /// The license of a dependency is incompatible with the license of the project
+    IncompatibleLicense,
+    /// The source archive of a package that has to be offered in source form is missing, see
+    /// [`crate::export_source_offer`]
+    MissingSourceArchive,
+    /// A generated attribution does not reproduce the `NOTICE` file of an Apache-2.0 package, see
+    /// [`crate::obligations::verify_notices`]
+    NoticeNotReproduced,
+    /// The package is affected by an advisory, e.g. of the RustSec advisory database
+    Advisory,
+    /// The version of the crate was yanked from crates.io, see [`crate::Osv::yanked`]
+    Yanked,
+    /// An entry of the configuration matched nothing in the run
+    UnusedConfigEntry,
+    /// The crate links native code, whose bundled sources have to be reviewed (e.g. with a thirdparty file)
+    NativeCode,
+    /// The SPDX header of a source file declares a license that is not part of the package license
+    SourceFileLicenseMismatch,
+    /// Several versions of a package are included, see [`crate::DuplicateVersions`]
+    DuplicateVersions,
+    /// The manifest had no Cargo.lock, so one was written, see [`crate::GatherOptions::allow_lockfile_generation`]
+    GeneratedLockfile,
+}
+
+impl DiagnosticKind {
+    /// What can be done about the anomaly, e.g. which section of the configuration resolves it
+    pub fn remediation(self) -> &'static str {
+        match self {
+            DiagnosticKind::UnknownLicense => {
+                "declare the license in `[[overrides]]` or `[unknown-license]`, or ask upstream to add it"
+            },
+            DiagnosticKind::UnknownLicenseResolved => "check that the resolved license is correct",
+            DiagnosticKind::Unlicensed => "ask the authors for a license or remove the dependency",
+            DiagnosticKind::LicenseOverridden => "remove the override once upstream declares the license",
+            DiagnosticKind::LicenseChanged => "review the obligations of the new license before accepting the update",
+            DiagnosticKind::MissingPackageLicense => "declare the license of the package in `[[overrides]]`",
+            DiagnosticKind::NoLicenseFiles => {
+                "add the license text with `[[overrides]]` (`license-file`) or fetch it from the repository"
+            },
+            DiagnosticKind::UnreadableLicenseFile => "check the permissions and the encoding of the file",
+            DiagnosticKind::ConvertedLicenseFile => "check that the converted text is legible",
+            DiagnosticKind::LicenseCountMismatch => {
+                "check whether license files are missing or superfluous, e.g. with `ignore-license-files`"
+            },
+            DiagnosticKind::UnsupportedGitClarification => {
+                "use a file clarification for packages that are not crates"
+            },
+            DiagnosticKind::ClarificationChecksumMismatch => {
+                "review the changed file and update the clarified checksum"
+            },
+            DiagnosticKind::LowConfidence | DiagnosticKind::NeedsReview => {
+                "review the text manually and clarify its license, e.g. with `[[overrides]]`"
+            },
+            DiagnosticKind::InvalidDetectedLicense => "add the license to `[[custom-licenses]]` or override it",
+            DiagnosticKind::FileLicenseMismatch | DiagnosticKind::SourceFileLicenseMismatch => {
+                "check whether the package license is incomplete and override it if needed"
+            },
+            DiagnosticKind::SplitLicenseFile => "check that the license texts were split correctly",
+            DiagnosticKind::HeuristicLicenseFiles
+            | DiagnosticKind::ReadmeLicense
+            | DiagnosticKind::SynthesizedLicenseText
+            | DiagnosticKind::FetchedLicenseFiles => "check that the license texts belong to the package",
+            DiagnosticKind::RepositoryFetchFailed => "add the license text with `[[overrides]]` (`license-file`)",
+            DiagnosticKind::ClearlyDefinedLicense | DiagnosticKind::CratesIoLicense => {
+                "check the license and declare it in `[[overrides]]` to not depend on the remote lookup"
+            },
+            DiagnosticKind::ClearlyDefinedMismatch | DiagnosticKind::CratesIoLicenseMismatch => {
+                "check which of the licenses is correct"
+            },
+            DiagnosticKind::CratesIoLookupFailed => "retry with network access or run offline",
+            DiagnosticKind::MinimizationFailed | DiagnosticKind::AcceptedSuggestion => {
+                "accept one of the licenses of the package in `accepted` or for the crate"
+            },
+            DiagnosticKind::RemovedByMinimization => "nothing to do, the license is not required",
+            DiagnosticKind::PolicyViolation => "replace the dependency or add an exception to `[policy]`",
+            DiagnosticKind::NotApproved => "review the license and approve it, e.g. with `Approvals::approve`",
+            DiagnosticKind::ExpiringPolicyException => "renew the exception or replace the dependency",
+            DiagnosticKind::IncompatibleLicense => "replace the dependency or relicense the project",
+            DiagnosticKind::MissingSourceArchive => "offer the sources of the package separately",
+            DiagnosticKind::NoticeNotReproduced => "regenerate the attribution, it has to contain the complete NOTICE",
+            DiagnosticKind::Advisory => "update the package to a version that is not affected",
+            DiagnosticKind::Yanked => "update the package to a version that is not yanked",
+            DiagnosticKind::UnusedConfigEntry => "remove the entry from the configuration",
+            DiagnosticKind::NativeCode => {
+                "review the licenses of the bundled native sources, e.g. with a thirdparty file"
+            },
+            DiagnosticKind::DuplicateVersions => "update the dependents to a common version",
+            DiagnosticKind::GeneratedLockfile => "commit the Cargo.lock, so every run resolves the same dependencies",
+        }
+    }
+}
+
+/// An anomaly found while collecting or processing licenses
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    /// Name of the affected package, if any
+    pub package_name: Option<String>,
+    /// Version of the affected package, if any
+    pub package_version: Option<String>,
+    /// Name of the affected license file, if any
+    pub file: Option<String>,
+    /// Human readable description
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new<M: Into<String>>(severity: Severity, kind: DiagnosticKind, message: M) -> Self {
+        Self { severity, kind, package_name: None, package_version: None, file: None, message: message.into() }
+    }
+
+    pub fn warning<M: Into<String>>(kind: DiagnosticKind, message: M) -> Self {
+        Self::new(Severity::Warning, kind, message)
+    }
+
+    pub fn with_package<N: Into<String>, V: Display>(mut self, name: N, version: V) -> Self {
+        self.package_name = Some(name.into());
+        self.package_version = Some(version.to_string());
+        self
+    }
+
+    pub fn with_file<F: Into<String>>(mut self, file: F) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Sink for the [`Diagnostic`]s emitted by the library functions.
+///
+/// Every diagnostic is also logged via `tracing` at the matching level.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        match diagnostic.severity {
+            Severity::Info => tracing::info!("{}", diagnostic.message),
+            Severity::Warning => tracing::warn!("{}", diagnostic.message),
+            Severity::Error => tracing::error!("{}", diagnostic.message),
+        }
+
+        self.0.push(diagnostic);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.0.iter()
+    }
+
+    /// The license files that have to be reviewed manually
+    pub fn needs_review(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter().filter(|d| d.kind == DiagnosticKind::NeedsReview)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether any diagnostic has at least the given severity
+    pub fn any_at_least(&self, severity: Severity) -> bool {
+        self.0.iter().any(|d| d.severity >= severity)
+    }
+
+    pub fn into_vec(self) -> Vec<Diagnostic> {
+        self.0
+    }
+}
+
+/// Adds diagnostics that were already reported (e.g. the ones of another run) without logging them again, unlike
+/// [`Diagnostics::push`]
+impl Extend<Diagnostic> for Diagnostics {
+    fn extend<T: IntoIterator<Item = Diagnostic>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+impl<'a> IntoIterator for &'a Diagnostics {
+    type Item = &'a Diagnostic;
+    type IntoIter = std::slice::Iter<'a, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
@@ -0,0 +1,141 @@
+//! Overrides of the configuration by `TLA_*` environment variables, see [`EnvOverrides`]
+
+use crate::{AggregateError, Utf8PathBuf};
+
+/// The accepted licenses, separated by commas or whitespace, e.g. `TLA_ACCEPTED="MIT, Apache-2.0"`
+pub const ENV_ACCEPTED: &str = "TLA_ACCEPTED";
+/// The minimum score of licenses detected in the texts of thirdparty packages, like `threshold` of the `[detection]`
+/// section (see [`crate::analysis::DetectionOptions::threshold`])
+pub const ENV_CONFIDENCE_THRESHOLD: &str = "TLA_CONFIDENCE_THRESHOLD";
+/// The minimum score for files in the sources of rust crates to be considered license files, like `rust-threshold` of
+/// the `[detection]` section (see [`crate::GatherOptions::confidence_threshold`])
+pub const ENV_RUST_CONFIDENCE_THRESHOLD: &str = "TLA_RUST_CONFIDENCE_THRESHOLD";
+/// Whether cargo and the remote lookups must not access the network, `true`/`1` or `false`/`0`
+pub const ENV_OFFLINE: &str = "TLA_OFFLINE";
+/// The file the packages are written to as JSON, see [`crate::Aggregator::output`]
+pub const ENV_OUTPUT: &str = "TLA_OUTPUT";
+
+/// The values of the `TLA_*` environment variables, which replace the ones of the configuration and of the
+/// [`crate::Aggregator`], so CI pipelines can tweak a run without changing the configuration file.
+/// Unset and empty variables are ignored.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EnvOverrides {
+    pub(crate) accepted: Option<Vec<spdx::Licensee>>,
+    pub(crate) confidence_threshold: Option<f32>,
+    pub(crate) rust_confidence_threshold: Option<f32>,
+    pub(crate) offline: Option<bool>,
+    pub(crate) output: Option<Utf8PathBuf>,
+}
+
+impl EnvOverrides {
+    /// Read the variables of the environment, fails on values that cannot be parsed
+    pub(crate) fn from_env() -> Result<Self, AggregateError> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Read the variables with the given lookup of their values, see [`EnvOverrides::from_env`]
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, AggregateError> {
+        let var = |name: &str| lookup(name).map(|value| value.trim().to_owned()).filter(|value| !value.is_empty());
+        let invalid =
+            |variable: &str, reason: String| AggregateError::Environment { variable: variable.to_owned(), reason };
+
+        let accepted = var(ENV_ACCEPTED)
+            .map(|accepted| {
+                accepted
+                    .split([',', ' ', '\t', '\n'])
+                    .filter(|l| !l.is_empty())
+                    .map(|l| spdx::Licensee::parse(l).map_err(|e| invalid(ENV_ACCEPTED, format!("'{l}': {e}"))))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
+        let score = |variable: &str| {
+            var(variable)
+                .map(|threshold| {
+                    let score = threshold.parse::<f32>().ok().filter(|t| (0.0..=1.0).contains(t));
+                    let reason = || format!("'{threshold}' is no score between 0 and 1");
+                    score.ok_or_else(|| invalid(variable, reason()))
+                })
+                .transpose()
+        };
+        let confidence_threshold = score(ENV_CONFIDENCE_THRESHOLD)?;
+        let rust_confidence_threshold = score(ENV_RUST_CONFIDENCE_THRESHOLD)?;
+
+        let offline = var(ENV_OFFLINE)
+            .map(|offline| match offline.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => Ok(true),
+                "0" | "false" | "no" => Ok(false),
+                _ => Err(invalid(ENV_OFFLINE, format!("'{offline}' is neither true nor false"))),
+            })
+            .transpose()?;
+
+        Ok(Self {
+            accepted,
+            confidence_threshold,
+            rust_confidence_threshold,
+            offline,
+            output: var(ENV_OUTPUT).map(Utf8PathBuf::from),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn overrides(vars: &[(&str, &str)]) -> Result<EnvOverrides, AggregateError> {
+        let vars: HashMap<_, _> = vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        EnvOverrides::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    fn invalid_variable(result: Result<EnvOverrides, AggregateError>) -> String {
+        match result {
+            Err(AggregateError::Environment { variable, .. }) => variable,
+            other => panic!("expected an invalid variable, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn unset_and_empty() {
+        let env = overrides(&[(ENV_OUTPUT, ""), (ENV_OFFLINE, "  ")]).unwrap();
+        assert!(env.accepted.is_none() && env.offline.is_none() && env.output.is_none());
+        assert!(env.confidence_threshold.is_none() && env.rust_confidence_threshold.is_none());
+    }
+
+    #[test]
+    fn values() {
+        let env = overrides(&[
+            (ENV_ACCEPTED, " MIT, Apache-2.0\tISC\n"),
+            (ENV_CONFIDENCE_THRESHOLD, "0.9"),
+            (ENV_RUST_CONFIDENCE_THRESHOLD, "1"),
+            (ENV_OFFLINE, "TRUE"),
+            (ENV_OUTPUT, "target/licenses.json "),
+        ])
+        .unwrap();
+
+        let accepted: Vec<_> = env.accepted.unwrap().iter().map(ToString::to_string).collect();
+        assert_eq!(accepted, ["MIT", "Apache-2.0", "ISC"]);
+        assert_eq!(env.confidence_threshold, Some(0.9));
+        assert_eq!(env.rust_confidence_threshold, Some(1.0));
+        assert_eq!(env.offline, Some(true));
+        assert_eq!(env.output.as_deref(), Some(crate::Utf8Path::new("target/licenses.json")));
+
+        for (value, offline) in [("1", true), ("yes", true), ("0", false), ("false", false), ("No", false)] {
+            assert_eq!(overrides(&[(ENV_OFFLINE, value)]).unwrap().offline, Some(offline), "{value}");
+        }
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(invalid_variable(overrides(&[(ENV_ACCEPTED, "MIT, not-a-license")])), ENV_ACCEPTED);
+        assert_eq!(invalid_variable(overrides(&[(ENV_CONFIDENCE_THRESHOLD, "1.5")])), ENV_CONFIDENCE_THRESHOLD);
+        assert_eq!(invalid_variable(overrides(&[(ENV_CONFIDENCE_THRESHOLD, "high")])), ENV_CONFIDENCE_THRESHOLD);
+        let negative = overrides(&[(ENV_RUST_CONFIDENCE_THRESHOLD, "-0.1")]);
+        assert_eq!(invalid_variable(negative), ENV_RUST_CONFIDENCE_THRESHOLD);
+        assert_eq!(invalid_variable(overrides(&[(ENV_OFFLINE, "maybe")])), ENV_OFFLINE);
+
+        let message = overrides(&[(ENV_OFFLINE, "maybe")]).unwrap_err().to_string();
+        assert!(message.contains("'maybe' is neither true nor false"), "{message}");
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{ParseError, Utf8PathBuf};
+use spdx::expression::MinimizeError;
+
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The error type of all public functions of this library
+#[derive(Debug, thiserror::Error)]
+pub enum AggregateError {
+    /// The dependency graph of the manifest could not be resolved (e.g. `cargo metadata` failed)
+    #[error("Unable to resolve the dependency graph of {manifest}")]
+    ManifestResolution {
+        manifest: Utf8PathBuf,
+        #[source]
+        source: BoxedError,
+    },
+    /// The license store could not be loaded
+    #[error("Unable to load the license store")]
+    LicenseStore(#[source] BoxedError),
+    /// The configuration file could not be read
+    #[error("Unable to read config {path}")]
+    ConfigRead {
+        path: Utf8PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The configuration file is not valid
+    #[cfg(feature = "cargo")]
+    #[error("Unable to parse config {path}")]
+    ConfigParse {
+        path: Utf8PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    /// A file included by the configuration (see [`crate::load_aggregator_config`]) could not be included
+    #[cfg(feature = "cargo")]
+    #[error("Unable to include config {path}: {reason}")]
+    ConfigInclude { path: Utf8PathBuf, reason: String },
+    /// An environment variable overriding the configuration (e.g. `TLA_OFFLINE`) has an invalid value
+    #[error("Invalid value of the environment variable {variable}: {reason}")]
+    Environment { variable: String, reason: String },
+    /// A curation file of the OSS Review Toolkit is invalid, see [`crate::load_ort_curations`]
+    #[cfg(feature = "cargo")]
+    #[error("Invalid ORT curations {path}: {reason}")]
+    OrtCurations { path: Utf8PathBuf, reason: String },
+    /// A custom license of the configuration is invalid
+    #[cfg(feature = "cargo")]
+    #[error("Invalid custom license '{id}': {reason}")]
+    CustomLicense { id: String, reason: String },
+    /// A file containing license information (e.g. a license or thirdparty file) could not be read
+    #[error("Unable to read {path}")]
+    LicenseRead {
+        path: Utf8PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// An output file (e.g. a saved license store) could not be written
+    #[error("Unable to write {path}")]
+    Write {
+        path: Utf8PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// A file of an attestation could not be hashed, see [`crate::Statement`]
+    #[cfg(feature = "cargo")]
+    #[error("Unable to hash {path} for the attestation")]
+    Attestation {
+        path: Utf8PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// A generated file could not be signed, see [`crate::sign_with_minisign`]
+    #[cfg(feature = "cargo")]
+    #[error("Unable to sign {path}: {reason}")]
+    Signature { path: Utf8PathBuf, reason: String },
+    /// A string is not a valid SPDX expression
+    #[error("Unable to parse SPDX expression '{expression}'")]
+    SpdxParse {
+        expression: String,
+        #[source]
+        source: ParseError,
+    },
+    /// A thirdparty file does not contain a valid list of packages
+    #[error("Unable to parse thirdparty file {path}")]
+    ThirdpartyParse {
+        path: Utf8PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A file is not valid JSON output of ScanCode Toolkit, see [`crate::ScanCodeSource`]
+    #[cfg(feature = "cargo")]
+    #[error("Unable to parse ScanCode results {path}")]
+    ScanCodeParse {
+        path: Utf8PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A glob pattern (e.g. of ignored crates) is invalid
+    #[cfg(feature = "cargo")]
+    #[error("Invalid pattern '{pattern}'")]
+    Pattern {
+        pattern: String,
+        #[source]
+        source: globset::Error,
+    },
+    /// The license of a crate is unknown and [`crate::UnknownLicenseAction::Error`] is configured
+    #[cfg(feature = "cargo")]
+    #[error("The license of '{package}' is unknown")]
+    UnknownLicense { package: String },
+    /// More packages than allowed have an unknown license, see [`crate::Aggregator::max_unknown`]
+    #[cfg(feature = "cargo")]
+    #[error("{count} packages have an unknown license, at most {max} are allowed")]
+    TooManyUnknownLicenses { count: usize, max: usize },
+    /// The license requirements of a package could not be minimized with the accepted licenses
+    #[error("Unable to minimize requirements of '{package}' ({expression})")]
+    Minimization {
+        package: String,
+        expression: String,
+        #[source]
+        source: MinimizeError,
+    },
+    /// The HTTP client for fetching remote license information could not be created or a request failed
+    #[cfg(feature = "network")]
+    #[error("Network error")]
+    Network(#[from] reqwest::Error),
+    /// The packages violate the license policy, see [`crate::policy::check_policy`]
+    #[error("The packages violate the license policy:\n{0}")]
+    PolicyViolation(crate::policy::PolicyReport),
+    /// An attribution does not reproduce the `NOTICE` files of Apache-2.0 packages, see
+    /// [`crate::obligations::verify_notices`]
+    #[error("The attribution does not reproduce the NOTICE files of packages:\n{0}")]
+    NoticesNotReproduced(crate::obligations::NoticeReport),
+    /// The licenses of packages have not been approved, see [`crate::approvals::Approvals::review`]
+    #[cfg(feature = "cargo")]
+    #[error("The licenses of packages are not approved:\n{0}")]
+    NotApproved(crate::approvals::ApprovalReport),
+    /// The run was aborted via the cancel flag of the options
+    #[error("The aggregation was cancelled")]
+    Cancelled,
+    /// Error of a custom [`crate::LicenseSource`]
+    #[error(transparent)]
+    Other(BoxedError),
+}
+
+impl AggregateError {
+    /// Wrap an arbitrary error, e.g. from a custom [`crate::LicenseSource`]
+    pub fn other<E: Into<BoxedError>>(error: E) -> Self {
+        Self::Other(error.into())
+    }
+
+    /// Whether the error is a legal problem of the packages, a problem of the inputs or the environment, or a failure
+    /// of the tool itself, e.g. to exit with [`ErrorClass::exit_code`]
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            AggregateError::PolicyViolation(_) | AggregateError::NoticesNotReproduced(_) => ErrorClass::Policy,
+            #[cfg(feature = "cargo")]
+            AggregateError::NotApproved(_)
+            | AggregateError::UnknownLicense { .. }
+            | AggregateError::TooManyUnknownLicenses { .. } => ErrorClass::Policy,
+            AggregateError::ManifestResolution { .. }
+            | AggregateError::LicenseStore(_)
+            | AggregateError::ConfigRead { .. }
+            | AggregateError::Environment { .. }
+            | AggregateError::LicenseRead { .. }
+            | AggregateError::Write { .. }
+            | AggregateError::SpdxParse { .. }
+            | AggregateError::ThirdpartyParse { .. } => ErrorClass::Io,
+            #[cfg(feature = "cargo")]
+            AggregateError::ConfigParse { .. }
+            | AggregateError::ConfigInclude { .. }
+            | AggregateError::OrtCurations { .. }
+            | AggregateError::CustomLicense { .. }
+            | AggregateError::Attestation { .. }
+            | AggregateError::Signature { .. }
+            | AggregateError::ScanCodeParse { .. }
+            | AggregateError::Pattern { .. } => ErrorClass::Io,
+            #[cfg(feature = "network")]
+            AggregateError::Network(_) => ErrorClass::Io,
+            AggregateError::Minimization { .. } | AggregateError::Cancelled | AggregateError::Other(_) => {
+                ErrorClass::Internal
+            },
+        }
+    }
+}
+
+/// The class of an [`AggregateError`], see [`AggregateError::class`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// The packages violate the configured policy, e.g. a denied or unknown license
+    Policy,
+    /// An input (e.g. the manifest, the configuration or a thirdparty file) is invalid or could not be read, an output
+    /// could not be written, or a request failed
+    Io,
+    /// Any other error, e.g. of a custom [`crate::LicenseSource`]
+    Internal,
+}
+
+impl ErrorClass {
+    /// Distinct exit codes for command line tools, so CI scripts can tell a legal problem from a crash:
+    /// `1` for policy violations, `2` for IO errors and `3` for internal errors
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorClass::Policy => 1,
+            ErrorClass::Io => 2,
+            ErrorClass::Internal => 3,
+        }
+    }
+}
+
+/// Renders the error together with its chain of causes
+#[cfg(feature = "network")]
+pub(crate) fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = e.source();
+
+    while let Some(cause) = source {
+        msg.push_str(": ");
+        msg.push_str(&cause.to_string());
+        source = cause.source();
+    }
+
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes() {
+        let policy = AggregateError::PolicyViolation(crate::policy::PolicyReport::default());
+        assert_eq!(policy.class(), ErrorClass::Policy);
+
+        let io = AggregateError::LicenseRead { path: "LICENSE".into(), source: std::io::ErrorKind::NotFound.into() };
+        assert_eq!(io.class(), ErrorClass::Io);
+        let environment = AggregateError::Environment { variable: "TLA_OFFLINE".to_owned(), reason: String::new() };
+        assert_eq!(environment.class(), ErrorClass::Io);
+
+        assert_eq!(AggregateError::other("custom source failed").class(), ErrorClass::Internal);
+
+        let codes = [ErrorClass::Policy, ErrorClass::Io, ErrorClass::Internal].map(ErrorClass::exit_code);
+        assert_eq!(codes, [1, 2, 3]);
+    }
+}
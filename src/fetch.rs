@@ -0,0 +1,459 @@
+use crate::{
+    error::error_chain, AggregateError, DetectionMethod, Diagnostic, DiagnosticKind, Diagnostics, Expression,
+    HttpOptions, LicenseCache, LicenseFile, Package, Severity,
+};
+#[cfg(feature = "tokio")]
+use crate::nonblocking::{join_ordered, AsyncHttp, AsyncOptions};
+use reqwest::{blocking::Client, StatusCode, Url};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Files that are looked up in GitLab repositories, which have no endpoint for the license of a repository
+const GITLAB_LICENSE_FILES: &[&str] =
+    &["LICENSE", "LICENSE-MIT", "LICENSE-APACHE", "LICENSE.md", "LICENSE.txt", "COPYING", "UNLICENSE"];
+
+/// Fetches the license files of packages that ship none from their GitHub or GitLab (gitlab.com) repository,
+/// at the tag of the released version (`v<version>` or `<version>`).
+///
+/// Fetched files are marked with [`DetectionMethod::RemoteFetch`] and their [`LicenseFile::source_url`].
+/// This is opt-in, see [`crate::Aggregator::fetch_from_repository`].
+///
+/// # Example
+/// `RepositoryFetcher::from_env().cache(LicenseCache::user_default().unwrap())`
+#[derive(Clone, Debug, Default)]
+pub struct RepositoryFetcher {
+    http: HttpOptions,
+    github_token: Option<String>,
+    gitlab_token: Option<String>,
+    cache: Option<LicenseCache>,
+}
+
+#[derive(Deserialize)]
+struct GithubLicense {
+    path: String,
+    html_url: Option<String>,
+    download_url: String,
+    license: Option<GithubLicenseInfo>,
+}
+
+#[derive(Deserialize)]
+struct GithubLicenseInfo {
+    spdx_id: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Repository {
+    Github { owner: String, repo: String },
+    Gitlab { path: String },
+}
+
+impl RepositoryFetcher {
+    /// Fetcher without authentication, which is subject to strict rate limits of the APIs
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetcher with the tokens from `GITHUB_TOKEN` and `GITLAB_TOKEN`, if set
+    pub fn from_env() -> Self {
+        let token = |var| std::env::var(var).ok().filter(|t: &String| !t.is_empty());
+        Self { github_token: token("GITHUB_TOKEN"), gitlab_token: token("GITLAB_TOKEN"), ..Self::default() }
+    }
+
+    pub fn http(mut self, http: HttpOptions) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn github_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.github_token = Some(token.into());
+        self
+    }
+
+    pub fn gitlab_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.gitlab_token = Some(token.into());
+        self
+    }
+
+    /// Keep the fetched files in (a `repository` subdirectory of) the cache, so they are only fetched once
+    pub fn cache(mut self, cache: LicenseCache) -> Self {
+        self.cache = Some(cache.subcache("repository"));
+        self
+    }
+
+    /// Fetch the license files of all packages that have only heuristically found or synthesized license files
+    /// (or none at all) and a GitHub or GitLab url.
+    ///
+    /// Packages for which fetching fails are reported as [`DiagnosticKind::RepositoryFetchFailed`]
+    /// and left as they are, only failing to create the HTTP client is an error.
+    pub fn fetch_missing(&self, packages: &mut [Package], diagnostics: &mut Diagnostics) -> Result<(), AggregateError> {
+        let mut client = None;
+
+        for pkg in packages {
+            let Some(repository) = self.pending(pkg) else {
+                continue;
+            };
+
+            let client = match &client {
+                Some(client) => client,
+                None => client.insert(self.http.build_client()?),
+            };
+
+            let files = self.fetch(client, &repository, &pkg.package_version);
+            self.apply(pkg, files, diagnostics);
+        }
+
+        Ok(())
+    }
+
+    /// Like [`RepositoryFetcher::fetch_missing`], with the files of all packages fetched concurrently
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn fetch_missing_async(
+        &self,
+        packages: &mut [Package],
+        diagnostics: &mut Diagnostics,
+        options: &AsyncOptions,
+    ) -> Result<(), AggregateError> {
+        let (indices, repositories): (Vec<_>, Vec<_>) = packages
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, pkg)| self.pending(pkg).map(|repository| (i, repository)))
+            .unzip();
+        if indices.is_empty() {
+            return Ok(());
+        }
+
+        let http = AsyncHttp::new(&self.http, options)?;
+        let fetches = indices.iter().zip(repositories).map(|(&i, repository)| {
+            let (fetcher, http, version) = (self.clone(), http.clone(), packages[i].package_version.clone());
+            async move { fetcher.fetch_async(&http, &repository, &version).await }
+        });
+        let fetched = join_ordered(fetches.collect::<Vec<_>>()).await?;
+
+        for (i, files) in indices.into_iter().zip(fetched) {
+            self.apply(&mut packages[i], files, diagnostics);
+        }
+
+        Ok(())
+    }
+
+    /// The repository to fetch the license files of the package from, `None` if the package has license files or
+    /// no GitHub or GitLab url, or if its fetched files are cached (which are used right away)
+    fn pending(&self, pkg: &mut Package) -> Option<Repository> {
+        if !pkg.license_files.iter().all(|l| l.heuristic || l.synthesized || !l.kind.is_license()) {
+            return None;
+        }
+
+        let repository = pkg.package_url.as_deref().and_then(Repository::parse)?;
+
+        if let Some(cached) = self.cache.as_ref().and_then(|c| c.get(&pkg.package_name, &pkg.package_version)) {
+            pkg.license_files.retain(|l| !l.heuristic && !l.synthesized);
+            pkg.license_files.extend(cached.license_files.into_iter().filter(|l| l.kind.is_license()));
+            return None;
+        }
+
+        Some(repository)
+    }
+
+    /// Replace the heuristically found or synthesized license files of the package by the fetched ones
+    fn apply(&self, pkg: &mut Package, files: Result<Vec<LicenseFile>, AggregateError>, diagnostics: &mut Diagnostics) {
+        let url = pkg.package_url.clone().unwrap_or_default();
+        match files {
+            Ok(files) if files.is_empty() => diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::RepositoryFetchFailed,
+                    format!(
+                        "No license files found in {url} at the tag of '{} {}'",
+                        pkg.package_name, pkg.package_version
+                    ),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version),
+            ),
+            Ok(files) => {
+                let names: Vec<_> = files.iter().map(|l| l.name.as_str()).collect();
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Info,
+                        DiagnosticKind::FetchedLicenseFiles,
+                        format!(
+                            "Fetched {} of '{} {}' from {url}",
+                            names.join(", "),
+                            pkg.package_name,
+                            pkg.package_version
+                        ),
+                    )
+                    .with_package(&pkg.package_name, &pkg.package_version),
+                );
+
+                pkg.license_files.retain(|l| !l.heuristic && !l.synthesized);
+                pkg.license_files.extend(files);
+                if let Some(cache) = &self.cache {
+                    cache.put(pkg);
+                }
+            },
+            Err(e) => diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::RepositoryFetchFailed,
+                    format!(
+                        "Unable to fetch license files of '{} {}' from {url}: {}",
+                        pkg.package_name,
+                        pkg.package_version,
+                        error_chain(&e)
+                    ),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version),
+            ),
+        }
+    }
+
+    fn fetch(
+        &self,
+        client: &Client,
+        repository: &Repository,
+        version: &str,
+    ) -> Result<Vec<LicenseFile>, AggregateError> {
+        for tag in tags(version) {
+            let files = match repository {
+                Repository::Github { owner, repo } => self.fetch_github(client, owner, repo, &tag)?,
+                Repository::Gitlab { path } => self.fetch_gitlab(client, path, &tag)?,
+            };
+
+            if !files.is_empty() {
+                return Ok(files);
+            }
+        }
+
+        Ok(vec![])
+    }
+
+    fn fetch_github(
+        &self,
+        client: &Client,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+    ) -> Result<Vec<LicenseFile>, AggregateError> {
+        let mut request = client.get(github_url(owner, repo, tag)).header("Accept", "application/vnd.github+json");
+        if let Some(token) = &self.github_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = self.http.send(request)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(vec![]);
+        }
+
+        let body = response.error_for_status()?.text()?;
+        let license: GithubLicense = serde_json::from_str(&body).map_err(AggregateError::other)?;
+        let text = self.http.send(client.get(&license.download_url))?.error_for_status()?.text()?;
+
+        Ok(vec![github_file(license, text)])
+    }
+
+    fn fetch_gitlab(&self, client: &Client, path: &str, tag: &str) -> Result<Vec<LicenseFile>, AggregateError> {
+        let mut files = vec![];
+
+        for name in GITLAB_LICENSE_FILES {
+            let mut request = client.get(gitlab_url(path, name, tag));
+            if let Some(token) = &self.gitlab_token {
+                request = request.header("PRIVATE-TOKEN", token);
+            }
+
+            let response = self.http.send(request)?;
+            if response.status() == StatusCode::NOT_FOUND {
+                continue;
+            }
+
+            files.push(gitlab_file(path, name, tag, response.error_for_status()?.text()?));
+        }
+
+        Ok(files)
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn fetch_async(
+        &self,
+        http: &AsyncHttp,
+        repository: &Repository,
+        version: &str,
+    ) -> Result<Vec<LicenseFile>, AggregateError> {
+        for tag in tags(version) {
+            let files = match repository {
+                Repository::Github { owner, repo } => self.fetch_github_async(http, owner, repo, &tag).await?,
+                Repository::Gitlab { path } => self.fetch_gitlab_async(http, path, &tag).await?,
+            };
+
+            if !files.is_empty() {
+                return Ok(files);
+            }
+        }
+
+        Ok(vec![])
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn fetch_github_async(
+        &self,
+        http: &AsyncHttp,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+    ) -> Result<Vec<LicenseFile>, AggregateError> {
+        let mut request =
+            http.client().get(github_url(owner, repo, tag)).header("Accept", "application/vnd.github+json");
+        if let Some(token) = &self.github_token {
+            request = request.bearer_auth(token);
+        }
+
+        let Some(body) = http.text_if_found(request).await? else {
+            return Ok(vec![]);
+        };
+
+        let license: GithubLicense = serde_json::from_str(&body).map_err(AggregateError::other)?;
+        let text = http.text(http.client().get(&license.download_url)).await?;
+
+        Ok(vec![github_file(license, text)])
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn fetch_gitlab_async(
+        &self,
+        http: &AsyncHttp,
+        path: &str,
+        tag: &str,
+    ) -> Result<Vec<LicenseFile>, AggregateError> {
+        let mut files = vec![];
+
+        for name in GITLAB_LICENSE_FILES {
+            let mut request = http.client().get(gitlab_url(path, name, tag));
+            if let Some(token) = &self.gitlab_token {
+                request = request.header("PRIVATE-TOKEN", token);
+            }
+
+            if let Some(text) = http.text_if_found(request).await? {
+                files.push(gitlab_file(path, name, tag, text));
+            }
+        }
+
+        Ok(files)
+    }
+}
+
+/// The tags a released version is looked up at
+fn tags(version: &str) -> [String; 2] {
+    [format!("v{version}"), version.to_owned()]
+}
+
+fn github_url(owner: &str, repo: &str, tag: &str) -> Url {
+    let mut url = Url::parse("https://api.github.com/repos").expect("valid url");
+    url.path_segments_mut().expect("https urls have a path").extend([owner, repo, "license"]);
+    url.query_pairs_mut().append_pair("ref", tag);
+    url
+}
+
+fn github_file(license: GithubLicense, text: String) -> LicenseFile {
+    let spdx = license
+        .license
+        .and_then(|l| l.spdx_id)
+        .and_then(|id| Expression::from_str(&id).ok());
+
+    let name = license.path.rsplit('/').next().unwrap_or(&license.path).to_owned();
+    let mut file = LicenseFile::new(name, spdx, text);
+    file.detection_method = Some(DetectionMethod::RemoteFetch);
+    file.source_url = Some(license.html_url.unwrap_or(license.download_url));
+    file
+}
+
+fn gitlab_url(path: &str, name: &str, tag: &str) -> Url {
+    let mut url = Url::parse("https://gitlab.com/api/v4/projects").expect("valid url");
+    // the path of the project is a single segment, i.e. its slashes are encoded as well
+    url.path_segments_mut().expect("https urls have a path").extend([path, "repository", "files", name, "raw"]);
+    url.query_pairs_mut().append_pair("ref", tag);
+    url
+}
+
+fn gitlab_file(path: &str, name: &str, tag: &str, text: String) -> LicenseFile {
+    let mut url = Url::parse("https://gitlab.com").expect("valid url");
+    url.path_segments_mut().expect("https urls have a path").extend(path.split('/')).extend(["-", "blob", tag, name]);
+
+    let mut file = LicenseFile::new(name.to_owned(), None, text);
+    file.detection_method = Some(DetectionMethod::RemoteFetch);
+    file.source_url = Some(url.into());
+    file
+}
+
+impl Repository {
+    fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url.strip_prefix("git+").unwrap_or(url)).ok()?;
+        if !matches!(url.scheme(), "https" | "http") {
+            return None;
+        }
+
+        let segments: Vec<_> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        let repo = |name: &str| name.strip_suffix(".git").unwrap_or(name).to_owned();
+        match url.host_str()? {
+            // cut off e.g. `/tree/main/crates/foo`
+            "github.com" | "www.github.com" => match segments[..] {
+                [owner, name, ..] => Some(Self::Github { owner: owner.to_owned(), repo: repo(name) }),
+                _ => None,
+            },
+            // cut off e.g. `/-/tree/main`, the path of a project may contain any number of groups
+            "gitlab.com" | "www.gitlab.com" => {
+                let path: Vec<_> = segments.iter().take_while(|s| **s != "-").collect();
+                let (name, groups) = path.split_last()?;
+                let groups: Vec<_> = groups.iter().map(|group| group.to_string()).collect();
+                (!groups.is_empty()).then(|| Self::Gitlab { path: format!("{}/{}", groups.join("/"), repo(name)) })
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn github(owner: &str, repo: &str) -> Option<Repository> {
+        Some(Repository::Github { owner: owner.to_owned(), repo: repo.to_owned() })
+    }
+
+    fn gitlab(path: &str) -> Option<Repository> {
+        Some(Repository::Gitlab { path: path.to_owned() })
+    }
+
+    #[test]
+    fn repositories() {
+        assert_eq!(Repository::parse("https://github.com/serde-rs/serde"), github("serde-rs", "serde"));
+        assert_eq!(Repository::parse("git+https://github.com/serde-rs/serde.git"), github("serde-rs", "serde"));
+        assert_eq!(Repository::parse("http://www.github.com/serde-rs/serde/"), github("serde-rs", "serde"));
+        assert_eq!(
+            Repository::parse("https://github.com/tokio-rs/tokio/tree/master/tokio-util"),
+            github("tokio-rs", "tokio")
+        );
+        assert_eq!(Repository::parse("https://github.com/serde-rs"), None);
+
+        assert_eq!(Repository::parse("https://gitlab.com/group/project"), gitlab("group/project"));
+        assert_eq!(Repository::parse("git+https://gitlab.com/group/sub/project.git"), gitlab("group/sub/project"));
+        assert_eq!(Repository::parse("https://gitlab.com/group/project/-/tree/main/crate"), gitlab("group/project"));
+        assert_eq!(Repository::parse("https://gitlab.com/project"), None);
+
+        assert_eq!(Repository::parse("https://codeberg.org/owner/repo"), None);
+        assert_eq!(Repository::parse("ssh://git@github.com/serde-rs/serde.git"), None);
+        assert_eq!(Repository::parse("not a url"), None);
+    }
+
+    #[test]
+    fn api_urls() {
+        assert_eq!(
+            github_url("serde-rs", "serde", "v1.0.200").as_str(),
+            "https://api.github.com/repos/serde-rs/serde/license?ref=v1.0.200"
+        );
+        assert_eq!(
+            gitlab_url("group/project", "LICENSE-MIT", "release/1.0").as_str(),
+            "https://gitlab.com/api/v4/projects/group%2Fproject/repository/files/LICENSE-MIT/raw?ref=release%2F1.0"
+        );
+
+        let file = gitlab_file("group/project", "LICENSE", "v1.0", "text".to_owned());
+        assert_eq!(file.source_url.as_deref(), Some("https://gitlab.com/group/project/-/blob/v1.0/LICENSE"));
+        assert_eq!(file.detection_method, Some(DetectionMethod::RemoteFetch));
+    }
+}
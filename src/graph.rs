@@ -0,0 +1,151 @@
+//! Helpers to relate collected [`Package`]s to the resolved dependency graph (see [`crate::get_all_krates`])
+
+use crate::{compatibility::CompatibilityReport, Krate, Krates, Package};
+use krates::{
+    petgraph::visit::{Dfs, EdgeRef},
+    DepKind, Edge, Node, NodeId,
+};
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
+
+/// Find the crate a package was collected from, by name and version
+pub fn krate_for_package<'k>(krates: &'k Krates, package: &Package) -> Option<&'k Krate> {
+    krates
+        .krates_by_name(&package.package_name)
+        .map(|m| m.krate)
+        .find(|krate| krate.version.to_string() == package.package_version)
+}
+
+/// All crates reachable from `root` in the resolved dependency graph, including `root` itself
+pub fn reachable_krates<'k>(krates: &'k Krates, root: &Krate) -> Vec<&'k Krate> {
+    let Some(root) = krates.nid_for_kid(&root.id.clone().into()) else {
+        return Vec::new();
+    };
+
+    let graph = krates.graph();
+    let mut dfs = Dfs::new(graph, root);
+    let mut reachable = Vec::new();
+
+    while let Some(nid) = dfs.next(graph) {
+        if let Node::Krate { krate, .. } = &graph[nid] {
+            reachable.push(krate);
+        }
+    }
+
+    reachable
+}
+
+/// The shortest chain of dependencies from a workspace member to `target`, starting with the workspace member and ending
+/// with `target`. Empty if `target` is not reachable from the workspace.
+pub fn dependency_path<'k>(krates: &'k Krates, target: &Krate) -> Vec<&'k Krate> {
+    let Some(target) = krates.nid_for_kid(&target.id.clone().into()) else {
+        return Vec::new();
+    };
+
+    let graph = krates.graph();
+    let mut predecessors: HashMap<NodeId, Option<NodeId>> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for member in krates.workspace_members() {
+        if let Some(nid) = node_id(krates, member) {
+            predecessors.insert(nid, None);
+            queue.push_back(nid);
+        }
+    }
+
+    while let Some(nid) = queue.pop_front() {
+        if nid == target {
+            let mut path = vec![];
+            let mut current = Some(nid);
+            while let Some(nid) = current {
+                if let Node::Krate { krate, .. } = &graph[nid] {
+                    path.push(krate);
+                }
+                current = predecessors[&nid];
+            }
+
+            path.reverse();
+            return path;
+        }
+
+        for next in graph.neighbors(nid) {
+            if let Entry::Vacant(entry) = predecessors.entry(next) {
+                entry.insert(Some(nid));
+                queue.push_back(next);
+            }
+        }
+    }
+
+    Vec::new()
+}
+
+/// The crates that are only needed to build the workspace and are not part of the shipped artifact: proc-macros,
+/// build (and dev) dependencies and all crates that are only reachable through them
+pub fn build_time_krates(krates: &Krates) -> Vec<&Krate> {
+    let graph = krates.graph();
+    let is_proc_macro = |nid: NodeId| {
+        // features of a proc-macro enable the features of its dependencies, which are build-time as well
+        let nid = match &graph[nid] {
+            Node::Krate { .. } => nid,
+            Node::Feature { krate_index, .. } => *krate_index,
+        };
+
+        match &graph[nid] {
+            Node::Krate { krate, .. } => krate.targets.iter().any(|t| t.kind.iter().any(|k| k == "proc-macro")),
+            Node::Feature { .. } => false,
+        }
+    };
+
+    let members: HashSet<_> = krates.workspace_members().filter_map(|member| node_id(krates, member)).collect();
+    let mut shipped = HashSet::new();
+    let mut queue: VecDeque<_> = members.iter().copied().filter(|nid| !is_proc_macro(*nid)).collect();
+
+    while let Some(nid) = queue.pop_front() {
+        if !shipped.insert(nid) {
+            continue;
+        }
+
+        for edge in graph.edges(nid) {
+            let normal = match edge.weight() {
+                Edge::Dep { kind, .. } | Edge::DepFeature { kind, .. } => *kind == DepKind::Normal,
+                Edge::Feature => true,
+            };
+
+            if normal && !is_proc_macro(edge.target()) {
+                queue.push_back(edge.target());
+            }
+        }
+    }
+
+    graph
+        .node_indices()
+        .filter(|nid| !shipped.contains(nid) && !members.contains(nid))
+        .filter_map(|nid| match &graph[nid] {
+            Node::Krate { krate, .. } => Some(krate),
+            Node::Feature { .. } => None,
+        })
+        .collect()
+}
+
+/// Fill in the [`crate::compatibility::LicenseConflict::path`] of all conflicts of the report
+pub fn annotate_dependency_paths(krates: &Krates, report: &mut CompatibilityReport) {
+    for conflict in &mut report.conflicts {
+        let krate = krates
+            .krates_by_name(&conflict.package_name)
+            .map(|m| m.krate)
+            .find(|krate| krate.version.to_string() == conflict.package_version);
+
+        if let Some(krate) = krate {
+            conflict.path = dependency_path(krates, krate)
+                .into_iter()
+                .map(|k| format!("{} {}", k.name, k.version))
+                .collect();
+        }
+    }
+}
+
+fn node_id(krates: &Krates, node: &Node<Krate>) -> Option<NodeId> {
+    match node {
+        Node::Krate { id, .. } => krates.nid_for_kid(id),
+        Node::Feature { .. } => None,
+    }
+}
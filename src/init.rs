@@ -0,0 +1,148 @@
+//! Generation of a starter configuration from the licenses of a workspace, see [`init_config`]
+
+use crate::{
+    obligations::{classify_license, LicenseCategory},
+    AggregateError, Aggregator, AggregatorConfig, GatherOptions, Package, Utf8PathBuf,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+/// Number of package names listed per license, the remaining ones are only counted
+const LISTED_PACKAGES: usize = 3;
+
+/// Scan the workspace of the manifest and propose an `about.toml` for it, see [`propose_config`].
+/// An existing configuration next to the manifest is ignored, so the proposal only reflects the dependencies.
+pub fn init_config<P: Into<Utf8PathBuf>>(manifest: P, options: GatherOptions) -> Result<String, AggregateError> {
+    let aggregation = Aggregator::new(manifest)
+        .gather_options(options)
+        .aggregator_config(AggregatorConfig::default())
+        .minimize(false)
+        .run()?;
+
+    Ok(propose_config(&aggregation.packages))
+}
+
+/// Propose an `about.toml` with every license used by the packages in `accepted`, e.g.
+/// ```toml
+/// accepted = [
+///     "Apache-2.0", # 42 packages: anyhow, serde, syn and 39 more
+///     "MIT", # 57 packages: anyhow, itoa, serde and 54 more
+///     # weak copyleft, review the obligations before accepting it
+///     # "MPL-2.0", # 1 package: webpki-roots
+/// ]
+/// ```
+/// Permissive and public domain licenses are accepted right away, all other licenses (copyleft, proprietary and
+/// unlicensed) are commented out until they are reviewed. Packages without a license are listed at the end.
+/// The packages should not be minimized, so all alternatives of `OR` expressions are proposed.
+pub fn propose_config(packages: &[Package]) -> String {
+    let mut licenses: BTreeMap<String, (LicenseCategory, BTreeSet<&str>)> = BTreeMap::new();
+    let mut unknown = BTreeSet::new();
+    for p in packages {
+        let Some(expression) = &p.license_spdx else {
+            unknown.insert(format!("{} {}", p.package_name, p.package_version));
+            continue;
+        };
+
+        for req in expression.requirements() {
+            let (_, names) =
+                licenses.entry(req.req.to_string()).or_insert_with(|| (classify_license(&req.req), BTreeSet::new()));
+            names.insert(&p.package_name);
+        }
+    }
+
+    let mut config = format!(
+        "# Proposed from the licenses of {} packages, review it before committing.\n\
+         # Permissive and public domain licenses are accepted, all others are commented out until they are reviewed.\n",
+        packages.len()
+    );
+
+    config.push_str("accepted = [\n");
+    let mut by_category: BTreeMap<LicenseCategory, Vec<_>> = BTreeMap::new();
+    for (license, (category, names)) in &licenses {
+        by_category.entry(*category).or_default().push((license, names));
+    }
+    for (category, entries) in by_category {
+        let accepted = matches!(category, LicenseCategory::PublicDomain | LicenseCategory::Permissive);
+        if !accepted {
+            let _ = writeln!(config, "    # {category}, review the obligations before accepting it");
+        }
+        for (license, names) in entries {
+            let prefix = if accepted { "" } else { "# " };
+            let _ = writeln!(config, "    {prefix}\"{license}\", # {}", describe(names));
+        }
+    }
+    config.push_str("]\n");
+
+    if !unknown.is_empty() {
+        config.push_str("\n# No license was found for the following packages, declare one in `[[overrides]]`:\n");
+        for p in unknown {
+            let _ = writeln!(config, "# - {p}");
+        }
+    }
+
+    config
+}
+
+fn describe(names: &BTreeSet<&str>) -> String {
+    let count = match names.len() {
+        1 => "1 package".to_owned(),
+        n => format!("{n} packages"),
+    };
+    let listed: Vec<_> = names.iter().take(LISTED_PACKAGES).copied().collect();
+    match names.len().saturating_sub(LISTED_PACKAGES) {
+        0 => format!("{count}: {}", listed.join(", ")),
+        more => format!("{count}: {} and {more} more", listed.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, license: Option<&str>) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "package_name": name,
+            "package_version": "1.0.0",
+            "package_url": null,
+            "license_spdx": license,
+            "license_files": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn descriptions() {
+        assert_eq!(describe(&BTreeSet::from(["a"])), "1 package: a");
+        assert_eq!(describe(&BTreeSet::from(["c", "b", "a"])), "3 packages: a, b, c");
+        assert_eq!(describe(&BTreeSet::from(["e", "d", "c", "b", "a"])), "5 packages: a, b, c and 2 more");
+    }
+
+    #[test]
+    fn proposal() {
+        let packages = [
+            package("anyhow", Some("MIT OR Apache-2.0")),
+            package("itoa", Some("MIT OR Apache-2.0")),
+            package("serde", Some("MIT OR Apache-2.0")),
+            package("syn", Some("MIT OR Apache-2.0")),
+            package("webpki-roots", Some("MPL-2.0")),
+            package("readline", Some("GPL-3.0-or-later")),
+            package("constant_time_eq", Some("CC0-1.0")),
+            package("unknown", None),
+        ];
+        let config = propose_config(&packages);
+
+        let accepted: AggregatorConfig = toml::from_str(&config).unwrap();
+        let accepted: Vec<_> = accepted.about.accepted.iter().map(ToString::to_string).collect();
+        assert_eq!(accepted, ["CC0-1.0", "Apache-2.0", "MIT"], "permissive and public domain licenses");
+
+        assert!(config.starts_with("# Proposed from the licenses of 8 packages"), "{config}");
+        assert!(config.contains("    \"MIT\", # 4 packages: anyhow, itoa, serde and 1 more\n"), "{config}");
+        assert!(config.contains("    \"CC0-1.0\", # 1 package: constant_time_eq\n"), "{config}");
+        assert!(config.contains("    # \"MPL-2.0\", # 1 package: webpki-roots\n"), "{config}");
+        assert!(config.contains("    # \"GPL-3.0-or-later\", # 1 package: readline\n"), "{config}");
+        assert!(config.contains(", review the obligations before accepting it\n    # \"MPL-2.0\""), "{config}");
+        assert!(config.ends_with("declare one in `[[overrides]]`:\n# - unknown 1.0.0\n"), "{config}");
+    }
+}
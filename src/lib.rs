@@ -1,26 +1,155 @@
-use anyhow::Context;
-use cargo_about::{
-    licenses::{
-        config::{Clarification, ClarificationFile},
-        Gatherer, KrateLicense, LicenseFileKind, LicenseInfo,
-    },
-    validate_sha256, Krates,
-};
-use krates::LockOptions;
 use serde::{
-    de::{Error, Visitor},
+    de::{DeserializeSeed, Error, SeqAccess, Visitor},
     Deserialize, Deserializer, Serialize, Serializer,
 };
+use sha2::{Digest, Sha256};
 use std::{
     fmt::{Display, Formatter},
     ops::Deref,
     str::FromStr,
-    sync::Arc,
 };
 
-pub use cargo_about::licenses::{config::Config, LicenseStore};
-pub use krates::{Utf8Path, Utf8PathBuf};
+pub mod analysis;
+#[cfg(feature = "cargo")]
+pub mod approvals;
+#[cfg(feature = "cargo")]
+mod attestation;
+#[cfg(feature = "cargo")]
+mod audit;
+#[cfg(feature = "cargo")]
+mod cache;
+#[cfg(feature = "cargo")]
+mod cargo;
+pub mod changes;
+#[cfg(feature = "network")]
+mod clearlydefined;
+pub mod compatibility;
+#[cfg(feature = "cargo")]
+mod config;
+#[cfg(feature = "network")]
+mod crates_io;
+mod dedup;
+#[cfg(feature = "cargo")]
+mod deny;
+mod diagnostic;
+#[cfg(feature = "cargo")]
+mod env;
+mod error;
+#[cfg(feature = "network")]
+mod fetch;
+#[cfg(feature = "cargo")]
+pub mod graph;
+#[cfg(feature = "cargo")]
+mod init;
+#[cfg(feature = "cargo")]
+mod lint;
+#[cfg(feature = "tokio")]
+pub mod nonblocking;
+pub mod obligations;
+#[cfg(feature = "cargo")]
+mod offer;
+#[cfg(feature = "cargo")]
+mod ort;
+#[cfg(feature = "network")]
+mod osv;
+#[cfg(feature = "cargo")]
+mod options;
+#[cfg(feature = "cargo")]
+mod plan;
+pub mod policy;
+mod problems;
+mod purl;
+mod review;
+#[cfg(feature = "cargo")]
+mod run;
+#[cfg(feature = "cargo")]
+mod scancode;
+mod search;
+#[cfg(feature = "cargo")]
+mod source;
+mod store;
+#[cfg(feature = "cargo")]
+mod validate;
+
+pub use analysis::SpdxParsing;
+pub use askalono::Store as LicenseStore;
+#[cfg(feature = "cargo")]
+pub use attestation::{
+    sign_with_minisign, write_checksum, AttributionPredicate, ResourceDescriptor, Statement, Tool,
+    ATTRIBUTION_PREDICATE_TYPE, STATEMENT_TYPE,
+};
+#[cfg(feature = "cargo")]
+pub use audit::{AuditDetection, AuditRecord};
+pub use camino::{Utf8Path, Utf8PathBuf};
+#[cfg(feature = "cargo")]
+pub use cache::LicenseCache;
+#[cfg(feature = "cargo")]
+pub use cargo::*;
+#[cfg(feature = "network")]
+pub use clearlydefined::ClearlyDefined;
+#[cfg(feature = "cargo")]
+pub use config::{
+    add_custom_licenses, load_aggregator_config, AggregatorConfig, Attribution, CustomLicense, DetectionConfig,
+    LicenseOverride, MinimizationConfig, ProjectConfig, ThirdpartyConfig, UnknownLicenseConfig,
+};
+#[cfg(feature = "network")]
+pub use config::HttpConfig;
+#[cfg(feature = "cargo")]
+pub use cargo_about::{
+    licenses::{
+        config::Config, KrateLicense, LicenseFile as GatheredLicenseFile,
+        LicenseFileKind as GatheredLicenseFileKind, LicenseInfo,
+    },
+    Krate, Krates,
+};
+pub use dedup::{merge_versions, DedupedPackages, DuplicateVersions, GroupedPackages};
+#[cfg(feature = "cargo")]
+pub use deny::{load_deny_config, DenyException, DenyLicenses};
+pub use diagnostic::{Diagnostic, DiagnosticKind, Diagnostics, Severity};
+#[cfg(feature = "cargo")]
+pub use env::{ENV_ACCEPTED, ENV_CONFIDENCE_THRESHOLD, ENV_OFFLINE, ENV_OUTPUT, ENV_RUST_CONFIDENCE_THRESHOLD};
+pub use error::{AggregateError, BoxedError, ErrorClass};
+#[cfg(feature = "network")]
+pub use fetch::RepositoryFetcher;
+#[cfg(feature = "cargo")]
+pub use init::{init_config, propose_config};
+#[cfg(feature = "cargo")]
+pub use lint::{lint_config, ConfigLint};
+#[cfg(feature = "network")]
+pub use options::{HttpOptions, Registry, DEFAULT_HTTP_BACKOFF, DEFAULT_HTTP_RETRIES};
+#[cfg(feature = "cargo")]
+pub use offer::{export_source_offer, SourceArchive, SOURCE_OFFER_MANIFEST};
+#[cfg(feature = "cargo")]
+pub use ort::{load_ort_curations, ort_curations, write_ort_curations};
+#[cfg(feature = "network")]
+pub use osv::{Advisory, AdvisoryKind, Osv};
+#[cfg(feature = "cargo")]
+pub use options::{GatherOptions, KrateHook, UnknownLicenseAction};
+#[cfg(feature = "cargo")]
+pub use plan::{Plan, PlannedFetch, PlannedKrate, PlannedThirdpartyFile};
+pub use problems::{write_problems, Problem, ProblemsReport, PROBLEMS_FILE};
+pub use purl::{cargo_purl, generic_purl};
+pub use review::{export_unreviewed, needs_review, UnreviewedFile, UnreviewedPackage, UNREVIEWED_INDEX};
+#[cfg(feature = "cargo")]
+pub use run::RunContext;
+#[cfg(feature = "cargo")]
+pub use scancode::ScanCodeSource;
+pub use search::{
+    check_spdx_headers, declares_public_domain, find_license_files, find_notice_files, readme_license,
+    readme_license_section, reserves_all_rights, scan_spdx_headers, SpdxHeader,
+};
+#[cfg(feature = "cargo")]
+pub use source::{CargoSource, CollectContext, LicenseSource, ThirdpartySource};
 pub use spdx::error::ParseError;
+pub use store::{
+    add_license_texts, license_store_from_spdx_data, load_license_store, save_license_store, LicenseStoreProvider,
+};
+#[cfg(feature = "network")]
+pub use store::download_license_store;
+#[cfg(feature = "cargo")]
+pub use store::{shared_license_store, LazyLicenseStore};
+#[cfg(feature = "cargo")]
+pub use validate::{validate_config, ConfigIssue};
 
 #[derive(Clone, Debug)]
 pub struct Expression(pub spdx::Expression);
@@ -29,6 +158,18 @@ impl Expression {
     pub fn parse(s: &str) -> Result<Expression, ParseError> {
         Ok(Self(spdx::Expression::parse(s)?))
     }
+
+    /// Parse the expression, normalizing nonstandard expressions (see [`analysis::normalize_expression`])
+    pub fn parse_lenient(s: &str) -> Result<Expression, ParseError> {
+        analysis::normalize_expression(s).or_else(|_| Self::parse(s))
+    }
+
+    pub fn parse_with(s: &str, mode: SpdxParsing) -> Result<Expression, ParseError> {
+        match mode {
+            SpdxParsing::Lenient => Self::parse_lenient(s),
+            SpdxParsing::Strict => Self::parse(s),
+        }
+    }
 }
 
 impl FromStr for Expression {
@@ -77,10 +218,7 @@ impl<'de> Deserialize<'de> for Expression {
             where
                 E: Error,
             {
-                match spdx::Expression::parse(v) {
-                    Ok(spdx) => Ok(Expression(spdx)),
-                    Err(e) => Err(E::custom(e)),
-                }
+                Expression::parse(v).map_err(E::custom)
             }
         }
 
@@ -97,17 +235,154 @@ impl Serialize for Expression {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct LicenseFile {
     /// Filename of the license file
     pub name: String,
     /// If known, the SPDX identifier of the license
     pub spdx: Option<Expression>,
-    /// The content of the license file
+    /// The content of the license file, may be shortened (see [`truncate_texts`])
+    /// or moved into a text table (see [`DedupedPackages`]), in which case it is empty and not serialized
+    #[serde(default)]
     pub text: String,
+    /// Key of the text in [`DedupedPackages::texts`], if it was moved there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_ref: Option<String>,
+    /// Whether `text` was shortened or omitted, the SHA-256 of the complete text is in `text_sha256`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub text_truncated: bool,
+    /// The hex encoded SHA-256 of the complete text (see [`hash_texts`]), always set if it was shortened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_sha256: Option<String>,
+    /// Confidence (between 0 and 1) of the classification of the file, if it was detected from the text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detection_score: Option<f32>,
+    /// How the SPDX of the file was determined
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detection_method: Option<DetectionMethod>,
+    /// The best matching licenses, if the detection was ambiguous (scored below the threshold)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<LicenseCandidate>,
+    /// The file was not reported by the gatherer, but found by searching the package directory
+    /// (see [`find_license_files`])
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub heuristic: bool,
+    /// The text is the canonical SPDX text of the license, since the package ships no license file
+    /// (see [`analysis::synthesize_license_files`])
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub synthesized: bool,
+    /// Where the text was fetched from, if it was not part of the package (see [`DetectionMethod::RemoteFetch`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    /// The page of the license on spdx.org, if the file has a single license of the SPDX list (see [`link_licenses`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_url: Option<String>,
+    /// Where the file can be viewed in the repository of the package at the packaged revision, if that is known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository_url: Option<String>,
+    /// Whether the file is a license text or a notice that accompanies it
+    #[serde(default, skip_serializing_if = "FileKind::is_license")]
+    pub kind: FileKind,
+    /// The requirement of the package license the file satisfies, e.g. `MIT` for the `LICENSE-MIT` of
+    /// `MIT OR Apache-2.0` (see [`analysis::pair_license_files`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requirement: Option<String>,
+    /// The encoding the text was converted from, if the file was not UTF-8 (e.g. `ISO-8859-1` or `UTF-16LE`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+/// The kind of a [`LicenseFile`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileKind {
+    /// A license text
+    #[default]
+    License,
+    /// A `NOTICE` file, whose contents have to be reproduced e.g. by Apache-2.0 §4(d)
+    Notice,
+    /// A `PATENTS` file with an additional patent grant
+    PatentGrant,
+}
+
+impl FileKind {
+    pub fn is_license(&self) -> bool {
+        *self == FileKind::License
+    }
+}
+
+/// A license that possibly matches the text of a [`LicenseFile`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LicenseCandidate {
+    /// Name of the license in the license store, usually an SPDX identifier
+    pub name: String,
+    /// Similarity between the text and the license, between 0 and 1
+    pub score: f32,
+}
+
+/// The provenance of the SPDX identifier of a [`LicenseFile`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DetectionMethod {
+    /// Declared by the package metadata (e.g. given in a thirdparty file)
+    DeclaredMetadata,
+    /// Detected from the text of the file with the license store
+    FileAnalysis,
+    /// Set by a clarification of the configuration
+    ManualOverride,
+    /// Taken from a remote service, i.e. the harvested license information of clearlydefined.io
+    /// or the repository of the package
+    RemoteFetch,
+    /// Detected by an external scanner, i.e. ScanCode Toolkit (see [`ScanCodeSource`])
+    ExternalScan,
+    /// Mentioned by the license section of a README (see [`readme_license`]), a fallback of low confidence for
+    /// packages without license files
+    ReadmeSection,
 }
 
-#[derive(Serialize, Deserialize)]
+impl LicenseFile {
+    pub fn new(name: String, spdx: Option<Expression>, text: String) -> Self {
+        Self {
+            name,
+            spdx,
+            text,
+            text_ref: None,
+            text_truncated: false,
+            text_sha256: None,
+            detection_score: None,
+            detection_method: None,
+            candidates: Vec::new(),
+            heuristic: false,
+            synthesized: false,
+            source_url: None,
+            license_url: None,
+            repository_url: None,
+            kind: FileKind::License,
+            requirement: None,
+            encoding: None,
+        }
+    }
+
+    /// Read a license file and detect its SPDX identifier with the license store.
+    ///
+    /// If the detection is not confident the SPDX is left unknown, so it is reported by [`augment_licenses`]
+    /// (or [`analysis::detect_file_licenses`]) later on.
+    pub fn from_path(path: &Utf8Path, license_store: &LicenseStore) -> Result<LicenseFile, AggregateError> {
+        let (text, encoding) =
+            search::read_text(path).map_err(|source| AggregateError::LicenseRead { path: path.to_owned(), source })?;
+
+        let classification = analysis::classify_text(license_store, &text);
+        let spdx = classification.spdx.filter(|_| classification.score >= analysis::DEFAULT_CONFIDENCE_THRESHOLD);
+
+        let mut file = LicenseFile::new(path.file_name().unwrap_or(path.as_str()).to_owned(), spdx, text);
+        file.detection_score = Some(classification.score);
+        file.detection_method = Some(DetectionMethod::FileAnalysis);
+        file.encoding = encoding.map(ToOwned::to_owned);
+        Ok(file)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Package {
     /// Name of the package
     pub package_name: String,
@@ -115,278 +390,449 @@ pub struct Package {
     pub package_version: String,
     /// Url of the package (this might be the repository or the crates.io page or a homepage)
     pub package_url: Option<String>,
+    /// Authors of the package as given in its metadata, e.g. `Jane Doe <jane@example.org>`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// One-line description of the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The page of the package in its registry, e.g. `https://crates.io/crates/serde/1.0.200`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry_url: Option<String>,
+    /// The package URL identifying the package across tools, e.g. `pkg:cargo/serde@1.0.200` (see [`cargo_purl`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purl: Option<String>,
+    /// The hex encoded SHA-256 of the `.crate` archive, as recorded in the Cargo.lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// The exact source archive of a thirdparty package, a url or a path (relative to the thirdparty file), which is
+    /// part of the source offer (see [`export_source_offer`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_archive: Option<String>,
     /// If known, the combined SPDX expression for all licenses of the package (e.g. MIT OR Apache-2.0)
     pub license_spdx: Option<Expression>,
     /// All the license files that couldd be found for the package
     pub license_files: Vec<LicenseFile>,
+    /// Copyright holders of the package, if known (e.g. from clearlydefined.io)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub copyrights: Vec<String>,
+    /// The native code the crate links, if any, whose bundled sources need a review of their own licenses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub native_code: Option<NativeCode>,
+    /// How the package is linked into the shipped artifact, rust crates are linked statically if not configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linkage: Option<Linkage>,
+    /// Where the sources of the crate come from, unset for crates of crates.io and thirdparty packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<Origin>,
+    /// The upstream crate the package is a modified copy of, if it is a fork
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fork: Option<Fork>,
+    /// All versions of the package, if several are included (see [`DuplicateVersions`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<String>,
+    /// The feature sets whose build pulls in the package, only set in the union of a feature matrix
+    /// (`FeatureSetAggregation::union`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feature_sets: Vec<String>,
+    /// The target triples whose build pulls in the package, only set in the union of several targets
+    /// (`TargetAggregation::union`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub platforms: Vec<String>,
 }
 
-/// Create a license store from an internal cache
-pub fn license_store_from_cache() -> anyhow::Result<Arc<LicenseStore>> {
-    Ok(Arc::new(cargo_about::licenses::store_from_cache()?))
+/// How a package is linked into the shipped artifact, which changes the obligations of weak copyleft licenses
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Linkage {
+    /// Linked into the binary, e.g. rust crates and static C++ libraries
+    Static,
+    /// Shipped as a shared library next to the binary
+    Dynamic,
+    /// Only used while building (e.g. code generators), not shipped at all
+    ToolOnly,
 }
 
-/// Retrieve all rust packages and their licenses based on the Cargo.toml at the given path
-pub fn get_all_licenses<P: AsRef<Utf8Path>>(
-    cargo_toml: P,
-    features: Vec<String>,
-    license_store: Arc<LicenseStore>,
-    config: &Config,
-) -> anyhow::Result<Vec<Package>> {
-    let krates = cargo_about::get_all_crates(
-        cargo_toml.as_ref(),
-        false,
-        false,
-        features,
-        false,
-        LockOptions { offline: false, frozen: false, locked: true },
-        config,
-        &[],
-    )
-    .context("Unable to get crates")?;
+/// Native code that a crate links, which is often built from bundled C sources whose licenses differ from the crate
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NativeCode {
+    /// The `links` key of the manifest, i.e. the name of the native library
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<String>,
+    /// Whether the crate follows the `-sys` naming convention of crates binding a native library
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sys_crate: bool,
+}
 
-    collect_krate_licenses(&krates, license_store, config)
+/// The source of a crate that is not from crates.io
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Origin {
+    /// An alternative registry, identified by its index, e.g. `sparse+https://artifactory.example.org/api/cargo/index/`
+    Registry { url: String },
+    /// A git repository
+    Git {
+        url: String,
+        /// The resolved commit
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        commit: Option<String>,
+        /// The branch, tag or revision the commit was resolved from, the default branch if there is none
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reference: Option<GitReference>,
+    },
+    /// A member of the workspace or a path dependency
+    Path,
+    /// A crate (usually of crates.io) whose sources were vendored with `cargo vendor`, see [`Package::origin`]
+    Vendored,
 }
 
-/// If the SPDX identifier of individual licenses in the packages are unknown
-/// use the license store to analyze the license contents to determine their SPDX.
-///
-/// This is typically used when the list of packages is generated by a third party tool
-/// that has no knowledge of the SPDX identifiers of the individual licenses (e.g. conan).
-pub fn augment_licenses(
-    licenses: &mut [Package],
-    license_store: Arc<LicenseStore>,
-    config: &Config,
-) -> anyhow::Result<()> {
-    for pkg in licenses {
-        let clarify = select_clarification(&pkg.package_name, config);
-
-        if let Some(clarify) = clarify {
-            if !clarify.git.is_empty() {
-                tracing::warn!(
-                    "Unsupported git clarification for '{} {}', use files clarification instead",
-                    pkg.package_name,
-                    pkg.package_version
-                );
-            }
+/// The reference of an [`Origin::Git`], e.g. `{ "tag": "v1.3.1" }`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GitReference {
+    Branch(String),
+    Tag(String),
+    /// A revision, e.g. a (possibly abbreviated) commit
+    Rev(String),
+}
 
-            pkg.license_spdx = Some(clarify.license.clone().into());
-        } else if pkg.license_spdx.is_none() {
-            tracing::warn!(
-                "No combined license SPDX available for '{} {}'",
-                pkg.package_name,
-                pkg.package_version
-            );
-        }
+/// A crate that replaces its upstream crate with a modified copy, either with a `[patch]` section or as a git
+/// dependency on a repository other than the one of its manifest. Distributing a modified copy may require stating
+/// the modifications (e.g. Apache-2.0 §4(b)), so forks are marked in the output.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Fork {
+    /// Name of the upstream crate, which differs from the package name if the fork was renamed
+    pub upstream: String,
+    /// The repository of the upstream crate as declared by the manifest of the fork
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_url: Option<String>,
+    /// The repository of the fork, unset for a patch with a path within the workspace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Whether the upstream crate is replaced by a `[patch]` section of the workspace
+    #[serde(default)]
+    pub patched: bool,
+}
 
-        for l in &mut pkg.license_files {
-            if let Some(clarify) = select_file_license_clarification(clarify, &l.name) {
-                l.spdx = clarify.license.clone().map(Into::into);
-
-                if let Err(e) = validate_sha256(&l.text, &clarify.checksum) {
-                    tracing::warn!(
-                        "Unable to validate clarification for {} of '{} {}': {}",
-                        l.name,
-                        pkg.package_name,
-                        pkg.package_version,
-                        e
-                    );
-                }
-            }
+/// Load a list of packages as JSON, e.g. the output of a previous run
+pub fn load_packages<P: AsRef<Utf8Path>>(path: P) -> Result<Vec<Package>, AggregateError> {
+    let path = path.as_ref();
+    let file =
+        std::fs::File::open(path).map_err(|source| AggregateError::LicenseRead { path: path.to_owned(), source })?;
 
-            if l.spdx.is_none() {
-                let text = l.text.as_str().into();
-                let analysis = license_store.analyze(&text);
-
-                if analysis.score < 0.95 {
-                    tracing::warn!(
-                        "Low confidence of {} for {} on license file SPDX detection for {} of '{} {}'",
-                        analysis.score,
-                        analysis.name,
-                        l.name,
-                        pkg.package_name,
-                        pkg.package_version
-                    );
-                }
-
-                match Expression::from_str(analysis.name) {
-                    Ok(file_spdx) => {
-                        if pkg
-                            .license_spdx
-                            .as_ref()
-                            .is_some_and(|pkg_spdx| !spdx_any_in_common(pkg_spdx, &file_spdx))
-                        {
-                            tracing::warn!(
-                                "License detection of file {} detected as {} for '{} {}' is probably wrong: package license and file license have nothing in common",
-                                l.name,
-                                file_spdx,
-                                pkg.package_name,
-                                pkg.package_version
-                            );
-                        }
-
-                        l.spdx = Some(file_spdx)
-                    },
-                    Err(e) => tracing::warn!("License analysis yielded invalid license: {e}"),
-                }
-            }
+    let mut packages = Vec::new();
+    stream_packages(std::io::BufReader::new(file), |p| packages.push(p))
+        .map_err(|source| AggregateError::ThirdpartyParse { path: path.to_owned(), source })?;
+
+    Ok(packages)
+}
+
+/// Parse a JSON array of packages from the reader and pass them to `f` one by one,
+/// so only a single package is held in memory at a time
+pub fn stream_packages<R: std::io::Read, F: FnMut(Package)>(reader: R, f: F) -> Result<(), serde_json::Error> {
+    stream_packages_with(reader, SpdxParsing::Strict, f)
+}
+
+/// Like [`stream_packages`], but with the SPDX expressions of the packages parsed in the given mode, e.g. to normalize
+/// nonstandard expressions in thirdparty files (see [`Expression::parse_with`])
+pub fn stream_packages_with<R: std::io::Read, F: FnMut(Package)>(
+    reader: R,
+    mode: SpdxParsing,
+    f: F,
+) -> Result<(), serde_json::Error> {
+    struct PackageSeq<F>(SpdxParsing, F);
+
+    impl<'de, F: FnMut(Package)> Visitor<'de> for PackageSeq<F> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            write!(formatter, "an array of packages")
         }
 
-        let licenses_in_top_level_expr = licenses_in_expr_opt(pkg.license_spdx.as_ref());
-        let licenses_in_files: usize = pkg
-            .license_files
-            .iter()
-            .map(|file| licenses_in_expr_opt(file.spdx.as_ref()))
-            .sum();
-
-        if licenses_in_top_level_expr != licenses_in_files {
-            tracing::warn!("Mismatch between license SPDX and number of licenses found in files for crate '{} {}'. SPDX specifies {licenses_in_top_level_expr} but found {licenses_in_files} in files",
-                pkg.package_name,
-                pkg.package_version
-            );
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Self::Value, A::Error> {
+            while let Some(package) = seq.next_element_seed(PackageSeed(self.0))? {
+                (self.1)(package);
+            }
+
+            Ok(())
         }
     }
 
-    Ok(())
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    de.deserialize_seq(PackageSeq(mode, f))?;
+    de.end()
 }
 
-fn spdx_any_in_common(expr1: &Expression, expr2: &Expression) -> bool {
-    expr1
-        .requirements()
-        .any(|req1| expr2.requirements().any(|req2| req1 == req2))
+/// Deserializes a [`Package`] with its SPDX expressions parsed in the given mode, [`Expression`]s themselves are
+/// always deserialized strictly
+pub struct PackageSeed(pub SpdxParsing);
+
+impl<'de> DeserializeSeed<'de> for PackageSeed {
+    type Value = Package;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        if self.0 == SpdxParsing::Strict {
+            return Package::deserialize(deserializer);
+        }
+
+        // the nonstandard expressions are replaced by their normalized form before deserializing the package
+        let mut package = serde_json::Value::deserialize(deserializer)?;
+        let normalize = |spdx: Option<&mut serde_json::Value>| {
+            let Some(spdx) = spdx else { return };
+            if let Some(Ok(expression)) = spdx.as_str().map(Expression::parse_lenient) {
+                *spdx = serde_json::Value::String(expression.to_string());
+            }
+        };
+        normalize(package.get_mut("license_spdx"));
+        for file in package.get_mut("license_files").and_then(|f| f.as_array_mut()).into_iter().flatten() {
+            normalize(file.get_mut("spdx"));
+        }
+
+        Package::deserialize(package).map_err(D::Error::custom)
+    }
 }
 
-fn select_clarification<'cfg>(package_name: &str, config: &'cfg Config) -> Option<&'cfg Clarification> {
-    config.crates.get(package_name)?.clarify.as_ref()
+/// Bring the packages into a deterministic form, so repeated runs produce identical output:
+/// packages are sorted by name and version (by semver precedence if both versions are semver, e.g. `1.9.0` before
+/// `1.10.0`, as strings if neither is, semver versions first otherwise), license files by name, line endings of
+/// license texts are normalized to `\n` and SPDX expressions are brought into their canonical form
+/// (see [`analysis::canonical_expression`]).
+pub fn canonicalize(packages: &mut [Package]) {
+    let versions = |a: &str, b: &str| match (semver::Version::parse(a), semver::Version::parse(b)) {
+        // versions differing only in build metadata are equal by precedence, but still ordered deterministically
+        (Ok(a), Ok(b)) => a.cmp_precedence(&b).then_with(|| a.build.cmp(&b.build)),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    };
+    packages.sort_by(|a, b| {
+        a.package_name
+            .cmp(&b.package_name)
+            .then_with(|| versions(&a.package_version, &b.package_version))
+    });
+
+    for p in packages {
+        p.license_files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if let Some(spdx) = &mut p.license_spdx {
+            *spdx = analysis::canonical_expression(spdx);
+        }
+
+        for l in &mut p.license_files {
+            if let Some(spdx) = &mut l.spdx {
+                *spdx = analysis::canonical_expression(spdx);
+            }
+
+            if l.text.contains('\r') {
+                l.text = l.text.replace("\r\n", "\n").replace('\r', "\n");
+            }
+        }
+    }
 }
 
-fn select_file_license_clarification<'c>(
-    clarification: Option<&'c Clarification>,
-    license_name: &str,
-) -> Option<&'c ClarificationFile> {
-    clarification?.files.iter().find(|f| f.path == license_name)
+/// Normalize the license texts, so texts that only differ cosmetically are identical (see [`normalize_text`]).
+/// Files whose text was already shortened or moved into a text table are left as they are.
+pub fn normalize_texts(packages: &mut [Package]) {
+    for l in packages.iter_mut().flat_map(|p| p.license_files.iter_mut()) {
+        if l.text_truncated || l.text_ref.is_some() {
+            continue;
+        }
+
+        l.text = normalize_text(&l.text);
+    }
 }
 
-/// Minimize the license requirements for the packages, based on preferences in the configuration.
-/// SPDX identifiers in the configuration are ordered based on preference, starting with the most preferred.
+/// Normalize line endings to `\n`, strip trailing whitespace and collapse wrapped prose: the lines of every
+/// paragraph (separated by blank lines) are joined by single spaces, so the text is reflowed by the renderer.
+/// Lines starting a list item (`-`, `*`, `+`, `1.`, `1)`, `a)` or `(a)`) and indented lines keep their line break and
+/// indentation, so lists, code and ASCII art survive.
 ///
-/// # Example
-/// `MIT OR Apache-2.0` may be minimized to just `MIT`
-pub fn minimize_requirements(packages: &mut [Package], config: &Config) -> anyhow::Result<()> {
-    for p in packages {
-        if let Some(lspdx) = &p.license_spdx {
-            let minimized: Vec<_> = lspdx
-                .minimized_requirements(&config.accepted)
-                .with_context(|| {
-                    format!(
-                        "Unable to minimize requirements of '{} {}' with {:?}",
-                        p.package_name, p.package_version, p.license_spdx
-                    )
-                })?
-                .into_iter()
-                .collect();
-
-            // retain the file if any of its SPDX components
-            // appear in the minimized version
-            p.license_files.retain(|license_file| {
-                license_file.spdx.is_none()
-                    || license_file.spdx.as_ref().is_some_and(|file_spdx| {
-                        file_spdx
-                            .requirements()
-                            .any(|file_req| minimized.contains(&file_req.req))
-                    })
-            })
+/// Blank lines between paragraphs are reduced to one and leading and trailing blank lines are removed.
+pub fn normalize_text(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    let mut paragraphs: Vec<String> = vec![];
+    let mut paragraph: Option<String> = None;
+    for line in text.lines().map(str::trim_end) {
+        match (&mut paragraph, line.is_empty()) {
+            (Some(p), false) if line.starts_with(char::is_whitespace) || is_list_item(line) => {
+                p.push('\n');
+                p.push_str(line);
+            },
+            (Some(p), false) => {
+                p.push(' ');
+                p.push_str(line);
+            },
+            (None, false) => paragraph = Some(line.to_owned()),
+            (_, true) => paragraphs.extend(paragraph.take()),
         }
     }
+    paragraphs.extend(paragraph);
 
-    Ok(())
+    let mut normalized = paragraphs.join("\n\n");
+    if !normalized.is_empty() {
+        normalized.push('\n');
+    }
+
+    normalized
 }
 
-fn collect_krate_licenses(
-    krates: &Krates,
-    license_store: Arc<LicenseStore>,
-    config: &Config,
-) -> anyhow::Result<Vec<Package>> {
-    let g = Gatherer::with_store(license_store);
-    let c = reqwest::blocking::Client::new();
+/// Whether the line starts with a list marker followed by whitespace, see [`normalize_text`]
+fn is_list_item(line: &str) -> bool {
+    let Some((marker, _)) = line.split_once(char::is_whitespace) else {
+        return false;
+    };
+    let enumerator = |s: &str| !s.is_empty() && s.len() <= 4 && s.chars().all(|c| c.is_ascii_alphanumeric());
+
+    match marker {
+        "-" | "*" | "+" => true,
+        _ if marker.starts_with('(') && marker.ends_with(')') => enumerator(&marker[1..marker.len() - 1]),
+        _ => match marker.strip_suffix('.').or_else(|| marker.strip_suffix(')')) {
+            // a single letter followed by a dot is more likely an abbreviation or initial than an enumerator
+            Some(n) if marker.ends_with('.') => n.chars().all(|c| c.is_ascii_digit()) && enumerator(n),
+            Some(n) => enumerator(n),
+            None => false,
+        },
+    }
+}
 
-    let mut packages = Vec::new();
+/// Record the SHA-256 of the text of every license file in `text_sha256`, so the attribution can be verified against
+/// the shipped artifacts. Files whose text was already shortened or moved into a text table keep their hash.
+pub fn hash_texts(packages: &mut [Package]) {
+    for l in packages.iter_mut().flat_map(|p| p.license_files.iter_mut()) {
+        if l.text_truncated || l.text_ref.is_some() {
+            continue;
+        }
 
-    for KrateLicense { krate, lic_info, license_files } in g.gather(krates, config, Some(c)) {
-        let license = match &lic_info {
-            LicenseInfo::Expr(expr) => {
-                let licenses_in_top_level_expr = licenses_in_expr(expr);
-                let licenses_in_files: usize = license_files
-                    .iter()
-                    .map(|file| licenses_in_expr(&file.license_expr))
-                    .sum();
+        l.text_sha256 = Some(format!("{:x}", Sha256::digest(l.text.as_bytes())));
+    }
+}
 
-                if licenses_in_top_level_expr != licenses_in_files {
-                    tracing::warn!("Mismatch between license SPDX and number of licenses found in files for crate '{krate}'. SPDX specifies {licenses_in_top_level_expr} but found {licenses_in_files} in files");
-                }
+/// Link the license files to the page of their license on spdx.org (`license_url`), so rendered reports can link
+/// out instead of inlining the texts. Files that were fetched from the repository of the package are also linked
+/// to it (`repository_url`). Files with `LicenseRef-` licenses or with several licenses get no `license_url`.
+pub fn link_licenses(packages: &mut [Package]) {
+    for l in packages.iter_mut().flat_map(|p| p.license_files.iter_mut()) {
+        if l.license_url.is_none() {
+            l.license_url = l.spdx.as_ref().and_then(spdx_license_url);
+        }
+        if l.repository_url.is_none() {
+            l.repository_url.clone_from(&l.source_url);
+        }
+    }
+}
 
-                Some(expr.clone().into())
-            },
-            LicenseInfo::Unknown => {
-                tracing::warn!("crate '{krate}' has unknown license");
-                None
-            },
-            LicenseInfo::Ignore => {
-                // private/proprietary dependency (with publish = false in Cargo.toml)
-                continue;
-            },
-        };
+/// The spdx.org page of the license of the expression, if it consists of a single license of the SPDX list
+/// (exceptions aside, e.g. `https://spdx.org/licenses/Apache-2.0.html` for `Apache-2.0 WITH LLVM-exception`)
+pub fn spdx_license_url(expression: &Expression) -> Option<String> {
+    let mut requirements = expression.requirements();
+    let (Some(req), None) = (requirements.next(), requirements.next()) else {
+        return None;
+    };
+    match &req.req.license {
+        spdx::LicenseItem::Spdx { id, .. } => Some(format!("https://spdx.org/licenses/{}.html", id.name)),
+        spdx::LicenseItem::Other { .. } => None,
+    }
+}
 
-        let mut lfiles = vec![];
-        for l in license_files {
-            let name = l.path.file_name().unwrap().to_owned();
-
-            match l.kind {
-                LicenseFileKind::Text(text) | LicenseFileKind::AddendumText(text, _) => {
-                    lfiles.push(LicenseFile { name, spdx: Some(l.license_expr.into()), text })
-                },
-                LicenseFileKind::Header => {
-                    let license_path = if l.path.is_absolute() {
-                        l.path.to_owned()
-                    } else {
-                        krate.manifest_path.parent().unwrap().join(l.path)
-                    };
-
-                    let name = license_path.file_name().unwrap().to_owned();
-                    match std::fs::read_to_string(&license_path) {
-                        Ok(text) => lfiles.push(LicenseFile { name, spdx: Some(l.license_expr.into()), text }),
-                        Err(e) => tracing::warn!("Unable to read license file {license_path}: {e:#}"),
-                    }
-                },
-            }
+/// Shorten all license texts to at most `max_bytes` (at a character boundary), `0` omits the texts entirely.
+/// Shortened files are marked with `text_truncated` and carry the SHA-256 of their complete text.
+///
+/// This is meant for use cases that only need the inventory and SPDX data, the texts make up most of the output.
+pub fn truncate_texts(packages: &mut [Package], max_bytes: usize) {
+    for l in packages.iter_mut().flat_map(|p| p.license_files.iter_mut()) {
+        if l.text.len() <= max_bytes || l.text_truncated {
+            continue;
         }
 
-        if lfiles.is_empty() {
-            tracing::warn!("Unable to find any license files for {krate}");
+        let mut end = max_bytes;
+        while !l.text.is_char_boundary(end) {
+            end -= 1;
         }
 
-        let package = Package {
-            package_name: krate.name.clone(),
-            package_version: krate.version.to_string(),
-            package_url: krate
-                .repository
-                .as_ref()
-                .or(krate.homepage.as_ref())
-                .map(ToOwned::to_owned),
-            license_spdx: license,
-            license_files: lfiles,
+        l.text_sha256 = Some(format!("{:x}", Sha256::digest(l.text.as_bytes())));
+        l.text.truncate(end);
+        l.text_truncated = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spdx_parsing_modes() {
+        assert!(serde_json::from_str::<Expression>("\"MIT/Apache-2.0\"").is_err());
+
+        let json = r#"[{"package_name": "zlib", "package_version": "1.3", "package_url": null,
+            "license_spdx": "MIT/Apache-2.0", "license_files": [{"name": "LICENSE", "spdx": "GPL-2.0", "text": ""}]}]"#;
+        assert!(stream_packages(json.as_bytes(), |_| ()).is_err());
+
+        let mut packages = vec![];
+        stream_packages_with(json.as_bytes(), SpdxParsing::Lenient, |p| packages.push(p)).unwrap();
+        assert_eq!(packages[0].license_spdx.as_ref().unwrap().to_string(), "MIT OR Apache-2.0");
+        assert_eq!(packages[0].license_files[0].spdx.as_ref().unwrap().to_string(), "GPL-2.0-only");
+    }
+
+    #[test]
+    fn canonical_order() {
+        let package = |version: &str| -> Package {
+            let json = serde_json::json!({"package_name": "a", "package_version": version, "package_url": null,
+                "license_spdx": null, "license_files": []});
+            serde_json::from_value(json).unwrap()
         };
+        let mut packages: Vec<_> = ["1.10.0", "r9", "1.9.0", "r10", "1.9.0-rc.1"].map(package).into();
+        canonicalize(&mut packages);
 
-        packages.extend(std::iter::once(package));
+        let order: Vec<_> = packages.iter().map(|p| p.package_version.as_str()).collect();
+        assert_eq!(order, ["1.9.0-rc.1", "1.9.0", "1.10.0", "r10", "r9"]);
     }
 
-    Ok(packages)
-}
+    #[test]
+    fn normalize_prose() {
+        let text = "Permission is granted,\r\nfree of charge,  \r\nto any person\n\n\n\nTHE SOFTWARE IS PROVIDED\n";
+        let expected = "Permission is granted, free of charge, to any person\n\nTHE SOFTWARE IS PROVIDED\n";
+        assert_eq!(normalize_text(text), expected);
+        assert_eq!(normalize_text("\n\n"), "");
+    }
 
-fn licenses_in_expr(expr: &spdx::Expression) -> usize {
-    expr.requirements().count()
-}
+    #[test]
+    fn normalize_keeps_lists_and_indentation() {
+        let text = "Conditions:\n- retain the notice\n  in the sources\n* keep it\n1. first\n2) second\n(a) third\n\
+            (iv) fourth\nb) fifth\n\n    fn main() {\n    }\n";
+        let expected = "Conditions:\n- retain the notice\n  in the sources\n* keep it\n1. first\n2) second\n(a) third\n\
+            (iv) fourth\nb) fifth\n\n    fn main() {\n    }\n";
+        assert_eq!(normalize_text(text), expected);
+        assert_eq!(normalize_text("Written by\nA. Smith\n"), "Written by A. Smith\n");
+        assert!(!is_list_item("-- separator"));
+        assert!(!is_list_item("(see below) for"));
+        assert!(!is_list_item("1.0 is"));
+    }
 
-fn licenses_in_expr_opt(expr: Option<&Expression>) -> usize {
-    expr.map(|expr| licenses_in_expr(&expr.0)).unwrap_or(0)
+    #[test]
+    fn license_urls() {
+        let url = |expression: &str| spdx_license_url(&Expression::parse(expression).unwrap());
+        assert_eq!(url("MIT").as_deref(), Some("https://spdx.org/licenses/MIT.html"));
+        let apache = Some("https://spdx.org/licenses/Apache-2.0.html");
+        assert_eq!(url("Apache-2.0 WITH LLVM-exception").as_deref(), apache);
+        assert_eq!(url("MIT OR Apache-2.0"), None);
+        assert_eq!(url("LicenseRef-Proprietary"), None);
+
+        let json = serde_json::json!({"package_name": "a", "package_version": "1.0.0", "package_url": null,
+            "license_spdx": "MIT OR Apache-2.0", "license_files": [
+                {"name": "LICENSE-MIT", "spdx": "MIT", "text": "", "source_url": "https://example.org/LICENSE-MIT"},
+                {"name": "LICENSE-APACHE", "spdx": "Apache-2.0", "text": "", "license_url": "https://example.org/"},
+                {"name": "COPYING", "spdx": "MIT OR Apache-2.0", "text": ""},
+            ]});
+        let mut packages = [serde_json::from_value::<Package>(json).unwrap()];
+        link_licenses(&mut packages);
+
+        let files = &packages[0].license_files;
+        assert_eq!(files[0].license_url.as_deref(), Some("https://spdx.org/licenses/MIT.html"));
+        assert_eq!(files[0].repository_url.as_deref(), Some("https://example.org/LICENSE-MIT"));
+        assert_eq!(files[1].license_url.as_deref(), Some("https://example.org/"), "linked files are kept");
+        assert_eq!(files[1].repository_url, None);
+        assert_eq!(files[2].license_url, None);
+    }
 }
@@ -0,0 +1,187 @@
+//! Detection of configuration entries that are no longer needed, see [`lint_config`]
+
+use crate::{Package, RunContext};
+use globset::Glob;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+};
+
+/// A configuration entry that matched nothing in a run
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigLint {
+    /// The part of the configuration, e.g. `accepted` or `ignore`
+    pub section: &'static str,
+    /// The entry of the section, e.g. a license or the name of a crate
+    pub entry: String,
+}
+
+impl Display for ConfigLint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' of {} matched nothing", self.entry, self.section)
+    }
+}
+
+/// Find the entries of the configuration and the options of the context that matched nothing, so they can be removed
+/// before they accumulate:
+/// - `accepted` licenses (globally and per crate) that are not part of the license of any package,
+/// - `ignore` patterns that match no crate of the dependency graph,
+/// - crate specific sections, licenses for unknown licenses, pinned licenses, policy exceptions, overrides
+///   and linkages
+///   for crates that are not part of the dependency graph or the packages.
+pub fn lint_config(ctx: &RunContext, packages: &[Package]) -> Vec<ConfigLint> {
+    let config = ctx.config();
+    let options = ctx.options();
+    let mut lints = vec![];
+
+    let names: HashSet<&str> = ctx
+        .krates()
+        .krates()
+        .map(|k| k.name.as_str())
+        .chain(packages.iter().map(|p| p.package_name.as_str()))
+        .collect();
+
+    let is_used = |licensee: &spdx::Licensee, name: Option<&str>| {
+        packages
+            .iter()
+            .filter(|p| name.is_none_or(|name| p.package_name == name))
+            .filter_map(|p| p.license_spdx.as_ref())
+            .any(|l| l.requirements().any(|r| licensee.satisfies(&r.req)))
+    };
+
+    for licensee in &config.accepted {
+        if !is_used(licensee, None) {
+            lints.push(ConfigLint { section: "accepted", entry: licensee.to_string() });
+        }
+    }
+
+    for pattern in &options.ignore {
+        let matcher = Glob::new(pattern).map(|g| g.compile_matcher());
+        if matcher.is_ok_and(|m| !names.iter().any(|name| m.is_match(name))) {
+            lints.push(ConfigLint { section: "ignore", entry: pattern.clone() });
+        }
+    }
+
+    for (name, krate) in &config.crates {
+        if !names.contains(name.as_str()) {
+            lints.push(ConfigLint { section: "the crate sections", entry: name.clone() });
+            continue;
+        }
+
+        for licensee in &krate.accepted {
+            if !is_used(licensee, Some(name)) {
+                lints.push(ConfigLint { section: "accepted", entry: format!("{licensee} ({name})") });
+            }
+        }
+    }
+
+    let per_crate = [
+        ("unknown-license.licenses", options.unknown_license_overrides.keys().collect::<Vec<_>>()),
+        ("minimization.pinned", options.minimization.pinned.keys().collect()),
+        ("policy.exceptions", options.policy.exceptions.iter().map(|e| &e.package).collect()),
+        ("overrides", options.license_overrides.iter().map(|o| &o.name).collect()),
+        ("attributions", options.attributions.iter().map(|a| &a.name).collect()),
+        ("linkage", options.linkage.keys().collect()),
+    ];
+
+    for (section, krates) in per_crate {
+        for name in krates {
+            if !names.contains(name.as_str()) {
+                lints.push(ConfigLint { section, entry: name.clone() });
+            }
+        }
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aggregator, AggregatorConfig, GatherOptions, LicenseStore, Utf8PathBuf};
+    use std::sync::Arc;
+
+    const CONFIG: &str = r#"
+        accepted = ["MIT", "Apache-2.0", "BSD-3-Clause"]
+
+        [app]
+        accepted = ["MIT", "ISC"]
+
+        [removed-crate]
+        accepted = ["ISC"]
+
+        [unknown-license]
+        licenses = { "app" = "MIT", "removed-unknown" = "MIT" }
+
+        [minimization]
+        pinned = { "zlib" = "Zlib", "removed-pinned" = "MIT" }
+
+        [policy]
+        exceptions = [{ package = "removed-exception", justification = "needed" }]
+
+        [linkage]
+        "zlib" = "dynamic"
+        "removed-linkage" = "tool-only"
+
+        [[overrides]]
+        name = "removed-override"
+        license = "MIT"
+
+        [[attributions]]
+        name = "zlib"
+        text = "zlib attribution"
+
+        [[attributions]]
+        name = "removed-attribution"
+        text = "attribution"
+    "#;
+
+    #[test]
+    fn unused_entries() {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let dir = dir.join(format!("tla-lint-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n";
+        std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        std::fs::write(dir.join("Cargo.lock"), "version = 4\n\n[[package]]\nname = \"app\"\nversion = \"0.1.0\"\n")
+            .unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+
+        let config: AggregatorConfig = toml::from_str(CONFIG).unwrap();
+        let options = GatherOptions::new().offline(true).ignore(vec!["app*".to_owned(), "removed-*".to_owned()]);
+        let ctx = Aggregator::new(dir.join("Cargo.toml"))
+            .aggregator_config(config)
+            .license_store(Arc::new(LicenseStore::new()))
+            .gather_options(options)
+            .context();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let ctx = ctx.unwrap();
+
+        let package = |name: &str, license: &str| -> Package {
+            serde_json::from_value(serde_json::json!({"package_name": name, "package_version": "1.0.0",
+                "package_url": null, "license_spdx": license, "license_files": []}))
+            .unwrap()
+        };
+        // a thirdparty package, which is not part of the dependency graph
+        let packages = [package("app", "MIT"), package("zlib", "Zlib AND Apache-2.0")];
+
+        let lints: Vec<_> = lint_config(&ctx, &packages).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lints,
+            [
+                "'BSD-3-Clause' of accepted matched nothing",
+                "'removed-*' of ignore matched nothing",
+                "'ISC (app)' of accepted matched nothing",
+                "'removed-crate' of the crate sections matched nothing",
+                "'removed-unknown' of unknown-license.licenses matched nothing",
+                "'removed-pinned' of minimization.pinned matched nothing",
+                "'removed-exception' of policy.exceptions matched nothing",
+                "'removed-override' of overrides matched nothing",
+                "'removed-attribution' of attributions matched nothing",
+                "'removed-linkage' of linkage matched nothing",
+            ]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! The gathering functions, run off the tokio runtime so they can be awaited within it.
+//!
+//! The gathering itself runs on tokio's blocking thread pool. With the `network` feature, its remote lookups
+//! (repository fetches, registries, clearlydefined.io and OSV) are sent concurrently through an asynchronous client
+//! instead, at most [`AsyncOptions::parallelism`] at a time. Lookups of crates.io are still spaced by its crawler
+//! policy. The source scans of cargo-about, which are parallelized with rayon, run on a dedicated thread pool whose
+//! size is the configured parallelism, so a run does not occupy all threads of the global rayon pool.
+
+use crate::{
+    AggregateError, Aggregation, Aggregator, Config, Diagnostics, GatherOptions, LicenseStore, Package, Utf8PathBuf,
+};
+#[cfg(feature = "network")]
+use crate::{Advisory, ClearlyDefined, HttpOptions, Osv, RepositoryFetcher};
+#[cfg(feature = "network")]
+use std::future::Future;
+use std::sync::Arc;
+
+/// Options for running the blocking functions off the runtime
+#[derive(Clone, Debug)]
+pub struct AsyncOptions {
+    /// Number of threads of the pool the source scans of cargo-about run on,
+    /// and the maximum number of remote lookups in flight at once
+    pub parallelism: usize,
+}
+
+impl Default for AsyncOptions {
+    fn default() -> Self {
+        Self { parallelism: std::thread::available_parallelism().map_or(4, |n| n.get()) }
+    }
+}
+
+/// [`crate::get_all_licenses`], run off the runtime
+pub async fn get_all_licenses(
+    cargo_toml: Utf8PathBuf,
+    gather_options: GatherOptions,
+    license_store: Arc<LicenseStore>,
+    config: Arc<Config>,
+    options: AsyncOptions,
+) -> Result<(Vec<Package>, Diagnostics), AggregateError> {
+    #[cfg(feature = "network")]
+    let gather_options = gather_options.concurrent(Concurrent::current(&options));
+    run_blocking(options, move || {
+        let mut diagnostics = Diagnostics::new();
+        let packages =
+            crate::get_all_licenses(cargo_toml, &gather_options, license_store, &config, &mut diagnostics)?;
+        Ok((packages, diagnostics))
+    })
+    .await
+}
+
+/// [`crate::collect_thirdparty_licenses`] run off the runtime, resolving the dependency graph of `cargo_toml` first
+pub async fn collect_thirdparty_licenses(
+    cargo_toml: Utf8PathBuf,
+    gather_options: GatherOptions,
+    license_store: Arc<LicenseStore>,
+    config: Arc<Config>,
+    metadata_key: String,
+    options: AsyncOptions,
+) -> Result<(Vec<Package>, Diagnostics), AggregateError> {
+    run_blocking(options, move || {
+        let mut diagnostics = Diagnostics::new();
+        if gather_options.generates_lockfile(&cargo_toml) {
+            diagnostics.push(crate::cargo::generated_lockfile(&cargo_toml));
+        }
+        let krates = crate::get_all_krates(cargo_toml, &gather_options, &config)?;
+        let packages = crate::collect_thirdparty_licenses(
+            &krates,
+            license_store,
+            &config,
+            &gather_options,
+            &metadata_key,
+            &mut diagnostics,
+        )?;
+        Ok((packages, diagnostics))
+    })
+    .await
+}
+
+/// [`Aggregator::run`], run off the runtime. The aggregator is built by `aggregator` on the blocking thread, since
+/// its custom sources are not required to be [`Send`]. Its remote lookups are sent concurrently.
+///
+/// # Example
+/// `nonblocking::run_aggregator(|| Aggregator::new("Cargo.toml").check_policy(true), AsyncOptions::default()).await`
+pub async fn run_aggregator<F>(aggregator: F, options: AsyncOptions) -> Result<Aggregation, AggregateError>
+where
+    F: FnOnce() -> Aggregator + Send + 'static,
+{
+    #[cfg(feature = "network")]
+    let concurrent = Concurrent::current(&options);
+    run_blocking(options, move || {
+        let aggregator = aggregator();
+        #[cfg(feature = "network")]
+        let aggregator = aggregator.concurrent(concurrent);
+        aggregator.run()
+    })
+    .await
+}
+
+/// [`RepositoryFetcher::fetch_missing`], fetching the files of all packages concurrently
+#[cfg(feature = "network")]
+pub async fn fetch_missing(
+    fetcher: &RepositoryFetcher,
+    packages: &mut [Package],
+    diagnostics: &mut Diagnostics,
+    options: &AsyncOptions,
+) -> Result<(), AggregateError> {
+    fetcher.fetch_missing_async(packages, diagnostics, options).await
+}
+
+/// [`ClearlyDefined::enrich`], sending the batches of definitions concurrently
+#[cfg(feature = "network")]
+pub async fn enrich(
+    clearlydefined: &ClearlyDefined,
+    packages: &mut [Package],
+    diagnostics: &mut Diagnostics,
+    options: &AsyncOptions,
+) -> Result<(), AggregateError> {
+    clearlydefined.enrich_async(packages, diagnostics, options).await
+}
+
+/// [`Osv::check`], sending the batches and the lookups of the advisory details concurrently
+#[cfg(feature = "network")]
+pub async fn check_advisories(
+    osv: &Osv,
+    packages: &[Package],
+    diagnostics: &mut Diagnostics,
+    options: &AsyncOptions,
+) -> Result<Vec<Advisory>, AggregateError> {
+    osv.check_async(packages, diagnostics, options).await
+}
+
+/// The runtime the blocking gathering hands its remote lookups to, see [`GatherOptions::concurrent`]
+#[cfg(feature = "network")]
+#[derive(Clone, Debug)]
+pub(crate) struct Concurrent {
+    handle: tokio::runtime::Handle,
+    options: AsyncOptions,
+}
+
+#[cfg(feature = "network")]
+impl Concurrent {
+    /// The runtime of the calling task
+    fn current(options: &AsyncOptions) -> Self {
+        Self { handle: tokio::runtime::Handle::current(), options: options.clone() }
+    }
+
+    pub(crate) fn options(&self) -> &AsyncOptions {
+        &self.options
+    }
+
+    /// Wait for the future on the blocking thread, while the runtime drives it
+    pub(crate) fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.handle.block_on(future)
+    }
+}
+
+/// The asynchronous client shared by the lookups of one call, which lets at most
+/// [`AsyncOptions::parallelism`] requests (including their retries and the reading of the body) run at once
+#[cfg(feature = "network")]
+#[derive(Clone)]
+pub(crate) struct AsyncHttp {
+    client: reqwest::Client,
+    http: HttpOptions,
+    permits: Arc<tokio::sync::Semaphore>,
+}
+
+#[cfg(feature = "network")]
+impl AsyncHttp {
+    pub(crate) fn new(http: &HttpOptions, options: &AsyncOptions) -> Result<Self, AggregateError> {
+        Ok(Self {
+            client: http.build_async_client()?,
+            http: http.clone(),
+            permits: Arc::new(tokio::sync::Semaphore::new(options.parallelism.max(1))),
+        })
+    }
+
+    pub(crate) fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// The body of the response, failing for error statuses
+    pub(crate) async fn text(&self, request: reqwest::RequestBuilder) -> Result<String, reqwest::Error> {
+        let _permit = self.permits.acquire().await.expect("the semaphore is never closed");
+        self.http.send_async(request).await?.error_for_status()?.text().await
+    }
+
+    /// Like [`AsyncHttp::text`], but `None` if the resource does not exist
+    pub(crate) async fn text_if_found(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Option<String>, reqwest::Error> {
+        let _permit = self.permits.acquire().await.expect("the semaphore is never closed");
+        let response = self.http.send_async(request).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        response.error_for_status()?.text().await.map(Some)
+    }
+}
+
+/// Run the futures as tasks of the runtime and collect their outputs in the order of the futures
+#[cfg(feature = "network")]
+pub(crate) async fn join_ordered<T, F>(futures: impl IntoIterator<Item = F>) -> Result<Vec<T>, AggregateError>
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, future) in futures.into_iter().enumerate() {
+        tasks.spawn(async move { (i, future.await) });
+    }
+
+    let mut outputs: Vec<_> = std::iter::repeat_with(|| None).take(tasks.len()).collect();
+    while let Some(joined) = tasks.join_next().await {
+        let (i, output) = joined.map_err(AggregateError::other)?;
+        outputs[i] = Some(output);
+    }
+
+    Ok(outputs.into_iter().flatten().collect())
+}
+
+/// Run the blocking function on tokio's blocking thread pool, with a rayon pool of `options.parallelism` threads
+async fn run_blocking<T, F>(options: AsyncOptions, f: F) -> Result<T, AggregateError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, AggregateError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.parallelism.max(1))
+            .thread_name(|i| format!("tla-gather-{i}"))
+            .build()
+            .map_err(AggregateError::other)?;
+
+        pool.install(f)
+    })
+    .await
+    .map_err(AggregateError::other)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "network")]
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    /// An OSV API affecting the only package of the batch by `advisories` advisories, whose details take a while.
+    /// Returns its url and the maximum number of requests that were in flight at once.
+    #[cfg(feature = "network")]
+    fn serve_advisories(advisories: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (in_flight, max_in_flight) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+
+        let max = max_in_flight.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (in_flight, max) = (in_flight.clone(), max.clone());
+                std::thread::spawn(move || {
+                    let mut stream = BufReader::new(stream.unwrap());
+                    let mut line = String::new();
+                    stream.read_line(&mut line).unwrap();
+                    let path = line.split(' ').nth(1).unwrap().to_owned();
+
+                    let mut length = 0;
+                    loop {
+                        line.clear();
+                        stream.read_line(&mut line).unwrap();
+                        match line.trim().split_once(':') {
+                            Some((name, value)) if name.eq_ignore_ascii_case("content-length") => {
+                                length = value.trim().parse().unwrap();
+                            },
+                            Some(_) => {},
+                            None => break,
+                        }
+                    }
+                    stream.read_exact(&mut vec![0; length]).unwrap();
+
+                    max.fetch_max(in_flight.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    let body = if path == "/v1/querybatch" {
+                        let vulns: Vec<_> =
+                            (0..advisories).map(|i| serde_json::json!({ "id": format!("A-{i}") })).collect();
+                        serde_json::json!({ "results": [{ "vulns": vulns }] }).to_string()
+                    } else {
+                        std::thread::sleep(Duration::from_millis(50));
+                        serde_json::json!({ "summary": path }).to_string()
+                    };
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    stream.get_mut().write_all(response.as_bytes()).unwrap();
+                });
+            }
+        });
+
+        (url, max_in_flight)
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn bounds_the_requests_in_flight() {
+        let (url, max_in_flight) = serve_advisories(6);
+        let packages: Vec<Package> = vec![serde_json::from_value(serde_json::json!({
+            "package_name": "a",
+            "package_version": "1.0.0",
+            "package_url": null,
+            "license_spdx": "MIT",
+            "license_files": [],
+        }))
+        .unwrap()];
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let mut diagnostics = Diagnostics::new();
+        let osv = Osv::new().http(HttpOptions::new().retries(0)).url(url);
+        let advisories = runtime
+            .block_on(check_advisories(&osv, &packages, &mut diagnostics, &AsyncOptions { parallelism: 2 }))
+            .unwrap();
+
+        assert_eq!(advisories.len(), 6);
+        assert_eq!(diagnostics.len(), 6);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn runs_on_a_pool_of_the_parallelism() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let threads = runtime.block_on(run_blocking(AsyncOptions { parallelism: 2 }, || {
+            Ok(rayon::current_num_threads())
+        }));
+        assert_eq!(threads.unwrap(), 2);
+    }
+}
@@ -0,0 +1,488 @@
+//! Classification of licenses by their copyleft strength and the resulting obligations of shipping the packages,
+//! see [`obligations_report`]
+
+use crate::{AggregateError, FileKind, Linkage, Package};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Formatter},
+};
+
+/// Licenses that dedicate the work to the public domain or grant all rights without any condition
+const PUBLIC_DOMAIN: &[&str] = &["0BSD", "CC0-1.0", "CC-PDDC", "MIT-0", "PDDL-1.0", "Unlicense", "WTFPL"];
+
+/// The license reference for works that are declared to be in the public domain in prose, e.g. by a README
+pub const PUBLIC_DOMAIN_REF: &str = "LicenseRef-PublicDomain";
+
+/// The license reference for works that claim a copyright without granting any license (see
+/// [`crate::analysis::detect_unlicensed`]), nobody but the authors may use them
+pub const ALL_RIGHTS_RESERVED_REF: &str = "LicenseRef-AllRightsReserved";
+
+/// Licenses whose copyleft extends to the use over a network
+const NETWORK_COPYLEFT: &[&str] = &["AGPL-", "SSPL-", "OSL-", "RPL-", "CPAL-"];
+
+/// Licenses whose copyleft is limited to the files or the library itself
+const WEAK_COPYLEFT: &[&str] = &["LGPL-", "MPL-", "EPL-", "CDDL-", "CPL-", "MS-RL", "CECILL-C", "ErlPL-", "APSL-"];
+
+/// How restrictive a license is, ordered from the least to the most restrictive
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LicenseCategory {
+    /// Nothing is required, not even attribution, e.g. Unlicense, CC0-1.0, 0BSD or works declared to be in the public
+    /// domain ([`PUBLIC_DOMAIN_REF`])
+    PublicDomain,
+    /// Only attribution is required, e.g. MIT, Apache-2.0, BSD-3-Clause
+    Permissive,
+    /// Modifications of the licensed files or library have to be published, e.g. LGPL, MPL
+    WeakCopyleft,
+    /// The complete work has to be published under the same license, e.g. GPL
+    StrongCopyleft,
+    /// Like strong copyleft, but already providing the work over a network counts as distribution, e.g. AGPL
+    NetworkCopyleft,
+    /// Licenses that are not on the SPDX license list (`LicenseRef-...`), whose terms have to be reviewed
+    Proprietary,
+    /// No license is granted at all, all rights are reserved by the authors ([`ALL_RIGHTS_RESERVED_REF`])
+    ProprietaryUnlicensed,
+}
+
+impl Display for LicenseCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LicenseCategory::PublicDomain => write!(f, "public domain"),
+            LicenseCategory::Permissive => write!(f, "permissive"),
+            LicenseCategory::WeakCopyleft => write!(f, "weak copyleft"),
+            LicenseCategory::StrongCopyleft => write!(f, "strong copyleft"),
+            LicenseCategory::NetworkCopyleft => write!(f, "network copyleft"),
+            LicenseCategory::Proprietary => write!(f, "proprietary"),
+            LicenseCategory::ProprietaryUnlicensed => write!(f, "proprietary (unlicensed)"),
+        }
+    }
+}
+
+/// What shipping a package requires
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Obligation {
+    /// Reproduce the license texts and copyright notices
+    Attribution,
+    /// Reproduce the `NOTICE` files of the package
+    NoticeReproduction,
+    /// Offer the source code of the package (including modifications)
+    SourceOffer,
+    /// Allow the users to relink the work with a modified version of the statically linked library (LGPL §6),
+    /// e.g. by providing the object files of the work
+    Relinking,
+    /// Offer the source code of the complete work the package is part of, under the license of the package
+    CombinedWorkSource,
+    /// Offer the source code also to users that only interact with the work over a network
+    NetworkSourceOffer,
+    /// Review the terms of the license manually
+    ReviewTerms,
+}
+
+impl Display for Linkage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Linkage::Static => write!(f, "static"),
+            Linkage::Dynamic => write!(f, "dynamic"),
+            Linkage::ToolOnly => write!(f, "tool only"),
+        }
+    }
+}
+
+impl Display for Obligation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Obligation::Attribution => write!(f, "attribution"),
+            Obligation::NoticeReproduction => write!(f, "NOTICE reproduction"),
+            Obligation::SourceOffer => write!(f, "source offer"),
+            Obligation::Relinking => write!(f, "relinking"),
+            Obligation::CombinedWorkSource => write!(f, "source of the combined work"),
+            Obligation::NetworkSourceOffer => write!(f, "source offer to network users"),
+            Obligation::ReviewTerms => write!(f, "review of the terms"),
+        }
+    }
+}
+
+/// The category of a single license
+pub fn classify_license(req: &spdx::LicenseReq) -> LicenseCategory {
+    let id = match &req.license {
+        spdx::LicenseItem::Spdx { id, .. } => id,
+        other if other.to_string() == PUBLIC_DOMAIN_REF => return LicenseCategory::PublicDomain,
+        other if other.to_string() == ALL_RIGHTS_RESERVED_REF => return LicenseCategory::ProprietaryUnlicensed,
+        spdx::LicenseItem::Other { .. } => return LicenseCategory::Proprietary,
+    };
+
+    if PUBLIC_DOMAIN.contains(&id.name) {
+        LicenseCategory::PublicDomain
+    } else if NETWORK_COPYLEFT.iter().any(|prefix| id.name.starts_with(prefix)) {
+        LicenseCategory::NetworkCopyleft
+    } else if WEAK_COPYLEFT.iter().any(|prefix| id.name.starts_with(prefix)) {
+        LicenseCategory::WeakCopyleft
+    } else if id.is_copyleft() {
+        LicenseCategory::StrongCopyleft
+    } else {
+        LicenseCategory::Permissive
+    }
+}
+
+/// The category of a license expression: the least restrictive alternative of `OR`
+/// and the most restrictive part of `AND` expressions, i.e. the most restrictive of [`license_categories`]
+pub fn classify_expression(expression: &spdx::Expression) -> LicenseCategory {
+    license_categories(expression).last().copied().unwrap_or(LicenseCategory::Proprietary)
+}
+
+/// The categories of all licenses that have to be complied with: every part of `AND` expressions
+/// and the least restrictive alternative of `OR` expressions (the one whose most restrictive category is the least),
+/// so e.g. `GPL-3.0-only AND LicenseRef-vendor` requires both the source of the combined work and a review
+pub fn license_categories(expression: &spdx::Expression) -> BTreeSet<LicenseCategory> {
+    let mut stack: Vec<BTreeSet<LicenseCategory>> = vec![];
+
+    for node in expression.iter() {
+        match node {
+            spdx::expression::ExprNode::Req(req) => stack.push(BTreeSet::from([classify_license(&req.req)])),
+            spdx::expression::ExprNode::Op(op) => {
+                let (Some(rhs), Some(mut lhs)) = (stack.pop(), stack.pop()) else {
+                    break;
+                };
+
+                stack.push(match op {
+                    spdx::expression::Operator::And => {
+                        lhs.extend(rhs);
+                        lhs
+                    },
+                    spdx::expression::Operator::Or => {
+                        if (rhs.last(), &rhs) < (lhs.last(), &lhs) {
+                            rhs
+                        } else {
+                            lhs
+                        }
+                    },
+                });
+            },
+        }
+    }
+
+    stack.pop().unwrap_or_default()
+}
+
+/// The obligations of shipping a single package
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PackageObligations {
+    pub package_name: String,
+    pub package_version: String,
+    pub license: Option<String>,
+    /// The category of the license, `None` if the license of the package is unknown
+    pub category: Option<LicenseCategory>,
+    pub linkage: Linkage,
+    pub obligations: Vec<Obligation>,
+}
+
+/// The obligations of shipping all packages, see [`obligations_report`].
+///
+/// Serializes to JSON for further processing, or renders as a markdown table via [`Display`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ObligationsReport {
+    pub packages: Vec<PackageObligations>,
+}
+
+impl ObligationsReport {
+    /// The packages of the given category
+    pub fn in_category(&self, category: LicenseCategory) -> impl Iterator<Item = &PackageObligations> {
+        self.packages.iter().filter(move |p| p.category == Some(category))
+    }
+}
+
+impl Display for ObligationsReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "| Package | Version | License | Category | Linkage | Obligations |")?;
+        writeln!(f, "|---|---|---|---|---|---|")?;
+
+        for p in &self.packages {
+            let obligations: Vec<_> = p.obligations.iter().map(ToString::to_string).collect();
+            writeln!(
+                f,
+                "| {} | {} | {} | {} | {} | {} |",
+                p.package_name,
+                p.package_version,
+                p.license.as_deref().unwrap_or("unknown"),
+                p.category.map_or_else(|| "unknown".to_owned(), |c| c.to_string()),
+                p.linkage,
+                obligations.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Classify the licenses of the packages and list what shipping each of them requires.
+///
+/// The obligations of all licenses that have to be complied with (see [`license_categories`]) are combined.
+/// Packages of the public domain category require no attribution, packages with an unknown license have to be
+/// reviewed, `NOTICE` files have to be reproduced for all packages
+/// that ship one. The [`Linkage`] of the packages (static if not known) is taken into account: statically linked
+/// LGPL libraries require [`Obligation::Relinking`] and tool-only packages are not shipped, so they have no
+/// obligations at all.
+pub fn obligations_report(packages: &[Package]) -> ObligationsReport {
+    let packages = packages
+        .iter()
+        .map(|p| {
+            let categories = p.license_spdx.as_ref().map(|l| license_categories(&l.0)).unwrap_or_default();
+            let category = categories.last().copied();
+            let linkage = p.linkage.unwrap_or(Linkage::Static);
+
+            let mut obligations = BTreeSet::new();
+            if category != Some(LicenseCategory::PublicDomain) {
+                obligations.insert(Obligation::Attribution);
+            }
+            if p.license_files.iter().any(|l| l.kind == FileKind::Notice) {
+                obligations.insert(Obligation::NoticeReproduction);
+            }
+            if categories.is_empty() {
+                obligations.insert(Obligation::ReviewTerms);
+            }
+
+            // every license of an `AND` expression has to be complied with
+            for category in &categories {
+                match category {
+                    LicenseCategory::PublicDomain | LicenseCategory::Permissive => {},
+                    LicenseCategory::WeakCopyleft => {
+                        obligations.insert(Obligation::SourceOffer);
+                        if linkage == Linkage::Static && p.license_spdx.as_ref().is_some_and(requires_relinking) {
+                            obligations.insert(Obligation::Relinking);
+                        }
+                    },
+                    LicenseCategory::StrongCopyleft => {
+                        obligations.extend([Obligation::SourceOffer, Obligation::CombinedWorkSource])
+                    },
+                    LicenseCategory::NetworkCopyleft => obligations.extend([
+                        Obligation::SourceOffer,
+                        Obligation::CombinedWorkSource,
+                        Obligation::NetworkSourceOffer,
+                    ]),
+                    LicenseCategory::Proprietary | LicenseCategory::ProprietaryUnlicensed => {
+                        obligations.insert(Obligation::ReviewTerms);
+                    },
+                }
+            }
+
+            if linkage == Linkage::ToolOnly {
+                obligations.clear();
+            }
+
+            PackageObligations {
+                package_name: p.package_name.clone(),
+                package_version: p.package_version.clone(),
+                license: p.license_spdx.as_ref().map(ToString::to_string),
+                category,
+                linkage,
+                obligations: obligations.into_iter().collect(),
+            }
+        })
+        .collect();
+
+    ObligationsReport { packages }
+}
+
+/// Whether an LGPL license is part of the expression, whose libraries have to be relinkable when linked statically
+fn requires_relinking(expression: &crate::Expression) -> bool {
+    expression.requirements().any(|r| match &r.req.license {
+        spdx::LicenseItem::Spdx { id, .. } => id.name.starts_with("LGPL-"),
+        spdx::LicenseItem::Other { .. } => false,
+    })
+}
+
+/// A `NOTICE` file of an Apache-2.0 package that an attribution does not reproduce, see [`verify_notices`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MissingNotice {
+    pub package_name: String,
+    pub package_version: String,
+    /// Name of the `NOTICE` file within the package
+    pub file: String,
+}
+
+impl Display for MissingNotice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The attribution does not reproduce the {} of '{} {}'",
+            self.file, self.package_name, self.package_version
+        )
+    }
+}
+
+/// The result of [`verify_notices`]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NoticeReport {
+    pub missing: Vec<MissingNotice>,
+}
+
+impl NoticeReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /// Fail with [`AggregateError::NoticesNotReproduced`] if notices are missing
+    pub fn into_result(self) -> Result<Self, AggregateError> {
+        if self.is_ok() {
+            Ok(self)
+        } else {
+            Err(AggregateError::NoticesNotReproduced(self))
+        }
+    }
+}
+
+impl Display for NoticeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for missing in &self.missing {
+            writeln!(f, "- {missing}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Verify that a generated attribution (e.g. a rendered HTML or text file) reproduces the `NOTICE` files of all
+/// shipped Apache-2.0 packages, as required by Apache-2.0 §4(d).
+///
+/// The texts are compared without regard to whitespace and the entities of HTML (`&amp;`, `&lt;`, ...), so the
+/// reflowing and escaping of templates does not matter. The packages need their complete texts, i.e. they have to be
+/// verified before [`crate::truncate_texts`].
+pub fn verify_notices(packages: &[Package], attribution: &str) -> NoticeReport {
+    let attribution = normalize_notice(attribution);
+    let apache = |p: &Package| {
+        let is_apache = |r: &spdx::expression::ExpressionReq| {
+            matches!(&r.req.license, spdx::LicenseItem::Spdx { id, .. } if id.name == "Apache-2.0")
+        };
+        p.license_spdx.as_ref().is_some_and(|l| l.requirements().any(is_apache))
+    };
+
+    let missing = packages
+        .iter()
+        .filter(|p| p.linkage != Some(Linkage::ToolOnly) && apache(p))
+        .flat_map(|p| {
+            p.license_files
+                .iter()
+                .filter(|l| l.kind == FileKind::Notice)
+                .filter(|l| {
+                    let notice = normalize_notice(&l.text);
+                    !notice.is_empty() && !attribution.contains(&notice)
+                })
+                .map(|l| MissingNotice {
+                    package_name: p.package_name.clone(),
+                    package_version: p.package_version.clone(),
+                    file: l.name.clone(),
+                })
+        })
+        .collect();
+
+    NoticeReport { missing }
+}
+
+/// The words of the text, with the most common HTML entities decoded
+fn normalize_notice(text: &str) -> String {
+    let decoded = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(expression: &str) -> LicenseCategory {
+        classify_expression(&spdx::Expression::parse(expression).unwrap())
+    }
+
+    #[test]
+    fn expression_categories() {
+        assert_eq!(classify("MIT"), LicenseCategory::Permissive);
+        assert_eq!(classify("CC0-1.0"), LicenseCategory::PublicDomain);
+        assert_eq!(classify("MPL-2.0"), LicenseCategory::WeakCopyleft);
+        assert_eq!(classify("GPL-3.0-only"), LicenseCategory::StrongCopyleft);
+        assert_eq!(classify("AGPL-3.0-or-later"), LicenseCategory::NetworkCopyleft);
+        assert_eq!(classify("LicenseRef-vendor"), LicenseCategory::Proprietary);
+        assert_eq!(classify(ALL_RIGHTS_RESERVED_REF), LicenseCategory::ProprietaryUnlicensed);
+        assert_eq!(classify(PUBLIC_DOMAIN_REF), LicenseCategory::PublicDomain);
+
+        // the least restrictive alternative and the most restrictive part
+        assert_eq!(classify("GPL-2.0-only OR MIT"), LicenseCategory::Permissive);
+        assert_eq!(classify("MIT AND LGPL-2.1-only"), LicenseCategory::WeakCopyleft);
+        assert_eq!(classify("(GPL-3.0-only OR MPL-2.0) AND MIT"), LicenseCategory::WeakCopyleft);
+        assert_eq!(classify("GPL-3.0-only AND (MIT OR Unlicense)"), LicenseCategory::StrongCopyleft);
+        assert_eq!(classify("GPL-3.0-only AND LicenseRef-vendor"), LicenseCategory::Proprietary);
+
+        // all parts of the least restrictive alternative
+        let categories = |expression| license_categories(&spdx::Expression::parse(expression).unwrap());
+        assert_eq!(
+            categories("GPL-3.0-only AND LicenseRef-vendor"),
+            BTreeSet::from([LicenseCategory::StrongCopyleft, LicenseCategory::Proprietary])
+        );
+        assert_eq!(
+            categories("(AGPL-3.0-only AND MIT) OR LicenseRef-vendor"),
+            BTreeSet::from([LicenseCategory::Permissive, LicenseCategory::NetworkCopyleft])
+        );
+        assert_eq!(categories("MIT OR Apache-2.0"), BTreeSet::from([LicenseCategory::Permissive]));
+    }
+
+    #[test]
+    fn obligations_of_all_parts() {
+        let package = |license: &str| -> Package {
+            let json = serde_json::json!({"package_name": "a", "package_version": "1.0.0", "package_url": null,
+                "license_spdx": license, "license_files": []});
+            serde_json::from_value(json).unwrap()
+        };
+        let packages = [package("GPL-3.0-only AND LicenseRef-vendor"), package("MIT OR GPL-3.0-only")];
+        let report = obligations_report(&packages);
+
+        assert_eq!(report.packages[0].category, Some(LicenseCategory::Proprietary));
+        assert_eq!(
+            report.packages[0].obligations,
+            [Obligation::Attribution, Obligation::SourceOffer, Obligation::CombinedWorkSource, Obligation::ReviewTerms]
+        );
+        assert_eq!(report.packages[1].obligations, [Obligation::Attribution]);
+    }
+
+    #[test]
+    fn notices() {
+        let package = |name: &str, license: &str, linkage: &str, notice: &str| -> Package {
+            let json = serde_json::json!({"package_name": name, "package_version": "1.0.0", "package_url": null,
+                "license_spdx": license, "linkage": linkage, "license_files": [
+                    {"name": "LICENSE", "spdx": null, "text": "Apache License"},
+                    {"name": "NOTICE", "spdx": null, "kind": "notice", "text": notice},
+                ]});
+            serde_json::from_value(json).unwrap()
+        };
+        let packages = [
+            package("reflowed", "Apache-2.0", "static", "Copyright 2024 A & B\n  <team@example.com>"),
+            package("missing", "MIT OR Apache-2.0", "static", "Copyright 2024 C"),
+            package("tool", "Apache-2.0", "tool-only", "Copyright 2024 D"),
+            package("mit", "MIT", "static", "Copyright 2024 E"),
+            package("empty", "Apache-2.0", "static", " \n"),
+        ];
+
+        let attribution = "<p>Copyright 2024 A &amp; B &lt;team@example.com&gt;</p>";
+        let report = verify_notices(&packages, attribution);
+        let missing: Vec<_> = report.missing.iter().map(|m| (m.package_name.as_str(), m.file.as_str())).collect();
+        assert_eq!(missing, [("missing", "NOTICE")]);
+        assert!(!report.is_ok());
+        assert_eq!(report.to_string(), "- The attribution does not reproduce the NOTICE of 'missing 1.0.0'\n");
+        assert!(matches!(report.into_result(), Err(AggregateError::NoticesNotReproduced(r)) if r.missing.len() == 1));
+
+        let attribution = format!("{attribution}\nCopyright 2024 C");
+        assert!(verify_notices(&packages, &attribution).into_result().is_ok());
+
+        // a part of the notice is not enough
+        let quoted = [package("quoted", "Apache-2.0", "static", "\"Quoted\" Software\nCopyright 2024 F")];
+        assert!(verify_notices(&quoted, "<p>&quot;Quoted&quot; Software Copyright 2024 F</p>").is_ok());
+        assert!(!verify_notices(&quoted, "<p>Copyright 2024 F</p>").is_ok());
+    }
+}
@@ -0,0 +1,265 @@
+//! Collection of the source archives of the packages that have to be offered in source form, see
+//! [`export_source_offer`]
+
+use crate::{
+    obligations::{obligations_report, Obligation},
+    AggregateError, Diagnostic, DiagnosticKind, Diagnostics, GatherOptions, Origin, Package, Utf8Path, Utf8PathBuf,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Name of the manifest in the source offer directory
+pub const SOURCE_OFFER_MANIFEST: &str = "manifest.json";
+
+/// The source archive of a package, an entry of the manifest of [`export_source_offer`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SourceArchive {
+    pub package_name: String,
+    pub package_version: String,
+    pub license: Option<String>,
+    /// The archive, relative to the source offer directory
+    pub path: String,
+    /// The hex encoded SHA-256 of the archive
+    pub sha256: String,
+    /// Where the archive was copied or downloaded from
+    pub source: String,
+}
+
+/// Copy the exact source archives of all packages with an [`Obligation::SourceOffer`] (i.e. under a copyleft license
+/// like the GPL, LGPL or MPL and not tool-only) into the given directory, along with a manifest of them and their
+/// hashes as JSON ([`SOURCE_OFFER_MANIFEST`]), so the directory can be shipped to satisfy written offers.
+/// Returns the entries of the manifest.
+///
+/// The `.crate` archives of crates are taken from the registry cache of cargo (verified with the checksum of the
+/// Cargo.lock), the archives of thirdparty packages from their [`Package::source_archive`] (stored with the name and
+/// version of the package as prefix of the file name). Missing archives are downloaded unless the options are offline
+/// (with the `network` feature). Packages without an archive, e.g. git dependencies, are reported as
+/// [`DiagnosticKind::MissingSourceArchive`], their sources have to be offered separately.
+pub fn export_source_offer<P: AsRef<Utf8Path>>(
+    packages: &[Package],
+    dir: P,
+    options: &GatherOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<SourceArchive>, AggregateError> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(write_err(dir))?;
+
+    let report = obligations_report(packages);
+    let mut manifest = vec![];
+    for (p, obligations) in packages.iter().zip(&report.packages) {
+        if !obligations.obligations.contains(&Obligation::SourceOffer) {
+            continue;
+        }
+
+        let Some((file_name, source, bytes)) = source_archive(p, options, diagnostics) else {
+            diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::MissingSourceArchive,
+                    format!(
+                        "No source archive found for '{} {}' ({}), its sources have to be offered separately",
+                        p.package_name, p.package_version, obligations.license.as_deref().unwrap_or("unknown")
+                    ),
+                )
+                .with_package(&p.package_name, &p.package_version),
+            );
+            continue;
+        };
+
+        let file_name = file_name.replace(|c: char| !(c.is_ascii_alphanumeric() || "._+-".contains(c)), "_");
+        let path = dir.join(&file_name);
+        std::fs::write(&path, &bytes).map_err(write_err(&path))?;
+
+        manifest.push(SourceArchive {
+            package_name: p.package_name.clone(),
+            package_version: p.package_version.clone(),
+            license: obligations.license.clone(),
+            path: file_name,
+            sha256: format!("{:x}", Sha256::digest(&bytes)),
+            source,
+        });
+    }
+
+    let path = dir.join(SOURCE_OFFER_MANIFEST);
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| write_err(&path)(e.into()))?;
+    std::fs::write(&path, json).map_err(write_err(&path))?;
+
+    Ok(manifest)
+}
+
+/// The file name, the origin and the contents of the source archive of the package
+#[cfg_attr(not(feature = "network"), allow(unused_variables))]
+fn source_archive(
+    p: &Package,
+    options: &GatherOptions,
+    diagnostics: &mut Diagnostics,
+) -> Option<(String, String, Vec<u8>)> {
+    if let Some(archive) = &p.source_archive {
+        // archives are often named after the version only (e.g. `v1.2.tar.gz` or `source.tar.gz`), prefixed with the
+        // package the names of several packages can't collide
+        let prefix = format!("{}-{}", p.package_name, p.package_version);
+        let file_name = match archive.rsplit(['/', '\\']).next().filter(|name| !name.is_empty()) {
+            Some(name) if name.starts_with(&prefix) => name.to_owned(),
+            Some(name) => format!("{prefix}-{name}"),
+            None => prefix,
+        };
+
+        if !archive.contains("://") {
+            return match std::fs::read(archive) {
+                Ok(bytes) => Some((file_name, archive.clone(), bytes)),
+                Err(e) => {
+                    tracing::debug!("unable to read the source archive {archive}: {e}");
+                    None
+                },
+            };
+        }
+
+        #[cfg(feature = "network")]
+        if !options.offline {
+            return download(archive, options, p, diagnostics).map(|bytes| (file_name, archive.clone(), bytes));
+        }
+
+        return None;
+    }
+
+    // only crates of registries have a `.crate` archive, the sources of git and path dependencies are not packaged
+    if !matches!(p.origin, None | Some(Origin::Registry { .. } | Origin::Vendored)) {
+        return None;
+    }
+
+    let file_name = format!("{}-{}.crate", p.package_name, p.package_version);
+    let matches_checksum = |bytes: &[u8]| {
+        p.checksum.as_ref().is_none_or(|checksum| format!("{:x}", Sha256::digest(bytes)) == *checksum)
+    };
+
+    for cache in registry_caches() {
+        let path = cache.join(&file_name);
+        if let Ok(bytes) = std::fs::read(&path).map_err(|e| tracing::trace!("no source archive {path}: {e}")) {
+            if matches_checksum(&bytes) {
+                return Some((file_name, path.to_string(), bytes));
+            }
+        }
+    }
+
+    #[cfg(feature = "network")]
+    if !options.offline && p.origin.is_none() {
+        let url = format!("https://static.crates.io/crates/{0}/{file_name}", p.package_name);
+        return download(&url, options, p, diagnostics)
+            .filter(|bytes| matches_checksum(bytes))
+            .map(|bytes| (file_name, url, bytes));
+    }
+
+    None
+}
+
+#[cfg(feature = "network")]
+fn download(url: &str, options: &GatherOptions, p: &Package, diagnostics: &mut Diagnostics) -> Option<Vec<u8>> {
+    let response = options
+        .http
+        .build_client()
+        .and_then(|client| Ok(options.http.send(client.get(url))?.error_for_status()?.bytes()?));
+
+    match response {
+        Ok(bytes) => Some(bytes.to_vec()),
+        Err(e) => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::MissingSourceArchive,
+                    format!(
+                        "Unable to download the source archive {url} of '{} {}': {e}",
+                        p.package_name, p.package_version
+                    ),
+                )
+                .with_package(&p.package_name, &p.package_version),
+            );
+            None
+        },
+    }
+}
+
+/// The directories in which cargo caches the downloaded `.crate` archives, one per registry
+pub(crate) fn registry_caches() -> Vec<Utf8PathBuf> {
+    let home = || std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"));
+    let cargo_home = std::env::var("CARGO_HOME")
+        .map(Utf8PathBuf::from)
+        .or_else(|_| home().map(|home| Utf8PathBuf::from(home).join(".cargo")));
+    let Ok(cache) = cargo_home.map(|home| home.join("registry").join("cache")) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<_> = match cache.read_dir_utf8() {
+        Ok(entries) => entries.filter_map(Result::ok).map(|e| e.into_path()).collect(),
+        Err(e) => {
+            tracing::debug!("unable to search the registry cache {cache}: {e}");
+            Vec::new()
+        },
+    };
+    dirs.sort();
+    dirs
+}
+
+fn write_err(path: &Utf8Path) -> impl FnOnce(std::io::Error) -> AggregateError + '_ {
+    move |source| AggregateError::Write { path: path.to_owned(), source }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, license: &str, archive: Option<&Utf8Path>) -> Package {
+        let mut p: Package = serde_json::from_value(serde_json::json!({
+            "package_name": name,
+            "package_version": "1.0.0",
+            "package_url": null,
+            "license_spdx": license,
+            "license_files": [],
+        }))
+        .unwrap();
+        p.source_archive = archive.map(Utf8Path::to_string);
+        p.origin = Some(Origin::Git { url: format!("https://example.org/{name}.git"), commit: None, reference: None });
+        p
+    }
+
+    #[test]
+    fn source_offer() {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let dir = dir.join(format!("tla-offer-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for name in ["a", "b"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            std::fs::write(dir.join(name).join("source.tar.gz"), name).unwrap();
+        }
+        std::fs::write(dir.join("c-1.0.0.tar.gz"), "c").unwrap();
+
+        let packages = [
+            package("a", "GPL-3.0", Some(&dir.join("a").join("source.tar.gz"))),
+            package("b", "LGPL-2.1", Some(&dir.join("b").join("source.tar.gz"))),
+            package("c", "MPL-2.0", Some(&dir.join("c-1.0.0.tar.gz"))),
+            package("mit", "MIT", Some(&dir.join("a").join("source.tar.gz"))),
+            package("git", "GPL-3.0", None),
+        ];
+        let offer = dir.join("offer");
+        let options = GatherOptions::new().offline(true);
+        let mut diagnostics = Diagnostics::new();
+        let manifest = export_source_offer(&packages, &offer, &options, &mut diagnostics).unwrap();
+
+        let paths: Vec<_> = manifest.iter().map(|a| (a.package_name.as_str(), a.path.as_str())).collect();
+        assert_eq!(paths, [("a", "a-1.0.0-source.tar.gz"), ("b", "b-1.0.0-source.tar.gz"), ("c", "c-1.0.0.tar.gz")]);
+        for archive in &manifest {
+            let bytes = std::fs::read(offer.join(&archive.path)).unwrap();
+            assert_eq!(bytes, archive.package_name.as_bytes());
+            assert_eq!(archive.sha256, format!("{:x}", Sha256::digest(&bytes)));
+        }
+        assert_eq!(manifest[0].license.as_deref(), Some("GPL-3.0"));
+        assert_eq!(manifest[0].source, dir.join("a").join("source.tar.gz").as_str());
+
+        let written: Vec<SourceArchive> =
+            serde_json::from_str(&std::fs::read_to_string(offer.join(SOURCE_OFFER_MANIFEST)).unwrap()).unwrap();
+        assert_eq!(written.len(), 3);
+
+        let missing: Vec<_> = diagnostics.iter().filter(|d| d.kind == DiagnosticKind::MissingSourceArchive).collect();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].package_name.as_deref(), Some("git"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,712 @@
+use crate::{
+    analysis::{DetectionOptions, MinimizationOptions, SpdxParsing},
+    policy::Policy,
+    AggregateError, Attribution, Expression, LicenseCache, LicenseOverride, Linkage, Package, Utf8Path,
+};
+use cargo_about::licenses::KrateLicense;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+#[cfg(feature = "network")]
+use std::time::Duration;
+
+/// Invoked for every gathered crate before it is converted into a [`crate::Package`].
+///
+/// The hook may mutate the entry (e.g. to annotate or correct it) and returns `false` to skip the crate entirely.
+pub trait KrateHook: Send + Sync {
+    fn process(&self, krate_license: &mut KrateLicense<'_>) -> bool;
+}
+
+impl<F> KrateHook for F
+where
+    F: Fn(&mut KrateLicense<'_>) -> bool + Send + Sync,
+{
+    fn process(&self, krate_license: &mut KrateLicense<'_>) -> bool {
+        self(krate_license)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(pub(crate) Vec<Arc<dyn KrateHook>>);
+
+impl Debug for Hooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{} hooks]", self.0.len())
+    }
+}
+
+/// The packages of a previous run, keyed by name and version
+#[derive(Clone, Default)]
+pub(crate) struct Previous(Arc<HashMap<(String, String), Package>>);
+
+impl Previous {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn get(&self, name: &str, version: &str) -> Option<Package> {
+        self.0.get(&(name.to_owned(), version.to_owned())).cloned()
+    }
+
+    pub(crate) fn packages(&self) -> Vec<Package> {
+        self.0.values().cloned().collect()
+    }
+}
+
+impl Debug for Previous {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{} previous packages]", self.0.len())
+    }
+}
+
+/// What happens with a crate whose license could not be determined by cargo-about
+/// and for which no license is configured (see [`GatherOptions::unknown_license_override`])
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnknownLicenseAction {
+    /// Leave the license of the package unknown and report a warning
+    #[default]
+    Warn,
+    /// Use the licenses detected in the license files of the crate (all of them are required),
+    /// falling back to [`UnknownLicenseAction::Warn`] if none were detected
+    Detect,
+    /// Leave the license of the package unknown and report it for manual review
+    /// (see [`crate::Diagnostics::needs_review`])
+    Review,
+    /// Abort the run with [`AggregateError::UnknownLicense`]
+    Error,
+}
+
+/// Options for resolving the dependency graph and gathering the licenses of the crates
+///
+/// # Example
+/// `GatherOptions::new().features(vec!["cuda".to_owned()]).targets(vec!["x86_64-unknown-linux-musl".to_owned()])`
+#[derive(Clone, Debug)]
+pub struct GatherOptions {
+    pub(crate) features: Vec<String>,
+    pub(crate) no_default_features: bool,
+    pub(crate) all_features: bool,
+    pub(crate) targets: Vec<String>,
+    pub(crate) offline: bool,
+    pub(crate) frozen: bool,
+    pub(crate) locked: bool,
+    pub(crate) allow_lockfile_generation: bool,
+    pub(crate) include_dev_dependencies: bool,
+    pub(crate) ignore: Vec<String>,
+    pub(crate) ignore_license_files: Vec<String>,
+    pub(crate) confidence_threshold: f32,
+    pub(crate) detection: DetectionOptions,
+    pub(crate) hooks: Hooks,
+    pub(crate) cancelled: Option<Arc<AtomicBool>>,
+    pub(crate) cache: Option<LicenseCache>,
+    pub(crate) previous: Previous,
+    pub(crate) scan_spdx_headers: bool,
+    pub(crate) spdx_parsing: SpdxParsing,
+    pub(crate) unknown_license: UnknownLicenseAction,
+    pub(crate) unknown_license_overrides: HashMap<String, Expression>,
+    pub(crate) license_overrides: Vec<LicenseOverride>,
+    pub(crate) attributions: Vec<Attribution>,
+    pub(crate) linkage: HashMap<String, Linkage>,
+    pub(crate) minimization: MinimizationOptions,
+    pub(crate) policy: Policy,
+    pub(crate) project_licenses: Vec<spdx::Licensee>,
+    #[cfg(feature = "network")]
+    pub(crate) http: HttpOptions,
+    #[cfg(feature = "network")]
+    pub(crate) crates_io_fallback: bool,
+    #[cfg(feature = "network")]
+    pub(crate) registries: Vec<Registry>,
+    #[cfg(all(feature = "network", feature = "tokio"))]
+    pub(crate) concurrent: Option<crate::nonblocking::Concurrent>,
+}
+
+impl Default for GatherOptions {
+    fn default() -> Self {
+        Self {
+            features: Vec::new(),
+            no_default_features: false,
+            all_features: false,
+            targets: Vec::new(),
+            offline: false,
+            frozen: false,
+            locked: true,
+            allow_lockfile_generation: false,
+            include_dev_dependencies: true,
+            ignore: Vec::new(),
+            ignore_license_files: Vec::new(),
+            confidence_threshold: 0.8,
+            detection: DetectionOptions::default(),
+            hooks: Hooks::default(),
+            cancelled: None,
+            cache: None,
+            previous: Previous::default(),
+            scan_spdx_headers: false,
+            spdx_parsing: SpdxParsing::default(),
+            unknown_license: UnknownLicenseAction::default(),
+            unknown_license_overrides: HashMap::new(),
+            license_overrides: Vec::new(),
+            attributions: Vec::new(),
+            linkage: HashMap::new(),
+            minimization: MinimizationOptions::default(),
+            policy: Policy::default(),
+            project_licenses: Vec::new(),
+            #[cfg(feature = "network")]
+            http: HttpOptions::default(),
+            #[cfg(feature = "network")]
+            crates_io_fallback: false,
+            #[cfg(feature = "network")]
+            registries: Vec::new(),
+            #[cfg(all(feature = "network", feature = "tokio"))]
+            concurrent: None,
+        }
+    }
+}
+
+impl GatherOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cargo features to enable
+    pub fn features(mut self, features: Vec<String>) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn no_default_features(mut self, no_default_features: bool) -> Self {
+        self.no_default_features = no_default_features;
+        self
+    }
+
+    pub fn all_features(mut self, all_features: bool) -> Self {
+        self.all_features = all_features;
+        self
+    }
+
+    /// Only include dependencies for the given target triples, overrides `targets` of the configuration
+    pub fn targets(mut self, targets: Vec<String>) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// Prevent cargo from accessing the network during resolution
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Require the Cargo.lock to be up-to-date and prevent cargo from accessing the network
+    pub fn frozen(mut self, frozen: bool) -> Self {
+        self.frozen = frozen;
+        self
+    }
+
+    /// Require the Cargo.lock to be up-to-date, enabled by default
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Resolve the dependencies and write a Cargo.lock if there is none (e.g. in a freshly cloned library crate),
+    /// instead of failing because of [`GatherOptions::locked`] or [`GatherOptions::frozen`]
+    pub fn allow_lockfile_generation(mut self, allow_lockfile_generation: bool) -> Self {
+        self.allow_lockfile_generation = allow_lockfile_generation;
+        self
+    }
+
+    /// Whether to include dev-dependencies, enabled by default.
+    /// Dev-dependencies are always excluded if `ignore-dev-dependencies` is set in the configuration.
+    pub fn include_dev_dependencies(mut self, include_dev_dependencies: bool) -> Self {
+        self.include_dev_dependencies = include_dev_dependencies;
+        self
+    }
+
+    /// Glob patterns of crate names that are excluded from the collected packages (e.g. `tentris-*`)
+    pub fn ignore(mut self, patterns: Vec<String>) -> Self {
+        self.ignore = patterns;
+        self
+    }
+
+    /// Glob patterns of license files that are not collected, relative to the directory of their package,
+    /// e.g. `**/LICENSE-THIRD-PARTY` or `tests/**`
+    pub fn ignore_license_files(mut self, patterns: Vec<String>) -> Self {
+        self.ignore_license_files = patterns;
+        self
+    }
+
+    /// The minimum confidence (between 0 and 1) for a file to be considered a license file of a crate, defaults to 0.8
+    pub fn confidence_threshold(mut self, confidence_threshold: f32) -> Self {
+        self.confidence_threshold = confidence_threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    /// How licenses are detected in the texts of thirdparty packages (see [`crate::augment_licenses_with`])
+    pub fn detection(mut self, detection: DetectionOptions) -> Self {
+        self.detection = detection;
+        self
+    }
+
+    /// Register a hook that is invoked for every gathered crate, hooks run in the order of registration
+    pub fn hook<H: KrateHook + 'static>(mut self, hook: H) -> Self {
+        self.hooks.0.push(Arc::new(hook));
+        self
+    }
+
+    /// Abort the run with [`AggregateError::Cancelled`] once the flag is set (e.g. from a GUI or another thread).
+    ///
+    /// The flag is checked before and after resolution and gathering, between the conversion of the individual crates
+    /// and between the sources of an [`crate::Aggregator`]. The gathering of cargo-about itself (scanning of the
+    /// crate sources and remote lookups) is not interrupted.
+    pub fn cancel_flag(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = Some(cancelled);
+        self
+    }
+
+    /// Cache the gathered packages of crates from crates.io on disk, see [`LicenseCache`]
+    pub fn cache(mut self, cache: LicenseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Disable a previously set cache
+    pub fn no_cache(mut self) -> Self {
+        self.cache = None;
+        self
+    }
+
+    /// Incremental mode: reuse the packages of a previous run (e.g. loaded with [`crate::load_packages`]) for all crates
+    /// from crates.io whose version did not change in the lockfile.
+    ///
+    /// If every crate from crates.io is covered by the previous run (or the [`LicenseCache`]), gathering runs
+    /// without remote lookups and only the remaining (path and git) crates are converted anew.
+    /// [`KrateHook`]s are not invoked for reused crates. An [`crate::Aggregator`] also reports the crates whose license
+    /// changed with a version bump since the previous run (see [`crate::changes::detect_license_changes`]).
+    pub fn previous(mut self, packages: Vec<Package>) -> Self {
+        self.previous = Previous(Arc::new(
+            packages
+                .into_iter()
+                .map(|p| ((p.package_name.clone(), p.package_version.clone()), p))
+                .collect(),
+        ));
+        self
+    }
+
+    /// Read the SPDX headers of all source files of the crates and report files whose license is not part of the
+    /// crate license (see [`crate::check_spdx_headers`]). This reads every file of every crate, so it is disabled
+    /// by default. Crates taken from the cache or a previous run are not scanned.
+    pub fn scan_spdx_headers(mut self, scan_spdx_headers: bool) -> Self {
+        self.scan_spdx_headers = scan_spdx_headers;
+        self
+    }
+
+    /// How nonstandard SPDX expressions in thirdparty files are treated, normalized by default
+    pub fn spdx_parsing(mut self, spdx_parsing: SpdxParsing) -> Self {
+        self.spdx_parsing = spdx_parsing;
+        self
+    }
+
+    /// What happens with crates whose license is unknown, a warning is reported by default
+    pub fn unknown_license(mut self, unknown_license: UnknownLicenseAction) -> Self {
+        self.unknown_license = unknown_license;
+        self
+    }
+
+    /// The license of the crate with the given name, if cargo-about is unable to determine it
+    pub fn unknown_license_override<S: Into<String>>(mut self, krate: S, license: Expression) -> Self {
+        self.unknown_license_overrides.insert(krate.into(), license);
+        self
+    }
+
+    /// Replace the license of the matching crates, whatever their metadata says.
+    /// The first matching override of a crate applies.
+    pub fn license_override(mut self, license_override: LicenseOverride) -> Self {
+        self.license_overrides.push(license_override);
+        self
+    }
+
+    /// Add a notice to the matching packages of every source (see [`Attribution`]) after they were collected
+    pub fn attribution(mut self, attribution: Attribution) -> Self {
+        self.attributions.push(attribution);
+        self
+    }
+
+    /// How the package with the given name is linked into the shipped artifact, which is taken into account by the
+    /// obligations and the compatibility of an [`crate::Aggregator`]. Replaces the linkage declared by thirdparty files.
+    pub fn linkage<S: Into<String>>(mut self, package: S, linkage: Linkage) -> Self {
+        self.linkage.insert(package.into(), linkage);
+        self
+    }
+
+    /// How the licenses that remain after the minimization of an [`crate::Aggregator`] are chosen
+    pub fn minimization(mut self, minimization: MinimizationOptions) -> Self {
+        self.minimization = minimization;
+        self
+    }
+
+    /// The license policy that is enforced by [`crate::Aggregator::check_policy`]
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The licenses of the project itself, against which an [`crate::Aggregator`] checks the compatibility of the
+    /// dependencies (see [`crate::compatibility::check_compatibility`]). Nothing is checked if empty, the default.
+    pub fn project_licenses(mut self, project_licenses: Vec<spdx::Licensee>) -> Self {
+        self.project_licenses = project_licenses;
+        self
+    }
+
+    /// Options for the HTTP client used while gathering
+    #[cfg(feature = "network")]
+    pub fn http(mut self, http: HttpOptions) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Look up the url and license of crates from crates.io whose manifest has neither `repository` nor `homepage`,
+    /// or no `license`, and report licenses that differ from crates.io. Not done in offline mode.
+    /// Crates of alternative registries are looked up in their registry, see [`GatherOptions::registry`].
+    #[cfg(feature = "network")]
+    pub fn crates_io_fallback(mut self, crates_io_fallback: bool) -> Self {
+        self.crates_io_fallback = crates_io_fallback;
+        self
+    }
+
+    /// Register an alternative registry, so the metadata of its crates can be looked up like the ones of crates.io
+    /// (see [`GatherOptions::crates_io_fallback`])
+    #[cfg(feature = "network")]
+    pub fn registry(mut self, registry: Registry) -> Self {
+        self.registries.push(registry);
+        self
+    }
+
+    /// Send the remote lookups concurrently on the runtime, see [`crate::nonblocking`]
+    #[cfg(all(feature = "network", feature = "tokio"))]
+    pub(crate) fn concurrent(mut self, concurrent: crate::nonblocking::Concurrent) -> Self {
+        self.concurrent = Some(concurrent);
+        self
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), AggregateError> {
+        check_cancelled(self.cancelled.as_deref())
+    }
+
+    /// Run all hooks on the entry, returns whether the crate should be kept
+    pub(crate) fn run_hooks(&self, krate_license: &mut KrateLicense<'_>) -> bool {
+        self.hooks.0.iter().all(|hook| hook.process(krate_license))
+    }
+
+    /// Whether resolving the dependencies of the manifest writes a Cargo.lock, see
+    /// [`GatherOptions::allow_lockfile_generation`]
+    pub(crate) fn generates_lockfile(&self, cargo_toml: &Utf8Path) -> bool {
+        // the Cargo.lock of a workspace is next to its root manifest, i.e. in a parent of every member
+        let has_lockfile = || cargo_toml.ancestors().any(|dir| dir.join("Cargo.lock").is_file());
+        (self.locked || self.frozen) && self.allow_lockfile_generation && !has_lockfile()
+    }
+
+    pub(crate) fn lock_options(&self, cargo_toml: &Utf8Path) -> krates::LockOptions {
+        if self.generates_lockfile(cargo_toml) {
+            return krates::LockOptions { offline: self.offline || self.frozen, frozen: false, locked: false };
+        }
+
+        krates::LockOptions { offline: self.offline, frozen: self.frozen, locked: self.locked }
+    }
+
+    pub(crate) fn ignore_set(&self) -> Result<GlobSet, AggregateError> {
+        glob_set(&self.ignore)
+    }
+
+    pub(crate) fn ignore_license_files_set(&self) -> Result<GlobSet, AggregateError> {
+        glob_set(&self.ignore_license_files)
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, AggregateError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|source| AggregateError::Pattern { pattern: pattern.clone(), source })?;
+        builder.add(glob);
+    }
+
+    builder.build().map_err(|source| AggregateError::Pattern { pattern: patterns.join(", "), source })
+}
+
+pub(crate) fn check_cancelled(cancelled: Option<&AtomicBool>) -> Result<(), AggregateError> {
+    match cancelled {
+        Some(cancelled) if cancelled.load(Ordering::Relaxed) => Err(AggregateError::Cancelled),
+        _ => Ok(()),
+    }
+}
+
+/// Options for the HTTP client that fetches remote license files (e.g. of git clarifications).
+///
+/// The requests of this crate (repository fetches, registry, clearlydefined.io and OSV lookups) are retried with an
+/// exponential backoff if they fail with a connection error, a timeout, `429 Too Many Requests` or a server error.
+/// The lookups of cargo-about itself use a client of cargo-about, which honors `HTTPS_PROXY`
+/// but uses `clearly-defined-timeout-secs` of the configuration as timeout.
+/// Failed requests are not retried by cargo-about, the affected crates fall back to scanning their local sources.
+///
+/// # Example
+/// `HttpOptions::new().proxy("http://proxy.corp:3128").timeout(Duration::from_secs(10)).retries(3)`
+#[cfg(feature = "network")]
+#[derive(Clone, Debug, Default)]
+pub struct HttpOptions {
+    pub(crate) proxy: Option<String>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) retries: Option<u32>,
+    pub(crate) backoff: Option<Duration>,
+}
+
+/// Number of times a failed request is retried by default, see [`HttpOptions::retries`]
+#[cfg(feature = "network")]
+pub const DEFAULT_HTTP_RETRIES: u32 = 2;
+
+/// Delay before the first retry of a failed request by default, see [`HttpOptions::backoff`]
+#[cfg(feature = "network")]
+pub const DEFAULT_HTTP_BACKOFF: Duration = Duration::from_millis(500);
+
+/// An alternative registry (e.g. an Artifactory) with a crates.io compatible API, see [`GatherOptions::registry`]
+///
+/// # Example
+/// ```toml
+/// [registries.internal]
+/// index = "sparse+https://artifactory.example.org/api/cargo/crates/index/"
+/// token-env = "ARTIFACTORY_TOKEN"
+/// ```
+#[cfg(feature = "network")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Registry {
+    /// The index of the registry as configured in `.cargo/config.toml`
+    pub index: String,
+    /// The base url of the API, by default the `api` of the `config.json` of sparse indexes
+    pub api: Option<String>,
+    /// The environment variable holding the token for the `Authorization` header, if the registry requires one
+    pub token_env: Option<String>,
+}
+
+#[cfg(feature = "network")]
+impl Registry {
+    pub fn new<S: Into<String>>(index: S) -> Self {
+        Self { index: index.into(), api: None, token_env: None }
+    }
+
+    pub fn api<S: Into<String>>(mut self, api: S) -> Self {
+        self.api = Some(api.into());
+        self
+    }
+
+    pub fn token_env<S: Into<String>>(mut self, token_env: S) -> Self {
+        self.token_env = Some(token_env.into());
+        self
+    }
+
+    /// Whether the registry has the given index, e.g. the [`crate::Origin::Registry`] of a package
+    pub(crate) fn has_index(&self, index: &str) -> bool {
+        let normalize = |index: &str| index.trim_start_matches("registry+").trim_end_matches('/').to_owned();
+        normalize(&self.index) == normalize(index)
+    }
+}
+
+#[cfg(feature = "network")]
+impl HttpOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Proxy for all requests, without it the proxy is taken from `HTTPS_PROXY`/`HTTP_PROXY`
+    pub fn proxy<S: Into<String>>(mut self, proxy: S) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Timeout of a complete request, defaults to 30 seconds
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout for establishing a connection
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// The user agent of the requests, defaults to `tentris-license-aggregator/<version>`
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Number of times a request is retried after a transient failure, defaults to [`DEFAULT_HTTP_RETRIES`].
+    /// `0` disables retries.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    /// Delay before the first retry, which doubles with every further retry, defaults to [`DEFAULT_HTTP_BACKOFF`]
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// Send the request, retrying it with an exponential backoff as long as it fails transiently (see
+    /// [`HttpOptions`]). The response of the last attempt is returned, whatever its status.
+    pub(crate) fn send(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> Result<reqwest::blocking::Response, reqwest::Error> {
+        let retries = self.retries.unwrap_or(DEFAULT_HTTP_RETRIES);
+        let mut backoff = self.backoff.unwrap_or(DEFAULT_HTTP_BACKOFF);
+        for _ in 0..retries {
+            // requests with a streamed body can not be repeated
+            let Some(attempt) = request.try_clone() else {
+                break;
+            };
+            match attempt.send() {
+                Ok(response) if !is_transient_status(response.status()) => return Ok(response),
+                Err(e) if !(e.is_connect() || e.is_timeout() || e.is_request()) => return Err(e),
+                Ok(response) => tracing::debug!("Retrying {} after status {}", response.url(), response.status()),
+                Err(e) => tracing::debug!("Retrying the request after: {e}"),
+            }
+            std::thread::sleep(backoff);
+            backoff = backoff.saturating_mul(2);
+        }
+
+        request.send()
+    }
+
+    /// Like [`HttpOptions::send`], for the asynchronous client of [`crate::nonblocking`]
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn send_async(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let retries = self.retries.unwrap_or(DEFAULT_HTTP_RETRIES);
+        let mut backoff = self.backoff.unwrap_or(DEFAULT_HTTP_BACKOFF);
+        for _ in 0..retries {
+            let Some(attempt) = request.try_clone() else {
+                break;
+            };
+            match attempt.send().await {
+                Ok(response) if !is_transient_status(response.status()) => return Ok(response),
+                Err(e) if !(e.is_connect() || e.is_timeout() || e.is_request()) => return Err(e),
+                Ok(response) => tracing::debug!("Retrying {} after status {}", response.url(), response.status()),
+                Err(e) => tracing::debug!("Retrying the request after: {e}"),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = backoff.saturating_mul(2);
+        }
+
+        request.send().await
+    }
+
+    /// Like [`HttpOptions::build_client`], for the asynchronous client of [`crate::nonblocking`]
+    #[cfg(feature = "tokio")]
+    pub(crate) fn build_async_client(&self) -> Result<reqwest::Client, AggregateError> {
+        let mut builder = reqwest::Client::builder().user_agent(self.user_agent_or_default());
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    fn user_agent_or_default(&self) -> String {
+        self.user_agent
+            .clone()
+            .unwrap_or_else(|| concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_owned())
+    }
+
+    pub(crate) fn build_client(&self) -> Result<reqwest::blocking::Client, AggregateError> {
+        let mut builder = reqwest::blocking::Client::builder().user_agent(self.user_agent_or_default());
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// Whether a request that failed with the status might succeed when repeated
+#[cfg(feature = "network")]
+fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+#[cfg(all(test, feature = "network"))]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    /// A server that answers the requests with the given statuses, one connection per request
+    fn serve(statuses: &'static [u16]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (status, stream) in statuses.iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).unwrap();
+                let response = format!("HTTP/1.1 {status} X\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn retries_transient_failures() {
+        let url = serve(&[503, 429, 200]);
+        let http = HttpOptions::new().retries(2).backoff(Duration::from_millis(1));
+        let response = http.send(http.build_client().unwrap().get(url)).unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[test]
+    fn returns_the_last_response() {
+        let url = serve(&[503, 502]);
+        let http = HttpOptions::new().retries(1).backoff(Duration::from_millis(1));
+        let response = http.send(http.build_client().unwrap().get(url)).unwrap();
+        assert_eq!(response.status(), 502);
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let url = serve(&[404, 200]);
+        let http = HttpOptions::new().retries(2).backoff(Duration::from_millis(1));
+        let response = http.send(http.build_client().unwrap().get(url)).unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
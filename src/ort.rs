@@ -0,0 +1,298 @@
+//! Exchange of license corrections with the OSS Review Toolkit (ORT) via its package curations (`curations.yml`), so
+//! the overrides of the configuration and the curations of ORT based pipelines can be shared
+
+use crate::{AggregateError, Expression, LicenseOverride, Utf8Path};
+use krates::semver::{Comparator, Op, Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The ORT package type of rust crates, the first component of ORT identifiers like `Crate::serde:1.0.200`
+const CRATE_TYPE: &str = "Crate";
+
+/// A package curation of ORT, of which only the concluded license is used
+#[derive(Debug, Serialize, Deserialize)]
+struct Curation {
+    /// The ORT identifier of the package, e.g. `Crate::serde:1.0.200`
+    id: String,
+    #[serde(default)]
+    curations: CurationData,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CurationData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    concluded_license: Option<String>,
+}
+
+/// Load the curations of an ORT curations file as overrides (see [`LicenseOverride`]), so the file can be listed in
+/// `ort-curations` of the configuration.
+///
+/// Only the curations of crates (`Crate::<name>:<version>`) with a `concluded_license` are imported, the other
+/// curated fields are ignored. The version of the identifier may be empty (all versions), a version or an Ivy range
+/// like `[1.0.0,2.0.0)`.
+pub fn load_ort_curations<P: AsRef<Utf8Path>>(path: P) -> Result<Vec<LicenseOverride>, AggregateError> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .map_err(|source| AggregateError::ConfigRead { path: path.to_owned(), source })?;
+    let invalid = |reason: String| AggregateError::OrtCurations { path: path.to_owned(), reason };
+
+    parse_ort_curations(&content).map_err(invalid)
+}
+
+fn parse_ort_curations(content: &str) -> Result<Vec<LicenseOverride>, String> {
+    // an empty file (or one with comments only) is a YAML null
+    let curations: Option<Vec<Curation>> = serde_yaml_ng::from_str(content).map_err(|e| e.to_string())?;
+
+    let mut overrides = vec![];
+    for Curation { id, curations } in curations.unwrap_or_default() {
+        let Some(license) = curations.concluded_license else {
+            tracing::debug!("skipping the curation of {id} without a concluded license");
+            continue;
+        };
+
+        let mut components = id.splitn(4, ':');
+        let (Some(package_type), Some(_namespace), Some(name), version) =
+            (components.next(), components.next(), components.next(), components.next())
+        else {
+            return Err(format!("'{id}' is not an ORT identifier (type:namespace:name:version)"));
+        };
+        if !package_type.eq_ignore_ascii_case(CRATE_TYPE) {
+            tracing::debug!("skipping the curation of {id}, which is not a crate");
+            continue;
+        }
+
+        let version = parse_version(version.unwrap_or_default()).map_err(|reason| format!("{id}: {reason}"))?;
+        let license = Expression::from_str(&license)
+            .map_err(|e| format!("{id}: '{license}' is not a valid SPDX expression: {}", e.reason))?;
+
+        overrides.push(LicenseOverride {
+            name: name.to_owned(),
+            version,
+            license,
+            text: None,
+            license_file: None,
+        });
+    }
+
+    Ok(overrides)
+}
+
+/// The overrides as ORT curations, i.e. the contents of a `curations.yml` with a `concluded_license` per override.
+/// The version requirements become Ivy ranges, the license texts of the overrides are not part of the curations.
+pub fn ort_curations(overrides: &[LicenseOverride]) -> String {
+    let curations: Vec<_> = overrides
+        .iter()
+        .map(|o| Curation {
+            id: format!("{CRATE_TYPE}::{}:{}", o.name, o.version.as_ref().map(ivy_range).unwrap_or_default()),
+            curations: CurationData {
+                comment: Some("License override of the aggregator configuration".to_owned()),
+                concluded_license: Some(o.license.to_string()),
+            },
+        })
+        .collect();
+
+    let yaml = serde_yaml_ng::to_string(&curations).expect("curations are serializable");
+    format!("# Package curations of the OSS Review Toolkit (ORT)\n{yaml}")
+}
+
+/// Write the overrides as ORT curations to the given file, see [`ort_curations`]
+pub fn write_ort_curations<P: AsRef<Utf8Path>>(overrides: &[LicenseOverride], path: P) -> Result<(), AggregateError> {
+    let path = path.as_ref();
+    std::fs::write(path, ort_curations(overrides))
+        .map_err(|source| AggregateError::Write { path: path.to_owned(), source })
+}
+
+/// The version requirement of the version of an ORT identifier: empty for all versions, an Ivy range (with brackets
+/// or parentheses for exclusive bounds, e.g. `[1.0,2.0)` or `]1.0,2.0[`) or a single version
+fn parse_version(version: &str) -> Result<Option<VersionReq>, String> {
+    let version = version.trim();
+    if version.is_empty() {
+        return Ok(None);
+    }
+
+    let req = match version.split_once(',') {
+        Some((lower, upper)) if lower.starts_with(['[', ']', '(']) && upper.ends_with([']', '[', ')']) => {
+            let (lower_inclusive, lower) = (lower.starts_with('['), lower[1..].trim());
+            let (upper_inclusive, upper) = (upper.ends_with(']'), upper[..upper.len() - 1].trim());
+
+            let mut comparators = vec![];
+            if !lower.is_empty() {
+                comparators.push(format!("{}{lower}", if lower_inclusive { ">=" } else { ">" }));
+            }
+            if !upper.is_empty() {
+                comparators.push(format!("{}{upper}", if upper_inclusive { "<=" } else { "<" }));
+            }
+            if comparators.is_empty() {
+                return Ok(None);
+            }
+            comparators.join(", ")
+        },
+        _ => format!("={version}"),
+    };
+
+    VersionReq::parse(&req).map(Some).map_err(|e| format!("'{version}' is not a valid version or range: {e}"))
+}
+
+/// The version requirement as Ivy range (or a single version), the bounds of all comparators are intersected
+fn ivy_range(req: &VersionReq) -> String {
+    if let [c] = req.comparators.as_slice() {
+        if let (Op::Exact, Some(minor), Some(patch)) = (c.op, c.minor, c.patch) {
+            return Version { major: c.major, minor, patch, pre: c.pre.clone(), build: Default::default() }.to_string();
+        }
+    }
+
+    let mut lower: Option<(Version, bool)> = None;
+    let mut upper: Option<(Version, bool)> = None;
+    for c in &req.comparators {
+        let (l, u) = bounds(c);
+        if let Some(l) = l.filter(|l| lower.as_ref().is_none_or(|lower| l.0 > lower.0)) {
+            lower = Some(l);
+        }
+        if let Some(u) = u.filter(|u| upper.as_ref().is_none_or(|upper| u.0 < upper.0)) {
+            upper = Some(u);
+        }
+    }
+
+    let lower = match lower {
+        Some((v, inclusive)) => format!("{}{v}", if inclusive { '[' } else { '(' }),
+        None => "(".to_owned(),
+    };
+    let upper = match upper {
+        Some((v, inclusive)) => format!("{v}{}", if inclusive { ']' } else { ')' }),
+        None => ")".to_owned(),
+    };
+    format!("{lower},{upper}")
+}
+
+/// The lower and upper bound of a comparator, with whether they are inclusive
+type Bounds = (Option<(Version, bool)>, Option<(Version, bool)>);
+
+fn bounds(c: &Comparator) -> Bounds {
+    let version = |major, minor, patch| Version::new(major, minor, patch);
+    let base = Version {
+        major: c.major,
+        minor: c.minor.unwrap_or(0),
+        patch: c.patch.unwrap_or(0),
+        pre: c.pre.clone(),
+        build: Default::default(),
+    };
+    // the first version after all versions matching the given components, e.g. 1.3.0 for 1.2
+    let next = match (c.minor, c.patch) {
+        (None, _) => version(c.major + 1, 0, 0),
+        (Some(minor), None) => version(c.major, minor + 1, 0),
+        (Some(minor), Some(patch)) => version(c.major, minor, patch + 1),
+    };
+
+    match c.op {
+        Op::Exact | Op::Wildcard if c.patch.is_some() => (Some((base.clone(), true)), Some((base, true))),
+        Op::Exact | Op::Wildcard => (Some((base, true)), Some((next, false))),
+        Op::Greater if c.patch.is_some() => (Some((base, false)), None),
+        Op::Greater => (Some((next, true)), None),
+        Op::GreaterEq => (Some((base, true)), None),
+        Op::Less => (None, Some((base, false))),
+        Op::LessEq if c.patch.is_some() => (None, Some((base, true))),
+        Op::LessEq => (None, Some((next, false))),
+        Op::Tilde => {
+            let upper = match c.minor {
+                Some(minor) => version(c.major, minor + 1, 0),
+                None => version(c.major + 1, 0, 0),
+            };
+            (Some((base, true)), Some((upper, false)))
+        },
+        _ => {
+            // caret: the left-most non-zero component must not change
+            let upper = match (c.major, c.minor, c.patch) {
+                (0, Some(0), Some(patch)) => version(0, 0, patch + 1),
+                (0, Some(minor), _) if minor > 0 || c.patch.is_none() => version(0, minor + 1, 0),
+                (major, _, _) => version(major + 1, 0, 0),
+            };
+            (Some((base, true)), Some((upper, false)))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license_override(name: &str, version: Option<&str>, license: &str) -> LicenseOverride {
+        LicenseOverride {
+            name: name.to_owned(),
+            version: version.map(|v| VersionReq::parse(v).unwrap()),
+            license: Expression::from_str(license).unwrap(),
+            text: None,
+            license_file: None,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let overrides = [
+            license_override("ring", None, "ISC AND MIT AND OpenSSL"),
+            license_override("some-crate", Some("=0.2.3"), "MIT"),
+            license_override("other-crate", Some("^0.2.3"), "MIT OR Apache-2.0"),
+            license_override("old-crate", Some("<1.0"), "BSD-3-Clause"),
+        ];
+
+        let loaded = parse_ort_curations(&ort_curations(&overrides)).unwrap();
+        assert_eq!(loaded.len(), overrides.len());
+        for (loaded, o) in loaded.iter().zip(&overrides) {
+            assert_eq!(loaded.name, o.name);
+            assert_eq!(loaded.license.to_string(), o.license.to_string());
+            for version in ["0.1.0", "0.2.3", "0.2.9", "0.3.0", "1.0.0"] {
+                assert_eq!(loaded.matches(&o.name, version), o.matches(&o.name, version), "{} {version}", o.name);
+            }
+        }
+    }
+
+    #[test]
+    fn ivy_ranges() {
+        let req = |r: &str| ivy_range(&VersionReq::parse(r).unwrap());
+        assert_eq!(req("=0.2.3"), "0.2.3");
+        assert_eq!(req("^0.2.3"), "[0.2.3,0.3.0)");
+        assert_eq!(req("~1.2"), "[1.2.0,1.3.0)");
+        assert_eq!(req(">=1.0, <2.0"), "[1.0.0,2.0.0)");
+        assert_eq!(req("<1.0"), "(,1.0.0)");
+
+        let parse = |v: &str| parse_version(v).unwrap().map(|r| r.to_string());
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("1.2.3").as_deref(), Some("=1.2.3"));
+        assert_eq!(parse("[1.0.0,2.0.0)").as_deref(), Some(">=1.0.0, <2.0.0"));
+        assert_eq!(parse("]1.0.0,2.0.0]").as_deref(), Some(">1.0.0, <=2.0.0"));
+        assert_eq!(parse("(,2.0.0)").as_deref(), Some("<2.0.0"));
+        assert!(parse_version("[a,b)").is_err());
+    }
+
+    #[test]
+    fn curation_files() {
+        let yaml = r#"
+# shared comment
+- id: "Maven:org.example:lib:1.0"
+  curations:
+    concluded_license: "MIT"
+- id: Crate::no-license:1.0.0
+  curations: { comment: "only a comment" }
+- id: Crate::anchored:1.0.0
+  curations: &mit
+    comment: >
+      folded
+      comment
+    concluded_license: MIT
+    declared_license_mapping: { "MIT/Apache": "MIT OR Apache-2.0" }
+- id: Crate::aliased:[1.0.0,2.0.0)
+  curations: *mit
+"#;
+        let overrides = parse_ort_curations(yaml).unwrap();
+        let names: Vec<_> = overrides.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["anchored", "aliased"]);
+        assert!(overrides[1].matches("aliased", "1.5.0"));
+        assert!(!overrides[1].matches("aliased", "2.0.0"));
+
+        assert!(parse_ort_curations("").unwrap().is_empty());
+        assert!(parse_ort_curations("# no curations\n").unwrap().is_empty());
+        assert!(parse_ort_curations("- id: Crate::invalid:1.0\n  curations:\n    concluded_license: MIT/X").is_err());
+        assert!(parse_ort_curations("- id: invalid\n  curations:\n    concluded_license: MIT").is_err());
+    }
+}
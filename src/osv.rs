@@ -0,0 +1,432 @@
+#[cfg(feature = "tokio")]
+use crate::nonblocking::{join_ordered, AsyncHttp, AsyncOptions};
+use crate::{
+    crates_io::CratesIo, AggregateError, Diagnostic, DiagnosticKind, Diagnostics, HttpOptions, Package, Severity,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{Display, Formatter},
+};
+
+/// Number of packages per request of the batch API
+const BATCH_SIZE: usize = 500;
+
+/// The class of an [`Advisory`], following the informational advisories of RustSec
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AdvisoryKind {
+    /// A security vulnerability
+    Vulnerability,
+    /// The crate is no longer maintained
+    Unmaintained,
+    /// The crate allows undefined behavior from safe code
+    Unsound,
+    /// Any other informational advisory
+    Notice,
+}
+
+impl Display for AdvisoryKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdvisoryKind::Vulnerability => write!(f, "vulnerability"),
+            AdvisoryKind::Unmaintained => write!(f, "unmaintained"),
+            AdvisoryKind::Unsound => write!(f, "unsound"),
+            AdvisoryKind::Notice => write!(f, "notice"),
+        }
+    }
+}
+
+/// An advisory (e.g. of the RustSec advisory database) affecting a package
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Advisory {
+    /// The identifier of the advisory, e.g. `RUSTSEC-2020-0071`
+    pub id: String,
+    pub package_name: String,
+    pub package_version: String,
+    pub kind: AdvisoryKind,
+    pub summary: Option<String>,
+    /// Other identifiers of the advisory, e.g. CVEs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+/// Cross-references packages with the advisories of osv.dev, which includes the RustSec advisory database.
+///
+/// Packages are looked up as crates from crates.io, so thirdparty packages usually have no advisories.
+/// Withdrawn advisories are skipped. Every advisory is reported as [`DiagnosticKind::Advisory`], notices as infos
+/// and all other advisories as warnings. Crates whose version was yanked from crates.io are reported as well (as
+/// [`DiagnosticKind::Yanked`]), unless disabled with [`Osv::yanked`].
+///
+/// # Example
+/// `let advisories = Osv::new().check(&packages, &mut diagnostics)?;`
+#[derive(Clone, Debug)]
+pub struct Osv {
+    http: HttpOptions,
+    url: Option<String>,
+    pub(crate) yanked: bool,
+}
+
+impl Default for Osv {
+    fn default() -> Self {
+        Self { http: HttpOptions::default(), url: None, yanked: true }
+    }
+}
+
+#[derive(Serialize)]
+struct Query<'a> {
+    package: QueryPackage<'a>,
+    version: &'a str,
+}
+
+#[derive(Serialize)]
+struct QueryPackage<'a> {
+    name: &'a str,
+    ecosystem: &'static str,
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    #[serde(default)]
+    results: Vec<BatchResult>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BatchResult {
+    vulns: Vec<VulnId>,
+}
+
+#[derive(Deserialize)]
+struct VulnId {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Vuln {
+    summary: Option<String>,
+    withdrawn: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    affected: Vec<Affected>,
+    #[serde(default)]
+    database_specific: DatabaseSpecific,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Affected {
+    database_specific: DatabaseSpecific,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct DatabaseSpecific {
+    informational: Option<String>,
+}
+
+impl Vuln {
+    fn kind(&self) -> AdvisoryKind {
+        let informational = self
+            .database_specific
+            .informational
+            .as_deref()
+            .or_else(|| self.affected.iter().find_map(|a| a.database_specific.informational.as_deref()));
+
+        match informational {
+            None => AdvisoryKind::Vulnerability,
+            Some("unmaintained") => AdvisoryKind::Unmaintained,
+            Some("unsound") => AdvisoryKind::Unsound,
+            Some(_) => AdvisoryKind::Notice,
+        }
+    }
+}
+
+impl Osv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn http(mut self, http: HttpOptions) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Base url of the API, defaults to `https://api.osv.dev`
+    pub fn url<S: Into<String>>(mut self, url: S) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Whether to look up the crates on crates.io and report the yanked versions, enabled by default. The lookups
+    /// are spaced by the interval of the crawler policy of crates.io, i.e. one crate per second.
+    pub fn yanked(mut self, yanked: bool) -> Self {
+        self.yanked = yanked;
+        self
+    }
+
+    /// Look up the advisories of all packages, see [`Osv`]
+    pub fn check(&self, packages: &[Package], diagnostics: &mut Diagnostics) -> Result<Vec<Advisory>, AggregateError> {
+        if packages.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let client = self.http.build_client()?;
+        let url = self.base_url();
+
+        let mut affected: Vec<(&Package, String)> = vec![];
+        for chunk in packages.chunks(BATCH_SIZE) {
+            let request = client
+                .post(format!("{url}/v1/querybatch"))
+                .header("Content-Type", "application/json")
+                .body(batch_body(chunk)?);
+            let response = self.http.send(request)?.error_for_status()?.text()?;
+            collect_affected(chunk, &response, &mut affected)?;
+        }
+
+        // the batch API only returns the identifiers, the details are fetched once per advisory
+        let ids: BTreeSet<_> = affected.iter().map(|(_, id)| id.as_str()).collect();
+        let mut vulns = HashMap::new();
+        for id in ids {
+            let response = self.http.send(client.get(format!("{url}/v1/vulns/{id}")))?.error_for_status()?.text()?;
+            vulns.insert(id.to_owned(), serde_json::from_str(&response).map_err(AggregateError::other)?);
+        }
+
+        let advisories = report(affected, &vulns, diagnostics);
+        if self.yanked {
+            CratesIo::new(self.http.clone(), Vec::new()).report_yanked(packages, diagnostics);
+        }
+
+        Ok(advisories)
+    }
+
+    /// Like [`Osv::check`], with the batches and the details of the advisories fetched concurrently
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn check_async(
+        &self,
+        packages: &[Package],
+        diagnostics: &mut Diagnostics,
+        options: &AsyncOptions,
+    ) -> Result<Vec<Advisory>, AggregateError> {
+        if packages.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let http = AsyncHttp::new(&self.http, options)?;
+        let url = self.base_url();
+
+        let mut batches = vec![];
+        for chunk in packages.chunks(BATCH_SIZE) {
+            let request = http
+                .client()
+                .post(format!("{url}/v1/querybatch"))
+                .header("Content-Type", "application/json")
+                .body(batch_body(chunk)?);
+            let http = http.clone();
+            batches.push(async move { http.text(request).await });
+        }
+
+        let mut affected: Vec<(&Package, String)> = vec![];
+        for (chunk, response) in packages.chunks(BATCH_SIZE).zip(join_ordered(batches).await?) {
+            collect_affected(chunk, &response?, &mut affected)?;
+        }
+
+        let ids: BTreeSet<_> = affected.iter().map(|(_, id)| id.clone()).collect();
+        let details = ids.into_iter().map(|id| {
+            let request = http.client().get(format!("{url}/v1/vulns/{id}"));
+            let http = http.clone();
+            async move { (id, http.text(request).await) }
+        });
+
+        let mut vulns = HashMap::new();
+        for (id, response) in join_ordered(details).await? {
+            vulns.insert(id, serde_json::from_str(&response?).map_err(AggregateError::other)?);
+        }
+
+        let advisories = report(affected, &vulns, diagnostics);
+        if self.yanked {
+            let mut crates_io = CratesIo::new(self.http.clone(), Vec::new());
+            let crates = packages.iter().filter(|p| p.registry_url.is_some()).map(|p| (None, p.package_name.clone()));
+            crates_io.prefetch(crates.collect(), options).await?;
+            crates_io.report_yanked(packages, diagnostics);
+        }
+
+        Ok(advisories)
+    }
+
+    fn base_url(&self) -> &str {
+        self.url.as_deref().unwrap_or("https://api.osv.dev")
+    }
+}
+
+/// The body of a request of the batch API
+fn batch_body(chunk: &[Package]) -> Result<String, AggregateError> {
+    let queries: Vec<_> = chunk
+        .iter()
+        .map(|p| Query {
+            package: QueryPackage { name: &p.package_name, ecosystem: "crates.io" },
+            version: &p.package_version,
+        })
+        .collect();
+    serde_json::to_string(&serde_json::json!({ "queries": queries })).map_err(AggregateError::other)
+}
+
+/// Pair the packages of the batch with the identifiers of the advisories affecting them
+fn collect_affected<'p>(
+    chunk: &'p [Package],
+    response: &str,
+    affected: &mut Vec<(&'p Package, String)>,
+) -> Result<(), AggregateError> {
+    let response: BatchResponse = serde_json::from_str(response).map_err(AggregateError::other)?;
+    for (pkg, result) in chunk.iter().zip(response.results) {
+        affected.extend(result.vulns.into_iter().map(|v| (pkg, v.id)));
+    }
+
+    Ok(())
+}
+
+/// The advisories that are not withdrawn, each reported as [`DiagnosticKind::Advisory`]
+fn report(
+    affected: Vec<(&Package, String)>,
+    vulns: &HashMap<String, Vuln>,
+    diagnostics: &mut Diagnostics,
+) -> Vec<Advisory> {
+    let mut advisories = vec![];
+    for (pkg, id) in affected {
+        let Some(vuln) = vulns.get(&id).filter(|v| v.withdrawn.is_none()) else {
+            continue;
+        };
+
+        let advisory = Advisory {
+            id,
+            package_name: pkg.package_name.clone(),
+            package_version: pkg.package_version.clone(),
+            kind: vuln.kind(),
+            summary: vuln.summary.clone(),
+            aliases: vuln.aliases.clone(),
+        };
+
+        let severity = match advisory.kind {
+            AdvisoryKind::Notice => Severity::Info,
+            _ => Severity::Warning,
+        };
+        diagnostics.push(
+            Diagnostic::new(
+                severity,
+                DiagnosticKind::Advisory,
+                format!(
+                    "'{} {}' is affected by {} ({}): {}",
+                    advisory.package_name,
+                    advisory.package_version,
+                    advisory.id,
+                    advisory.kind,
+                    advisory.summary.as_deref().unwrap_or("no summary")
+                ),
+            )
+            .with_package(&advisory.package_name, &advisory.package_version),
+        );
+
+        advisories.push(advisory);
+    }
+
+    advisories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Abridged from `https://api.osv.dev/v1/vulns/RUSTSEC-2021-0139`
+    const UNMAINTAINED: &str = r#"{
+        "id": "RUSTSEC-2021-0139",
+        "summary": "ansi_term is Unmaintained",
+        "modified": "2023-06-13T13:10:24Z",
+        "published": "2021-08-18T12:00:00Z",
+        "affected": [{
+            "package": {"ecosystem": "crates.io", "name": "ansi_term", "purl": "pkg:cargo/ansi_term"},
+            "ranges": [{"type": "SEMVER", "events": [{"introduced": "0.0.0-0"}]}],
+            "ecosystem_specific": {"affects": {"arch": [], "os": [], "functions": []}},
+            "database_specific": {"categories": [], "cvss": null, "informational": "unmaintained"}
+        }],
+        "database_specific": {"license": "CC0-1.0"}
+    }"#;
+
+    /// Abridged from `https://api.osv.dev/v1/vulns/RUSTSEC-2020-0071`
+    const VULNERABILITY: &str = r#"{
+        "id": "RUSTSEC-2020-0071",
+        "summary": "Potential segfault in time",
+        "aliases": ["CVE-2020-26235", "GHSA-wcg3-cvx6-7396"],
+        "affected": [{
+            "package": {"ecosystem": "crates.io", "name": "time", "purl": "pkg:cargo/time"},
+            "database_specific": {"categories": ["code-execution", "memory-corruption"], "informational": null}
+        }],
+        "database_specific": {"license": "CC0-1.0"}
+    }"#;
+
+    fn vuln(json: &str) -> Vuln {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(vuln(UNMAINTAINED).kind(), AdvisoryKind::Unmaintained);
+        assert_eq!(vuln(VULNERABILITY).kind(), AdvisoryKind::Vulnerability);
+
+        let unsound = r#"{"id": "RUSTSEC-2022-0040", "database_specific": {"informational": "unsound"}}"#;
+        assert_eq!(vuln(unsound).kind(), AdvisoryKind::Unsound);
+        let notice = r#"{"id": "RUSTSEC-2019-0036", "affected": [{"database_specific": {"informational": "notice"}}]}"#;
+        assert_eq!(vuln(notice).kind(), AdvisoryKind::Notice);
+    }
+
+    #[test]
+    fn reported_advisories() {
+        let packages: Vec<Package> = ["ansi_term", "time"]
+            .into_iter()
+            .map(|name| {
+                serde_json::from_value(serde_json::json!({
+                    "package_name": name,
+                    "package_version": "0.1.0",
+                    "package_url": null,
+                    "license_spdx": "MIT",
+                    "license_files": [],
+                }))
+                .unwrap()
+            })
+            .collect();
+
+        let batch = r#"{"results": [{"vulns": [{"id": "RUSTSEC-2021-0139", "modified": "2023-06-13T13:10:24Z"}]},
+            {"vulns": [{"id": "RUSTSEC-2020-0071"}, {"id": "RUSTSEC-2020-0000"}]}]}"#;
+        let mut affected = vec![];
+        collect_affected(&packages, batch, &mut affected).unwrap();
+        assert_eq!(affected.len(), 3);
+
+        let withdrawn = r#"{"id": "RUSTSEC-2020-0000", "withdrawn": "2020-12-01T00:00:00Z"}"#;
+        let vulns = HashMap::from([
+            ("RUSTSEC-2021-0139".to_owned(), vuln(UNMAINTAINED)),
+            ("RUSTSEC-2020-0071".to_owned(), vuln(VULNERABILITY)),
+            ("RUSTSEC-2020-0000".to_owned(), vuln(withdrawn)),
+        ]);
+        let mut diagnostics = Diagnostics::new();
+        let advisories = report(affected, &vulns, &mut diagnostics);
+
+        let ids: Vec<_> = advisories.iter().map(|a| (a.package_name.as_str(), a.id.as_str())).collect();
+        assert_eq!(ids, [("ansi_term", "RUSTSEC-2021-0139"), ("time", "RUSTSEC-2020-0071")]);
+        assert_eq!(advisories[1].aliases, ["CVE-2020-26235", "GHSA-wcg3-cvx6-7396"]);
+
+        let messages: Vec<_> = diagnostics.iter().map(|d| (d.severity, d.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    Severity::Warning,
+                    "'ansi_term 0.1.0' is affected by RUSTSEC-2021-0139 (unmaintained): ansi_term is Unmaintained"
+                ),
+                (
+                    Severity::Warning,
+                    "'time 0.1.0' is affected by RUSTSEC-2020-0071 (vulnerability): Potential segfault in time"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! What a run would collect and fetch, for validating configuration changes without running the pipeline, see
+//! [`crate::Aggregator::plan`]
+
+use crate::{Origin, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The crates, thirdparty files and remote lookups of a run of an [`crate::Aggregator`]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Plan {
+    /// The crates of the dependency graph whose licenses would be gathered, the ignored ones aside
+    pub krates: Vec<PlannedKrate>,
+    /// The thirdparty files declared by the crates, empty if thirdparty licenses are not collected
+    pub thirdparty_files: Vec<PlannedThirdpartyFile>,
+    /// The remote lookups of the run, by service
+    pub fetches: Vec<PlannedFetch>,
+    /// Number of custom sources (see [`crate::Aggregator::source`]), what they collect is only known when running them
+    pub custom_sources: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedKrate {
+    pub name: String,
+    pub version: String,
+    /// The source of the crate, `None` for crates.io
+    pub origin: Option<Origin>,
+    /// Whether the crate is only needed for building (see [`crate::graph::build_time_krates`])
+    pub build_time: bool,
+    /// Whether the package of a previous run or the [`crate::LicenseCache`] would be used instead of scanning the
+    /// sources of the crate
+    pub reused: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedThirdpartyFile {
+    /// Name of the crate declaring the file
+    pub krate: String,
+    pub path: Utf8PathBuf,
+    /// Whether the file exists, a missing file fails the run
+    pub exists: bool,
+}
+
+/// The lookups of a remote service
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedFetch {
+    /// The service, e.g. `clearlydefined.io`
+    pub service: String,
+    /// What is looked up
+    pub purpose: String,
+    /// The crates (`name version`) that are looked up, empty if they are only known after scanning the sources (e.g.
+    /// the packages without license files)
+    pub packages: Vec<String>,
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let reused = self.krates.iter().filter(|k| k.reused).count();
+        writeln!(f, "{} crates, {reused} of them from a previous run or the cache", self.krates.len())?;
+        for k in &self.krates {
+            let build_time = if k.build_time { " (build time)" } else { "" };
+            writeln!(f, "- {} {}{build_time}", k.name, k.version)?;
+        }
+
+        if !self.thirdparty_files.is_empty() {
+            writeln!(f, "{} thirdparty files", self.thirdparty_files.len())?;
+            for file in &self.thirdparty_files {
+                let missing = if file.exists { "" } else { " (missing)" };
+                writeln!(f, "- {} of {}{missing}", file.path, file.krate)?;
+            }
+        }
+
+        if self.custom_sources > 0 {
+            writeln!(f, "{} custom sources", self.custom_sources)?;
+        }
+
+        if self.fetches.is_empty() {
+            writeln!(f, "no remote lookups")?;
+        }
+        for fetch in &self.fetches {
+            match fetch.packages.len() {
+                0 => writeln!(f, "- {}: {}", fetch.service, fetch.purpose)?,
+                n => writeln!(f, "- {}: {} of {n} crates", fetch.service, fetch.purpose)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let plan = Plan {
+            krates: vec![
+                PlannedKrate { name: "a".into(), version: "1.0.0".into(), origin: None, build_time: false, reused: true },
+                PlannedKrate { name: "b".into(), version: "0.2.0".into(), origin: None, build_time: true, reused: false },
+            ],
+            thirdparty_files: vec![PlannedThirdpartyFile {
+                krate: "b".into(),
+                path: "b/thirdparty.toml".into(),
+                exists: false,
+            }],
+            fetches: vec![
+                PlannedFetch { service: "osv.dev".into(), purpose: "advisories".into(), packages: vec!["a 1.0.0".into()] },
+                PlannedFetch { service: "GitHub".into(), purpose: "license files".into(), packages: vec![] },
+            ],
+            custom_sources: 0,
+        };
+        let expected = "2 crates, 1 of them from a previous run or the cache\n- a 1.0.0\n- b 0.2.0 (build time)\n\
+            1 thirdparty files\n- b/thirdparty.toml of b (missing)\n\
+            - osv.dev: advisories of 1 crates\n- GitHub: license files\n";
+        assert_eq!(plan.to_string(), expected);
+        assert!(Plan::default().to_string().ends_with("no remote lookups\n"));
+    }
+}
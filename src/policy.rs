@@ -0,0 +1,578 @@
+//! Enforcement of a license policy on the aggregated packages, see [`check_policy`]
+
+use crate::{
+    obligations::{classify_license, LicenseCategory},
+    AggregateError, Package,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// Number of days before the expiry of an exception from which on it is reported as expiring, if not configured
+const DEFAULT_EXPIRY_NOTICE_DAYS: u32 = 30;
+
+/// The license policy of an organization: the `[policy]` section of the configuration.
+///
+/// # Example
+/// ```toml
+/// [policy]
+/// denied-licenses = ["AGPL-3.0", "SSPL-1.0"]
+/// denied-packages = ["openssl-sys"]
+/// accept-public-domain = true
+///
+/// [[policy.exceptions]]
+/// package = "some-crate"
+/// licenses = ["GPL-3.0"]
+/// justification = "only used by the internal test tooling"
+///
+/// [[policy.exceptions]]
+/// package = "other-crate"
+/// licenses = ["AGPL-3.0"]
+/// justification = "tolerated while we replace it"
+/// expires = "2026-12-31"
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Policy {
+    /// Licenses that must not be required by any package
+    #[serde(default)]
+    pub denied_licenses: Vec<String>,
+    /// Names of packages that must not be used at all
+    #[serde(default)]
+    pub denied_packages: Vec<String>,
+    /// Packages that are allowed to violate the policy
+    #[serde(default)]
+    pub exceptions: Vec<PolicyException>,
+    /// Exceptions that expire within this number of days are reported as expiring, 30 if not configured
+    pub expiry_notice_days: Option<u32>,
+    /// Accept the licenses of the public domain category (see [`LicenseCategory::PublicDomain`]) without listing them
+    /// in the accepted licenses, by the policy check as well as by the minimization
+    #[serde(default)]
+    pub accept_public_domain: bool,
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        self.denied_licenses.is_empty()
+            && self.denied_packages.is_empty()
+            && self.exceptions.is_empty()
+            && !self.accept_public_domain
+    }
+}
+
+/// A package that is allowed to violate the policy
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PolicyException {
+    /// Name of the package
+    pub package: String,
+    /// The licenses the package may use although they are denied or not accepted,
+    /// if empty the package is exempt from the policy entirely
+    #[serde(default)]
+    pub licenses: Vec<String>,
+    /// Why the exception was granted, recorded in the [`PolicyReport`]
+    pub justification: String,
+    /// The last day on which the exception applies, afterwards the package violates the policy again
+    pub expires: Option<Date>,
+}
+
+impl PolicyException {
+    /// Whether the exception no longer applies on the given day
+    pub fn is_expired(&self, today: Date) -> bool {
+        self.expires.is_some_and(|expires| expires < today)
+    }
+}
+
+/// A calendar day (`YYYY-MM-DD`), e.g. of the expiry of a [`PolicyException`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// The current day (UTC)
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn today() -> Self {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        Self::from_days((seconds / 86_400) as i64)
+    }
+
+    /// The number of days from `self` to `other`, negative if `other` is earlier
+    pub fn days_until(&self, other: Date) -> i64 {
+        other.days() - self.days()
+    }
+
+    /// Days since 1970-01-01, see <https://howardhinnant.github.io/date_algorithms.html>
+    fn days(&self) -> i64 {
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self { year: year as i32, month: month as u8, day: day as u8 }
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{s}' is not a date of the form YYYY-MM-DD");
+
+        let mut parts = s.splitn(3, '-');
+        let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+
+        let date = Date {
+            year: year.parse().map_err(|_| invalid())?,
+            month: month.parse().map_err(|_| invalid())?,
+            day: day.parse().map_err(|_| invalid())?,
+        };
+
+        let is_leap_year = date.year % 4 == 0 && (date.year % 100 != 0 || date.year % 400 == 0);
+        let days_in_month = match date.month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap_year => 29,
+            2 => 28,
+            _ => return Err(invalid()),
+        };
+
+        if date.day == 0 || date.day > days_in_month {
+            return Err(invalid());
+        }
+
+        Ok(date)
+    }
+}
+
+impl TryFrom<String> for Date {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Date> for String {
+    fn from(date: Date) -> Self {
+        date.to_string()
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// The kind of a [`PolicyViolation`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ViolationKind {
+    /// The package is denied
+    DeniedPackage,
+    /// The license of the package cannot be satisfied without a denied license
+    DeniedLicense,
+    /// The license of the package cannot be satisfied with the accepted licenses
+    NotAccepted,
+    /// The package grants no license, all rights are reserved by its authors (see
+    /// [`crate::obligations::ALL_RIGHTS_RESERVED_REF`])
+    Unlicensed,
+}
+
+/// A package that violates the policy
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PolicyViolation {
+    pub kind: ViolationKind,
+    pub package_name: String,
+    pub package_version: String,
+    /// Human readable description
+    pub message: String,
+}
+
+/// An exception that was needed for a package to pass the policy
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppliedException {
+    pub package_name: String,
+    pub package_version: String,
+    pub justification: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<Date>,
+}
+
+/// The result of [`check_policy`]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PolicyReport {
+    pub violations: Vec<PolicyViolation>,
+    /// The exceptions that allowed packages to pass, so they can be audited
+    pub applied_exceptions: Vec<AppliedException>,
+    /// The applied exceptions that expire soon (see [`Policy::expiry_notice_days`]), so the packages can be replaced
+    /// before the check fails
+    #[serde(default)]
+    pub expiring_exceptions: Vec<AppliedException>,
+}
+
+impl PolicyReport {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Fail with [`AggregateError::PolicyViolation`] if there are violations
+    pub fn into_result(self) -> Result<Self, AggregateError> {
+        if self.is_ok() {
+            Ok(self)
+        } else {
+            Err(AggregateError::PolicyViolation(self))
+        }
+    }
+}
+
+impl Display for PolicyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for violation in &self.violations {
+            writeln!(f, "- {}", violation.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Evaluate the packages against the policy and the accepted licenses (of the cargo-about configuration) on the
+/// current day, see [`check_policy_on`]
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub fn check_policy(
+    packages: &[Package],
+    policy: &Policy,
+    accepted: &[spdx::Licensee],
+) -> Result<PolicyReport, AggregateError> {
+    check_policy_on(packages, policy, accepted, Date::today())
+}
+
+/// Evaluate the packages against the policy and the accepted licenses (of the cargo-about configuration) on the given
+/// day.
+///
+/// The license of a package has to be satisfiable with licenses that are accepted (if any are configured, see also
+/// [`Policy::accept_public_domain`]) and not denied, or allowed by an exception for the package that has not expired
+/// yet. Packages without license are not checked. Fails if a license of the policy is not a valid SPDX identifier.
+pub fn check_policy_on(
+    packages: &[Package],
+    policy: &Policy,
+    accepted: &[spdx::Licensee],
+    today: Date,
+) -> Result<PolicyReport, AggregateError> {
+    let denied = parse_licensees(&policy.denied_licenses)?;
+    let notice_days = i64::from(policy.expiry_notice_days.unwrap_or(DEFAULT_EXPIRY_NOTICE_DAYS));
+    let mut report = PolicyReport::default();
+
+    for p in packages {
+        let (expired, exceptions): (Vec<_>, Vec<_>) = policy
+            .exceptions
+            .iter()
+            .filter(|e| e.package == p.package_name)
+            .partition(|e| e.is_expired(today));
+
+        let mut apply = |exception: &PolicyException| {
+            let applied = AppliedException {
+                package_name: p.package_name.clone(),
+                package_version: p.package_version.clone(),
+                justification: exception.justification.clone(),
+                expires: exception.expires,
+            };
+
+            if exception.expires.is_some_and(|expires| today.days_until(expires) <= notice_days) {
+                report.expiring_exceptions.push(applied.clone());
+            }
+            report.applied_exceptions.push(applied);
+        };
+
+        if let Some(exception) = exceptions.iter().find(|e| e.licenses.is_empty()) {
+            apply(exception);
+            continue;
+        }
+
+        // mentions the expiry in the violation, so it is obvious why a package that passed before fails now
+        let expiry = expired.iter().filter_map(|e| e.expires).max().map_or_else(String::new, |expires| {
+            format!(" (its exception expired on {expires})")
+        });
+        let violation = |kind, message: String| PolicyViolation {
+            kind,
+            package_name: p.package_name.clone(),
+            package_version: p.package_version.clone(),
+            message: message + &expiry,
+        };
+
+        if policy.denied_packages.contains(&p.package_name) {
+            report.violations.push(violation(
+                ViolationKind::DeniedPackage,
+                format!("The package '{} {}' is denied", p.package_name, p.package_version),
+            ));
+            continue;
+        }
+
+        let Some(license) = &p.license_spdx else {
+            continue;
+        };
+
+        // works without any license are never accepted, unless an exception lists the reference explicitly
+        let is_unlicensed = |req: &spdx::LicenseReq| classify_license(req) == LicenseCategory::ProprietaryUnlicensed;
+        let is_denied = |req: &spdx::LicenseReq| is_unlicensed(req) || denied.iter().any(|d| d.satisfies(req));
+        let allowed = |req: &spdx::LicenseReq| {
+            let public_domain = policy.accept_public_domain && classify_license(req) == LicenseCategory::PublicDomain;
+            !is_denied(req) && (accepted.is_empty() || accepted.iter().any(|a| a.satisfies(req)) || public_domain)
+        };
+
+        if license.evaluate(allowed) {
+            continue;
+        }
+
+        let mut excepted = None;
+        for exception in &exceptions {
+            let licenses = parse_licensees(&exception.licenses)?;
+            if license.evaluate(|req| allowed(req) || licenses.iter().any(|l| l.satisfies(req))) {
+                excepted = Some(exception);
+                break;
+            }
+        }
+
+        if let Some(exception) = excepted {
+            apply(exception);
+        } else if !license.evaluate(|req| !is_unlicensed(req)) {
+            report.violations.push(violation(
+                ViolationKind::Unlicensed,
+                format!(
+                    "'{} {}' grants no license ({license}), all rights are reserved by its authors",
+                    p.package_name, p.package_version
+                ),
+            ));
+        } else if !license.evaluate(|req| !is_denied(req)) {
+            report.violations.push(violation(
+                ViolationKind::DeniedLicense,
+                format!(
+                    "The license {license} of '{} {}' cannot be satisfied without a denied license",
+                    p.package_name, p.package_version
+                ),
+            ));
+        } else {
+            report.violations.push(violation(
+                ViolationKind::NotAccepted,
+                format!(
+                    "The license {license} of '{} {}' cannot be satisfied with the accepted licenses",
+                    p.package_name, p.package_version
+                ),
+            ));
+        }
+    }
+
+    Ok(report)
+}
+
+fn parse_licensees(licenses: &[String]) -> Result<Vec<spdx::Licensee>, AggregateError> {
+    licenses
+        .iter()
+        .map(|l| {
+            spdx::Licensee::parse(l).map_err(|source| AggregateError::SpdxParse { expression: l.clone(), source })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn date_parsing() {
+        assert_eq!(date("2026-01-05"), Date { year: 2026, month: 1, day: 5 });
+        assert_eq!(date("2026-1-5").to_string(), "2026-01-05");
+        assert!("2024-02-29".parse::<Date>().is_ok());
+        assert!("2000-02-29".parse::<Date>().is_ok());
+        let invalid = ["2023-02-29", "1900-02-29", "2026-04-31", "2026-13-01", "2026-00-10", "2026-01-00", "2026-01"];
+        for invalid in invalid {
+            assert!(invalid.parse::<Date>().is_err(), "{invalid}");
+        }
+        assert!("2026-01-05T00:00".parse::<Date>().is_err());
+        assert!("".parse::<Date>().is_err());
+
+        let json = serde_json::to_string(&date("2026-10-14")).unwrap();
+        assert_eq!(json, "\"2026-10-14\"");
+        assert_eq!(serde_json::from_str::<Date>(&json).unwrap(), date("2026-10-14"));
+        assert_eq!("2026-02-30".parse::<Date>().unwrap_err(), "'2026-02-30' is not a date of the form YYYY-MM-DD");
+
+        // the expiry of exceptions is compared by year, month and day
+        assert!(date("2025-12-31") < date("2026-01-01"));
+        assert!(date("2026-02-01") > date("2026-01-31"));
+        assert!(Date::today() > date("2026-01-01"));
+
+        let exception = |expires| {
+            let json = serde_json::json!({"package": "a", "justification": "replaced soon", "expires": expires});
+            serde_json::from_value::<PolicyException>(json)
+        };
+        assert_eq!(exception("2026-09-01").unwrap().expires, Some(date("2026-09-01")));
+        let error = exception("2026-09-31").unwrap_err();
+        assert!(error.to_string().contains("'2026-09-31' is not a date"), "{error}");
+    }
+
+    #[test]
+    fn days_until() {
+        assert_eq!(date("1970-01-01").days(), 0);
+        assert_eq!(date("2024-02-28").days_until(date("2024-03-01")), 2);
+        assert_eq!(date("2023-02-28").days_until(date("2023-03-01")), 1);
+        assert_eq!(date("2023-12-31").days_until(date("2024-01-01")), 1);
+        assert_eq!(date("2024-01-01").days_until(date("2023-01-01")), -365);
+        assert_eq!(date("2000-01-01").days_until(date("2100-01-01")), 36_525);
+
+        for s in ["1969-12-31", "2000-02-29", "2026-10-14", "2100-03-01"] {
+            assert_eq!(Date::from_days(date(s).days()), date(s));
+        }
+    }
+
+    fn package(name: &str, license: &str) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "package_name": name,
+            "package_version": "1.0.0",
+            "package_url": null,
+            "license_spdx": license,
+            "license_files": [],
+        }))
+        .unwrap()
+    }
+
+    fn exception(package: &str, licenses: &[&str], expires: Option<&str>) -> PolicyException {
+        PolicyException {
+            package: package.to_owned(),
+            licenses: licenses.iter().map(|l| l.to_string()).collect(),
+            justification: format!("{package} is needed"),
+            expires: expires.map(date),
+        }
+    }
+
+    fn accepted(licenses: &[&str]) -> Vec<spdx::Licensee> {
+        licenses.iter().map(|l| spdx::Licensee::parse(l).unwrap()).collect()
+    }
+
+    fn violations(report: &PolicyReport) -> Vec<(&str, ViolationKind)> {
+        report.violations.iter().map(|v| (v.package_name.as_str(), v.kind)).collect()
+    }
+
+    #[test]
+    fn violations_of_the_policy() {
+        let policy = Policy {
+            denied_licenses: vec!["GPL-3.0".to_owned()],
+            denied_packages: vec!["openssl-sys".to_owned()],
+            ..Policy::default()
+        };
+        let packages = [
+            package("openssl-sys", "MIT"),
+            package("gpl", "GPL-3.0-or-later"),
+            package("choice", "GPL-3.0 OR MIT"),
+            package("mpl", "MPL-2.0"),
+            package("both", "MIT AND GPL-3.0"),
+            package("ok", "Apache-2.0 OR MIT"),
+        ];
+        let accepted = accepted(&["MIT", "Apache-2.0"]);
+        let report = check_policy_on(&packages, &policy, &accepted, date("2026-10-14")).unwrap();
+
+        assert_eq!(
+            violations(&report),
+            [
+                ("openssl-sys", ViolationKind::DeniedPackage),
+                ("gpl", ViolationKind::DeniedLicense),
+                ("mpl", ViolationKind::NotAccepted),
+                ("both", ViolationKind::DeniedLicense),
+            ]
+        );
+        assert_eq!(report.violations[0].message, "The package 'openssl-sys 1.0.0' is denied");
+        assert_eq!(
+            report.violations[2].message,
+            "The license MPL-2.0 of 'mpl 1.0.0' cannot be satisfied with the accepted licenses"
+        );
+        assert!(matches!(report.into_result(), Err(AggregateError::PolicyViolation(_))));
+
+        // all licenses are accepted if none are configured
+        let report = check_policy_on(&packages[2..], &policy, &[], date("2026-10-14")).unwrap();
+        assert_eq!(violations(&report), [("both", ViolationKind::DeniedLicense)]);
+    }
+
+    #[test]
+    fn exceptions() {
+        let policy = Policy {
+            denied_licenses: vec!["GPL-3.0".to_owned()],
+            denied_packages: vec!["openssl-sys".to_owned()],
+            exceptions: vec![
+                exception("gpl", &["GPL-3.0"], None),
+                exception("mpl", &["MPL-2.0"], Some("2026-11-01")),
+                exception("openssl-sys", &[], Some("2027-01-01")),
+                exception("wrong", &["LGPL-2.1"], None),
+                exception("expired", &["MPL-2.0"], Some("2026-10-13")),
+                exception("expired", &["MPL-2.0"], Some("2026-09-30")),
+            ],
+            ..Policy::default()
+        };
+        let packages = [
+            package("gpl", "GPL-3.0"),
+            package("mpl", "MPL-2.0"),
+            package("openssl-sys", "OpenSSL"),
+            package("wrong", "MPL-2.0"),
+            package("expired", "MPL-2.0"),
+        ];
+        let report = check_policy_on(&packages, &policy, &accepted(&["MIT"]), date("2026-10-14")).unwrap();
+
+        assert_eq!(
+            violations(&report),
+            [("wrong", ViolationKind::NotAccepted), ("expired", ViolationKind::NotAccepted)]
+        );
+        assert_eq!(
+            report.violations[1].message,
+            "The license MPL-2.0 of 'expired 1.0.0' cannot be satisfied with the accepted licenses \
+             (its exception expired on 2026-10-13)"
+        );
+
+        let applied: Vec<_> = report.applied_exceptions.iter().map(|e| e.package_name.as_str()).collect();
+        assert_eq!(applied, ["gpl", "mpl", "openssl-sys"]);
+        assert_eq!(report.applied_exceptions[2].justification, "openssl-sys is needed");
+
+        // within the 30 days of the notice
+        let expiring: Vec<_> = report.expiring_exceptions.iter().map(|e| e.package_name.as_str()).collect();
+        assert_eq!(expiring, ["mpl"]);
+
+        // the exception applies on the day of its expiry
+        let policy = Policy { expiry_notice_days: Some(100), ..policy };
+        let report = check_policy_on(&packages[..3], &policy, &accepted(&["MIT"]), date("2026-11-01")).unwrap();
+        assert!(report.is_ok());
+        let expiring: Vec<_> = report.expiring_exceptions.iter().map(|e| e.package_name.as_str()).collect();
+        assert_eq!(expiring, ["mpl", "openssl-sys"]);
+
+        let report = check_policy_on(&packages[1..2], &policy, &accepted(&["MIT"]), date("2026-11-02")).unwrap();
+        assert_eq!(violations(&report), [("mpl", ViolationKind::NotAccepted)]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Report of the anomalies of a run for their triage, see [`write_problems`]
+
+use crate::{AggregateError, Diagnostic, DiagnosticKind, Diagnostics, Severity, Utf8Path};
+use serde::{Deserialize, Serialize};
+
+/// Name of the problems report written next to the output of an [`crate::Aggregator`]
+pub const PROBLEMS_FILE: &str = "problems.json";
+
+/// The anomalies of a run, the warnings and errors of its [`Diagnostics`]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ProblemsReport {
+    pub errors: usize,
+    pub warnings: usize,
+    /// The problems ordered by severity, the errors first
+    pub problems: Vec<Problem>,
+}
+
+/// A warning or error of a run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Problem {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub package_name: Option<String>,
+    pub package_version: Option<String>,
+    /// Name of the affected license file, if any
+    pub file: Option<String>,
+    pub message: String,
+    /// What can be done about the problem, see [`DiagnosticKind::remediation`]
+    pub remediation: String,
+}
+
+impl From<&Diagnostic> for Problem {
+    fn from(d: &Diagnostic) -> Self {
+        Self {
+            severity: d.severity,
+            kind: d.kind,
+            package_name: d.package_name.clone(),
+            package_version: d.package_version.clone(),
+            file: d.file.clone(),
+            message: d.message.clone(),
+            remediation: d.kind.remediation().to_owned(),
+        }
+    }
+}
+
+impl ProblemsReport {
+    /// The warnings and errors of the diagnostics, informational diagnostics are no problems
+    pub fn new(diagnostics: &Diagnostics) -> Self {
+        let mut problems: Vec<_> =
+            diagnostics.iter().filter(|d| d.severity >= Severity::Warning).map(Problem::from).collect();
+        problems.sort_by_key(|p| std::cmp::Reverse(p.severity));
+
+        Self {
+            errors: problems.iter().filter(|p| p.severity == Severity::Error).count(),
+            warnings: problems.iter().filter(|p| p.severity == Severity::Warning).count(),
+            problems,
+        }
+    }
+}
+
+/// Write the problems of the diagnostics with their package and a suggested remediation as JSON to the given file,
+/// so the triage of a run does not depend on its log. Returns the written report.
+pub fn write_problems<P: AsRef<Utf8Path>>(
+    diagnostics: &Diagnostics,
+    path: P,
+) -> Result<ProblemsReport, AggregateError> {
+    let path = path.as_ref();
+    let write_err = |source| AggregateError::Write { path: path.to_owned(), source };
+
+    let report = ProblemsReport::new(diagnostics);
+    let json = serde_json::to_string_pretty(&report).map_err(|e| write_err(e.into()))?;
+    std::fs::write(path, json).map_err(write_err)?;
+
+    Ok(report)
+}
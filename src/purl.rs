@@ -0,0 +1,62 @@
+//! Package URLs (purls, see <https://github.com/package-url/purl-spec>), the identifiers by which SBOMs and
+//! vulnerability scanners join the packages of different tools
+
+use crate::Origin;
+
+/// The purl of a crate, e.g. `pkg:cargo/serde@1.0.200`.
+/// Crates of alternative registries and git repositories are qualified with the url of their source.
+pub fn cargo_purl(name: &str, version: &str, origin: Option<&Origin>) -> String {
+    let purl = format!("pkg:cargo/{}@{}", encode(name, b""), encode(version, b""));
+
+    match origin {
+        Some(Origin::Registry { url }) => format!("{purl}?repository_url={}", encode(url, b":/@")),
+        Some(Origin::Git { url, commit, .. }) => {
+            let commit = commit.as_ref().map_or_else(String::new, |commit| format!("@{commit}"));
+            format!("{purl}?vcs_url={}", encode(&format!("git+{url}{commit}"), b":/@"))
+        },
+        _ => purl,
+    }
+}
+
+/// The purl of a package of an unknown ecosystem, e.g. `pkg:generic/zlib@1.3.1`.
+/// Thirdparty files should contain the purls of their ecosystem instead (e.g. `pkg:conan/zlib@1.3.1`).
+pub fn generic_purl(name: &str, version: &str) -> String {
+    format!("pkg:generic/{}@{}", encode(name, b""), encode(version, b""))
+}
+
+/// Percent-encodes everything but the unreserved characters and the given ones, e.g. the `:` and `/` of urls within
+/// qualifiers. Purls are built without the `network` feature as well, so this can't use the `Url` of reqwest.
+fn encode(s: &str, safe: &[u8]) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"._-~".contains(&b) || safe.contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purls() {
+        assert_eq!(cargo_purl("serde", "1.0.200", None), "pkg:cargo/serde@1.0.200");
+        assert_eq!(cargo_purl("a", "1.0.0+build.1", None), "pkg:cargo/a@1.0.0%2Bbuild.1");
+
+        let registry = Origin::Registry { url: "sparse+https://example.org/index/".to_owned() };
+        assert_eq!(
+            cargo_purl("a", "1.0.0", Some(&registry)),
+            "pkg:cargo/a@1.0.0?repository_url=sparse%2Bhttps://example.org/index/"
+        );
+        let commit = Some("abc".to_owned());
+        let git = Origin::Git { url: "https://github.com/o/a".to_owned(), commit, reference: None };
+        assert_eq!(cargo_purl("a", "1.0.0", Some(&git)), "pkg:cargo/a@1.0.0?vcs_url=git%2Bhttps://github.com/o/a@abc");
+
+        assert_eq!(generic_purl("zlib", "1.3.1"), "pkg:generic/zlib@1.3.1");
+    }
+}
@@ -0,0 +1,110 @@
+//! Export of the license texts that have to be reviewed manually, see [`export_unreviewed`]
+
+use crate::{AggregateError, DetectionMethod, LicenseCandidate, LicenseFile, Package, Utf8Path};
+use serde::{Deserialize, Serialize};
+
+/// Name of the index in the export directory
+pub const UNREVIEWED_INDEX: &str = "index.json";
+
+/// A package with license texts that could not be classified confidently, an entry of the index of
+/// [`export_unreviewed`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnreviewedPackage {
+    pub package_name: String,
+    pub package_version: String,
+    /// The license of the package, if known
+    pub license: Option<String>,
+    /// The file containing the texts, relative to the export directory
+    pub path: String,
+    pub files: Vec<UnreviewedFile>,
+}
+
+/// A license text that could not be classified confidently
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnreviewedFile {
+    /// Name of the license file within the package
+    pub name: String,
+    /// The SPDX of the file, if any was detected despite the low confidence
+    pub spdx: Option<String>,
+    pub detection_score: Option<f32>,
+    pub detection_method: Option<DetectionMethod>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<LicenseCandidate>,
+}
+
+/// Whether the license of the file was not determined confidently: license texts without an SPDX, texts that were
+/// detected with a score below the threshold and licenses that were only mentioned by a README
+pub fn needs_review(l: &LicenseFile, threshold: f32) -> bool {
+    let low_confidence = l.detection_method == Some(DetectionMethod::FileAnalysis)
+        && l.detection_score.is_some_and(|score| score < threshold);
+
+    l.kind.is_license()
+        && (l.spdx.is_none() || low_confidence || l.detection_method == Some(DetectionMethod::ReadmeSection))
+}
+
+/// Write every license text that has to be reviewed (see [`needs_review`]) into the given directory, one file per
+/// package (e.g. `openssl-src-300.2.3.txt`), and an index of them as JSON ([`UNREVIEWED_INDEX`]), so they can be
+/// worked through as a queue. The directory is created if needed, files of earlier exports are overwritten but not
+/// removed. Returns the entries of the index.
+///
+/// The texts have to be complete, i.e. neither shortened nor moved into a text table.
+pub fn export_unreviewed<P: AsRef<Utf8Path>>(
+    packages: &[Package],
+    dir: P,
+    threshold: f32,
+) -> Result<Vec<UnreviewedPackage>, AggregateError> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(write_err(dir))?;
+
+    let mut index = vec![];
+    for p in packages {
+        let files: Vec<_> = p.license_files.iter().filter(|l| needs_review(l, threshold)).collect();
+        if files.is_empty() {
+            continue;
+        }
+
+        let license = p.license_spdx.as_ref().map(ToString::to_string);
+        let mut content = format!(
+            "{} {}\nLicense: {}\n",
+            p.package_name,
+            p.package_version,
+            license.as_deref().unwrap_or("unknown")
+        );
+        for l in &files {
+            let score = l.detection_score.map_or_else(String::new, |score| format!(" (score {score:.2})"));
+            content.push_str(&format!("\n==> {}{score} <==\n{}\n", l.name, l.text.trim_end()));
+        }
+
+        let file_name = format!("{}-{}.txt", p.package_name, p.package_version)
+            .replace(|c: char| !(c.is_ascii_alphanumeric() || "._+-".contains(c)), "_");
+        let path = dir.join(&file_name);
+        std::fs::write(&path, content).map_err(write_err(&path))?;
+
+        index.push(UnreviewedPackage {
+            package_name: p.package_name.clone(),
+            package_version: p.package_version.clone(),
+            license,
+            path: file_name,
+            files: files
+                .into_iter()
+                .map(|l| UnreviewedFile {
+                    name: l.name.clone(),
+                    spdx: l.spdx.as_ref().map(ToString::to_string),
+                    detection_score: l.detection_score,
+                    detection_method: l.detection_method,
+                    candidates: l.candidates.clone(),
+                })
+                .collect(),
+        });
+    }
+
+    let path = dir.join(UNREVIEWED_INDEX);
+    let json = serde_json::to_string_pretty(&index).map_err(|e| write_err(&path)(e.into()))?;
+    std::fs::write(&path, json).map_err(write_err(&path))?;
+
+    Ok(index)
+}
+
+fn write_err(path: &Utf8Path) -> impl FnOnce(std::io::Error) -> AggregateError + '_ {
+    move |source| AggregateError::Write { path: path.to_owned(), source }
+}
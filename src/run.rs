@@ -0,0 +1,120 @@
+use crate::{
+    collect_krate_licenses, collect_thirdparty_licenses, get_all_krates, stream_thirdparty_licenses, AggregateError,
+    CollectContext, Config, Diagnostics, GatherOptions, Krates, LicenseStore, Package, Utf8Path, Utf8PathBuf,
+};
+use std::sync::Arc;
+
+/// A resolved dependency graph together with the configuration and license store of a run.
+///
+/// Resolving the graph is the expensive part of a run, so callers producing several exports from one scan
+/// (e.g. rust and thirdparty licenses separately, or several [`crate::Aggregator`]s, see [`crate::Aggregator::run_with`])
+/// resolve it once and share the context.
+pub struct RunContext {
+    manifest: Utf8PathBuf,
+    krates: Krates,
+    config: Config,
+    license_store: Arc<LicenseStore>,
+    options: GatherOptions,
+    /// Whether resolving the graph wrote the missing Cargo.lock of the manifest
+    generated_lockfile: bool,
+}
+
+impl RunContext {
+    /// Resolve the dependency graph of the Cargo.toml at the given path
+    pub fn new<P: AsRef<Utf8Path>>(
+        cargo_toml: P,
+        options: GatherOptions,
+        config: Config,
+        license_store: Arc<LicenseStore>,
+    ) -> Result<Self, AggregateError> {
+        let manifest = cargo_toml.as_ref().to_owned();
+        let generated_lockfile = options.generates_lockfile(&manifest);
+        let krates = get_all_krates(&manifest, &options, &config)?;
+        Ok(Self { manifest, krates, config, license_store, options, generated_lockfile })
+    }
+
+    /// Resolve the dependency graph again with other options (e.g. other features or targets), keeping the
+    /// configuration and the license store
+    pub fn resolve(&mut self, options: GatherOptions) -> Result<(), AggregateError> {
+        let generated_lockfile = options.generates_lockfile(&self.manifest);
+        self.krates = get_all_krates(&self.manifest, &options, &self.config)?;
+        self.generated_lockfile |= generated_lockfile;
+        self.options = options;
+        Ok(())
+    }
+
+    pub fn krates(&self) -> &Krates {
+        &self.krates
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn license_store(&self) -> &Arc<LicenseStore> {
+        &self.license_store
+    }
+
+    pub fn options(&self) -> &GatherOptions {
+        &self.options
+    }
+
+    /// Whether resolving the graph wrote the missing Cargo.lock of the manifest, see
+    /// [`GatherOptions::allow_lockfile_generation`]
+    pub fn generated_lockfile(&self) -> bool {
+        self.generated_lockfile
+    }
+
+    pub(crate) fn manifest(&self) -> &Utf8Path {
+        &self.manifest
+    }
+
+    /// The context passed to [`crate::LicenseSource`]s
+    pub fn collect_context(&self) -> CollectContext<'_> {
+        CollectContext {
+            krates: &self.krates,
+            license_store: self.license_store.clone(),
+            config: &self.config,
+            options: &self.options,
+        }
+    }
+
+    /// See [`crate::collect_krate_licenses`]
+    pub fn collect_krate_licenses(&self, diagnostics: &mut Diagnostics) -> Result<Vec<Package>, AggregateError> {
+        collect_krate_licenses(&self.krates, self.license_store.clone(), &self.config, &self.options, diagnostics)
+    }
+
+    /// See [`crate::collect_thirdparty_licenses`]
+    pub fn collect_thirdparty_licenses(
+        &self,
+        metadata_key: &str,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Package>, AggregateError> {
+        collect_thirdparty_licenses(
+            &self.krates,
+            self.license_store.clone(),
+            &self.config,
+            &self.options,
+            metadata_key,
+            diagnostics,
+        )
+    }
+
+    /// See [`crate::stream_thirdparty_licenses`]
+    pub fn stream_thirdparty_licenses<F: FnMut(Package)>(
+        &self,
+        metadata_key: &str,
+        diagnostics: &mut Diagnostics,
+        f: F,
+    ) -> Result<(), AggregateError> {
+        stream_thirdparty_licenses(
+            &self.krates,
+            self.license_store.clone(),
+            &self.config,
+            &self.options,
+            metadata_key,
+            diagnostics,
+            f,
+        )
+    }
+}
@@ -0,0 +1,516 @@
+//! Import of the results of ScanCode Toolkit (`scancode --json`) as packages, see [`ScanCodeSource`]
+
+use crate::{
+    augment_licenses_cached,
+    search::{file_kind, is_license_file_name, read_text},
+    AggregateError, CollectContext, DetectionMethod, Diagnostic, DiagnosticKind, Diagnostics, Expression, GitReference,
+    LicenseFile, LicenseSource, Origin, Package, Utf8Path, Utf8PathBuf,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, process::Command, str::FromStr};
+
+/// Files of which ScanCode matched at least this percentage as license text are license files, whatever their name
+const LICENSE_TEXT_PERCENTAGE: f32 = 90.0;
+
+/// Version of the packages whose version is not known to ScanCode
+const UNKNOWN_VERSION: &str = "unknown";
+
+/// Collects the packages of a ScanCode Toolkit JSON results file (`scancode --license --copyright --package --json`),
+/// so the findings of deep scans (e.g. of C++ sources) are merged with the rust packages.
+///
+/// The packages detected by ScanCode keep their declared license and get the license files that ScanCode assigned to
+/// them (`for_packages`). The remaining files are grouped by their directory at [`ScanCodeSource::package_depth`],
+/// each group with license files or copyrights becomes a package whose license is the combination of its license
+/// files. The licenses detected by ScanCode are marked with [`DetectionMethod::ExternalScan`], license texts without
+/// a detection are analyzed like the ones of thirdparty files (see [`crate::augment_licenses`]).
+///
+/// Directories that are git submodules get an [`Origin::Git`] with their checked out commit, so the attribution pins
+/// the revision of the scanned files.
+#[derive(Clone, Debug)]
+pub struct ScanCodeSource {
+    /// The JSON results of ScanCode
+    pub results: Utf8PathBuf,
+    /// The directory the paths of the results are relative to, i.e. the parent of the scanned directory (or the
+    /// scanned directory itself for results with `--strip-root`), defaults to the directory of the results
+    pub root: Option<Utf8PathBuf>,
+    /// The number of path components of the directories whose files form a package, `1` by default, i.e. all files of
+    /// the scanned directory form a single package
+    pub package_depth: usize,
+}
+
+impl ScanCodeSource {
+    pub fn new<P: Into<Utf8PathBuf>>(results: P) -> Self {
+        Self { results: results.into(), root: None, package_depth: 1 }
+    }
+
+    /// The directory the paths of the results are relative to, see [`ScanCodeSource::root`]
+    pub fn root<P: Into<Utf8PathBuf>>(mut self, root: P) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// The depth of the package directories, e.g. `3` for `tentris/thirdparty/zlib` (see
+    /// [`ScanCodeSource::package_depth`])
+    pub fn package_depth(mut self, package_depth: usize) -> Self {
+        self.package_depth = package_depth.max(1);
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct ScanResults {
+    #[serde(default)]
+    packages: Vec<ScanPackage>,
+    #[serde(default)]
+    files: Vec<ScanFile>,
+}
+
+#[derive(Deserialize)]
+struct ScanPackage {
+    name: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    purl: Option<String>,
+    homepage_url: Option<String>,
+    vcs_url: Option<String>,
+    declared_license_expression_spdx: Option<String>,
+    copyright: Option<String>,
+    package_uid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScanFile {
+    path: String,
+    #[serde(rename = "type")]
+    resource_type: String,
+    #[serde(default)]
+    is_license_text: bool,
+    percentage_of_license_text: Option<f32>,
+    detected_license_expression_spdx: Option<String>,
+    #[serde(default)]
+    license_detections: Vec<ScanDetection>,
+    #[serde(default)]
+    copyrights: Vec<ScanCopyright>,
+    #[serde(default)]
+    for_packages: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ScanDetection {
+    #[serde(default)]
+    matches: Vec<ScanMatch>,
+}
+
+#[derive(Deserialize)]
+struct ScanMatch {
+    /// Between 0 and 100
+    score: Option<f32>,
+    matched_text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScanCopyright {
+    copyright: String,
+}
+
+impl LicenseSource for ScanCodeSource {
+    fn collect(&self, ctx: &CollectContext<'_>, diagnostics: &mut Diagnostics) -> Result<Vec<Package>, AggregateError> {
+        ctx.options.check_cancelled()?;
+        let mut packages = self.load(diagnostics)?;
+        augment_licenses_cached(&mut packages, &ctx.license_store, ctx.config, ctx.options, diagnostics);
+        Ok(packages)
+    }
+}
+
+impl ScanCodeSource {
+    /// The packages of the results, without analyzing the license texts ScanCode detected nothing in
+    pub fn load(&self, diagnostics: &mut Diagnostics) -> Result<Vec<Package>, AggregateError> {
+        let path = &self.results;
+        let content =
+            std::fs::read(path).map_err(|source| AggregateError::LicenseRead { path: path.clone(), source })?;
+        let results: ScanResults = serde_json::from_slice(&content)
+            .map_err(|source| AggregateError::ScanCodeParse { path: path.clone(), source })?;
+
+        let root = match &self.root {
+            Some(root) => root.clone(),
+            None => path.parent().map(ToOwned::to_owned).unwrap_or_default(),
+        };
+
+        let mut packages = vec![];
+        let mut by_uid = HashMap::new();
+        for p in &results.packages {
+            let Some(name) = p.name.clone() else {
+                continue;
+            };
+            if let Some(uid) = &p.package_uid {
+                by_uid.insert(uid.as_str(), packages.len());
+            }
+
+            let version = p.version.clone().unwrap_or_else(|| UNKNOWN_VERSION.to_owned());
+            let license = p.declared_license_expression_spdx.as_deref().and_then(|l| {
+                parse_license(l, path.as_str(), &name, &version, diagnostics)
+            });
+            let mut pkg = package(name, version, license);
+            pkg.description = p.description.clone();
+            pkg.package_url = p.homepage_url.clone().or_else(|| p.vcs_url.clone());
+            pkg.purl = p.purl.clone();
+            pkg.copyrights.extend(p.copyright.clone());
+            packages.push(pkg);
+        }
+
+        // the files without a package are grouped by their directory, in the order of the results
+        let mut directories: Vec<(String, Package)> = vec![];
+        for file in results.files.iter().filter(|f| f.resource_type == "file") {
+            let pkg = match file.for_packages.iter().find_map(|uid| by_uid.get(uid.as_str())) {
+                Some(&index) => &mut packages[index],
+                None => {
+                    let dir = self.package_dir(&file.path);
+                    let index = match directories.iter().position(|(d, _)| *d == dir) {
+                        Some(index) => index,
+                        None => {
+                            // files in the root of results with `--strip-root` belong to the scanned directory
+                            let name = dir.rsplit('/').next().filter(|name| !name.is_empty()).or(root.file_name());
+                            let name = name.unwrap_or("scan").to_owned();
+                            directories.push((dir, package(name, UNKNOWN_VERSION.to_owned(), None)));
+                            directories.len() - 1
+                        },
+                    };
+                    &mut directories[index].1
+                },
+            };
+
+            for copyright in &file.copyrights {
+                if !pkg.copyrights.contains(&copyright.copyright) {
+                    pkg.copyrights.push(copyright.copyright.clone());
+                }
+            }
+
+            if let Some(l) = license_file(&root, file, pkg, diagnostics) {
+                pkg.license_files.push(l);
+            }
+        }
+
+        for (dir, mut pkg) in directories {
+            if pkg.license_files.is_empty() && pkg.copyrights.is_empty() {
+                continue;
+            }
+
+            let mut licenses: Vec<String> = vec![];
+            for l in pkg.license_files.iter().filter(|l| l.kind.is_license()) {
+                if let Some(spdx) = &l.spdx {
+                    let spdx = spdx.to_string();
+                    if !licenses.contains(&spdx) {
+                        licenses.push(spdx);
+                    }
+                }
+            }
+            if !licenses.is_empty() {
+                let combined = match licenses.as_slice() {
+                    [license] => license.clone(),
+                    licenses => {
+                        let parenthesized = |l: &String| if l.contains(' ') { format!("({l})") } else { l.clone() };
+                        licenses.iter().map(parenthesized).collect::<Vec<_>>().join(" AND ")
+                    },
+                };
+                pkg.license_spdx = Expression::from_str(&combined).ok();
+            }
+            pkg.origin = submodule_origin(&root.join(&dir));
+            packages.push(pkg);
+        }
+
+        for pkg in &mut packages {
+            if pkg.purl.is_none() {
+                pkg.purl = Some(crate::generic_purl(&pkg.package_name, &pkg.package_version));
+            }
+        }
+
+        Ok(packages)
+    }
+
+    /// The directory of the package the file belongs to, i.e. the first components of its directory
+    fn package_dir(&self, path: &str) -> String {
+        let components: Vec<_> = path.split('/').collect();
+        let depth = self.package_depth.min(components.len() - 1);
+        components[..depth].join("/")
+    }
+}
+
+/// The license or notice file of the scanned file, if it is one
+fn license_file(root: &Utf8Path, file: &ScanFile, pkg: &Package, diagnostics: &mut Diagnostics) -> Option<LicenseFile> {
+    let name = file.path.rsplit('/').next().unwrap_or(&file.path);
+    let license_text = file.is_license_text
+        || file.percentage_of_license_text.is_some_and(|percentage| percentage >= LICENSE_TEXT_PERCENTAGE);
+    if !license_text && !is_license_file_name(name) {
+        return None;
+    }
+
+    let matches = file.license_detections.iter().flat_map(|d| &d.matches);
+    let (text, encoding) = match read_text(&root.join(&file.path)) {
+        Ok((text, encoding)) => (text, encoding),
+        Err(e) => {
+            let matched: Vec<_> = matches.clone().filter_map(|m| m.matched_text.as_deref()).collect();
+            if matched.is_empty() {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        DiagnosticKind::UnreadableLicenseFile,
+                        format!("Unable to read the scanned license file {}: {e}", file.path),
+                    )
+                    .with_package(&pkg.package_name, &pkg.package_version)
+                    .with_file(&file.path),
+                );
+                return None;
+            }
+            (matched.join("\n\n"), None)
+        },
+    };
+
+    let mut l = LicenseFile::new(file.path.clone(), None, text);
+    l.kind = file_kind(name);
+    l.encoding = encoding.map(ToOwned::to_owned);
+    if let Some(detected) = &file.detected_license_expression_spdx {
+        l.spdx = parse_license(detected, &file.path, &pkg.package_name, &pkg.package_version, diagnostics);
+    }
+    if l.spdx.is_some() {
+        l.detection_method = Some(DetectionMethod::ExternalScan);
+        l.detection_score = matches.filter_map(|m| m.score).reduce(f32::min).map(|score| score / 100.0);
+    }
+    Some(l)
+}
+
+/// The origin of a directory that is a git submodule (whose `.git` is a file pointing into the superproject): its
+/// remote, the checked out commit and the tag of the commit, or else the branch the submodule tracks in `.gitmodules`
+pub(crate) fn submodule_origin(dir: &Utf8Path) -> Option<Origin> {
+    if !dir.join(".git").is_file() {
+        return None;
+    }
+
+    let url = git(dir, &["config", "--get", "remote.origin.url"])?;
+    let commit = git(dir, &["rev-parse", "HEAD"]);
+    let reference = match git(dir, &["describe", "--tags", "--exact-match", "HEAD"]) {
+        Some(tag) => Some(GitReference::Tag(tag)),
+        None => submodule_branch(dir).map(GitReference::Branch),
+    };
+
+    Some(Origin::Git { url, commit, reference })
+}
+
+/// The `branch` of the submodule in the `.gitmodules` of its superproject
+fn submodule_branch(dir: &Utf8Path) -> Option<String> {
+    let superproject = Utf8PathBuf::from(git(dir, &["rev-parse", "--show-superproject-working-tree"])?);
+    let path = dir.canonicalize_utf8().ok()?;
+    let path = path.strip_prefix(superproject.canonicalize_utf8().ok()?).ok()?.as_str().replace('\\', "/");
+
+    // e.g. `submodule.zlib.path thirdparty/zlib`
+    let paths = git(&superproject, &["config", "-f", ".gitmodules", "--get-regexp", r"^submodule\..*\.path$"])?;
+    let key = paths.lines().find_map(|line| line.strip_suffix(&format!(" {path}")))?;
+    let name = key.strip_prefix("submodule.")?.strip_suffix(".path")?;
+    git(&superproject, &["config", "-f", ".gitmodules", "--get", &format!("submodule.{name}.branch")])
+}
+
+/// The trimmed output of the git command in the directory, if it succeeded with any
+fn git(dir: &Utf8Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let stdout = stdout.trim();
+    (output.status.success() && !stdout.is_empty()).then(|| stdout.to_owned())
+}
+
+fn parse_license(
+    license: &str,
+    file: &str,
+    name: &str,
+    version: &str,
+    diagnostics: &mut Diagnostics,
+) -> Option<Expression> {
+    match Expression::from_str(license) {
+        Ok(expression) => Some(expression),
+        Err(e) => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::InvalidDetectedLicense,
+                    format!("ScanCode detected '{license}' in {file}, which is not a valid SPDX expression: {e}"),
+                )
+                .with_package(name, version),
+            );
+            None
+        },
+    }
+}
+
+fn package(package_name: String, package_version: String, license_spdx: Option<Expression>) -> Package {
+    Package {
+        package_name,
+        package_version,
+        package_url: None,
+        authors: Vec::new(),
+        description: None,
+        registry_url: None,
+        purl: None,
+        checksum: None,
+        source_archive: None,
+        license_spdx,
+        license_files: Vec::new(),
+        copyrights: Vec::new(),
+        native_code: None,
+        linkage: None,
+        origin: None,
+        fork: None,
+        versions: Vec::new(),
+        feature_sets: Vec::new(),
+        platforms: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_dir(name: &str) -> Utf8PathBuf {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let dir = dir.join(format!("tla-scancode-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Utf8Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn license(p: &Package) -> Option<String> {
+        p.license_spdx.as_ref().map(ToString::to_string)
+    }
+
+    #[test]
+    fn packages_and_directories() {
+        let dir = scan_dir("packages");
+        let thirdparty = dir.join("tentris").join("thirdparty");
+        write(&thirdparty.join("zlib").join("LICENSE"), "zlib license");
+        write(&thirdparty.join("serd").join("COPYING"), "ISC license");
+        write(&thirdparty.join("dual").join("COPYING"), "BSD license");
+        write(&thirdparty.join("dual").join("LICENSE-APACHE"), "Apache license");
+
+        let results = serde_json::json!({
+            "packages": [{
+                "name": "serd",
+                "version": "0.30.16",
+                "homepage_url": "https://drobilla.net/software/serd",
+                "declared_license_expression_spdx": "ISC",
+                "copyright": "Copyright 2011-2023 David Robillard",
+                "package_uid": "pkg:generic/serd@0.30.16?uuid=1"
+            }],
+            "files": [
+                {"path": "tentris", "type": "directory"},
+                {
+                    "path": "tentris/thirdparty/serd/COPYING",
+                    "type": "file",
+                    "is_license_text": true,
+                    "detected_license_expression_spdx": "ISC",
+                    "license_detections": [{"matches": [{"score": 100.0}]}],
+                    "for_packages": ["pkg:generic/serd@0.30.16?uuid=1"]
+                },
+                {
+                    "path": "tentris/thirdparty/zlib/LICENSE",
+                    "type": "file",
+                    "detected_license_expression_spdx": "Zlib",
+                    "license_detections": [{"matches": [{"score": 95.0}, {"score": 80.0}]}]
+                },
+                {
+                    "path": "tentris/thirdparty/zlib/src/zlib.h",
+                    "type": "file",
+                    "copyrights": [{"copyright": "Copyright (C) 1995-2024 Jean-loup Gailly and Mark Adler"}]
+                },
+                {
+                    "path": "tentris/thirdparty/fmt/doc/usage.txt",
+                    "type": "file",
+                    "percentage_of_license_text": 97.5,
+                    "detected_license_expression_spdx": "MIT",
+                    "license_detections": [{"matches": [{"matched_text": "Permission is hereby granted"}]}]
+                },
+                {
+                    "path": "tentris/thirdparty/dual/COPYING",
+                    "type": "file",
+                    "detected_license_expression_spdx": "BSD-3-Clause OR MIT"
+                },
+                {
+                    "path": "tentris/thirdparty/dual/LICENSE-APACHE",
+                    "type": "file",
+                    "detected_license_expression_spdx": "Apache-2.0"
+                },
+                {"path": "tentris/thirdparty/empty/README.md", "type": "file"},
+                {
+                    "path": "tentris/thirdparty/invalid/LICENSE",
+                    "type": "file",
+                    "detected_license_expression_spdx": "not a license!",
+                    "license_detections": [{"matches": [{"matched_text": "All rights"}]}]
+                }
+            ]
+        });
+        let path = dir.join("results.json");
+        std::fs::write(&path, results.to_string()).unwrap();
+
+        let mut diagnostics = Diagnostics::new();
+        let packages = ScanCodeSource::new(&path).package_depth(3).load(&mut diagnostics).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<_> = packages.iter().map(|p| (p.package_name.as_str(), p.package_version.as_str())).collect();
+        assert_eq!(
+            names,
+            [("serd", "0.30.16"), ("zlib", "unknown"), ("fmt", "unknown"), ("dual", "unknown"), ("invalid", "unknown")]
+        );
+
+        let serd = &packages[0];
+        assert_eq!(license(serd).as_deref(), Some("ISC"));
+        assert_eq!(serd.package_url.as_deref(), Some("https://drobilla.net/software/serd"));
+        assert_eq!(serd.purl.as_deref(), Some("pkg:generic/serd@0.30.16"));
+        assert_eq!(serd.license_files[0].text, "ISC license");
+
+        let zlib = &packages[1];
+        assert_eq!(license(zlib).as_deref(), Some("Zlib"));
+        assert_eq!(zlib.copyrights, ["Copyright (C) 1995-2024 Jean-loup Gailly and Mark Adler"]);
+        assert_eq!(zlib.license_files.len(), 1, "only the license files");
+        assert_eq!(zlib.license_files[0].detection_method, Some(DetectionMethod::ExternalScan));
+        assert_eq!(zlib.license_files[0].detection_score, Some(0.8));
+
+        // not on disk, the matched text is used instead
+        let fmt = &packages[2];
+        assert_eq!(fmt.license_files[0].text, "Permission is hereby granted");
+        assert_eq!(license(fmt).as_deref(), Some("MIT"));
+
+        assert_eq!(license(&packages[3]).as_deref(), Some("(BSD-3-Clause OR MIT) AND Apache-2.0"));
+
+        let invalid = &packages[4];
+        assert!(invalid.license_spdx.is_none());
+        assert!(invalid.license_files[0].spdx.is_none());
+        let kinds: Vec<_> = diagnostics.iter().map(|d| d.kind).collect();
+        assert_eq!(kinds, [DiagnosticKind::InvalidDetectedLicense]);
+    }
+
+    #[test]
+    fn strip_root() {
+        let dir = scan_dir("strip-root");
+        let scanned = dir.join("zlib");
+        write(&scanned.join("LICENSE"), "zlib license");
+
+        let results = serde_json::json!({
+            "files": [
+                {"path": "LICENSE", "type": "file", "detected_license_expression_spdx": "Zlib"},
+                {"path": "zlib.h", "type": "file", "copyrights": [{"copyright": "Copyright (C) Jean-loup Gailly"}]}
+            ]
+        });
+        let path = dir.join("results.json");
+        std::fs::write(&path, results.to_string()).unwrap();
+
+        let mut diagnostics = Diagnostics::new();
+        let packages = ScanCodeSource::new(&path).root(&scanned).load(&mut diagnostics).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(packages.len(), 1, "the files in the root form the package of the scanned directory");
+        assert_eq!(packages[0].package_name, "zlib");
+        assert_eq!(license(&packages[0]).as_deref(), Some("Zlib"));
+        assert_eq!(packages[0].license_files[0].text, "zlib license");
+        assert_eq!(packages[0].copyrights, ["Copyright (C) Jean-loup Gailly"]);
+        assert!(diagnostics.is_empty());
+    }
+}
@@ -0,0 +1,487 @@
+//! Heuristic search of package directories for license files, README license sections and SPDX headers of source
+//! files, see [`find_license_files`]
+
+use crate::{
+    DetectionMethod, Diagnostic, DiagnosticKind, Diagnostics, Expression, FileKind, LicenseFile, Package, Utf8Path,
+    Utf8PathBuf,
+};
+use std::{io::Read, str::FromStr};
+
+/// Files starting with one of these (case-insensitive) are considered license files
+const LICENSE_FILE_PREFIXES: &[&str] =
+    &["LICENSE", "LICENCE", "COPYING", "COPYRIGHT", "NOTICE", "PATENTS", "UNLICENSE"];
+
+/// All files in directories with one of these names (case-insensitive) are considered license files
+const LICENSE_DIRS: &[&str] = &["license", "licenses", "licence", "licences"];
+
+/// Files that accompany the license texts and are collected in addition to them
+const NOTICE_FILES: &[(&str, FileKind)] = &[
+    ("NOTICE", FileKind::Notice),
+    ("NOTICE.txt", FileKind::Notice),
+    ("NOTICE.md", FileKind::Notice),
+    ("PATENTS", FileKind::PatentGrant),
+    ("PATENTS.txt", FileKind::PatentGrant),
+];
+
+/// Search a package directory for license files, for packages where regular gathering found none.
+///
+/// Considers files named `LICENSE*`, `COPYING*`, `NOTICE*` (and similar), the files in `license/` directories,
+/// and if there are none of those, the license sections of `README*` files.
+/// The returned files are marked as [`LicenseFile::heuristic`] and have no SPDX, since nothing was detected, except
+/// for the README sections, which have the license they mention (see [`readme_license`]), and files that declare the
+/// work to be in the public domain (see [`declares_public_domain`]).
+pub fn find_license_files(dir: &Utf8Path) -> Vec<LicenseFile> {
+    let mut files = vec![];
+
+    for entry in sorted_entries(dir) {
+        let name = entry.file_name().unwrap_or_default();
+
+        if entry.is_dir() {
+            if LICENSE_DIRS.iter().any(|d| name.eq_ignore_ascii_case(d)) {
+                files.extend(
+                    sorted_entries(&entry)
+                        .into_iter()
+                        .filter(|path| path.is_file())
+                        .filter_map(|path| read_license_file(&path, format!("{name}/{}", path.file_name()?))),
+                );
+            }
+        } else if is_license_file_name(name) {
+            files.extend(read_license_file(&entry, name.to_owned()));
+        }
+    }
+
+    if files.is_empty() {
+        for entry in sorted_entries(dir) {
+            let name = entry.file_name().unwrap_or_default();
+            if !entry.is_file() || !name.to_ascii_uppercase().starts_with("README") {
+                continue;
+            }
+
+            let Ok((text, encoding)) = read_text(&entry) else {
+                continue;
+            };
+
+            if let Some(section) = readme_license_section(&text) {
+                let spdx = readme_license(&section);
+                let mut file = LicenseFile::new(format!("{name} (license section)"), spdx, section);
+                file.heuristic = true;
+                file.detection_method = Some(DetectionMethod::ReadmeSection);
+                file.encoding = encoding.map(ToOwned::to_owned);
+                files.push(file);
+            }
+        }
+    }
+
+    files
+}
+
+/// The `NOTICE` and `PATENTS` files in the root of a package directory, with the corresponding [`FileKind`]
+pub fn find_notice_files(dir: &Utf8Path) -> Vec<LicenseFile> {
+    sorted_entries(dir)
+        .into_iter()
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let name = path.file_name()?;
+            let (_, kind) = NOTICE_FILES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name))?;
+
+            let (text, encoding) = read_text(&path)
+                .inspect_err(|e| tracing::debug!("skipping notice file {path}: {e}"))
+                .ok()?;
+
+            let mut file = LicenseFile::new(name.to_owned(), None, text);
+            file.kind = *kind;
+            file.encoding = encoding.map(ToOwned::to_owned);
+            Some(file)
+        })
+        .collect()
+}
+
+/// Phrases (in lowercase) by which a text dedicates a work to the public domain
+const PUBLIC_DOMAIN_PHRASES: &[&str] = &[
+    "released into the public domain",
+    "placed in the public domain",
+    "placed into the public domain",
+    "dedicated to the public domain",
+    "is in the public domain",
+    "put into the public domain",
+];
+
+/// Whether the prose of a text declares the work to be in the public domain (e.g. `This code is placed in the public
+/// domain`), which has no SPDX identifier (see [`crate::obligations::PUBLIC_DOMAIN_REF`]).
+/// The text of the Unlicense also dedicates the work to the public domain, but is recognized as such by the store.
+pub fn declares_public_domain(text: &str) -> bool {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    !text.contains("unlicense") && PUBLIC_DOMAIN_PHRASES.iter().any(|phrase| text.contains(phrase))
+}
+
+/// Markers (in lowercase) of a copyright notice
+const COPYRIGHT_MARKERS: &[&str] = &["copyright", "(c)", "©"];
+
+/// Words (in lowercase) of which any license grant contains at least one
+const GRANT_WORDS: &[&str] = &[
+    "permission",
+    "permitted",
+    "granted",
+    "license",
+    "licence",
+    "redistribut",
+    "you may",
+    "public domain",
+    "free software",
+];
+
+/// Whether the text claims a copyright (e.g. `Copyright (c) 2019 Example Corp. All rights reserved.`) without granting
+/// any license, so nobody but the authors may use the work (see [`crate::obligations::ALL_RIGHTS_RESERVED_REF`]).
+pub fn reserves_all_rights(text: &str) -> bool {
+    let text = text.to_lowercase();
+    COPYRIGHT_MARKERS.iter().any(|marker| text.contains(marker)) && !GRANT_WORDS.iter().any(|word| text.contains(word))
+}
+
+fn public_domain_license() -> Option<Expression> {
+    Expression::from_str(crate::obligations::PUBLIC_DOMAIN_REF).ok()
+}
+
+/// Directories that are not part of the sources of a package
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+/// Only the beginning of a source file is searched for an SPDX header
+const HEADER_BYTES: u64 = 4096;
+
+/// A `SPDX-License-Identifier:` header of a source file
+#[derive(Clone, Debug)]
+pub struct SpdxHeader {
+    /// Path of the source file
+    pub path: Utf8PathBuf,
+    /// The declared license
+    pub license: Expression,
+}
+
+/// Read the `SPDX-License-Identifier:` headers (REUSE-style) of all files below a package directory.
+///
+/// Hidden directories as well as `target` and `node_modules` are skipped, headers that are not valid SPDX expressions
+/// are ignored.
+pub fn scan_spdx_headers(dir: &Utf8Path) -> Vec<SpdxHeader> {
+    let mut headers = vec![];
+    let mut dirs = vec![dir.to_owned()];
+
+    while let Some(dir) = dirs.pop() {
+        for path in sorted_entries(&dir) {
+            let name = path.file_name().unwrap_or_default();
+
+            if path.is_dir() {
+                if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name) {
+                    dirs.push(path);
+                }
+            } else if let Some(license) = read_spdx_header(&path) {
+                headers.push(SpdxHeader { path, license });
+            }
+        }
+    }
+
+    headers
+}
+
+/// Report the source files of the package whose SPDX header declares licenses that are not part of the package license
+/// (e.g. a GPL file vendored inside an MIT crate) as [`DiagnosticKind::SourceFileLicenseMismatch`]
+pub fn check_spdx_headers(pkg: &Package, dir: &Utf8Path, diagnostics: &mut Diagnostics) {
+    let Some(package_license) = &pkg.license_spdx else {
+        return;
+    };
+
+    for header in scan_spdx_headers(dir) {
+        let covered = header
+            .license
+            .requirements()
+            .all(|file_req| package_license.requirements().any(|pkg_req| pkg_req.req == file_req.req));
+
+        if !covered {
+            let file = header.path.strip_prefix(dir).unwrap_or(&header.path);
+            diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticKind::SourceFileLicenseMismatch,
+                    format!(
+                        "Source file {file} of '{} {}' is licensed under {}, which is not part of the package license {}",
+                        pkg.package_name, pkg.package_version, header.license, package_license
+                    ),
+                )
+                .with_package(&pkg.package_name, &pkg.package_version)
+                .with_file(file.as_str()),
+            );
+        }
+    }
+}
+
+fn read_spdx_header(path: &Utf8Path) -> Option<Expression> {
+    let mut head = Vec::new();
+    std::fs::File::open(path).ok()?.take(HEADER_BYTES).read_to_end(&mut head).ok()?;
+
+    let head = String::from_utf8_lossy(&head);
+    let (_, rest) = head.split_once("SPDX-License-Identifier:")?;
+    let line = rest.lines().next()?;
+
+    // strip the end of block comments, e.g. `/* SPDX-License-Identifier: MIT */`
+    let expression = line.trim().trim_end_matches("*/").trim_end_matches("-->").trim();
+
+    match Expression::from_str(expression) {
+        Ok(expression) => Some(expression),
+        Err(e) => {
+            tracing::debug!("ignoring invalid SPDX header of {path}: {e}");
+            None
+        },
+    }
+}
+
+/// The license section of a markdown README: everything below a heading mentioning the license
+/// up to the next heading of the same or a higher level
+pub fn readme_license_section(readme: &str) -> Option<String> {
+    let mut section: Option<(usize, Vec<&str>)> = None;
+
+    for line in readme.lines() {
+        let level = line.chars().take_while(|c| *c == '#').count();
+        let is_heading = level > 0 && line[level..].starts_with(' ');
+
+        match &mut section {
+            Some((section_level, _)) if is_heading && level <= *section_level => break,
+            Some((_, lines)) => lines.push(line),
+            None if is_heading && line[level..].to_lowercase().contains("licen") => section = Some((level, vec![])),
+            None => {},
+        }
+    }
+
+    let text = section?.1.join("\n");
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+/// The license stated by the license section of a README (see [`readme_license_section`]): the SPDX identifiers of
+/// the OSI approved or FSF libre licenses it mentions (others, e.g. `JSON`, are too common as words), as alternatives
+/// if the section offers a choice (`either`, `or` or `MIT/Apache-2.0`), otherwise all of them are required.
+/// Names that are no SPDX identifiers (e.g. `Apache License, Version 2.0`) are not recognized, sections that
+/// mention none but declare the work to be in the public domain (see [`declares_public_domain`]) have
+/// [`crate::obligations::PUBLIC_DOMAIN_REF`].
+pub fn readme_license(section: &str) -> Option<Expression> {
+    let mut ids: Vec<&str> = vec![];
+    for token in section.split(|c: char| !(c.is_ascii_alphanumeric() || "-.+".contains(c))) {
+        let token = token.trim_end_matches('.');
+        let free = spdx::license_id(token).is_some_and(|id| id.is_osi_approved() || id.is_fsf_free_libre());
+        if free && !ids.contains(&token) {
+            ids.push(token);
+        }
+    }
+
+    let lower = section.to_lowercase();
+    let choice = lower.contains("either") || lower.split_whitespace().any(|w| w == "or") || ids.len() > 1 && {
+        let pair = format!("{}/{}", ids[0], ids[1]);
+        section.contains(&pair)
+    };
+
+    if ids.is_empty() && declares_public_domain(section) {
+        return public_domain_license();
+    }
+
+    Expression::from_str(&ids.join(if choice { " OR " } else { " AND " })).ok()
+}
+
+fn read_license_file(path: &Utf8Path, name: String) -> Option<LicenseFile> {
+    match read_text(path) {
+        Ok((text, encoding)) => {
+            let mut file = LicenseFile::new(name, None, text);
+            file.heuristic = true;
+            file.kind = file_kind(path.file_name().unwrap_or_default());
+            if file.kind.is_license() && declares_public_domain(&file.text) {
+                file.spdx = public_domain_license();
+                file.detection_method = Some(DetectionMethod::FileAnalysis);
+            }
+            file.encoding = encoding.map(ToOwned::to_owned);
+            Some(file)
+        },
+        Err(e) => {
+            tracing::debug!("skipping possible license file {path}: {e}");
+            None
+        },
+    }
+}
+
+/// Read a text file that need not be UTF-8, returning the text and the encoding it was converted from.
+///
+/// Files with a UTF-16 byte order mark (or a NUL byte in every other position) are decoded as UTF-16, replacing
+/// invalid code units, all other files that are not valid UTF-8 are decoded as Latin-1 (ISO-8859-1), which maps every
+/// byte to a character, so a file can always be read.
+pub(crate) fn read_text(path: &Utf8Path) -> std::io::Result<(String, Option<&'static str>)> {
+    let bytes = std::fs::read(path)?;
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units = bytes.chunks_exact(2).map(|c| from_bytes([c[0], c[1]]));
+        char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect::<String>()
+    };
+
+    if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        return Ok((utf16(rest, u16::from_le_bytes), Some("UTF-16LE")));
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        return Ok((utf16(rest, u16::from_be_bytes), Some("UTF-16BE")));
+    }
+
+    // mostly ASCII text in UTF-16 has a NUL byte after (little endian) or before (big endian) every character, which
+    // is valid UTF-8 as well, so this is checked first
+    let nul_bytes = |offset: usize| bytes.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count();
+    if nul_bytes(1) * 4 > bytes.len() {
+        Ok((utf16(bytes, u16::from_le_bytes), Some("UTF-16LE")))
+    } else if nul_bytes(0) * 4 > bytes.len() {
+        Ok((utf16(bytes, u16::from_be_bytes), Some("UTF-16BE")))
+    } else if let Ok(text) = std::str::from_utf8(bytes) {
+        Ok((text.to_owned(), None))
+    } else {
+        Ok((bytes.iter().map(|&b| char::from(b)).collect(), Some("ISO-8859-1")))
+    }
+}
+
+/// Whether the name is the one of a license file, e.g. `LICENSE-MIT` or `COPYING`
+pub(crate) fn is_license_file_name(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    LICENSE_FILE_PREFIXES.iter().any(|prefix| upper.starts_with(prefix))
+}
+
+pub(crate) fn file_kind(name: &str) -> FileKind {
+    let upper = name.to_ascii_uppercase();
+    if upper.starts_with("NOTICE") {
+        FileKind::Notice
+    } else if upper.starts_with("PATENTS") {
+        FileKind::PatentGrant
+    } else {
+        FileKind::License
+    }
+}
+
+fn sorted_entries(dir: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let mut entries: Vec<_> = match dir.read_dir_utf8() {
+        Ok(entries) => entries.filter_map(Result::ok).map(|e| e.into_path()).collect(),
+        Err(e) => {
+            tracing::debug!("unable to search {dir} for license files: {e}");
+            return vec![];
+        },
+    };
+
+    entries.sort();
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license(section: &str) -> Option<String> {
+        readme_license(section).map(|e| e.to_string())
+    }
+
+    #[test]
+    fn license_sections() {
+        let readme = "# foo\n\nDoes things.\n\n## License\n\nLicensed under MIT.\n\n### Contribution\n\nSee above.\n\n\
+            ## Usage\n\nRun it.";
+        assert_eq!(
+            readme_license_section(readme).as_deref(),
+            Some("Licensed under MIT.\n\n### Contribution\n\nSee above."),
+            "up to the next heading of the same level"
+        );
+        assert_eq!(readme_license_section("# Licence\n\nMIT\n# Other").as_deref(), Some("MIT"));
+        assert_eq!(readme_license_section("## Licensing\nMIT").as_deref(), Some("MIT"));
+        assert_eq!(readme_license_section("#License\nMIT"), None, "not a heading");
+        assert_eq!(readme_license_section("# License\n\n# Usage\nMIT"), None, "empty section");
+        assert_eq!(readme_license_section("Licensed under MIT"), None);
+    }
+
+    #[test]
+    fn readme_licenses() {
+        assert_eq!(license("Licensed under the MIT license.").as_deref(), Some("MIT"));
+        assert_eq!(
+            license("Licensed under either of Apache-2.0 or MIT at your option.").as_deref(),
+            Some("Apache-2.0 OR MIT")
+        );
+        assert_eq!(license("Licensed under Apache-2.0 or MIT.").as_deref(), Some("Apache-2.0 OR MIT"));
+        assert_eq!(license("License: MIT/Apache-2.0").as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(
+            license("The code is MIT, the bundled font is OFL-1.1.").as_deref(),
+            Some("MIT AND OFL-1.1"),
+            "all licenses are required without a choice"
+        );
+        assert_eq!(license("MIT, see the MIT file.").as_deref(), Some("MIT"), "mentioned once");
+        assert_eq!(license("This is released under the JSON license").as_deref(), None, "neither OSI nor FSF");
+        assert_eq!(license("Apache License, Version 2.0").as_deref(), None);
+        let public_domain = license("This code is placed in the public domain.");
+        assert_eq!(public_domain.as_deref(), Some(crate::obligations::PUBLIC_DOMAIN_REF));
+    }
+
+    #[test]
+    fn public_domain() {
+        assert!(declares_public_domain("This code is placed in the public domain."));
+        assert!(declares_public_domain("This software is RELEASED\n  into the Public Domain."));
+        assert!(declares_public_domain("To the extent possible, this work is dedicated to the public domain."));
+        assert!(!declares_public_domain(
+            "This is free and unencumbered software released into the public domain.\n\n\
+             For more information, please refer to <http://unlicense.org/>"
+        ));
+        assert!(!declares_public_domain("Permission is hereby granted, free of charge, to any person"));
+        assert!(!declares_public_domain("Parts of the public domain data set are included"));
+
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let dir = dir.join(format!("tla-public-domain-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("COPYING"), "This code is placed in the public domain.").unwrap();
+        let files = find_license_files(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(files.len(), 1);
+        let spdx = files[0].spdx.as_ref().map(ToString::to_string);
+        assert_eq!(spdx.as_deref(), Some(crate::obligations::PUBLIC_DOMAIN_REF));
+        assert_eq!(files[0].detection_method, Some(DetectionMethod::FileAnalysis));
+    }
+
+    #[test]
+    fn all_rights_reserved() {
+        assert!(reserves_all_rights("Copyright (c) 2019 Example Corp. All rights reserved."));
+        assert!(reserves_all_rights("© Example Corp, confidential and proprietary"));
+        assert!(reserves_all_rights("/*\n * COPYRIGHT 2001 Example Corp.\n */"));
+        assert!(!reserves_all_rights(
+            "Copyright (c) 2019 Example Corp.\n\nPermission is hereby granted, free of charge, to any person"
+        ));
+        assert!(!reserves_all_rights("Copyright 2019 Example Corp. Licensed under the Apache License, Version 2.0"));
+        assert!(!reserves_all_rights("(c) 2019 Example Corp. Redistribution and use in source and binary forms"));
+        assert!(!reserves_all_rights("All rights reserved."), "no copyright notice");
+    }
+
+    fn read(name: &str, bytes: &[u8]) -> (String, Option<&'static str>) {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let path = dir.join(format!("tla-{name}-{}", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let text = read_text(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        text
+    }
+
+    #[test]
+    fn encodings() {
+        let utf16 = |text: &str, to_bytes: fn(u16) -> [u8; 2]| -> Vec<u8> {
+            text.encode_utf16().flat_map(to_bytes).collect()
+        };
+
+        assert_eq!(read("utf-8", "Copyright © 2001".as_bytes()), ("Copyright © 2001".to_owned(), None));
+        assert_eq!(read("utf-8-bom", b"\xEF\xBB\xBFMIT License"), ("MIT License".to_owned(), None));
+
+        let le = [b"\xFF\xFE".as_slice(), &utf16("MIT © 2001", u16::to_le_bytes)].concat();
+        assert_eq!(read("utf-16le-bom", &le), ("MIT © 2001".to_owned(), Some("UTF-16LE")));
+        let be = [b"\xFE\xFF".as_slice(), &utf16("MIT © 2001", u16::to_be_bytes)].concat();
+        assert_eq!(read("utf-16be-bom", &be), ("MIT © 2001".to_owned(), Some("UTF-16BE")));
+
+        // without a byte order mark
+        let le = utf16("MIT License\r\n", u16::to_le_bytes);
+        assert_eq!(read("utf-16le", &le), ("MIT License\r\n".to_owned(), Some("UTF-16LE")));
+        let be = utf16("MIT License\r\n", u16::to_be_bytes);
+        assert_eq!(read("utf-16be", &be), ("MIT License\r\n".to_owned(), Some("UTF-16BE")));
+
+        assert_eq!(
+            read("latin-1", b"Copyright \xA9 2001 J\xF6rg M\xFCller"),
+            ("Copyright © 2001 Jörg Müller".to_owned(), Some("ISO-8859-1"))
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    collect_krate_licenses, collect_thirdparty_licenses, AggregateError, Config, Diagnostics, GatherOptions, Krates,
+    LicenseStore, Package,
+};
+use std::sync::Arc;
+
+/// Everything a [`LicenseSource`] may need to collect its packages
+pub struct CollectContext<'a> {
+    /// The resolved dependency graph of the manifest
+    pub krates: &'a Krates,
+    /// The license store used to detect SPDX identifiers of license texts
+    pub license_store: Arc<LicenseStore>,
+    /// The configuration of the run
+    pub config: &'a Config,
+    /// The options the dependency graph was resolved with
+    pub options: &'a GatherOptions,
+}
+
+/// A collector of packages and their licenses, e.g. from cargo or from the thirdparty files generated by conan.
+///
+/// Custom collectors (e.g. for in-house package formats) can be registered via [`crate::Aggregator::source`].
+pub trait LicenseSource {
+    fn collect(&self, ctx: &CollectContext<'_>, diagnostics: &mut Diagnostics) -> Result<Vec<Package>, AggregateError>;
+}
+
+impl<F> LicenseSource for F
+where
+    F: Fn(&CollectContext<'_>, &mut Diagnostics) -> Result<Vec<Package>, AggregateError>,
+{
+    fn collect(&self, ctx: &CollectContext<'_>, diagnostics: &mut Diagnostics) -> Result<Vec<Package>, AggregateError> {
+        self(ctx, diagnostics)
+    }
+}
+
+/// Collects all rust packages in the dependency graph (see [`crate::get_all_licenses`])
+#[derive(Clone, Debug, Default)]
+pub struct CargoSource;
+
+impl LicenseSource for CargoSource {
+    fn collect(&self, ctx: &CollectContext<'_>, diagnostics: &mut Diagnostics) -> Result<Vec<Package>, AggregateError> {
+        collect_krate_licenses(ctx.krates, ctx.license_store.clone(), ctx.config, ctx.options, diagnostics)
+    }
+}
+
+/// Collects the packages of all thirdparty files declared in the crates' metadata (see [`collect_thirdparty_licenses`])
+#[derive(Clone, Debug)]
+pub struct ThirdpartySource {
+    /// The metadata key under which crates declare their thirdparty license file
+    pub metadata_key: String,
+}
+
+impl Default for ThirdpartySource {
+    fn default() -> Self {
+        Self { metadata_key: crate::DEFAULT_THIRDPARTY_METADATA_KEY.to_owned() }
+    }
+}
+
+impl LicenseSource for ThirdpartySource {
+    fn collect(&self, ctx: &CollectContext<'_>, diagnostics: &mut Diagnostics) -> Result<Vec<Package>, AggregateError> {
+        ctx.options.check_cancelled()?;
+        collect_thirdparty_licenses(
+            ctx.krates,
+            ctx.license_store.clone(),
+            ctx.config,
+            ctx.options,
+            &self.metadata_key,
+            diagnostics,
+        )
+    }
+}
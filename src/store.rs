@@ -0,0 +1,225 @@
+#[cfg(feature = "cargo")]
+use crate::license_store_from_cache;
+use crate::{AggregateError, LicenseStore, Utf8Path};
+#[cfg(feature = "network")]
+use crate::{HttpOptions, Utf8PathBuf};
+use askalono::TextData;
+#[cfg(feature = "cargo")]
+use std::sync::OnceLock;
+use std::sync::Arc;
+
+/// Provides the license store for a run, e.g. an already loaded store that is shared by many runs
+/// in a long-lived service, so the cost of loading it is only paid once.
+pub trait LicenseStoreProvider: Send + Sync {
+    fn license_store(&self) -> Result<Arc<LicenseStore>, AggregateError>;
+}
+
+impl LicenseStoreProvider for Arc<LicenseStore> {
+    fn license_store(&self) -> Result<Arc<LicenseStore>, AggregateError> {
+        Ok(self.clone())
+    }
+}
+
+/// Loads the license store from the internal cache on first use and hands out the same store afterwards
+#[cfg(feature = "cargo")]
+#[derive(Default)]
+pub struct LazyLicenseStore(OnceLock<Arc<LicenseStore>>);
+
+#[cfg(feature = "cargo")]
+impl LazyLicenseStore {
+    pub const fn new() -> Self {
+        Self(OnceLock::new())
+    }
+}
+
+#[cfg(feature = "cargo")]
+impl LicenseStoreProvider for LazyLicenseStore {
+    fn license_store(&self) -> Result<Arc<LicenseStore>, AggregateError> {
+        if let Some(store) = self.0.get() {
+            return Ok(store.clone());
+        }
+
+        // concurrent first uses may both load the store, only one of them is kept
+        let store = license_store_from_cache()?;
+        Ok(self.0.get_or_init(|| store).clone())
+    }
+}
+
+/// The license store from the internal cache, loaded once per process
+#[cfg(feature = "cargo")]
+pub fn shared_license_store() -> Result<Arc<LicenseStore>, AggregateError> {
+    static STORE: LazyLicenseStore = LazyLicenseStore::new();
+    STORE.license_store()
+}
+
+/// Create a license store from a checkout of the SPDX
+/// [license-list-data](https://github.com/spdx/license-list-data) repository (or its `json/details` directory),
+/// so SPDX identifiers newer than the internal cache are recognized
+pub fn license_store_from_spdx_data(dir: &Utf8Path) -> Result<LicenseStore, AggregateError> {
+    let details = dir.join("json").join("details");
+    let dir = if details.is_dir() { details.as_path() } else { dir };
+
+    let mut store = LicenseStore::new();
+    store
+        .load_spdx(dir.as_std_path(), false)
+        .map_err(|e| AggregateError::LicenseStore(e.into()))?;
+
+    Ok(store)
+}
+
+/// The latest release of the SPDX license list data, see [`download_license_store`]
+#[cfg(feature = "network")]
+const LICENSE_LIST_RELEASE: &str = "https://api.github.com/repos/spdx/license-list-data/releases/latest";
+
+/// Timeout of downloading the license list if none is configured, the archive is far larger than the responses of the
+/// other services
+#[cfg(feature = "network")]
+const LICENSE_LIST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Download the latest release of the SPDX [license-list-data](https://github.com/spdx/license-list-data), create a
+/// license store from it (see [`license_store_from_spdx_data`]) and save it to `path` (see [`save_license_store`]),
+/// so licenses added to the SPDX list after the release of this crate are recognized without a checkout.
+///
+/// Only the `json/details` files are extracted from the release archive, into a directory next to `path` that is
+/// removed afterwards. Without a configured [`HttpOptions::timeout`], the download may take up to 5 minutes.
+#[cfg(feature = "network")]
+pub fn download_license_store(path: &Utf8Path, http: &HttpOptions) -> Result<LicenseStore, AggregateError> {
+    #[derive(serde::Deserialize)]
+    struct Release {
+        tag_name: String,
+        tarball_url: String,
+    }
+
+    let http = match http.timeout {
+        Some(_) => http.clone(),
+        None => http.clone().timeout(LICENSE_LIST_TIMEOUT),
+    };
+    let client = http.build_client()?;
+
+    let body = http.send(client.get(LICENSE_LIST_RELEASE))?.error_for_status()?.text()?;
+    let release: Release = serde_json::from_str(&body).map_err(AggregateError::other)?;
+    tracing::info!("Downloading the SPDX license list {}", release.tag_name);
+    let archive = http.send(client.get(&release.tarball_url))?.error_for_status()?;
+
+    let dir = Utf8PathBuf::from(format!("{path}.spdx-data"));
+    let store = extract_license_details(archive, &dir).and_then(|()| license_store_from_spdx_data(&dir));
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        tracing::warn!("Unable to remove {dir}: {e}");
+    }
+
+    let store = store?;
+    save_license_store(&store, path)?;
+    Ok(store)
+}
+
+/// Extract the `json/details/*.json` files of a license-list-data archive (`.tar.gz`) into `dir`
+#[cfg(feature = "network")]
+fn extract_license_details<R: std::io::Read>(archive: R, dir: &Utf8Path) -> Result<(), AggregateError> {
+    let write_err = |source| AggregateError::Write { path: dir.to_owned(), source };
+    std::fs::create_dir_all(dir).map_err(write_err)?;
+
+    let mut extracted = 0;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    for entry in archive.entries().map_err(AggregateError::other)? {
+        let mut entry = entry.map_err(AggregateError::other)?;
+        // the entries are prefixed with the directory of the release, e.g. `spdx-license-list-data-<commit>/`
+        let entry_path = entry.path().map_err(AggregateError::other)?.into_owned();
+        let (Some(parent), Some(name)) = (entry_path.parent(), entry_path.file_name().and_then(|n| n.to_str())) else {
+            continue;
+        };
+
+        if parent.ends_with("json/details") && name.ends_with(".json") {
+            entry.unpack(dir.join(name)).map_err(write_err)?;
+            extracted += 1;
+        }
+    }
+
+    if extracted == 0 {
+        return Err(AggregateError::other("the archive contains no license details (json/details)"));
+    }
+
+    Ok(())
+}
+
+/// Add all `<SPDX identifier>.txt` files of the directory to the store (e.g. texts of custom `LicenseRef-` licenses),
+/// returns the number of added licenses
+pub fn add_license_texts(license_store: &mut LicenseStore, dir: &Utf8Path) -> Result<usize, AggregateError> {
+    let read_err = |source| AggregateError::LicenseRead { path: dir.to_owned(), source };
+
+    let mut paths = Vec::new();
+    for entry in dir.read_dir_utf8().map_err(read_err)? {
+        let path = entry.map_err(read_err)?.into_path();
+        if path.is_file() && path.extension() == Some("txt") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    for path in &paths {
+        let text = std::fs::read_to_string(path)
+            .map_err(|source| AggregateError::LicenseRead { path: path.clone(), source })?;
+
+        let name = path.file_stem().unwrap_or(path.as_str()).to_owned();
+        license_store.add_license(name, TextData::new(&text));
+    }
+
+    Ok(paths.len())
+}
+
+/// Load a license store that was saved with [`save_license_store`]
+pub fn load_license_store(path: &Utf8Path) -> Result<LicenseStore, AggregateError> {
+    let file =
+        std::fs::File::open(path).map_err(|source| AggregateError::LicenseRead { path: path.to_owned(), source })?;
+
+    LicenseStore::from_cache(std::io::BufReader::new(file)).map_err(|e| AggregateError::LicenseStore(e.into()))
+}
+
+/// Save the license store (e.g. one created with [`license_store_from_spdx_data`]), so it can be loaded quickly later on
+pub fn save_license_store(license_store: &LicenseStore, path: &Utf8Path) -> Result<(), AggregateError> {
+    let file =
+        std::fs::File::create(path).map_err(|source| AggregateError::Write { path: path.to_owned(), source })?;
+
+    license_store
+        .to_cache(std::io::BufWriter::new(file))
+        .map_err(|e| AggregateError::LicenseStore(e.into()))
+}
+
+#[cfg(all(test, feature = "network"))]
+mod tests {
+    use super::*;
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast()));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn license_details_extraction() {
+        let details = r#"{"licenseId": "MIT", "isDeprecatedLicenseId": false, "licenseText": "MIT License"}"#;
+        let release = archive(&[
+            ("spdx-license-list-data-0123abc/json/details/MIT.json", details),
+            ("spdx-license-list-data-0123abc/json/licenses.json", "{}"),
+            ("spdx-license-list-data-0123abc/text/MIT.txt", "MIT License"),
+        ]);
+
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap().join(format!("tla-{}", std::process::id()));
+        extract_license_details(release.as_slice(), &dir).unwrap();
+        let files: Vec<_> = dir.read_dir_utf8().unwrap().map(|e| e.unwrap().file_name().to_owned()).collect();
+        let store = license_store_from_spdx_data(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(files, ["MIT.json"]);
+        assert_eq!(store.unwrap().licenses().collect::<Vec<_>>(), ["MIT"]);
+
+        let empty = archive(&[("spdx-license-list-data-0123abc/README.md", "")]);
+        assert!(extract_license_details(empty.as_slice(), &dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+//! Validation of configuration files with the location of every problem, see [`validate_config`]
+
+use crate::{load_aggregator_config, Expression, Utf8Path, Utf8PathBuf};
+use globset::Glob;
+use krates::semver::VersionReq;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    ops::Range,
+    str::FromStr,
+};
+
+/// A problem of a configuration file
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigIssue {
+    /// The configuration file
+    pub path: Utf8PathBuf,
+    /// The line of the problem (starting at 1), if known
+    pub line: Option<usize>,
+    /// The column of the problem (starting at 1), if known
+    pub column: Option<usize>,
+    /// The invalid field, e.g. `accepted[2]` or `overrides[0].version`
+    pub field: Option<String>,
+    /// Human readable description
+    pub message: String,
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{line}:{column}")?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, ": {field}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+type Spanned = toml::Spanned<String>;
+
+/// The fields that are validated beyond their syntax, all other fields are ignored
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct Checked {
+    accepted: Vec<Spanned>,
+    ignore_license_files: Vec<Spanned>,
+    overrides: Vec<CheckedEntry>,
+    attributions: Vec<CheckedEntry>,
+    minimization: CheckedMinimization,
+    project: CheckedProject,
+}
+
+/// A top-level value, which cargo-about reads as the configuration of the crate of that name
+/// (`[ring] accepted = [...]`) if it is a table besides the known sections. The values are visited directly instead of
+/// `#[serde(flatten)]`ing them into [`Checked`], which would lose the spans.
+#[derive(Default)]
+struct CheckedCrate {
+    accepted: Vec<Spanned>,
+}
+
+impl<'de> Deserialize<'de> for CheckedCrate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = CheckedCrate;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "any value")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<CheckedCrate, A::Error> {
+                let mut krate = CheckedCrate::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "accepted" {
+                        krate.accepted = map.next_value()?;
+                    } else {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+                Ok(krate)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, seq: A) -> Result<CheckedCrate, A::Error> {
+                IgnoredAny.visit_seq(seq).map(|_| CheckedCrate::default())
+            }
+
+            fn visit_bool<E>(self, _: bool) -> Result<CheckedCrate, E> {
+                Ok(CheckedCrate::default())
+            }
+
+            fn visit_i64<E>(self, _: i64) -> Result<CheckedCrate, E> {
+                Ok(CheckedCrate::default())
+            }
+
+            fn visit_u64<E>(self, _: u64) -> Result<CheckedCrate, E> {
+                Ok(CheckedCrate::default())
+            }
+
+            fn visit_f64<E>(self, _: f64) -> Result<CheckedCrate, E> {
+                Ok(CheckedCrate::default())
+            }
+
+            fn visit_str<E>(self, _: &str) -> Result<CheckedCrate, E> {
+                Ok(CheckedCrate::default())
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct CheckedEntry {
+    version: Option<Spanned>,
+    license: Option<Spanned>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct CheckedMinimization {
+    priority: Vec<Spanned>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct CheckedProject {
+    licenses: Vec<Spanned>,
+}
+
+/// Check an aggregator configuration (see [`load_aggregator_config`]) without running anything, reporting every
+/// problem with its line and field instead of failing on the first one:
+/// - the TOML syntax and the structure of all sections,
+/// - the SPDX licenses of `accepted` (globally and per crate), `minimization.priority` and `project.licenses`,
+/// - the glob patterns of `ignore-license-files`,
+/// - the licenses and version requirements of `overrides` and `attributions`,
+/// - the files referenced by the configuration (e.g. included files and license texts).
+///
+/// Returns no issues for a valid configuration.
+pub fn validate_config<P: AsRef<Utf8Path>>(path: P) -> Vec<ConfigIssue> {
+    let path = path.as_ref();
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => return vec![issue(path, "", None, None, format!("unable to read the configuration: {e}"))],
+    };
+
+    let mut issues = vec![];
+    let checked = toml::from_str::<Checked>(&content)
+        .and_then(|checked| Ok((checked, toml::from_str::<BTreeMap<String, CheckedCrate>>(&content)?)));
+    match checked {
+        Ok((checked, crates)) => check_fields(path, &content, &checked, &crates, &mut issues),
+        Err(e) => {
+            issues.push(issue(path, &content, e.span(), None, single_line(e.message())));
+            return issues;
+        },
+    }
+
+    // the structure of the sections, the errors of the checked fields were reported precisely already
+    let lines: Vec<_> = issues.iter().map(|i| i.line).collect();
+    if let Err(e) = toml::from_str::<crate::AggregatorConfig>(&content) {
+        let structure = issue(path, &content, e.span(), None, single_line(e.message()));
+        if !lines.contains(&structure.line) {
+            issues.push(structure);
+        }
+    }
+
+    if issues.is_empty() {
+        if let Err(e) = load_aggregator_config(path) {
+            let mut message = e.to_string();
+            let mut source = std::error::Error::source(&e);
+            while let Some(e) = source {
+                message.push_str(&format!(": {e}"));
+                source = e.source();
+            }
+            issues.push(issue(path, "", None, None, message));
+        }
+    }
+
+    issues
+}
+
+fn check_fields(
+    path: &Utf8Path,
+    content: &str,
+    checked: &Checked,
+    crates: &BTreeMap<String, CheckedCrate>,
+    issues: &mut Vec<ConfigIssue>,
+) {
+    let mut check = |field: String, value: &Spanned, result: Result<(), String>| {
+        if let Err(message) = result {
+            issues.push(issue(path, content, Some(value.span()), Some(field), message));
+        }
+    };
+
+    let licensee = |l: &str| {
+        spdx::Licensee::parse(l).map(|_| ()).map_err(|e| format!("'{l}' is not a valid license: {}", e.reason))
+    };
+
+    let licensees = [
+        ("accepted".to_owned(), &checked.accepted),
+        ("minimization.priority".to_owned(), &checked.minimization.priority),
+        ("project.licenses".to_owned(), &checked.project.licenses),
+    ];
+    let per_crate = crates.iter().map(|(name, krate)| (format!("{name}.accepted"), &krate.accepted));
+    for (field, licenses) in licensees.into_iter().chain(per_crate) {
+        for (i, l) in licenses.iter().enumerate() {
+            check(format!("{field}[{i}]"), l, licensee(l.get_ref()));
+        }
+    }
+
+    for (i, pattern) in checked.ignore_license_files.iter().enumerate() {
+        let result = Glob::new(pattern.get_ref()).map(|_| ()).map_err(|e| format!("invalid glob pattern: {e}"));
+        check(format!("ignore-license-files[{i}]"), pattern, result);
+    }
+
+    for (section, entries) in [("overrides", &checked.overrides), ("attributions", &checked.attributions)] {
+        for (i, entry) in entries.iter().enumerate() {
+            if let Some(version) = &entry.version {
+                let result = VersionReq::parse(version.get_ref())
+                    .map(|_| ())
+                    .map_err(|e| format!("'{}' is not a valid version requirement: {e}", version.get_ref()));
+                check(format!("{section}[{i}].version"), version, result);
+            }
+            if let Some(license) = &entry.license {
+                let result = Expression::from_str(license.get_ref())
+                    .map(|_| ())
+                    .map_err(|e| format!("'{}' is not a valid SPDX expression: {}", license.get_ref(), e.reason));
+                check(format!("{section}[{i}].license"), license, result);
+            }
+        }
+    }
+}
+
+fn single_line(message: &str) -> String {
+    message.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(", ")
+}
+
+fn issue(
+    path: &Utf8Path,
+    content: &str,
+    span: Option<Range<usize>>,
+    field: Option<String>,
+    message: String,
+) -> ConfigIssue {
+    let position = span.map(|span| {
+        let before = &content[..span.start.min(content.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+        (line, column)
+    });
+
+    ConfigIssue {
+        path: path.to_owned(),
+        line: position.map(|(line, _)| line),
+        column: position.map(|(_, column)| column),
+        field,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(name: &str, content: &str) -> Vec<(Option<usize>, Option<usize>, Option<String>)> {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let path = dir.join(format!("tla-validate-{name}-{}.toml", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let issues = validate_config(&path);
+        std::fs::remove_file(&path).unwrap();
+        issues.into_iter().map(|i| (i.line, i.column, i.field)).collect()
+    }
+
+    #[test]
+    fn valid_config() {
+        assert_eq!(validate("valid", "accepted = [\"MIT\"]\n\n[ring]\naccepted = [\"ISC\"]\n"), []);
+    }
+
+    #[test]
+    fn licenses_with_their_location() {
+        let content = "accepted = [\"MIT\", \"not a license\"]\n\n[ring]\naccepted = [\"ISC\", \"bogus\"]\n";
+        assert_eq!(
+            validate("licenses", content),
+            [
+                (Some(1), Some(20), Some("accepted[1]".to_owned())),
+                (Some(4), Some(20), Some("ring.accepted[1]".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let content = "[[overrides]]\nname = \"a\"\nversion = \"not a version\"\nlicense = \"MIT OR\"\n";
+        assert_eq!(
+            validate("overrides", content),
+            [
+                (Some(3), Some(11), Some("overrides[0].version".to_owned())),
+                (Some(4), Some(11), Some("overrides[0].license".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(validate("syntax", "accepted = [\"MIT\"]\nbroken = \n"), [(Some(2), Some(10), None)]);
+    }
+
+    #[test]
+    fn display() {
+        let issue = ConfigIssue {
+            path: "about.toml".into(),
+            line: Some(3),
+            column: Some(11),
+            field: Some("overrides[0].version".to_owned()),
+            message: "invalid".to_owned(),
+        };
+        assert_eq!(issue.to_string(), "about.toml:3:11: overrides[0].version: invalid");
+    }
+}